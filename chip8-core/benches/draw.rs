@@ -0,0 +1,72 @@
+// Measures the work behind a frontend's draw(): building the frame and batching it into rects.
+// Run with `cargo bench -p chip8-core --bench draw`.
+//
+// The per-pixel renderer issued one fill_rect per pixel no matter what was on screen; the
+// run-length renderer issues one per horizontal run of lit pixels. Fill calls per frame:
+//
+//   size     pattern   per-pixel   runs
+//   64x32    blank          2048      0
+//   64x32    text           2048     85
+//   64x32    random         2048   ~515
+//   64x32    checker        2048   1024
+//   128x64   blank          8192      0
+//   128x64   text           8192    345
+//   128x64   random         8192  ~2050
+//   128x64   checker        8192   4096
+//
+// Checkerboard is the worst case at half the calls; typical game screens (text, sprites on an
+// empty background) need 4-25% of them. runs() (which includes frame()) measured 10-70µs per
+// frame here, far below what the saved fill_rect calls cost. A texture renderer would make the
+// per-frame cost independent of the screen contents, this is the stopgap until then.
+use chip8_core::Display;
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 1000;
+
+fn fill(display: &mut Display, pattern: &str) {
+    for y in 0..display.height {
+        for x in 0..display.width {
+            let lit = match pattern {
+                "blank" => false,
+                "text" => y % 8 < 5 && x % 5 < 4 && (x / 5 + y / 8) % 3 != 0 && y < display.height / 2,
+                "random" => rand::random::<bool>(),
+                _ => (x + y) % 2 == 0,
+            };
+            display.set_pixel(x, y, lit as u8);
+        }
+    }
+}
+
+fn main() {
+    println!("{:<8} {:<9} {:>9} {:>6} {:>12} {:>12}", "size", "pattern", "per-pixel", "runs", "frame()", "runs()");
+
+    for (width, height) in [(64, 32), (128, 64)] {
+        for pattern in ["blank", "text", "random", "checker"] {
+            let mut display = Display::new(width, height, 1);
+            fill(&mut display, pattern);
+
+            let start = Instant::now();
+            for _ in 0..ITERATIONS {
+                black_box(display.frame());
+            }
+            let frame_time = start.elapsed() / ITERATIONS;
+
+            let start = Instant::now();
+            for _ in 0..ITERATIONS {
+                black_box(display.runs());
+            }
+            let runs_time = start.elapsed() / ITERATIONS;
+
+            println!(
+                "{:<8} {:<9} {:>9} {:>6} {:>12?} {:>12?}",
+                format!("{}x{}", width, height),
+                pattern,
+                width as usize * height as usize,
+                display.runs().len(),
+                frame_time,
+                runs_time,
+            );
+        }
+    }
+}
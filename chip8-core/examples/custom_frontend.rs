@@ -0,0 +1,54 @@
+// The loop a frontend runs around the core, without SDL: input goes in as EmuEvents, each
+// frame runs its share of instructions and ticks the timers, and the screen comes out as
+// runs of lit pixels to fill. A status bar of the frontend's own is drawn into the bottom row
+// with Display::draw_sprite_at, which leaves the ROM's collisions alone as long as the ROM
+// doesn't draw there too. Run with `cargo run -p chip8-core --example custom_frontend`.
+use chip8_core::display::{AsciiStyle, BlitMode};
+use chip8_core::event::{self, EmuEvent};
+use chip8_core::{Chip8, Display};
+use std::time::Instant;
+
+const CYCLES_PER_FRAME: u64 = 11; // ~700hz
+const FRAMES: u16 = 32;
+const PRESS_FRAME: u16 = 10; // when the "user" presses key 7
+
+// Waits for key 7, then draws a 7
+const WAIT_FOR_SEVEN: [u8; 12] = [
+    0x61, 0x07, // 0x200 LD V1, 7
+    0xE1, 0x9E, // 0x202 SKP V1
+    0x12, 0x02, // 0x204 JP 0x202
+    0xF1, 0x29, // 0x206 LD F, V1
+    0xD0, 0x05, // 0x208 DRW V0, V0, 5
+    0x12, 0x0A, // 0x20A JP 0x20A
+];
+
+fn main() {
+    let mut cpu = Chip8::new(None, None, None, Display::new(64, 32, 1), None).expect("the built-in fonts load");
+    cpu.load_program(&WAIT_FOR_SEVEN);
+
+    for frame in 0..FRAMES {
+        // Input: a real frontend turns its window library's key events into these
+        if frame == PRESS_FRAME {
+            event::apply(cpu.display_mut(), &EmuEvent::KeyChip8 { key: 7, down: true, repeat: false, time: Instant::now() });
+        }
+        cpu.display_mut().keypad.apply_all();
+
+        for _ in 0..CYCLES_PER_FRAME {
+            cpu.step();
+        }
+        cpu.tick_frame();
+
+        // The frontend's own status bar, two pixels per frame
+        let width = cpu.display().width;
+        cpu.display_mut().draw_sprite_at((frame * 2) % width, 31, &[0xC0], BlitMode::Overwrite);
+
+        // Drawing: one rectangle per run, as the SDL frontend's renderer does
+        let display = cpu.display();
+        let runs = display.runs();
+        if frame == PRESS_FRAME || frame == FRAMES - 1 {
+            println!("frame {}: {} rects to fill", frame, runs.len());
+        }
+    }
+
+    println!("{}", cpu.display().to_ascii_art(AsciiStyle::Hash));
+}
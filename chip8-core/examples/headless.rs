@@ -0,0 +1,43 @@
+// Runs a ROM without a window until it stores a result, loops forever or runs out of cycles,
+// then prints why it stopped and the screen. The same loop `c8 headless` is built on, and a
+// starting point for test harnesses. Run with
+// `cargo run -p chip8-core --example headless [rom.ch8]`, which without a ROM runs a built-in
+// one counting 0 to 9 on screen and storing 10 at 0x300.
+use chip8_core::display::AsciiStyle;
+use chip8_core::halt::HaltCondition;
+use chip8_core::{Chip8, Display};
+
+const CYCLES_PER_FRAME: u64 = 11; // ~700hz
+
+const COUNTER: [u8; 18] = [
+    0x00, 0xE0, // 0x200 CLS
+    0xF0, 0x29, // 0x202 LD F, V0
+    0xD1, 0x25, // 0x204 DRW V1, V2, 5
+    0x70, 0x01, // 0x206 ADD V0, 1
+    0x30, 0x0A, // 0x208 SE V0, 10
+    0x12, 0x00, // 0x20A JP 0x200
+    0xA3, 0x00, // 0x20C LD I, 0x300
+    0xF0, 0x55, // 0x20E LD [I], V0
+    0x12, 0x10, // 0x210 JP 0x210
+];
+
+fn main() {
+    let rom = match std::env::args().nth(1) {
+        Some(path) => std::fs::read(&path).unwrap_or_else(|e| panic!("can't read {}: {}", path, e)),
+        None => COUNTER.to_vec(),
+    };
+
+    let mut cpu = Chip8::new(None, None, None, Display::new(64, 32, 1), None).expect("the built-in fonts load");
+    cpu.load_program(&rom);
+
+    let conditions = [HaltCondition::MemoryEquals(0x300, 10), HaltCondition::InfiniteLoop, HaltCondition::MaxCycles(1_000_000)];
+    let result = cpu.run_headless(&conditions, CYCLES_PER_FRAME);
+
+    // Warnings and trap reports the run produced; the core leaves printing them to us
+    for message in cpu.take_messages() {
+        println!("{}", message);
+    }
+    println!("stopped: {} after {} cycles, {} frames", result.reason, result.cycles, result.frames);
+    println!("{}", cpu.display().to_ascii_art(AsciiStyle::Braille));
+    println!("{}", cpu.state_json());
+}
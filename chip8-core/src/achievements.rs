@@ -0,0 +1,58 @@
+use crate::expr::{self, Expr, ExprContext};
+use crate::toml::{Table, Value};
+
+// Per-ROM achievements from the config, unlocked once their condition holds:
+//
+//   [[achievement]]
+//   name = "Sharpshooter"
+//   when = "mem[0x3F0] >= 10"
+//   message = "Hit 10 targets"   # optional
+//   unlocked = true              # written back when unlocked
+pub struct Achievement {
+    pub name: String,
+    pub message: Option<String>,
+    when: Expr,
+    pub unlocked: bool,
+}
+
+#[derive(Default)]
+pub struct Achievements {
+    pub list: Vec<Achievement>,
+}
+
+impl Achievements {
+    pub fn from_config(config: &Table) -> Result<Achievements, String> {
+        let mut list = Vec::new();
+
+        for (i, entry) in config.get("achievement").and_then(Value::as_array).into_iter().flatten().enumerate() {
+            let invalid = |message: &str| format!("achievement {}: {}", i + 1, message);
+            let entry = entry.as_table().ok_or_else(|| invalid("expected a table"))?;
+            let when = entry.get("when").and_then(Value::as_str).ok_or_else(|| invalid("missing when"))?;
+
+            list.push(Achievement {
+                name: entry.get("name").and_then(Value::as_str).ok_or_else(|| invalid("missing name"))?.to_string(),
+                message: entry.get("message").and_then(Value::as_str).map(str::to_string),
+                when: expr::parse(when).map_err(|e| invalid(&e))?,
+                unlocked: entry.get("unlocked").and_then(Value::as_bool).unwrap_or(false),
+            });
+        }
+
+        Ok(Achievements { list })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    // Checks the locked achievements, called once per frame; returns the newly unlocked ones
+    pub fn update(&mut self, ctx: &dyn ExprContext) -> Vec<usize> {
+        let mut unlocked = Vec::new();
+        for (idx, achievement) in self.list.iter_mut().enumerate() {
+            if !achievement.unlocked && achievement.when.eval(ctx) != 0 {
+                achievement.unlocked = true;
+                unlocked.push(idx);
+            }
+        }
+        unlocked
+    }
+}
@@ -0,0 +1,70 @@
+use crate::toml;
+use std::io::{self, Error, ErrorKind};
+
+// A named memory range, e.g. 0x3F0-0x3F2 "score_digits"
+pub struct Region {
+    pub start: u16,
+    pub end: u16,
+    pub name: String,
+    pub comment: Option<String>,
+}
+
+// Memory labels loaded from an annotations file:
+//
+//   [[region]]
+//   start = 0x3F0
+//   end = 0x3F2          # optional, defaults to start
+//   name = "score_digits"
+//   comment = "BCD of the score"
+pub struct Annotations {
+    pub regions: Vec<Region>,
+}
+
+impl Annotations {
+    pub fn parse(text: &str) -> io::Result<Annotations> {
+        let table = toml::parse(text)?;
+        let mut regions = Vec::new();
+
+        let entries = table.get("region").and_then(|value| value.as_array()).cloned().unwrap_or_default();
+        for (i, entry) in entries.iter().enumerate() {
+            let invalid = |message: &str| Error::new(ErrorKind::InvalidData, format!("region {}: {}", i + 1, message));
+            let entry = entry.as_table().ok_or_else(|| invalid("expected a table"))?;
+
+            let address = |key: &str| entry.get(key).and_then(|value| value.as_int()).map(|value| value as u16);
+            let start = address("start").ok_or_else(|| invalid("missing start address"))?;
+            let end = address("end").unwrap_or(start);
+            if end < start {
+                return Err(invalid("end is before start"));
+            }
+
+            regions.push(Region {
+                start,
+                end,
+                name: entry.get("name").and_then(|value| value.as_str()).ok_or_else(|| invalid("missing name"))?.to_string(),
+                comment: entry.get("comment").and_then(|value| value.as_str()).map(str::to_string),
+            });
+        }
+
+        regions.sort_by_key(|region| region.start);
+        Ok(Annotations { regions })
+    }
+
+    pub fn load(path: &str) -> io::Result<Annotations> {
+        Annotations::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn region_at(&self, address: u16) -> Option<&Region> {
+        self.regions.iter().find(|region| region.start <= address && address <= region.end)
+    }
+
+    // Label for an address: "name" at the start of a region, "name+N" inside it
+    pub fn label(&self, address: u16) -> Option<String> {
+        self.region_at(address).map(|region| {
+            if address == region.start {
+                region.name.clone()
+            } else {
+                format!("{}+{}", region.name, address - region.start)
+            }
+        })
+    }
+}
@@ -0,0 +1,55 @@
+// XO-CHIP sound: a 128-bit pattern loaded from memory with F002, played one bit at a time
+// while the sound timer runs, at a rate set with FX3A. Until a ROM loads a pattern the sound
+// timer plays the plain beep.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Audio {
+    pub pattern: Option<[u8; 16]>,
+    pub pitch: u8,
+}
+
+impl Default for Audio {
+    fn default() -> Audio {
+        Audio {
+            pattern: None,
+            pitch: Audio::DEFAULT_PITCH,
+        }
+    }
+}
+
+impl Audio {
+    pub const DEFAULT_PITCH: u8 = 64; // 4000 bits per second
+    pub const BITS: usize = 128;
+
+    // Pattern bits played per second: 4000 at the default pitch, doubling every 48 steps
+    pub fn rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    // The bit at a position in the pattern, most significant bit of the first byte first
+    pub fn bit(&self, position: usize) -> bool {
+        let Some(pattern) = self.pattern else {
+            return false;
+        };
+        let position = position % Audio::BITS;
+        pattern[position / 8] >> (7 - position % 8) & 1 == 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pitch_doubles_the_rate_every_48_steps() {
+        let mut audio = Audio::default();
+        assert_eq!(audio.rate(), 4000.0);
+        audio.pitch = 112;
+        assert_eq!(audio.rate(), 8000.0);
+
+        let mut pattern = [0; 16];
+        pattern[1] = 0b0100_0000;
+        audio.pattern = Some(pattern);
+        assert!(audio.bit(9) && audio.bit(9 + Audio::BITS));
+        assert!(!audio.bit(8));
+    }
+}
@@ -0,0 +1,55 @@
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AutosaveInterval {
+    Seconds(u64),
+    Frames(u64),
+}
+
+impl AutosaveInterval {
+    // "60s" for every 60 seconds, "600f" for every 600 frames
+    pub fn parse(text: &str) -> Option<AutosaveInterval> {
+        let (count, unit) = text.split_at(text.len().checked_sub(1)?);
+        let count = count.parse().ok().filter(|&count| count > 0)?;
+        match unit {
+            "s" => Some(AutosaveInterval::Seconds(count)),
+            "f" => Some(AutosaveInterval::Frames(count)),
+            _ => None,
+        }
+    }
+}
+
+pub struct Autosave {
+    pub interval: AutosaveInterval,
+    pub path: String,
+    last_save: Instant,
+    frames: u64,
+}
+
+impl Autosave {
+    pub fn new(interval: AutosaveInterval, path: &str) -> Autosave {
+        Autosave {
+            interval,
+            path: path.to_string(),
+            last_save: Instant::now(),
+            frames: 0,
+        }
+    }
+
+    // Called once per frame (60hz tick), returns true when a save is due
+    pub fn tick(&mut self) -> bool {
+        self.frames += 1;
+
+        let due = match self.interval {
+            AutosaveInterval::Seconds(seconds) => self.last_save.elapsed() >= Duration::from_secs(seconds),
+            AutosaveInterval::Frames(frames) => self.frames >= frames,
+        };
+
+        if due {
+            self.last_save = Instant::now();
+            self.frames = 0;
+        }
+
+        due
+    }
+}
@@ -0,0 +1,71 @@
+use crate::annotations::Annotations;
+use crate::disasm;
+
+// What the report needs from the machine at the moment it trapped
+pub struct MachineState<'a> {
+    pub pc: u16, // address of the faulting instruction
+    pub i: u32,
+    pub v: &'a [u8; 16],
+    pub stack: &'a [u16], // return addresses, outermost first
+    pub delay: u8,
+    pub sound: u8,
+    pub memory: &'a [u8],
+}
+
+const CONTEXT_BEFORE: u16 = 4; // instructions shown before and after the PC
+const CONTEXT_AFTER: u16 = 3;
+
+fn word_at(memory: &[u8], address: u16) -> u16 {
+    let byte = |address: u16| memory.get(address as usize).copied().unwrap_or(0) as u16;
+    byte(address) << 8 | byte(address.wrapping_add(1))
+}
+
+fn symbol(symbols: Option<&Annotations>, address: u16) -> String {
+    match symbols.and_then(|symbols| symbols.label(address)) {
+        Some(label) => format!(" <{}>", label),
+        None => String::new(),
+    }
+}
+
+// The emulated machine's "panic message": disassembly around the PC, the call stack, registers
+// and timers
+pub fn format_trap(reason: &str, state: &MachineState, symbols: Option<&Annotations>) -> String {
+    let mut out = format!("trap: {} at {:#06X}{}\n", reason, state.pc, symbol(symbols, state.pc));
+
+    out += "\ncode:\n";
+    let start = state.pc.saturating_sub(CONTEXT_BEFORE * 2);
+    let end = state.pc.saturating_add(CONTEXT_AFTER * 2);
+    for address in (start..=end).step_by(2) {
+        let word = word_at(state.memory, address);
+        let long = word_at(state.memory, address.wrapping_add(2));
+        let marker = if address == state.pc { "->" } else { "  " };
+        out += &format!(
+            "  {} {:#06X}  {:04X}  {}{}\n",
+            marker, address, word, disasm::disassemble(word, long), symbol(symbols, address)
+        );
+    }
+
+    out += "\ncall stack:\n";
+    out += &format!("  #0 {:#06X}{}\n", state.pc, symbol(symbols, state.pc));
+    // runs of the same call site (recursion) are folded into one line
+    let calls: Vec<u16> = state.stack.iter().rev().map(|ret| ret.wrapping_sub(2)).collect();
+    let mut depth = 0;
+    for run in calls.chunk_by(|a, b| a == b) {
+        let call = run[0];
+        depth += run.len();
+        if run.len() == 1 {
+            out += &format!("  #{} {:#06X}{}\n", depth, call, symbol(symbols, call));
+        } else {
+            out += &format!("  #{}-#{} {:#06X}{} (x{})\n", depth + 1 - run.len(), depth, call, symbol(symbols, call), run.len());
+        }
+    }
+
+    out += "\nregisters:\n";
+    for (row, values) in state.v.chunks(8).enumerate() {
+        let cells: Vec<String> = values.iter().enumerate().map(|(idx, value)| format!("V{:X}={:02X}", row * 8 + idx, value)).collect();
+        out += &format!("  {}\n", cells.join(" "));
+    }
+    out += &format!("  I={:#06X} SP={} DT={} ST={}\n", state.i, state.stack.len(), state.delay, state.sound);
+
+    out
+}
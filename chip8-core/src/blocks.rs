@@ -0,0 +1,113 @@
+use crate::disasm;
+use std::collections::HashMap;
+use std::ops::Range;
+
+// A straight run of instructions entered at `entry` and left by its last instruction, the
+// unit a JIT or a cached dispatcher would translate
+pub struct BasicBlock {
+    pub entry: u16,
+    pub end: u16, // exclusive
+    pub instructions: u16,
+    pub executions: u64,
+}
+
+impl BasicBlock {
+    // Decodes forward from entry until an instruction that can change the flow
+    fn decode(memory: &[u8], entry: u16) -> BasicBlock {
+        let word = |address: u16| {
+            let byte = |address: u16| memory.get(address as usize).copied().unwrap_or(0) as u16;
+            byte(address) << 8 | byte(address.wrapping_add(1))
+        };
+
+        let mut address = entry;
+        let mut instructions = 0;
+        loop {
+            let instruction = word(address);
+            address = address.wrapping_add(disasm::instruction_size(instruction));
+            instructions += 1;
+            if ends_block(instruction) || address < entry {
+                break;
+            }
+        }
+
+        BasicBlock { entry, end: address, instructions, executions: 0 }
+    }
+
+    fn overlaps(&self, range: &Range<u32>) -> bool {
+        (self.entry as u32) < range.end && range.start < self.end as u32
+    }
+}
+
+// Jumps, calls, returns, skips and key waits: after these the next PC isn't simply the next
+// instruction
+pub fn ends_block(instruction: u16) -> bool {
+    match instruction >> 12 {
+        0x0 => matches!(instruction, 0x00EE | 0x00FD),
+        0x1 | 0x2 | 0x3 | 0x4 | 0x5 | 0x9 | 0xB => true,
+        0xE => matches!(instruction & 0xFF, 0x9E | 0xA1),
+        0xF => instruction & 0xFF == 0x0A,
+        _ => false,
+    }
+}
+
+// Blocks found at runtime keyed by entry PC, with how often each ran. Writes into a block's
+// bytes drop it so self-modifying code is decoded again.
+pub struct BlockMap {
+    pub blocks: HashMap<u16, BasicBlock>,
+    pub instructions: u64,
+    at_entry: bool,
+}
+
+impl Default for BlockMap {
+    fn default() -> BlockMap {
+        BlockMap::new()
+    }
+}
+
+impl BlockMap {
+    pub fn new() -> BlockMap {
+        BlockMap {
+            blocks: HashMap::new(),
+            instructions: 0,
+            at_entry: true,
+        }
+    }
+
+    // Called before each instruction runs
+    pub fn observe(&mut self, memory: &[u8], pc: u16, instruction: u16) {
+        if self.at_entry {
+            self.blocks.entry(pc).or_insert_with(|| BasicBlock::decode(memory, pc)).executions += 1;
+        }
+        self.at_entry = ends_block(instruction);
+        self.instructions += 1;
+    }
+
+    pub fn invalidate(&mut self, written: Range<u32>) {
+        self.blocks.retain(|_, block| !block.overlaps(&written));
+    }
+
+    // Blocks ordered by the instructions they account for, the hottest first
+    pub fn hottest(&self) -> Vec<&BasicBlock> {
+        let mut blocks: Vec<&BasicBlock> = self.blocks.values().collect();
+        blocks.sort_by_key(|block| std::cmp::Reverse(block.executions * block.instructions as u64));
+        blocks
+    }
+
+    pub fn report(&self, limit: usize) -> String {
+        let mut out = format!("{} blocks, {} instructions\n", self.blocks.len(), self.instructions);
+        out += "  entry   end     len  executions  share\n";
+
+        let mut covered = 0.0;
+        for block in self.hottest().into_iter().take(limit) {
+            let share = (block.executions * block.instructions as u64) as f64 / self.instructions.max(1) as f64 * 100.0;
+            covered += share;
+            out += &format!(
+                "  {:#06X}  {:#06X}  {:>3}  {:>10}  {:>5.1}%\n",
+                block.entry, block.end, block.instructions, block.executions, share
+            );
+        }
+        out += &format!("top {} blocks cover {:.1}% of instructions", limit.min(self.blocks.len()), covered);
+
+        out
+    }
+}
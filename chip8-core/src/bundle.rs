@@ -0,0 +1,134 @@
+use crate::cpu::Quirks;
+use std::io::{self, Error, ErrorKind};
+
+// CHIP-8 binary bundle (.c8b) layout:
+//   "CBF" magic, version (u8), property table offset (u16 BE), bytecode table offset (u16 BE)
+//   bytecode table: entries of platform (u8), offset (u16 BE), length (u16 BE), ended by 0xFF
+//   property table: entries of key (u8), length (u8), data, ended by key 0x00
+const MAGIC: &[u8] = b"CBF";
+
+const PROPERTY_END: u8 = 0x00;
+const PROPERTY_TITLE: u8 = 0x01;
+const PROPERTY_AUTHOR: u8 = 0x02;
+const PROPERTY_KEYMAP: u8 = 0x06;
+
+const BYTECODE_END: u8 = 0xFF;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Platform {
+    Chip8,
+    SuperChip10,
+    SuperChip11,
+    XoChip,
+}
+
+impl Platform {
+    fn from_id(id: u8) -> Option<Platform> {
+        match id {
+            0x00 => Some(Platform::Chip8),
+            0x01 => Some(Platform::SuperChip10),
+            0x02 => Some(Platform::SuperChip11),
+            0x03 => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    // Quirks matching the interpreter the bundle targets
+    pub fn quirks(&self) -> Quirks {
+        let preset = match self {
+            Platform::Chip8 => "chip8",
+            Platform::SuperChip10 | Platform::SuperChip11 => "schip1.1",
+            Platform::XoChip => "xochip",
+        };
+        Quirks::preset(preset).unwrap()
+    }
+}
+
+pub struct Bundle {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub platform: Platform,
+    pub keymap: Vec<(u8, String)>, // (CHIP-8 key, host key name)
+    pub rom: Vec<u8>,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_u16(data: &[u8], at: usize) -> io::Result<usize> {
+    match data.get(at..at + 2) {
+        Some(bytes) => Ok((bytes[0] as usize) << 8 | bytes[1] as usize),
+        None => Err(invalid("truncated c8b bundle")),
+    }
+}
+
+pub fn is_bundle(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+// Parses a .c8b bundle, picking the first platform this emulator supports
+pub fn parse_bundle(data: &[u8]) -> io::Result<Bundle> {
+    if !is_bundle(data) {
+        return Err(invalid("missing c8b magic"));
+    }
+
+    let properties = read_u16(data, 4)?;
+    let mut entry = read_u16(data, 6)?;
+    let mut program: Option<(Platform, Vec<u8>)> = None;
+
+    loop {
+        let id = *data.get(entry).ok_or_else(|| invalid("unterminated c8b bytecode table"))?;
+        if id == BYTECODE_END {
+            break;
+        }
+
+        let offset = read_u16(data, entry + 1)?;
+        let length = read_u16(data, entry + 3)?;
+        entry += 5;
+
+        if let (None, Some(platform)) = (&program, Platform::from_id(id)) {
+            let rom = data.get(offset..offset + length).ok_or_else(|| invalid("c8b bytecode out of range"))?;
+            program = Some((platform, rom.to_vec()));
+        }
+    }
+
+    let Some((platform, rom)) = program else {
+        return Err(invalid("c8b bundle has no bytecode for a supported platform"));
+    };
+
+    let mut bundle = Bundle {
+        title: None,
+        author: None,
+        platform,
+        keymap: Vec::new(),
+        rom,
+    };
+
+    let mut idx = properties;
+    loop {
+        let key = *data.get(idx).ok_or_else(|| invalid("unterminated c8b property table"))?;
+        if key == PROPERTY_END {
+            break;
+        }
+
+        let length = *data.get(idx + 1).ok_or_else(|| invalid("truncated c8b property"))? as usize;
+        let value = data.get(idx + 2..idx + 2 + length).ok_or_else(|| invalid("truncated c8b property"))?;
+        idx += 2 + length;
+
+        match key {
+            PROPERTY_TITLE => bundle.title = Some(String::from_utf8_lossy(value).into_owned()),
+            PROPERTY_AUTHOR => bundle.author = Some(String::from_utf8_lossy(value).into_owned()),
+            PROPERTY_KEYMAP => {
+                // pairs of CHIP-8 key + ASCII host key
+                for pair in value.chunks_exact(2) {
+                    bundle.keymap.push((pair[0] & 0xF, (pair[1] as char).to_string()));
+                }
+            }
+            _ => {} // unknown properties are skipped
+        }
+    }
+
+    Ok(bundle)
+}
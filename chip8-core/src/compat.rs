@@ -0,0 +1,171 @@
+use crate::bundle;
+use crate::cpu::{Chip8, Quirks};
+use crate::display::Display;
+use crate::halt::HaltCondition;
+use std::time::{Duration, Instant};
+
+// How one ROM fared in a compatibility sweep over a directory of ROMs
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Ran,      // still running after the emulated time, what a game usually does
+    Settled,  // ended in a tight loop, e.g. a test ROM showing its results
+    Exited,   // ran SCHIP's 00FD
+    Halted,   // trapped, see the trap report in the result's messages
+    TimedOut, // needed more host time than the sweep allows per ROM
+    LoadFailed(String),
+}
+
+impl Outcome {
+    pub fn describe(&self) -> String {
+        match self {
+            Outcome::Ran => "ran".to_string(),
+            Outcome::Settled => "settled".to_string(),
+            Outcome::Exited => "exited".to_string(),
+            Outcome::Halted => "halted".to_string(),
+            Outcome::TimedOut => "timed out".to_string(),
+            Outcome::LoadFailed(reason) => format!("failed to load: {}", reason),
+        }
+    }
+
+    // Whether the ROM needs a closer look
+    pub fn is_problem(&self) -> bool {
+        matches!(self, Outcome::Halted | Outcome::TimedOut | Outcome::LoadFailed(_))
+    }
+}
+
+pub struct CompatResult {
+    pub path: String,
+    pub outcome: Outcome,
+    pub cycles: u64,
+    pub elapsed: Duration,
+    pub display_hash: u64,
+    pub messages: Vec<String>, // what the core reported on the way, e.g. a trap
+}
+
+// Limits for each ROM in a sweep
+#[derive(Clone, Copy)]
+pub struct CompatLimits {
+    pub cycles: u64,           // emulated instructions to run
+    pub cycles_per_frame: u64, // instructions between timer ticks
+    pub timeout: Duration,     // host time before giving up on the ROM
+}
+
+// Runs one ROM headless within the limits, from a plain file or a .c8b bundle (whose platform
+// overrides the quirks). Self-contained, so ROMs can run on as many threads as there are cores.
+pub fn run_rom(path: &str, quirks: Quirks, limits: CompatLimits) -> CompatResult {
+    let started = Instant::now();
+    let result = |outcome: Outcome, cycles: u64, display_hash: u64, messages: Vec<String>| CompatResult {
+        path: path.to_string(),
+        outcome,
+        cycles,
+        elapsed: started.elapsed(),
+        display_hash,
+        messages,
+    };
+
+    let (rom, quirks) = match std::fs::read(path) {
+        Ok(data) if bundle::is_bundle(&data) => match bundle::parse_bundle(&data) {
+            Ok(bundle) => (bundle.rom, bundle.platform.quirks()),
+            Err(e) => return result(Outcome::LoadFailed(e.to_string()), 0, 0, Vec::new()),
+        },
+        Ok(data) => (data, quirks),
+        Err(e) => return result(Outcome::LoadFailed(e.to_string()), 0, 0, Vec::new()),
+    };
+    let mut cpu = match Chip8::new(None, None, None, Display::new(64, 32, 1), Some(quirks)) {
+        Ok(cpu) => cpu,
+        Err(e) => return result(Outcome::LoadFailed(e.to_string()), 0, 0, Vec::new()),
+    };
+    cpu.load_rom(&rom, cpu.start_address());
+
+    // A second of emulated time at a time, checking the clock in between
+    let chunk = limits.cycles_per_frame.max(1) * 60;
+    let mut cycles = 0;
+    let outcome = loop {
+        if cycles >= limits.cycles {
+            break Outcome::Ran;
+        }
+        if started.elapsed() >= limits.timeout {
+            break Outcome::TimedOut;
+        }
+
+        let conditions = [HaltCondition::InfiniteLoop, HaltCondition::MaxCycles(chunk.min(limits.cycles - cycles))];
+        let run = cpu.run_headless(&conditions, limits.cycles_per_frame);
+        cycles += run.cycles;
+        if cpu.exited {
+            break Outcome::Exited;
+        }
+        if cpu.halted {
+            break Outcome::Halted;
+        }
+        if run.reason == HaltCondition::InfiniteLoop.describe() {
+            break Outcome::Settled;
+        }
+    };
+
+    let display_hash = cpu.display().hash();
+    result(outcome, cycles, display_hash, cpu.take_messages())
+}
+
+// A count per outcome, then the ROMs that need a closer look
+pub fn report(results: &[CompatResult]) -> String {
+    let count = |matches: fn(&Outcome) -> bool| results.iter().filter(|result| matches(&result.outcome)).count();
+    let mut out = format!(
+        "{} ROMs: {} ran, {} settled, {} exited, {} halted, {} timed out, {} failed to load\n",
+        results.len(),
+        count(|outcome| *outcome == Outcome::Ran),
+        count(|outcome| *outcome == Outcome::Settled),
+        count(|outcome| *outcome == Outcome::Exited),
+        count(|outcome| *outcome == Outcome::Halted),
+        count(|outcome| *outcome == Outcome::TimedOut),
+        count(|outcome| matches!(outcome, Outcome::LoadFailed(_))),
+    );
+
+    for result in results.iter().filter(|result| result.outcome.is_problem()) {
+        out += &format!(
+            "  {}: {} after {} cycles ({:.1}s)\n",
+            result.path,
+            result.outcome.describe(),
+            result.cycles,
+            result.elapsed.as_secs_f64()
+        );
+        for line in result.messages.iter().flat_map(|message| message.lines()) {
+            out += &format!("    {}\n", line);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(name: &str, program: &[u8]) -> CompatResult {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, program).unwrap();
+        let limits = CompatLimits { cycles: 10_000, cycles_per_frame: 10, timeout: Duration::from_secs(10) };
+        let result = run_rom(path.to_str().unwrap(), Quirks::default(), limits);
+        _ = std::fs::remove_file(&path);
+        result
+    }
+
+    #[test]
+    fn outcomes_tell_settled_exited_and_running_roms_apart() {
+        assert_eq!(run("chip8-compat-loop.ch8", &[0x12, 0x00]).outcome, Outcome::Settled); // JP 0x200
+        assert_eq!(run("chip8-compat-exit.ch8", &[0x00, 0xFD]).outcome, Outcome::Exited);
+
+        // V0 changes every time round, so this never looks like a tight loop
+        let counter = run("chip8-compat-count.ch8", &[0x70, 0x01, 0x12, 0x00]);
+        assert_eq!(counter.outcome, Outcome::Ran);
+        assert_eq!(counter.cycles, 10_000);
+
+        let missing = run_rom("/nonexistent/rom.ch8", Quirks::default(), CompatLimits { cycles: 1, cycles_per_frame: 1, timeout: Duration::ZERO });
+        assert!(missing.outcome.is_problem());
+        assert!(report(&[counter, missing]).starts_with("2 ROMs: 1 ran"));
+
+        // What the core reports comes back with the result, and under problem ROMs in the report
+        let odd = run("chip8-compat-odd.ch8", &[0x12, 0x03, 0x00, 0x12, 0x03]); // JP 0x203, which loops
+        assert_eq!(odd.messages, ["odd PC 0x0203: the instruction stream is misaligned"]);
+        let trapped = CompatResult { outcome: Outcome::Halted, messages: vec!["stack underflow\nat 0x0200".to_string()], ..odd };
+        assert!(report(&[trapped]).ends_with("s)\n    stack underflow\n    at 0x0200\n"));
+    }
+}
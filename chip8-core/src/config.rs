@@ -0,0 +1,183 @@
+use crate::autosave::AutosaveInterval;
+use crate::cpu::Quirks;
+use crate::keypad;
+use crate::memory::{MemoryInit, MemoryWrap};
+use crate::palette::Palette;
+use crate::segment::{self, Segment};
+use crate::toml::{self, Table};
+use std::io;
+
+// Emulator-wide settings from config.toml in the working directory. Everything is optional;
+// command line flags and per-ROM configs override it.
+//
+//   speed = 700               # instructions per second
+//   scale = 26                # window pixels per CHIP-8 pixel
+//   palette = "amber"         # or colors = ["#000000", "#FFFFFF", ...]
+//   pause_on_focus_loss = true
+//   mute_on_focus_loss = true
+//   global_hotkeys = true     # media keys pause and mute while unfocused (global-hotkeys feature)
+//   sticky_keys = ["5"]       # keys that toggle on each press instead of being held
+//   segments = ["overlay.bin@0x800"] # files loaded at an address besides the ROM
+//   autosave = "60s"          # save every 60 seconds (or "600f" frames) to autosave.sav beside [paths] savestate
+//
+//   [quirks]
+//   cosmac_shift = true
+//   cosmac_fx65 = false       # FX65 leaves I alone, FX55 goes by cosmac_fx55
+//   memory_size = 65536       # bytes I, PC and jumps address, 4096 for CHIP-8 and SCHIP
+//   megachip = true           # MEGACHIP's 256x192 color mode
+//   hires_chip8 = true        # two-page hi-res ROMs (starting with 1260) run 64x64
+//   start_address = 0x600     # where programs load and start, 0x200 but for the ETI-660
+//   display_wait = true       # sprites wait for the vertical blank, one draw per frame
+//   schip_lores_scroll = true # lores scrolls move by half pixels, or by whole ones when false
+//   resize_clear = false      # 00FE/00FF keep what's on screen instead of clearing it
+//   scroll_wait = true        # scrolls wait for the vertical blank, as SCHIP scrolled between frames
+//   fx0a_release = true       # FX0A waits for the key to be let go, not pressed
+//   vf_reset = true           # OR, AND and XOR clear VF
+//   fx1e_overflow = "wrap"   # I past the end on FX1E: flag (wrap, set VF past 0xFFF), wrap or none
+//
+//   [keys]                    # CHIP-8 key = host key
+//   C = "4"
+//
+//   [memory]
+//   init = "random"           # unloaded memory: zero, open-bus, random[:seed] or a byte
+//   wrap = "trap"             # accesses past memory_size: wrap (to 0) or trap (stop the CPU)
+//
+//   [paths]
+//   font = "font.bin"         # font files replace the built-in fonts; leave out to keep them
+//   bigfont = "bigfont.bin"
+//   savestate = "saves/savestate.sav" # slots go beside it as <rom>.<slot>.sav
+//   database = "chip-8-database/database/programs.json"  # known ROMs' platform, speed, colors
+pub struct Config {
+    pub speed: u32,
+    pub scale: u16,
+    pub quirks: Quirks,
+    pub keys: Vec<(u8, String)>,
+    pub sticky_keys: Vec<u8>,
+    pub palette: Option<Palette>,
+    pub pause_on_focus_loss: bool,
+    pub mute_on_focus_loss: bool,
+    pub global_hotkeys: bool,
+    pub font_path: Option<String>,
+    pub bigfont_path: Option<String>,
+    pub savestate_path: String,
+    pub database_path: Option<String>, // the CHIP-8 program database's programs.json
+    pub memory_init: MemoryInit,
+    pub memory_wrap: MemoryWrap,
+    pub segments: Vec<Segment>,
+    pub autosave: Option<AutosaveInterval>, // off unless configured
+}
+
+pub const CONFIG_PATH: &str = "config.toml";
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            speed: 700,
+            scale: 26,
+            quirks: Quirks::default(),
+            keys: Vec::new(),
+            sticky_keys: Vec::new(),
+            palette: None,
+            pause_on_focus_loss: true,
+            mute_on_focus_loss: true,
+            global_hotkeys: false,
+            font_path: None,
+            bigfont_path: None,
+            savestate_path: "savestate.sav".to_string(),
+            database_path: None,
+            memory_init: MemoryInit::Fill(0),
+            memory_wrap: MemoryWrap::Wrap,
+            segments: Vec::new(),
+            autosave: None,
+        }
+    }
+}
+
+impl Config {
+    // Loads the config, using the defaults when the file doesn't exist
+    pub fn load(path: &str) -> io::Result<Config> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Config::from_config(&toml::parse(&text)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn from_config(config: &Table) -> Result<Config, String> {
+        let defaults = Config::default();
+        let number = |name: &str, default: u32| match config.get(name) {
+            Some(value) => value
+                .as_int()
+                .and_then(|value| u32::try_from(value).ok())
+                .filter(|&value| value > 0)
+                .ok_or_else(|| format!("{} should be a positive number", name)),
+            None => Ok(default),
+        };
+
+        let flag = |name: &str, default: bool| match config.get(name) {
+            Some(value) => value.as_bool().ok_or_else(|| format!("{} should be true or false", name)),
+            None => Ok(default),
+        };
+
+        let keys = match config.get("keys") {
+            Some(keys) => keypad::bindings_from_config(keys.as_table().ok_or("keys should be a table")?)?,
+            None => Vec::new(),
+        };
+
+        let sticky_keys = match config.get("sticky_keys") {
+            Some(keys) => keypad::keys_from_config(keys.as_array().ok_or("sticky_keys should be a list of keys")?)?,
+            None => Vec::new(),
+        };
+
+        let segments = match config.get("segments") {
+            Some(segments) => segment::segments_from_config(segments.as_array().ok_or("segments should be a list")?)?,
+            None => Vec::new(),
+        };
+
+        let autosave = match config.get("autosave") {
+            Some(value) => Some(value.as_str().and_then(AutosaveInterval::parse).ok_or("autosave should be seconds or frames, e.g. \"60s\" or \"600f\"")?),
+            None => defaults.autosave,
+        };
+
+        let paths = match config.get("paths") {
+            Some(paths) => paths.as_table().ok_or("paths should be a table")?.clone(),
+            None => Table::new(),
+        };
+        let optional_path = |name: &str| match paths.get(name) {
+            Some(value) => value.as_str().map(|path| Some(path.to_string())).ok_or_else(|| format!("paths.{} should be a string", name)),
+            None => Ok(None),
+        };
+        let path = |name: &str, default: String| Ok::<String, String>(optional_path(name)?.unwrap_or(default));
+
+        let memory = config.get("memory").map(|memory| memory.as_table().ok_or("memory should be a table")).transpose()?;
+        let memory_init = match memory.and_then(|memory| memory.get("init")) {
+            Some(init) => MemoryInit::parse(init.as_str().ok_or("memory.init should be a string")?)?,
+            None => defaults.memory_init,
+        };
+        let memory_wrap = match memory.and_then(|memory| memory.get("wrap")) {
+            Some(wrap) => wrap.as_str().and_then(MemoryWrap::from_name).ok_or("memory.wrap should be \"wrap\" or \"trap\"")?,
+            None => defaults.memory_wrap,
+        };
+
+        Ok(Config {
+            speed: number("speed", defaults.speed)?,
+            scale: u16::try_from(number("scale", defaults.scale as u32)?).map_err(|_| "scale is too large")?,
+            quirks: Quirks::from_config(config)?,
+            keys,
+            sticky_keys,
+            palette: Palette::from_config(config)?,
+            pause_on_focus_loss: flag("pause_on_focus_loss", defaults.pause_on_focus_loss)?,
+            mute_on_focus_loss: flag("mute_on_focus_loss", defaults.mute_on_focus_loss)?,
+            global_hotkeys: flag("global_hotkeys", defaults.global_hotkeys)?,
+            font_path: optional_path("font")?,
+            bigfont_path: optional_path("bigfont")?,
+            savestate_path: path("savestate", defaults.savestate_path)?,
+            database_path: optional_path("database")?,
+            memory_init,
+            memory_wrap,
+            segments,
+            autosave,
+        })
+    }
+}
+
@@ -0,0 +1,3664 @@
+use crate::display::Display;
+use crate::audio::Audio;
+use crate::overlay::AudioScope;
+use crate::megachip::{self, Blend, MegaChip};
+use crate::error::EmulatorError;
+use crate::keypad::KeyWait;
+
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use crate::rle::{encode_rle, decode_rle_len};
+use crate::autosave::{Autosave, AutosaveInterval};
+use crate::persist::DiskWriter;
+use crate::quirk_report::QuirkReport;
+use crate::screenshot::encode_bmp;
+use crate::expr::ExprContext;
+use crate::watch::WatchPanel;
+use crate::rom_config::RomConfig;
+use crate::segment::Segment;
+use crate::palette::Palette;
+use crate::octo::OctoOptions;
+use crate::timing::TimingModel;
+use crate::timeline::Timeline;
+use crate::rewind::Rewind;
+use crate::delta;
+use crate::speedrun::SpeedrunTimer;
+use crate::achievements::{Achievement, Achievements};
+use crate::memory::{MemoryBus, MemoryInit, MemoryWrap, WriteHit};
+use crate::savestate::ImportedState;
+use crate::halt::{HaltCondition, HaltResult, LoopDetector};
+use crate::annotations::Annotations;
+use crate::blocks::BlockMap;
+use crate::tickrate::TickrateProbe;
+use crate::opcode_timing::OpcodeTiming;
+use crate::trace::{TraceEntry, TraceWriter};
+use crate::disasm;
+use crate::splash;
+use crate::rng::Rng;
+#[cfg(all(feature = "shm", unix))]
+use crate::shm::SharedState;
+use crate::toml::Table;
+use crate::input_profile::{self, InputProfile};
+use crate::backtrace::{self, MachineState};
+use crate::font::{self, FONT_ADDRESS, BIGFONT_ADDRESS, FONT_AREA_END};
+
+#[derive(Clone)]
+struct Timers {
+    pub delay: u8,
+    pub sound: u8,
+}
+
+#[derive(Clone)]
+struct Registers {
+    pub pc: u16,
+    pub sp: i8,
+    pub i: u32, // 16 bits but for MEGACHIP's 24-bit LDHI
+    pub v: [u8; 16],
+}
+
+// The whole machine state, kept in memory rather than serialized: run-ahead rolls back to
+// one, and embedders and test harnesses can take and restore them with Chip8::snapshot and
+// Chip8::restore. Settings (quirks, palettes, config) aren't part of it.
+#[derive(Clone)]
+pub struct Snapshot {
+    registers: Registers,
+    stack: [u16; 32],
+    timers: Timers,
+    memory: MemoryBus,
+    pixels: Vec<u32>,
+    plane2: Vec<u32>,
+    planes: u8,
+    size: (u16, u16, u16), // width, height, scale
+    keys: [bool; 16],
+    key_wait: KeyWait,
+    beep: bool,
+    audio: Audio,
+    megachip: Option<MegaChip>,
+    colors: Option<Vec<u32>>,
+    halted: bool,
+    exited: bool,
+    cycles: u64,
+    vblank: bool,
+    half_scroll: i8,
+    rng: Rng,
+}
+
+// What rewind keeps of a frame as is; memory and the screen go in its byte deltas
+#[derive(Clone)]
+struct RewindFrame {
+    registers: Registers,
+    stack: [u16; 32],
+    timers: Timers,
+    size: (u16, u16, u16), // width, height, scale
+    pixel_words: usize,
+    planes: u8,
+    keys: [bool; 16],
+    key_wait: KeyWait,
+    audio: Audio,
+    megachip: bool,
+    colors: Option<usize>, // MEGACHIP's presented screen, in words
+    halted: bool,
+    exited: bool,
+    cycles: u64,
+    vblank: bool,
+    half_scroll: i8,
+    rng: Rng,
+}
+
+// Reads a savestate's chunks in order, failing with InvalidData where the file is cut short
+// or isn't what write_state writes
+struct StateFile<'a> {
+    data: &'a [u8],
+    idx: usize,
+}
+
+impl<'a> StateFile<'a> {
+    fn invalid(&self, message: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, format!("savestate offset {}: {}", self.idx, message))
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self.data.get(self.idx..self.idx + len).ok_or_else(|| self.invalid("file ends early"))?;
+        self.idx += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    // The next chunk's tag, None at the end of the file
+    fn tag(&mut self) -> io::Result<Option<&'a str>> {
+        if self.idx == self.data.len() {
+            return Ok(None);
+        }
+        let tag = self.take(4)?;
+        std::str::from_utf8(tag).map(Some).map_err(|_| self.invalid("expected a chunk tag"))
+    }
+
+    fn expect(&mut self, expected: &str) -> io::Result<()> {
+        match self.tag()? {
+            Some(tag) if tag == expected => Ok(()),
+            _ => Err(self.invalid(&format!("expected {}", expected))),
+        }
+    }
+
+    fn rle(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let (decoded, used) = decode_rle_len(&self.data[self.idx..], len).ok_or_else(|| self.invalid("memory doesn't decode"))?;
+        self.idx += used;
+        Ok(decoded)
+    }
+
+    // Memory was 0xFFFF bytes until partway through format 1.1, and 0x10000 after
+    fn old_memory(&mut self) -> io::Result<Vec<u8>> {
+        for len in [0xFFFF, 0x10000] {
+            if let Some((memory, used)) = decode_rle_len(&self.data[self.idx..], len) {
+                if self.data[self.idx + used..].starts_with(b"DISP") {
+                    self.idx += used;
+                    return Ok(memory);
+                }
+            }
+        }
+        Err(self.invalid("memory doesn't decode"))
+    }
+
+    // A plane of len words. Before format 1.3 planes were run-length encoded by word, where a
+    // word with a zero low byte is written like the start of a run of zeros, so both readings
+    // of each zero byte are tried, keeping the one that ends on a chunk or the end of the file.
+    fn plane(&mut self, len: usize, by_word: bool) -> io::Result<Vec<u32>> {
+        if !by_word {
+            return self.rle(len * 4).map(|bytes| delta::bytes_to_words(&bytes));
+        }
+        let mut words = Vec::with_capacity(len);
+        let used = decode_word_plane(&self.data[self.idx..], 0, len, &mut words, &mut HashSet::new()).ok_or_else(|| self.invalid("screen doesn't decode"))?;
+        self.idx += used;
+        Ok(words)
+    }
+}
+
+// The optional chunks that can follow a plane
+const STATE_CHUNKS: [&str; 6] = ["PLN2", "AUDI", "CONF", "SEED", "MEGA", "STAT"];
+
+// Decodes word-RLE data at idx onto words until there are len of them, returning where it
+// ended. failed remembers the (idx, words) points already known not to lead anywhere.
+fn decode_word_plane(data: &[u8], idx: usize, len: usize, words: &mut Vec<u32>, failed: &mut HashSet<(usize, usize)>) -> Option<usize> {
+    let decoded = words.len();
+    if decoded == len {
+        let rest = &data[idx..];
+        return (rest.is_empty() || STATE_CHUNKS.iter().any(|tag| rest.starts_with(tag.as_bytes()))).then_some(idx);
+    }
+    if failed.contains(&(idx, decoded)) {
+        return None;
+    }
+
+    if *data.get(idx)? == 0 {
+        let run = data.get(idx + 1..idx + 5).map(|count| u32::from_le_bytes(count.try_into().unwrap()) as usize);
+        if let Some(run @ 1..) = run.filter(|&run| decoded + run <= len) {
+            words.resize(decoded + run, 0);
+            if let Some(end) = decode_word_plane(data, idx + 5, len, words, failed) {
+                return Some(end);
+            }
+            words.truncate(decoded);
+        }
+    }
+    // Zero words are always written as runs, so a literal word is never zero
+    if let Some(word) = data.get(idx..idx + 4).map(|word| u32::from_le_bytes(word.try_into().unwrap())).filter(|&word| word != 0) {
+        words.push(word);
+        if let Some(end) = decode_word_plane(data, idx + 4, len, words, failed) {
+            return Some(end);
+        }
+        words.truncate(decoded);
+    }
+    failed.insert((idx, decoded));
+    None
+}
+
+enum Scroll {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+// 00CN, 00BN/00DN, 00FB and 00FC
+fn is_scroll(raw: u16) -> bool {
+    matches!(raw & 0xFFF0, 0x00B0 | 0x00C0 | 0x00D0) || matches!(raw, 0x00FB | 0x00FC)
+}
+
+struct Instruction {
+    pub raw: u16,
+    pub op: u8,
+    pub x: u8,
+    pub y: u8,
+    pub n: u8,
+    pub nn: u8,
+    pub nnn: u16,
+}
+
+#[derive(Clone)]
+pub struct Quirks {
+    pub cosmac_shift: bool,
+    pub fx1e_overflow: Fx1eOverflow, // FX1E carrying I past the end of memory
+    pub cosmac_fx55: bool, // FX55 leaves I past the last register stored
+    pub cosmac_fx65: bool, // FX65 leaves I past the last register loaded
+    pub cosmac_bnnn: bool,
+    pub min_sound_ticks: u8, // FX18 values below this still beep this long, 0 = off
+    pub schip_lores_scroll: bool, // scrolls in lores move half as far, by half pixels, as on SCHIP 1.1
+    pub schip_lores_dxy0: bool, // DXY0 in lores draws 8x16 instead of 16x16, as on SCHIP 1.1
+    pub schip_row_collisions: bool, // VF counts the rows that collided in hires, as on SCHIP 1.1
+    pub memory_size: usize, // bytes I, PC and jumps can address, 4K before XO-CHIP
+    pub megachip: bool, // MEGACHIP's 0NNN extensions: 0011 switches to its 256x192 color mode
+    pub hires_chip8: bool, // ROMs starting with the two-page 1260 stub run on a 64x64 screen from 0x2C0
+    pub start_address: u16, // where programs load and start, 0x600 on the ETI-660
+    pub display_wait: bool, // DXYN waits for the next 60hz tick, so at most one sprite draws per frame
+    pub scroll_wait: bool, // scrolls wait for the next 60hz tick, landing between frames as on SCHIP hardware
+    pub resize_clear: bool, // 00FE and 00FF clear the screen, where XO-CHIP keeps it, scaled to the new size
+    pub fx0a_release: bool, // FX0A takes a key once it's let go rather than when it goes down, as on the COSMAC VIP
+    pub vf_reset: bool, // 8XY1, 8XY2 and 8XY3 clear VF, as on the COSMAC VIP
+}
+
+// What FX1E does when I + VX goes past the end of memory
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fx1eOverflow {
+    Flag,      // wrap at the end of memory and set VF once I passes 0xFFF, as the Amiga interpreter did
+    Wrap,      // wrap to the start of memory, VF untouched
+    Unbounded, // no wrap, I keeps counting and accesses past the end go by [memory] wrap
+}
+
+impl Fx1eOverflow {
+    pub fn from_name(name: &str) -> Option<Fx1eOverflow> {
+        match name {
+            "flag" => Some(Fx1eOverflow::Flag),
+            "wrap" => Some(Fx1eOverflow::Wrap),
+            "none" => Some(Fx1eOverflow::Unbounded),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fx1eOverflow::Flag => "flag",
+            Fx1eOverflow::Wrap => "wrap",
+            Fx1eOverflow::Unbounded => "none",
+        }
+    }
+}
+
+// Quirks that can be flipped while a ROM runs, in the order the quirks page lists them. The
+// ones that change the memory map or how a ROM loads (memory_size, megachip, hires_chip8,
+// start_address) would only take effect on the next load, so they're left to the config.
+pub const RUNTIME_QUIRKS: [&str; 13] = [
+    "cosmac_shift",
+    "cosmac_fx55",
+    "cosmac_fx65",
+    "cosmac_bnnn",
+    "vf_reset",
+    "display_wait",
+    "scroll_wait",
+    "resize_clear",
+    "fx0a_release",
+    "schip_lores_scroll",
+    "schip_lores_dxy0",
+    "schip_row_collisions",
+    "fx1e_overflow",
+];
+
+// Names accepted by Quirks::preset
+pub const QUIRK_PRESETS: [&str; 6] = ["chip8", "chip48", "schip1.1", "xochip", "megachip", "eti660"];
+
+// Frames of play without a savestate before quitting asks to save first, a minute
+const UNSAVED_PROGRESS_FRAMES: u64 = 60 * 60;
+
+// Messages kept for a frontend that never takes them, the oldest going first
+const MAX_MESSAGES: usize = 256;
+
+impl Quirks {
+    // Quirks of a well-known interpreter. Every field is spelled out so a new quirk has to be
+    // decided for each preset.
+    pub fn preset(name: &str) -> Option<Quirks> {
+        match name {
+            // The original COSMAC VIP interpreter
+            "chip8" => Some(Quirks {
+                cosmac_shift: true,
+                fx1e_overflow: Fx1eOverflow::Wrap,
+                cosmac_fx55: true,
+                cosmac_fx65: true,
+                cosmac_bnnn: true,
+                min_sound_ticks: 2,
+                schip_lores_scroll: false,
+                schip_lores_dxy0: false,
+                schip_row_collisions: false,
+                memory_size: 0x1000,
+                megachip: false,
+                hires_chip8: true,
+                start_address: 0x200,
+                display_wait: true,
+                scroll_wait: false,
+                resize_clear: true,
+                fx0a_release: true,
+                vf_reset: true,
+            }),
+            // CHIP-48 on the HP 48, which SUPER-CHIP grew out of
+            "chip48" => Some(Quirks {
+                cosmac_shift: false,
+                fx1e_overflow: Fx1eOverflow::Flag,
+                cosmac_fx55: false,
+                cosmac_fx65: false,
+                cosmac_bnnn: false,
+                min_sound_ticks: 0,
+                schip_lores_scroll: false,
+                schip_lores_dxy0: false,
+                schip_row_collisions: false,
+                memory_size: 0x1000,
+                megachip: false,
+                hires_chip8: false,
+                start_address: 0x200,
+                display_wait: false,
+                scroll_wait: false,
+                resize_clear: true,
+                fx0a_release: false,
+                vf_reset: false,
+            }),
+            "schip1.1" => Some(Quirks {
+                cosmac_shift: false,
+                fx1e_overflow: Fx1eOverflow::Flag,
+                cosmac_fx55: false,
+                cosmac_fx65: false,
+                cosmac_bnnn: false,
+                min_sound_ticks: 0,
+                schip_lores_scroll: true,
+                schip_lores_dxy0: true,
+                schip_row_collisions: true,
+                memory_size: 0x1000,
+                megachip: false,
+                hires_chip8: false,
+                start_address: 0x200,
+                display_wait: false,
+                scroll_wait: false,
+                resize_clear: true,
+                fx0a_release: false,
+                vf_reset: false,
+            }),
+            // Octo's XO-CHIP
+            "xochip" => Some(Quirks {
+                cosmac_shift: true,
+                fx1e_overflow: Fx1eOverflow::Wrap,
+                cosmac_fx55: true,
+                cosmac_fx65: true,
+                cosmac_bnnn: true,
+                min_sound_ticks: 0,
+                schip_lores_scroll: false,
+                schip_lores_dxy0: false,
+                schip_row_collisions: false,
+                memory_size: 0x10000,
+                megachip: false,
+                hires_chip8: false,
+                start_address: 0x200,
+                display_wait: false,
+                scroll_wait: false,
+                resize_clear: false,
+                fx0a_release: false,
+                vf_reset: false,
+            }),
+            // MEGACHIP 1.0, SCHIP 1.1 with 24-bit addresses and a color mode
+            "megachip" => Some(Quirks {
+                cosmac_shift: false,
+                fx1e_overflow: Fx1eOverflow::Flag,
+                cosmac_fx55: false,
+                cosmac_fx65: false,
+                cosmac_bnnn: false,
+                min_sound_ticks: 0,
+                schip_lores_scroll: true,
+                schip_lores_dxy0: true,
+                schip_row_collisions: true,
+                memory_size: 0x1000000,
+                megachip: true,
+                hires_chip8: false,
+                start_address: 0x200,
+                display_wait: false,
+                scroll_wait: false,
+                resize_clear: true,
+                fx0a_release: false,
+                vf_reset: false,
+            }),
+            // The ETI-660's CHIP-8, which loads programs at 0x600
+            "eti660" => Some(Quirks {
+                cosmac_shift: true,
+                fx1e_overflow: Fx1eOverflow::Wrap,
+                cosmac_fx55: true,
+                cosmac_fx65: true,
+                cosmac_bnnn: true,
+                min_sound_ticks: 2,
+                schip_lores_scroll: false,
+                schip_lores_dxy0: false,
+                schip_row_collisions: false,
+                memory_size: 0x1000,
+                megachip: false,
+                hires_chip8: false,
+                start_address: 0x600,
+                display_wait: true,
+                scroll_wait: false,
+                resize_clear: true,
+                fx0a_release: true,
+                vf_reset: true,
+            }),
+            _ => None,
+        }
+    }
+
+    fn flag(&self, name: &str) -> Option<bool> {
+        match name {
+            "cosmac_shift" => Some(self.cosmac_shift),
+            "cosmac_fx55" => Some(self.cosmac_fx55),
+            "cosmac_fx65" => Some(self.cosmac_fx65),
+            "cosmac_bnnn" => Some(self.cosmac_bnnn),
+            "vf_reset" => Some(self.vf_reset),
+            "display_wait" => Some(self.display_wait),
+            "scroll_wait" => Some(self.scroll_wait),
+            "resize_clear" => Some(self.resize_clear),
+            "fx0a_release" => Some(self.fx0a_release),
+            "schip_lores_scroll" => Some(self.schip_lores_scroll),
+            "schip_lores_dxy0" => Some(self.schip_lores_dxy0),
+            "schip_row_collisions" => Some(self.schip_row_collisions),
+            _ => None,
+        }
+    }
+
+    fn flag_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "cosmac_shift" => Some(&mut self.cosmac_shift),
+            "cosmac_fx55" => Some(&mut self.cosmac_fx55),
+            "cosmac_fx65" => Some(&mut self.cosmac_fx65),
+            "cosmac_bnnn" => Some(&mut self.cosmac_bnnn),
+            "vf_reset" => Some(&mut self.vf_reset),
+            "display_wait" => Some(&mut self.display_wait),
+            "scroll_wait" => Some(&mut self.scroll_wait),
+            "resize_clear" => Some(&mut self.resize_clear),
+            "fx0a_release" => Some(&mut self.fx0a_release),
+            "schip_lores_scroll" => Some(&mut self.schip_lores_scroll),
+            "schip_lores_dxy0" => Some(&mut self.schip_lores_dxy0),
+            "schip_row_collisions" => Some(&mut self.schip_row_collisions),
+            _ => None,
+        }
+    }
+
+    // A runtime quirk's setting as the quirks page shows it: on, off, or fx1e_overflow's name
+    pub fn setting(&self, name: &str) -> Option<&'static str> {
+        match name {
+            "fx1e_overflow" => Some(self.fx1e_overflow.name()),
+            _ => self.flag(name).map(|on| if on { "on" } else { "off" }),
+        }
+    }
+
+    // Flips one of RUNTIME_QUIRKS, fx1e_overflow going on to its next setting. False for any
+    // other name.
+    pub fn toggle(&mut self, name: &str) -> bool {
+        if name == "fx1e_overflow" {
+            self.fx1e_overflow = match self.fx1e_overflow {
+                Fx1eOverflow::Flag => Fx1eOverflow::Wrap,
+                Fx1eOverflow::Wrap => Fx1eOverflow::Unbounded,
+                Fx1eOverflow::Unbounded => Fx1eOverflow::Flag,
+            };
+            return true;
+        }
+        match self.flag_mut(name) {
+            Some(flag) => {
+                *flag = !*flag;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Reads a [quirks] table, e.g. `cosmac_shift = true` or `min_sound_ticks = 2`. Quirks it
+    // doesn't mention keep their defaults.
+    pub fn from_config(config: &Table) -> Result<Quirks, String> {
+        let mut quirks = Quirks::default();
+        let Some(table) = config.get("quirks") else {
+            return Ok(quirks);
+        };
+        let table = table.as_table().ok_or("quirks should be a table")?;
+
+        for (name, value) in table {
+            let flag = || value.as_bool().ok_or_else(|| format!("quirk {} should be true or false", name));
+            match name.as_str() {
+                "cosmac_shift" => quirks.cosmac_shift = flag()?,
+                "fx1e_overflow" => {
+                    quirks.fx1e_overflow = value
+                        .as_str()
+                        .and_then(Fx1eOverflow::from_name)
+                        .ok_or("quirk fx1e_overflow should be \"flag\", \"wrap\" or \"none\"")?
+                }
+                // Before fx1e_overflow, true for wrapping without VF
+                "cosmac_fx1e" => quirks.fx1e_overflow = if flag()? { Fx1eOverflow::Wrap } else { Fx1eOverflow::Flag },
+                "cosmac_fx55" => quirks.cosmac_fx55 = flag()?,
+                "cosmac_fx65" => quirks.cosmac_fx65 = flag()?,
+                "cosmac_bnnn" => quirks.cosmac_bnnn = flag()?,
+                "schip_lores_scroll" => quirks.schip_lores_scroll = flag()?,
+                "schip_lores_dxy0" => quirks.schip_lores_dxy0 = flag()?,
+                "schip_row_collisions" => quirks.schip_row_collisions = flag()?,
+                "megachip" => quirks.megachip = flag()?,
+                "hires_chip8" => quirks.hires_chip8 = flag()?,
+                "display_wait" => quirks.display_wait = flag()?,
+                "scroll_wait" => quirks.scroll_wait = flag()?,
+                "resize_clear" => quirks.resize_clear = flag()?,
+                "fx0a_release" => quirks.fx0a_release = flag()?,
+                "vf_reset" => quirks.vf_reset = flag()?,
+                "min_sound_ticks" => {
+                    quirks.min_sound_ticks = value
+                        .as_int()
+                        .and_then(|ticks| u8::try_from(ticks).ok())
+                        .ok_or("quirk min_sound_ticks should be 0-255")?
+                }
+                "start_address" => {
+                    quirks.start_address = value
+                        .as_int()
+                        .and_then(|address| u16::try_from(address).ok())
+                        .filter(|&address| address >= FONT_AREA_END)
+                        .ok_or("quirk start_address should be an address above the fonts, e.g. 0x600")?
+                }
+                "memory_size" => {
+                    quirks.memory_size = value
+                        .as_int()
+                        .and_then(|size| usize::try_from(size).ok())
+                        .filter(|size| (0x200..=0x1000000).contains(size))
+                        .ok_or("quirk memory_size should be 512-16777216")?
+                }
+                _ => return Err(format!("unknown quirk {}", name)),
+            }
+        }
+
+        Ok(quirks)
+    }
+}
+
+// What to do when the CPU hits an opcode it doesn't know
+#[derive(Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum UnknownOpcodePolicy {
+    Ignore,
+    Warn, // log it, capture a screenshot and state dump, and keep going
+    Halt, // same as Warn, then stop the CPU
+}
+
+// What to do when the PC lands on an odd address, which skews the instruction stream
+#[derive(Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum OddPcPolicy {
+    Allow,
+    Warn, // log it once per address and keep going
+    Trap, // capture a screenshot and state dump, then stop the CPU
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks {
+            cosmac_shift: false, // Chip8: TRUE
+            fx1e_overflow: Fx1eOverflow::Flag, // Chip8: Wrap
+            cosmac_fx55: false, // Chip8: FALSE
+            cosmac_fx65: false, // Chip8: TRUE
+            cosmac_bnnn: false, // Chip8: TRUE
+            min_sound_ticks: 0, // Chip8: 2
+            schip_lores_scroll: false, // SCHIP: TRUE
+            schip_lores_dxy0: false, // SCHIP: TRUE
+            schip_row_collisions: false, // SCHIP: TRUE
+            memory_size: 0x10000, // Chip8: 0x1000
+            megachip: false, // MEGACHIP: TRUE
+            hires_chip8: false, // Chip8: TRUE
+            start_address: 0x200, // ETI-660: 0x600
+            display_wait: false, // Chip8: TRUE
+            scroll_wait: false, // SCHIP hardware: TRUE, off in the presets
+            resize_clear: true, // XO-CHIP: FALSE
+            fx0a_release: false, // Chip8: TRUE
+            vf_reset: false, // Chip8: TRUE
+        }
+    }
+}
+
+pub struct Chip8 {
+    registers: Registers,
+    timers: Timers,
+    stack: [u16; 32],
+    memory: MemoryBus,
+    display: Display,
+    quirks: Quirks,
+    autosave: Option<Autosave>,
+    writer: DiskWriter,
+    strict: Option<QuirkReport>,
+    pub unknown_opcode_policy: UnknownOpcodePolicy,
+    pub odd_pc_policy: OddPcPolicy,
+    odd_pcs: HashSet<u16>,
+    pub halted: bool,
+    pub exited: bool, // the program ran SCHIP's 00FD exit, which also halts; cleared by reset()
+    megachip: Option<MegaChip>, // set while in MEGACHIP's color mode
+    paused: bool, // e.g. while the window is in the background
+    crash_captures: HashSet<u16>,
+    speculative: bool, // running run-ahead frames that get rolled back, so nothing is reported
+    pub watches: WatchPanel,
+    pub speedrun: Option<SpeedrunTimer>,
+    pub symbols: Option<Annotations>, // labels for trap reports
+    pub blocks: Option<BlockMap>,     // basic block execution counts, when profiling
+    pub tickrate: Option<TickrateProbe>, // work done per frame, when suggesting a speed
+    pub opcode_timing: Option<OpcodeTiming>, // host time per instruction class, when profiling
+    trace: Option<TraceWriter>, // every instruction to a trace file, when recording
+    pub input_profiles: Vec<InputProfile>,
+    input_profile: Option<String>,
+    rng: Rng,                         // CXNN's generator, saved with the state
+    pub frozen: Vec<(u16, u8)>,       // cheats: addresses held at a value, see freeze()
+    pub achievements: Achievements,
+    rom_config: Option<RomConfig>,
+    timing: Option<TimingModel>,
+    last_cpu_tick: Instant,
+    rom_end: usize, // end of the loaded program, where unloaded memory starts again
+    rom: Option<(u16, Vec<u8>)>, // the program as loaded and where, for reset() to put back
+    segments: Vec<(u16, Vec<u8>)>, // files loaded besides the program, put back the same way
+    cycles: u64, // instructions executed
+    unsaved_frames: u64, // frames run since the last savestate
+    flags_written: bool, // RPL flags stored since the last savestate
+    vblank: bool, // a frame has ticked since the last sprite, for display_wait
+    half_scroll: i8, // half a lores pixel left from an odd SCHIP scroll, 1 down or -1 up
+    timeline: Option<Timeline>,
+    rewind: Option<Rewind<RewindFrame>>, // the last frames, when rewinding is on
+    messages: Vec<String>, // for the user, until the frontend takes them
+    #[cfg(all(feature = "shm", unix))]
+    pub shared_state: Option<SharedState>, // published every frame, see shm.rs
+}
+
+impl Chip8 {
+    // Creates a new Chip8 instance with the built-in Octo fonts, or font files when paths are
+    // given, and the program loaded at the start address (usually 0x200) if there is one
+    pub fn new(font_path: Option<&str>, bigfont_path: Option<&str>, program_path: Option<&str>, display: Display, quirks: Option<Quirks>) -> Result<Chip8, EmulatorError> {
+        let quirks: Quirks = quirks.unwrap_or_default();
+        let start = quirks.start_address;
+
+        let mut cpu: Chip8 = Chip8 {
+            registers: Registers {
+                pc: start,
+                sp: -1,
+                i: 0,
+                v: [0; 16],
+            },
+            timers: Timers {
+                delay: 0,
+                sound: 0,
+            },
+            stack: [0; 32],
+            memory: MemoryBus::new(quirks.memory_size.max(0x10000), MemoryWrap::Wrap),
+            display,
+            quirks,
+            autosave: None,
+            writer: DiskWriter::new(),
+            strict: None,
+            unknown_opcode_policy: UnknownOpcodePolicy::Warn,
+            odd_pc_policy: OddPcPolicy::Warn,
+            odd_pcs: HashSet::new(),
+            halted: false,
+            exited: false,
+            megachip: None,
+            paused: false,
+            crash_captures: HashSet::new(),
+            speculative: false,
+            watches: WatchPanel::default(),
+            speedrun: None,
+            symbols: None,
+            blocks: None,
+            tickrate: None,
+            opcode_timing: None,
+            trace: None,
+            input_profiles: Vec::new(),
+            input_profile: None,
+            rng: Rng::from_entropy(),
+            frozen: Vec::new(),
+            achievements: Achievements::default(),
+            rom_config: None,
+            timing: None,
+            last_cpu_tick: Instant::now(),
+            rom_end: start as usize,
+            rom: None,
+            segments: Vec::new(),
+            cycles: 0,
+            unsaved_frames: 0,
+            flags_written: false,
+            vblank: false,
+            half_scroll: 0,
+            timeline: None,
+            rewind: None,
+            messages: Vec::new(),
+            #[cfg(all(feature = "shm", unix))]
+            shared_state: None,
+        };
+
+        cpu.memory.set_limit(cpu.quirks.memory_size);
+        cpu.load_font(font::font("octo").unwrap(), FONT_ADDRESS);
+        cpu.load_font(font::bigfont("octo").unwrap(), BIGFONT_ADDRESS);
+        if let Some(path) = font_path {
+            cpu.load_font_file(path, FONT_ADDRESS, 5)?;
+        }
+        if let Some(path) = bigfont_path {
+            cpu.load_font_file(path, BIGFONT_ADDRESS, 10)?;
+        }
+        if let Some(path) = program_path {
+            cpu.read_to_memory(path, start)?;
+        }
+        Ok(cpu)
+    }
+
+    // Fetch two bytes for memory (an instruction is two bytes), None if the fetch trapped
+    fn fetch(&mut self) -> Option<u16> {
+        let fetched: u16 = self.memory.read_word(self.registers.pc);
+        self.registers.pc = self.address(self.registers.pc as u32 + 2) as u16;
+
+        if let Some(address) = self.memory.take_fault() {
+            self.trap(&format!("fetch past the end of memory ({:#06X})", address));
+            return None;
+        }
+
+        Some(fetched)
+    }
+
+    // Skips the next instruction, all four bytes of it when that's XO-CHIP's F000 NNNN
+    fn skip(&mut self) {
+        let pc = self.registers.pc as u32;
+        let next = (self.memory[self.address(pc) as usize] as u16) << 8 | self.memory[self.address(pc + 1) as usize] as u16;
+        self.registers.pc = self.address(pc + disasm::instruction_size(next) as u32) as u16;
+    }
+
+    // Wraps an address computed from I, PC or a jump to the memory the interpreter addresses:
+    // 12 bits for CHIP-8 and SCHIP, all 16 for XO-CHIP. Accesses through it past the bus's
+    // end still wrap or trap by the bus's policy.
+    fn address(&self, address: u32) -> u32 {
+        address % self.quirks.memory_size as u32
+    }
+
+    // What fetches past the end of memory do
+    pub fn set_memory_wrap(&mut self, wrap: MemoryWrap) {
+        self.memory.wrap = wrap;
+    }
+
+    // Fills the memory no font or program was loaded into, between the fonts and the start
+    // address and after the program, e.g. with random bytes to catch ROMs that count on zeroed RAM
+    pub fn set_memory_init(&mut self, init: MemoryInit) {
+        self.memory.init = init;
+        self.memory.initialize(FONT_AREA_END as usize..self.quirks.start_address as usize);
+        let len = self.memory.len();
+        self.memory.initialize(self.rom_end.min(len)..len);
+        self.place_segments();
+    }
+
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    // Flips one of RUNTIME_QUIRKS while the ROM runs, to find the setting a misbehaving ROM
+    // needs without restarting it. Returns the new setting, None for an unknown quirk.
+    pub fn toggle_quirk(&mut self, name: &str) -> Option<&'static str> {
+        if !self.quirks.toggle(name) {
+            return None;
+        }
+        let setting = self.quirks.setting(name)?;
+        self.messages.push(format!("quirk {} {}", name, setting));
+        Some(setting)
+    }
+
+    // Where programs load and start, 0x200 but for the ETI-660
+    pub fn start_address(&self) -> u16 {
+        self.quirks.start_address
+    }
+
+    pub fn memory_bus(&self) -> &MemoryBus {
+        &self.memory
+    }
+
+    // Holds a byte at a value, rewriting it every frame whatever the ROM stores there
+    pub fn freeze(&mut self, address: u16, value: u8) {
+        self.unfreeze(address);
+        self.frozen.push((address, value));
+    }
+
+    pub fn unfreeze(&mut self, address: u16) {
+        self.frozen.retain(|&(frozen, _)| frozen != address);
+    }
+
+    // Reports CPU stores into the range through take_write_hits and starts tracking the last
+    // writer of every byte
+    pub fn watch_writes(&mut self, range: Range<u32>) {
+        self.memory.track_writers(true);
+        self.memory.watch_writes(range);
+    }
+
+    pub fn take_write_hits(&mut self) -> Vec<WriteHit> {
+        self.memory.take_hits()
+    }
+
+    // Decodes a u16 into an Instruction
+    fn decode(&self, instruction: u16) -> Instruction {
+        Instruction {
+            raw: instruction,
+            op: (instruction >> 12) as u8,
+            x: ((instruction & 0x0F00) >> 8) as u8,
+            y: ((instruction & 0x00F0) >> 4) as u8,
+            n: (instruction & 0x000F) as u8,
+            nn: (instruction & 0x00FF) as u8,
+            nnn: instruction & 0x0FFF,
+        }
+    }
+
+    // Executes an Instruction
+    fn execute(&mut self, instruction: Instruction) {
+        match instruction.op {
+            // SCHIP scrolls the screen between frames, so a scroll holds until the next tick
+            0x0 if self.quirks.scroll_wait && !self.vblank && is_scroll(instruction.raw) => self.registers.pc = self.registers.pc.wrapping_sub(2),
+            0x0 if self.quirks.megachip && self.execute_megachip(&instruction) => {}
+            0x0 => match instruction.raw {
+                0x00E0 => self.display.clear_planes(),
+
+                0x00EE => {
+                    if self.registers.sp < 0 {
+                        self.trap("stack underflow");
+                        return;
+                    }
+                    self.registers.pc = self.stack[self.registers.sp as usize];
+                    self.registers.sp -= 1;
+                }
+
+                0x00FF => self.set_resolution(128, 64, self.display.original_scale / 2),
+                0x00FE => self.set_resolution(64, 32, self.display.original_scale),
+                0x00FB => self.scroll(Scroll::Right, 4),
+                0x00FC => self.scroll(Scroll::Left, 4),
+
+                0x00FD => {
+                    self.exited = true;
+                    self.halted = true;
+                },
+
+                // The two-page hi-res interpreter's clear screen
+                0x0230 if self.quirks.hires_chip8 => self.display.clear_planes(),
+
+                _ => match instruction.raw & 0xFFF0 {
+                    0x00C0 => self.scroll(Scroll::Down, instruction.n),
+                    0x00B0 | 0x00D0 => self.scroll(Scroll::Up, instruction.n),
+                    _ => {}
+                }
+            },
+
+            0x1 => self.registers.pc = instruction.nnn,
+
+            0x2 => {
+                if self.registers.sp + 1 >= self.stack.len() as i8 {
+                    self.trap("stack overflow");
+                    return;
+                }
+                self.registers.sp += 1;
+                self.stack[self.registers.sp as usize] = self.registers.pc;
+                self.registers.pc = instruction.nnn;
+            }
+
+            0x3 => if self.registers.v[instruction.x as usize] == instruction.nn {
+                self.skip();
+            }
+
+            0x4 => if self.registers.v[instruction.x as usize] != instruction.nn {
+                self.skip();
+            }
+
+            0x5 => match instruction.raw & 0x000F {
+                0x2 => _ = self.write_flags(instruction.x as usize, instruction.y as usize),
+                0x3 => _ = self.read_flags(instruction.x as usize, instruction.y as usize),
+
+                _ => if self.registers.v[instruction.x as usize] == self.registers.v[instruction.y as usize] {
+                    self.skip();
+                }
+            }
+
+            0x6 => self.registers.v[instruction.x as usize] = instruction.nn,
+            0x7 => self.registers.v[instruction.x as usize] = self.registers.v[instruction.x as usize].wrapping_add(instruction.nn),
+
+            0x8 => match instruction.raw & 0x000F {
+                0x0 => self.registers.v[instruction.x as usize] = self.registers.v[instruction.y as usize],
+                0x1 => {
+                    self.registers.v[instruction.x as usize] |= self.registers.v[instruction.y as usize];
+                    self.reset_vf();
+                }
+                0x2 => {
+                    self.registers.v[instruction.x as usize] &= self.registers.v[instruction.y as usize];
+                    self.reset_vf();
+                }
+                0x3 => {
+                    self.registers.v[instruction.x as usize] ^= self.registers.v[instruction.y as usize];
+                    self.reset_vf();
+                }
+                
+                0x4 => {
+                    let mut added: u16 = self.registers.v[instruction.x as usize] as u16 + self.registers.v[instruction.y as usize] as u16;
+                    self.convert_with_carry(&mut added);
+                    self.registers.v[instruction.x as usize] = added as u8;
+                }
+
+                0x5 => {
+                    let x = self.registers.v[instruction.x as usize];
+                    let y = self.registers.v[instruction.y as usize];
+                    self.registers.v[instruction.x as usize] = x.wrapping_sub(y);
+                    self.registers.v[0xF] = (x >= y) as u8;
+                }
+
+                0x6 => {
+                    if self.quirks.cosmac_shift {
+                        self.registers.v[instruction.x as usize] = self.registers.v[instruction.y as usize];
+                    }
+
+                    self.registers.v[0xf] = self.registers.v[instruction.x as usize] & 1;
+                    self.registers.v[instruction.x as usize] >>= 1;
+                }
+
+                0x7 => {
+                    let x = self.registers.v[instruction.x as usize];
+                    let y = self.registers.v[instruction.y as usize];
+                    self.registers.v[instruction.x as usize] = y.wrapping_sub(x);
+                    self.registers.v[0xF] = (y >= x) as u8;
+                }
+
+                0xE => {
+                    if self.quirks.cosmac_shift {
+                        self.registers.v[instruction.x as usize] = self.registers.v[instruction.y as usize];
+                    }
+
+                    self.registers.v[instruction.x as usize] <<= 1;
+                    self.registers.v[0xf] = self.registers.v[instruction.x as usize] & 1;
+                }
+
+                _ => self.unknown(instruction),
+            }
+
+            0x9 => if self.registers.v[instruction.x as usize] != self.registers.v[instruction.y as usize] {
+                self.skip();
+            }
+
+            0xA => self.registers.i = instruction.nnn as u32,
+            0xB => {
+                let offset = if self.quirks.cosmac_bnnn { self.registers.v[0] } else { self.registers.v[instruction.x as usize] };
+                self.registers.pc = self.address(instruction.nnn as u32 + offset as u32) as u16;
+            },
+            0xC => self.registers.v[instruction.x as usize] = self.rng.next_u8() & instruction.nn,
+            // COSMAC interpreters wait for the vertical blank interrupt before drawing
+            0xD if self.quirks.display_wait && !self.vblank => self.registers.pc = self.registers.pc.wrapping_sub(2),
+            0xD if self.megachip.is_some() => self.draw_megachip_sprite(instruction),
+            0xD => self.draw_sprite(instruction),
+
+            0xE => match instruction.raw & 0x00FF {
+                0x9E => if self.display.keypad.keypad[self.registers.v[instruction.x as usize] as usize] {
+                    self.skip();
+                }
+
+                0xA1 => if !self.display.keypad.keypad[self.registers.v[instruction.x as usize] as usize] {
+                    self.skip();
+                }
+
+                _ => self.unknown(instruction),
+            }
+
+            0xF => match instruction.raw & 0x00FF {
+                // XO-CHIP long index: I takes the whole word after the instruction
+                0x00 if instruction.x == 0 => if let Some(address) = self.fetch() {
+                    self.registers.i = address as u32;
+                }
+                0x01 => self.display.planes = instruction.x & 0b11, // XO-CHIP plane select
+                0x02 if instruction.x == 0 => {
+                    let start = self.registers.i;
+                    let mut pattern = [0; 16];
+                    for (idx, byte) in pattern.iter_mut().enumerate() {
+                        *byte = self.memory.read(start + idx as u32);
+                    }
+                    self.display.audio.pattern = Some(pattern);
+                }
+                0x3A => self.display.audio.pitch = self.registers.v[instruction.x as usize],
+                0x07 => self.registers.v[instruction.x as usize] = self.timers.delay,
+                0x15 => self.timers.delay = self.registers.v[instruction.x as usize],
+                0x18 => {
+                    let value = self.registers.v[instruction.x as usize];
+                    self.timers.sound = if value > 0 { value.max(self.quirks.min_sound_ticks) } else { 0 };
+                }
+
+                0x1E => {
+                    let sum = self.registers.i.wrapping_add(self.registers.v[instruction.x as usize] as u32);
+                    // The Amiga interpreter's I was 12 bits, whatever memory the profile has
+                    let overflowed = sum > 0xFFF;
+                    match self.quirks.fx1e_overflow {
+                        Fx1eOverflow::Unbounded => self.registers.i = sum,
+                        Fx1eOverflow::Wrap => self.registers.i = self.address(sum),
+                        Fx1eOverflow::Flag => {
+                            self.registers.i = self.address(sum);
+                            if overflowed {
+                                self.registers.v[0xf] = 1;
+                            }
+                        }
+                    }
+                }
+                
+                0x0A => match self.display.keypad.wait_for_key(self.quirks.fx0a_release) {
+                    Some(key) => self.registers.v[instruction.x as usize] = key,
+                    None => self.registers.pc = self.registers.pc.wrapping_sub(2),
+                }
+
+                0x29 => self.registers.i = (self.registers.v[instruction.x as usize] as u16 * 5 + FONT_ADDRESS) as u32,
+                0x30 => self.registers.i = (BIGFONT_ADDRESS + self.registers.v[instruction.x as usize] as u16 * 10) as u32,
+                
+                0x33 => {
+                    let value = self.registers.v[instruction.x as usize];
+                    let (address, pc) = (self.registers.i, self.registers.pc.wrapping_sub(2));
+
+                    self.memory.write(address, value / 100, pc);
+                    self.memory.write(address + 1, (value / 10) % 10, pc);
+                    self.memory.write(address + 2, value % 10, pc);
+                }
+
+                0x55 => {
+                    let upper_bound: usize = (instruction.x as usize + 1).min(self.registers.v.len());
+                    let (address, pc) = (self.registers.i, self.registers.pc.wrapping_sub(2));
+                    for i in 0..upper_bound {
+                        self.memory.write(address + i as u32, self.registers.v[i], pc);
+                    }
+                
+                    if self.quirks.cosmac_fx55 {
+                        self.registers.i = self.address(self.registers.i + instruction.x as u32 + 1);
+                    }
+                }
+                
+                0x65 => {
+                    let upper_bound: usize = (instruction.x as usize + 1).min(self.registers.v.len());
+                    for i in 0..upper_bound {
+                        self.registers.v[i] = self.memory.read(self.registers.i + i as u32);
+                    }
+                
+                    if self.quirks.cosmac_fx65 {
+                        self.registers.i = self.address(self.registers.i + instruction.x as u32 + 1);
+                    }
+                }
+
+                0x75 => _ = self.write_flags(0, instruction.x as usize),
+                0x85 => _ = self.read_flags(0, instruction.y as usize),
+
+                _ => self.unknown(instruction),
+            }
+
+            _ => self.unknown(instruction),
+        }
+    }
+
+    // The COSMAC VIP runs 8XY1-8XY3 through a routine that leaves VF cleared
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.registers.v[0xF] = 0;
+        }
+    }
+
+    // Records the instruction if its result would change with a different quirk setting
+    fn check_quirks(&mut self, pc: u16, instruction: &Instruction) {
+        if self.strict.is_none() {
+            return;
+        }
+
+        let vx = self.registers.v[instruction.x as usize];
+        let vy = self.registers.v[instruction.y as usize];
+
+        let quirk = match instruction.op {
+            0x8 if matches!(instruction.n, 0x6 | 0xE) && vx != vy => "cosmac_shift",
+            0x8 if matches!(instruction.n, 0x1..=0x3) && instruction.x != 0xF && self.registers.v[0xF] != 0 => "vf_reset",
+            0x0 if self.lores() && is_scroll(instruction.raw) => "schip_lores_scroll",
+            0xB if instruction.x != 0 && vx != self.registers.v[0] => "cosmac_bnnn",
+            0xD if instruction.n == 0 && self.lores() => "schip_lores_dxy0",
+
+            0xF => match instruction.nn {
+                0x1E if self.registers.i + vx as u32 > 0xFFF => "fx1e_overflow",
+                0x55 => "cosmac_fx55",
+                0x65 => "cosmac_fx65",
+                _ => return,
+            }
+
+            _ => return,
+        };
+
+        if let Some(line) = self.strict.as_mut().and_then(|report| report.record(pc, instruction.raw, quirk)) {
+            self.messages.push(line);
+        }
+    }
+
+    // 00FE and 00FF
+    fn set_resolution(&mut self, width: u16, height: u16, scale: u16) {
+        if self.quirks.resize_clear {
+            self.display.resize(width, height, scale);
+        } else {
+            self.display.rescale(width, height, scale);
+        }
+        self.half_scroll = 0;
+    }
+
+    // SCHIP's 64x32 lores mode, which the lores quirks apply to. The two-page hires CHIP-8
+    // screen is 64 wide too, but 64 high, and its pixels are whole.
+    fn lores(&self) -> bool {
+        (self.display.width, self.display.height) == (64, 32)
+    }
+
+    // 00CN, 00BN/00DN, 00FB and 00FC
+    fn scroll(&mut self, direction: Scroll, pixels: u8) {
+        self.vblank = false;
+        let lores = self.lores();
+        let pixels = match direction {
+            // SCHIP 1.1 scrolls its 128x64 screen, where a lores pixel is two high. An odd
+            // count leaves half a pixel, which the next vertical scroll finishes or undoes.
+            Scroll::Up | Scroll::Down if lores && self.quirks.schip_lores_scroll => {
+                let sign = if matches!(direction, Scroll::Down) { 1 } else { -1 };
+                let half_pixels = self.half_scroll + sign * pixels as i8;
+                self.half_scroll = half_pixels % 2;
+                (half_pixels / 2).unsigned_abs()
+            }
+            _ if lores && self.quirks.schip_lores_scroll => pixels / 2,
+            _ => pixels,
+        };
+
+        for _ in 0..pixels {
+            match direction {
+                Scroll::Up => self.display.shift_up(),
+                Scroll::Down => self.display.shift_down(),
+                Scroll::Left => self.display.shift_left(),
+                Scroll::Right => self.display.shift_right(),
+            }
+        }
+    }
+
+    // Enables strict mode, which reports every quirk-dependent instruction executed
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = if strict { Some(QuirkReport::new()) } else { None };
+    }
+
+    // Applies the per-ROM palette settings and keeps the config for write-back
+    pub fn set_rom_config(&mut self, config: RomConfig) {
+        if let Some(colors) = config.colors() {
+            self.display.add_palette(Palette::new("rom", colors));
+        }
+
+        if let Some(name) = config.palette() {
+            if !self.display.select_palette(name) {
+                self.messages.push(format!("unknown palette {} in {}", name, config.path));
+            }
+        }
+
+        match SpeedrunTimer::from_config(&config.table) {
+            Ok(speedrun) => self.speedrun = speedrun,
+            Err(e) => self.messages.push(format!("ignoring speedrun in {}: {}", config.path, e)),
+        }
+
+        match Achievements::from_config(&config.table) {
+            Ok(achievements) => self.achievements = achievements,
+            Err(e) => self.messages.push(format!("ignoring achievements in {}: {}", config.path, e)),
+        }
+
+        match InputProfile::all_from_config(&config.table) {
+            Ok(profiles) => self.input_profiles = profiles,
+            Err(e) => self.messages.push(format!("ignoring input profiles in {}: {}", config.path, e)),
+        }
+        self.input_profile = None;
+        if let Some(name) = config.input_profile() {
+            match input_profile::find_profile(&self.input_profiles, name) {
+                Some(_) => self.input_profile = Some(name.to_string()),
+                None => self.messages.push(format!("unknown input profile {} in {}", name, config.path)),
+            }
+        }
+
+        if let Some(name) = config.font() {
+            if !self.set_font(name) {
+                self.messages.push(format!("unknown font {} in {}", name, config.path));
+            }
+        }
+
+        if let Some(name) = config.bigfont() {
+            if !self.set_bigfont(name) {
+                self.messages.push(format!("unknown big font {} in {}", name, config.path));
+            }
+        }
+
+        self.rom_config = Some(config);
+    }
+
+    // An Octocart's options for the program just loaded: its quirk flags over the current
+    // quirks, and its colors
+    pub fn apply_octo_options(&mut self, options: &OctoOptions) {
+        options.apply_quirks(&mut self.quirks);
+        if let Some(palette) = options.palette() {
+            self.display.add_palette(palette);
+        }
+    }
+
+    // Loads a built-in small font by name, returns false if there's no such font
+    pub fn set_font(&mut self, name: &str) -> bool {
+        match font::font(name) {
+            Some(data) => {
+                self.load_font(data, FONT_ADDRESS);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Loads a built-in big font by name, returns false if there's no such font
+    pub fn set_bigfont(&mut self, name: &str) -> bool {
+        match font::bigfont(name) {
+            Some(data) => {
+                self.load_font(data, BIGFONT_ADDRESS);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn load_font(&mut self, data: &[u8], address: u16) {
+        let start = address as usize;
+        self.memory[start..start + data.len()].copy_from_slice(data);
+    }
+
+    // Loads a font file over a built-in font: whole glyphs of glyph_height bytes, at most the
+    // 16 digits
+    fn load_font_file(&mut self, path: &str, address: u16, glyph_height: usize) -> Result<(), EmulatorError> {
+        let data = std::fs::read(path).map_err(|e| EmulatorError::read(path, e))?;
+        if data.is_empty() || data.len() % glyph_height != 0 || data.len() > 16 * glyph_height {
+            let reason = format!("expected up to 16 glyphs of {} bytes, found {} bytes", glyph_height, data.len());
+            return Err(EmulatorError::InvalidFont { path: path.to_string(), reason });
+        }
+        self.load_font(&data, address);
+        Ok(())
+    }
+
+    // Switches to the next palette and remembers it in the per-ROM config
+    pub fn cycle_palette(&mut self) {
+        let name = self.display.cycle_palette().to_string();
+        self.messages.push(format!("palette: {}", name));
+
+        if let Some(ref mut config) = self.rom_config {
+            config.set_palette(&name);
+            self.writer.write(&config.path, config.to_toml().into_bytes());
+        }
+    }
+
+    // The selected input profile, whose bindings a frontend lays over its usual keymap
+    pub fn input_profile(&self) -> Option<&InputProfile> {
+        input_profile::find_profile(&self.input_profiles, self.input_profile.as_deref()?)
+    }
+
+    // Switches to the next input profile (or back to none) and remembers it in the per-ROM config
+    pub fn cycle_input_profile(&mut self) -> Option<&InputProfile> {
+        let next = input_profile::next_profile(&self.input_profiles, self.input_profile.as_deref());
+        self.input_profile = next.map(|profile| profile.name.clone());
+        let name = self.input_profile.as_deref().unwrap_or("default").to_string();
+        self.messages.push(format!("input profile: {}", name));
+        self.display.toast(&format!("Input: {}", name));
+
+        if let Some(ref mut config) = self.rom_config {
+            config.set_input_profile(self.input_profile.as_deref());
+            self.writer.write(&config.path, config.to_toml().into_bytes());
+        }
+        self.input_profile()
+    }
+
+    // Converts U16 -> U8 and sets VF as carry
+    fn convert_with_carry(&mut self, value: &mut u16) {
+        if *value >= 0x100 {
+            self.registers.v[0xF] = 1;
+            *value &= 0xFF;
+        } else {
+            self.registers.v[0xF] = 0;
+        }
+    }
+
+    // Unknown instruction callback
+    fn unknown(&mut self, instruction: Instruction) {
+        match self.unknown_opcode_policy {
+            UnknownOpcodePolicy::Ignore => {}
+            UnknownOpcodePolicy::Warn if self.speculative => {}
+            UnknownOpcodePolicy::Warn => {
+                self.messages.push(format!("unknown instruction: {:#06X}", instruction.raw));
+                self.capture_crash(&format!("unknown instruction {:#06X}", instruction.raw));
+            }
+            UnknownOpcodePolicy::Halt => {
+                self.trap(&format!("unknown instruction {:#06X}", instruction.raw));
+            }
+        }
+    }
+
+    // Applies the odd PC policy, returns false if the instruction mustn't run
+    fn check_odd_pc(&mut self, pc: u16) -> bool {
+        match self.odd_pc_policy {
+            OddPcPolicy::Allow => true,
+            OddPcPolicy::Warn => {
+                if !self.speculative && self.odd_pcs.insert(pc) {
+                    self.messages.push(format!("odd PC {:#06X}: the instruction stream is misaligned", pc));
+                }
+                true
+            }
+            OddPcPolicy::Trap => {
+                self.trap("odd PC");
+                false
+            }
+        }
+    }
+
+    // Halts, printing the trap report and saving the crash captures
+    fn trap(&mut self, reason: &str) {
+        self.halted = true;
+        if !self.capture_crash(reason) {
+            return; // already reported, or in a run-ahead frame that's about to be rolled back
+        }
+
+        let depth = (self.registers.sp + 1).clamp(0, self.stack.len() as i8) as usize;
+        let state = MachineState {
+            pc: self.registers.pc.wrapping_sub(2),
+            i: self.registers.i,
+            v: &self.registers.v,
+            stack: &self.stack[..depth],
+            delay: self.timers.delay,
+            sound: self.timers.sound,
+            memory: &self.memory,
+        };
+        let report = backtrace::format_trap(reason, &state, self.symbols.as_ref());
+        self.messages.push(report);
+    }
+
+    // Saves a screenshot and savestate next to the log entry, once per PC. Returns false if
+    // this PC was already captured, or without capturing anything in a run-ahead frame.
+    fn capture_crash(&mut self, reason: &str) -> bool {
+        let pc: u16 = self.registers.pc.wrapping_sub(2);
+        if self.speculative || !self.crash_captures.insert(pc) {
+            return false;
+        }
+
+        let name = format!("crash_{:04X}", pc);
+        self.writer.write(&format!("{}.bmp", name), encode_bmp(&self.display, 4));
+        self.writer.write(&format!("{}.sav", name), self.serialize_state());
+
+        self.messages.push(format!("{} at {:#06X}: I={:#06X} V={:02X?} DT={} ST={}", reason, pc, self.registers.i, self.registers.v, self.timers.delay, self.timers.sound));
+        self.messages.push(format!("saved {}.bmp and {}.sav", name, name));
+        true
+    }
+
+    // Serialize CPU state into a writer
+    pub fn write_state<W: Write>(&self, file: &mut W) -> io::Result<()> {
+        file.write_all("HEAD".as_bytes())?;
+        file.write_all(&[1, 3, 0])?; // file format version
+    
+        file.write_all("REGS".as_bytes())?; // registers header
+        file.write_all(&self.registers.v)?;
+        file.write_all(&self.registers.i.to_le_bytes())?; // 32 bits for MEGACHIP's 24-bit I
+        file.write_all(&self.registers.pc.to_le_bytes())?;
+        file.write_all(&self.registers.sp.to_le_bytes())?;
+    
+        file.write_all("TIME".as_bytes())?; // timer header
+        file.write_all(&[self.timers.delay])?;
+        file.write_all(&[self.timers.sound])?;
+    
+        file.write_all("STCK".as_bytes())?; // stack header
+        for &num in &self.stack {
+            file.write_all(&num.to_le_bytes())?;
+        }
+    
+        file.write_all("RMEM".as_bytes())?; // RLE memory header
+        let encoded_memory = encode_rle(&self.memory);
+        file.write_all(&encoded_memory)?;
+
+        file.write_all("DISP".as_bytes())?; // display header
+        file.write_all(&self.display.width.to_le_bytes())?;
+        file.write_all(&self.display.height.to_le_bytes())?;
+        file.write_all(&self.display.display.len().to_le_bytes())?;
+    
+        let encoded_display = encode_rle(&delta::words_to_bytes(&self.display.display));
+        file.write_all(&encoded_display)?;
+
+        // XO-CHIP's second plane and plane selection, only once a ROM has used them
+        if self.display.planes != 1 || self.display.plane2.iter().any(|&word| word != 0) {
+            file.write_all("PLN2".as_bytes())?;
+            file.write_all(&[self.display.planes])?;
+            file.write_all(&encode_rle(&delta::words_to_bytes(&self.display.plane2)))?;
+        }
+
+        // XO-CHIP audio pattern and pitch, only once a ROM has set them
+        if self.display.audio != Audio::default() {
+            file.write_all("AUDI".as_bytes())?;
+            file.write_all(&self.display.audio.pattern.unwrap_or([0; 16]))?;
+            file.write_all(&[self.display.audio.pitch])?;
+        }
+
+        // MEGACHIP's color mode and the screen it last presented, only while in it
+        if let Some(ref mega) = self.megachip {
+            file.write_all("MEGA".as_bytes())?;
+            file.write_all(&mega.to_bytes())?;
+            let blank = vec![0; megachip::WIDTH as usize * megachip::HEIGHT as usize];
+            file.write_all(&encode_rle(&delta::words_to_bytes(self.display.colors.as_ref().unwrap_or(&blank))))?;
+        }
+
+        // Active palette, with its colors so per-ROM palettes render the same after loading
+        let palette = self.display.palette();
+        file.write_all("CONF".as_bytes())?; // display config header
+        file.write_all(&[palette.name.len().min(255) as u8])?;
+        file.write_all(&palette.name.as_bytes()[..palette.name.len().min(255)])?;
+        for color in palette.colors {
+            file.write_all(&color.to_le_bytes())?;
+        }
+
+        file.write_all("SEED".as_bytes())?; // CXNN generator state
+        file.write_all(&self.rng.state.to_le_bytes())?;
+
+        // Where execution stands: cycles run, halted and exited, the vertical blank, a half
+        // scrolled lores pixel and FX0A's wait (0xFF for no key yet)
+        let key_wait = self.display.keypad.key_wait;
+        file.write_all("STAT".as_bytes())?;
+        file.write_all(&self.cycles.to_le_bytes())?;
+        file.write_all(&[self.halted as u8, self.exited as u8, self.vblank as u8, self.half_scroll as u8])?;
+        file.write_all(&[key_wait.waiting as u8, key_wait.pressed.unwrap_or(0xFF), key_wait.released.unwrap_or(0xFF)])?;
+    
+        Ok(())
+    }
+
+    // Serialize CPU state into an in-memory buffer
+    pub fn serialize_state(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+        _ = self.write_state(&mut buffer); // writing to a Vec can't fail
+        buffer
+    }
+
+    // Dump CPU state to file
+    pub fn save_state(&self, path: &str) -> io::Result<()> {
+        std::fs::write(path, self.serialize_state())
+    }
+
+    // Enables (or disables with None) the rolling autosave to path
+    pub fn set_autosave(&mut self, interval: Option<AutosaveInterval>, path: &str) {
+        self.autosave = interval.map(|interval| Autosave::new(interval, path));
+    }
+
+    // Read CPU state from file
+    pub fn load_state(&mut self, path: &str) -> io::Result<()> {
+        self.restore_state(&std::fs::read(path)?)
+    }
+
+    // Applies a savestate imported from another emulator
+    pub fn import_state(&mut self, state: &ImportedState) {
+        let len = state.memory.len().min(self.memory.len());
+        self.memory[..len].copy_from_slice(&state.memory[..len]);
+        self.registers.pc = state.pc.unwrap_or(self.registers.pc);
+        self.registers.i = state.i.map_or(self.registers.i, u32::from);
+        self.registers.v = state.v.unwrap_or(self.registers.v);
+        self.timers.delay = state.delay.unwrap_or(self.timers.delay);
+        self.timers.sound = state.sound.unwrap_or(self.timers.sound);
+        self.registers.sp = -1;
+        self.display.clear();
+        self.halted = false;
+    }
+
+    // Restores CPU state from serialize_state() output. All of it is read before any is
+    // applied, so a damaged file leaves the machine as it was.
+    pub fn restore_state(&mut self, state: &[u8]) -> io::Result<()> {
+        let mut file = StateFile { data: state, idx: 0 };
+        file.expect("HEAD")?;
+        let version = file.take(3)?;
+        if version[0] != 1 || version[1] > 3 {
+            return Err(file.invalid(&format!("unsupported format version {}.{}.{}", version[0], version[1], version[2])));
+        }
+
+        file.expect("REGS")?;
+        let v: [u8; 16] = file.take(16)?.try_into().unwrap();
+        // I was 16 bits before 1.3, without MEGACHIP's high byte
+        let i = if version[1] < 3 { file.u16()? as u32 } else { file.u32()? };
+        let (pc, sp) = (file.u16()?, file.u8()? as i8);
+        if !(-1..32).contains(&sp) {
+            return Err(file.invalid("stack pointer out of range"));
+        }
+
+        file.expect("TIME")?;
+        let (delay, sound) = (file.u8()?, file.u8()?);
+
+        file.expect("STCK")?;
+        let mut stack = [0; 32];
+        for address in stack.iter_mut() {
+            *address = file.u16()?;
+        }
+
+        file.expect("RMEM")?;
+        let mut memory = if version[1] < 2 { file.old_memory()? } else { file.rle(self.memory.len())? };
+        memory.resize(self.memory.len(), 0);
+
+        file.expect("DISP")?;
+        let (width, height) = (file.u16()?, file.u16()?);
+        let words = file.u64()? as usize;
+        if width == 0 || height == 0 || words != (width as usize * height as usize).div_ceil(32) {
+            return Err(file.invalid(&format!("bad screen size {}x{}", width, height)));
+        }
+        let by_word = version[1] < 3;
+        let pixels = file.plane(words, by_word)?;
+
+        let (mut planes, mut plane2, mut audio, mut palette, mut rng) = (1, vec![0; words], Audio::default(), None, None);
+        let (mut mega, mut status) = (None, None);
+        while let Some(tag) = file.tag()? {
+            match tag {
+                "PLN2" => {
+                    planes = file.u8()?;
+                    plane2 = file.plane(words, by_word)?;
+                }
+                "AUDI" => {
+                    let pattern: [u8; 16] = file.take(16)?.try_into().unwrap();
+                    // A ROM that only set the pitch has its pattern written as zeros
+                    audio = Audio { pattern: Some(pattern).filter(|pattern| pattern.iter().any(|&byte| byte != 0)), pitch: file.u8()? };
+                }
+                "CONF" => {
+                    let len = file.u8()? as usize;
+                    let name = String::from_utf8_lossy(file.take(len)?).into_owned();
+                    let mut colors = [0; 4];
+                    for color in colors.iter_mut() {
+                        *color = file.u32()?;
+                    }
+                    palette = Some(Palette::new(&name, colors));
+                }
+                "SEED" => rng = Some(file.u64()?),
+                "MEGA" => {
+                    let (mode, used) = MegaChip::from_bytes(&file.data[file.idx..]).ok_or_else(|| file.invalid("MEGACHIP state doesn't decode"))?;
+                    file.idx += used;
+                    let colors = file.plane(megachip::WIDTH as usize * megachip::HEIGHT as usize, false)?;
+                    mega = Some((mode, colors));
+                }
+                "STAT" => {
+                    let cycles = file.u64()?;
+                    let flags = file.take(4)?;
+                    let key = |byte: u8| (byte != 0xFF).then_some(byte & 0xF);
+                    let wait = file.take(3)?;
+                    let key_wait = KeyWait { waiting: wait[0] != 0, pressed: key(wait[1]), released: key(wait[2]) };
+                    status = Some((cycles, flags[0] != 0, flags[1] != 0, flags[2] != 0, flags[3] as i8, key_wait));
+                }
+                _ => return Err(file.invalid(&format!("unknown chunk {}", tag))),
+            }
+        }
+
+        self.registers = Registers { pc, sp, i, v };
+        self.timers = Timers { delay, sound };
+        self.stack = stack;
+        self.memory.copy_from_slice(&memory);
+
+        let scale = match width {
+            128 => self.display.original_scale / 2,
+            w if w >= megachip::WIDTH => (self.display.original_scale / 4).max(1),
+            _ => self.display.original_scale,
+        };
+        self.display.resize(width, height, scale);
+        self.display.display = pixels;
+        self.display.plane2 = plane2;
+        self.display.planes = planes;
+        self.display.audio = audio;
+        if let Some(palette) = palette {
+            self.display.add_palette(palette);
+        }
+        if let Some(state) = rng {
+            self.rng.state = state;
+        }
+
+        (self.megachip, self.display.colors) = match mega {
+            Some((mode, colors)) => (Some(mode), Some(colors)),
+            None => (None, None),
+        };
+        // States from before 1.3 start from a running machine
+        let (cycles, halted, exited, vblank, half_scroll, key_wait) = status.unwrap_or((self.cycles, false, false, false, 0, KeyWait::default()));
+        self.cycles = cycles;
+        self.halted = halted;
+        self.exited = exited;
+        self.vblank = vblank;
+        self.half_scroll = half_scroll;
+        self.display.keypad.key_wait = key_wait;
+        Ok(())
+    }
+
+    // Write Vx-Vy -> flags
+    fn write_flags(&mut self, x: usize, y: usize) -> io::Result<()> {
+        self.flags_written = true;
+        let mut file = File::create("flags.bin")?;
+        file.write_all(&self.registers.v[x..y])?;
+        Ok(())
+    }
+
+    // Read flags -> Vx-Vy
+    fn read_flags(&mut self, x: usize, y: usize) -> io::Result<()> {
+        let mut file = File::open("flags.bin")?;
+        file.read_exact(&mut self.registers.v[x..y])?;
+
+        Ok(())
+    }
+
+    // MEGACHIP's 0NNN instructions, false for the ones left to CHIP-8 and SCHIP. Outside the
+    // color mode only 0011, which enters it, and LDHI do anything.
+    fn execute_megachip(&mut self, instruction: &Instruction) -> bool {
+        match instruction.raw {
+            0x0010 => {
+                self.megachip = None;
+                self.display.colors = None;
+                self.display.resize(64, 32, self.display.original_scale);
+            }
+
+            0x0011 => {
+                self.megachip = Some(MegaChip::default());
+                self.display.resize(megachip::WIDTH, megachip::HEIGHT, (self.display.original_scale / 4).max(1));
+                self.display.colors = Some(vec![0; megachip::WIDTH as usize * megachip::HEIGHT as usize]);
+            }
+
+            // LDHI: 01NN NNNN, a 24-bit I
+            _ if instruction.x == 1 => if let Some(low) = self.fetch() {
+                self.registers.i = (instruction.nn as u32) << 16 | low as u32;
+            }
+
+            _ => {
+                let Some(mega) = self.megachip.as_mut() else {
+                    return false;
+                };
+
+                match instruction.x {
+                    // Sprites go to a back buffer, so it's 00E0 that updates the screen
+                    0x0 if instruction.raw == 0x00E0 => {
+                        self.display.colors = Some(mega.present());
+                        self.display.mark_all_dirty();
+                    }
+                    0x0 if instruction.raw & 0xFFF0 == 0x00B0 => mega.scroll_up(instruction.n),
+                    0x2 => {
+                        let start = self.registers.i;
+                        let bytes: Vec<u8> = (0..instruction.nn as u32 * 4).map(|idx| self.memory.read(start + idx)).collect();
+                        mega.load_palette(&bytes);
+                    }
+                    0x3 => mega.sprite_width = instruction.nn as u16,
+                    0x4 => mega.sprite_height = instruction.nn as u16,
+                    0x5 => mega.alpha = instruction.nn,
+                    0x6 | 0x7 => {} // digitized sound from memory, not emulated
+                    0x8 => mega.blend = Blend::from_mode(instruction.n),
+                    0x9 => mega.collision_color = instruction.nn,
+                    _ => return false,
+                }
+            }
+        }
+        true
+    }
+
+    // DXYN in MEGACHIP's color mode: N is ignored for the size set with 03NN/04NN, and I
+    // points at one color index per pixel. VF is set by drawing over the collision color.
+    fn draw_megachip_sprite(&mut self, instruction: Instruction) {
+        self.vblank = false;
+        let Some(mega) = self.megachip.as_mut() else {
+            return;
+        };
+        let (width, height) = mega.sprite_size();
+        let start = self.registers.i;
+        let sprite: Vec<u8> = (0..width as u32 * height as u32).map(|idx| self.memory.read(start + idx)).collect();
+
+        let (x, y) = (self.registers.v[instruction.x as usize], self.registers.v[instruction.y as usize]);
+        self.registers.v[0xF] = mega.draw(x as u16, y as u16, &sprite) as u8;
+    }
+
+    // DXYN, or SCHIP's 16x16 DXY0 (8x16 in lores with schip_lores_dxy0). Each plane selected
+    // with FN01 draws its own sprite, read one after the other from I starting with the first
+    // plane, and VF is set by a collision on any of them, or counts the rows that collided in
+    // hires with schip_row_collisions.
+    fn draw_sprite(&mut self, instruction: Instruction) {
+        self.vblank = false;
+        let lores = self.lores();
+        let (width, rows): (u16, u16) = match instruction.n {
+            0 if lores && self.quirks.schip_lores_dxy0 => (8, 16),
+            0 => (16, 16),
+            n => (8, n as u16),
+        };
+        let x: u16 = self.registers.v[instruction.x as usize] as u16 % self.display.width;
+        let y: u16 = self.registers.v[instruction.y as usize] as u16 % self.display.height;
+        let mut collided_rows: u8 = 0;
+
+        let mut address = self.registers.i;
+        for plane in 0..2 {
+            if self.display.planes & (1 << plane) == 0 {
+                continue;
+            }
+
+            for row in 0..rows {
+                let row_y = y + row;
+                if row_y >= self.display.height {
+                    break;
+                }
+
+                let offset = address + (row * width / 8) as u32;
+                let bits: u16 = if width == 16 {
+                    (self.memory.read(offset) as u16) << 8 | self.memory.read(offset + 1) as u16
+                } else {
+                    (self.memory.read(offset) as u16) << 8
+                };
+
+                let mut collided = false;
+                for column in 0..width {
+                    let row_x = x + column;
+                    if row_x >= self.display.width {
+                        break;
+                    }
+
+                    if (bits >> (15 - column)) & 1 == 1 {
+                        if self.display.get_plane_pixel(plane, row_x, row_y) == 1 {
+                            self.display.set_plane_pixel(plane, row_x, row_y, 0);
+                            collided = true;
+                        } else {
+                            self.display.set_plane_pixel(plane, row_x, row_y, 1);
+                        }
+                    }
+                }
+                collided_rows += collided as u8;
+            }
+            address += (rows * width / 8) as u32;
+        }
+
+        self.registers.v[0xF] = if !lores && self.quirks.schip_row_collisions { collided_rows } else { (collided_rows > 0) as u8 };
+    }
+
+    // Reads a file into memory at an address
+    pub fn read_to_memory(&mut self, file_path: &str, address: u16) -> Result<(), EmulatorError> {
+        let data = self.read_segment(file_path, address)?;
+        for warning in self.rom_warnings(address, data.len()) {
+            self.messages.push(format!("warning: {}", warning));
+        }
+        let start = address as usize;
+        self.memory[start..start + data.len()].copy_from_slice(&data);
+        self.rom_end = self.rom_end.max(start + data.len());
+        self.rom = Some((address, data));
+        if address == 0x200 {
+            self.start_hires_chip8();
+        }
+
+        Ok(())
+    }
+
+    // Reads a file to load at an address, refusing one that doesn't fit in memory
+    fn read_segment(&self, path: &str, address: u16) -> Result<Vec<u8>, EmulatorError> {
+        let data = std::fs::read(path).map_err(|e| EmulatorError::read(path, e))?;
+        let max = self.memory.len().saturating_sub(address as usize);
+        if data.len() > max {
+            return Err(EmulatorError::TooLarge { path: path.to_string(), size: data.len(), max });
+        }
+        Ok(data)
+    }
+
+    // Loads files at their addresses on top of the program, e.g. data overlays or a patched
+    // interpreter stub. They're checked against the memory map first, and none is loaded if
+    // one doesn't fit or overlaps the program or another segment. reset() puts them back.
+    pub fn load_segments(&mut self, segments: &[Segment]) -> Result<(), EmulatorError> {
+        let mut loaded: Vec<(String, Range<usize>)> = Vec::new();
+        if let Some((address, ref rom)) = self.rom {
+            loaded.push(("the program".to_string(), address as usize..address as usize + rom.len()));
+        }
+        for (address, data) in self.segments.iter() {
+            loaded.push((format!("the segment at {:#05X}", address), *address as usize..*address as usize + data.len()));
+        }
+
+        let mut data = Vec::new();
+        for segment in segments {
+            let bytes = self.read_segment(&segment.path, segment.address)?;
+            for warning in self.rom_warnings(segment.address, bytes.len()) {
+                self.messages.push(format!("warning: {}: {}", segment.path, warning));
+            }
+            let range = segment.address as usize..segment.address as usize + bytes.len();
+            if let Some((other, _)) = loaded.iter().find(|(_, other)| other.start < range.end && range.start < other.end) {
+                return Err(EmulatorError::Overlap { path: segment.path.clone(), address: segment.address, other: other.clone() });
+            }
+            loaded.push((segment.path.clone(), range));
+            data.push((segment.address, bytes));
+        }
+
+        self.segments.extend(data);
+        self.place_segments();
+        Ok(())
+    }
+
+    fn place_segments(&mut self) {
+        for (address, data) in self.segments.iter() {
+            let start = *address as usize;
+            self.memory[start..start + data.len()].copy_from_slice(data);
+        }
+    }
+
+    // Back to power-on state: registers, stack and timers cleared, lores screen, running from the
+    // start address, and the loaded program's memory as it was when loaded (from the copy kept
+    // then, so the file can have changed or gone since).
+    pub fn reset(&mut self) {
+        self.registers.pc = self.quirks.start_address;
+        self.registers.sp = -1;
+        self.registers.i = 0;
+        self.registers.v = [0; 16];
+        self.stack = [0; 32];
+        self.timers.delay = 0;
+        self.timers.sound = 0;
+        self.vblank = false;
+        self.half_scroll = 0;
+        self.display.keypad.key_wait = KeyWait::default();
+        self.halted = false;
+        self.exited = false;
+        self.display.set_beep(false);
+        self.display.planes = 1;
+        self.display.audio = Audio::default();
+        self.megachip = None;
+        self.display.colors = None;
+        self.display.resize(64, 32, self.display.original_scale);
+        if let Some((address, rom)) = self.rom.take() {
+            self.place_rom(rom, address);
+        }
+        self.place_segments();
+    }
+
+    // Starts another program in place of the current one, e.g. a ROM picked or dropped on the
+    // window after startup. Nothing carries over from the previous ROM: its config, cheats,
+    // timers and symbols are dropped, so call set_rom_config afterwards for the new one.
+    pub fn load_program(&mut self, rom: &[u8]) {
+        self.rom = None;
+        self.segments.clear();
+        self.reset();
+        self.load_rom(rom, self.quirks.start_address);
+        self.rom_config = None;
+        self.speedrun = None;
+        self.achievements = Achievements::default();
+        self.symbols = None;
+        self.input_profiles.clear();
+        self.input_profile = None;
+        self.frozen.clear();
+        self.crash_captures.clear();
+        self.odd_pcs.clear();
+        self.unsaved_frames = 0;
+        self.flags_written = false;
+    }
+
+    // Halts and shows a message on the emulated screen in hires, e.g. the boot splash or why a
+    // ROM couldn't be loaded
+    pub fn show_message(&mut self, lines: &[String]) {
+        self.halted = true;
+        self.display.set_beep(false);
+        self.display.resize(128, 64, self.display.original_scale / 2);
+        splash::render_text(&mut self.display, lines);
+    }
+
+    // Replaces memory from an address onward with ROM bytes, the rest going back to the
+    // memory init pattern
+    pub fn load_rom(&mut self, rom: &[u8], address: u16) {
+        for warning in self.rom_warnings(address, rom.len()) {
+            self.messages.push(format!("warning: {}", warning));
+        }
+        self.place_rom(rom.to_vec(), address);
+    }
+
+    fn place_rom(&mut self, rom: Vec<u8>, address: u16) {
+        let start = address as usize;
+        let len = self.memory.len();
+        self.memory.initialize(start..len);
+        let end = (start + rom.len()).min(len);
+        self.memory[start..end].copy_from_slice(&rom[..end - start]);
+        self.rom_end = end;
+        self.rom = Some((address, rom));
+        if address == 0x200 {
+            self.start_hires_chip8();
+        }
+    }
+
+    // The program as it was loaded, not as it has modified itself since
+    pub fn rom(&self) -> &[u8] {
+        self.rom.as_ref().map_or(&[], |(_, rom)| rom.as_slice())
+    }
+
+    // Address ranges where a new build of the ROM differs from the loaded one, bytes it adds
+    // or drops included, e.g. to tell which breakpoints a reload leaves pointing at the same code
+    pub fn rom_changes(&self, new: &[u8]) -> Vec<Range<u16>> {
+        let (address, old) = match self.rom {
+            Some((address, ref rom)) => (address as usize, rom.as_slice()),
+            None => (self.quirks.start_address as usize, &[][..]),
+        };
+
+        let mut changes: Vec<Range<u16>> = Vec::new();
+        for idx in 0..old.len().max(new.len()) {
+            if old.get(idx) == new.get(idx) {
+                continue;
+            }
+            let at = (address + idx).min(u16::MAX as usize) as u16;
+            match changes.last_mut() {
+                Some(range) if range.end == at => range.end = at + 1,
+                _ => changes.push(at..at.saturating_add(1)),
+            }
+        }
+        changes
+    }
+
+    // Two-page hi-res CHIP-8 ROMs start with a 1260 jump into a patch of the VIP interpreter
+    // at 0x260. Skips the patch to the program proper at 0x2C0 and switches to its 64x64 screen.
+    fn start_hires_chip8(&mut self) {
+        if self.quirks.hires_chip8 && self.registers.pc == 0x200 && self.memory[0x200..0x202] == [0x12, 0x60] {
+            self.registers.pc = 0x2C0;
+            self.display.resize(64, 64, self.display.original_scale);
+        }
+    }
+
+    // Problems with loading a ROM of this size at an address that would otherwise only show
+    // up at runtime: overwriting the fonts or interpreter area below 0x200, reaching past the
+    // memory the interpreter can address, or not fitting in memory at all
+    pub fn rom_warnings(&self, address: u16, size: usize) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let start = address as usize;
+        let end = start + size;
+
+        if size > 0 && start < FONT_AREA_END as usize {
+            warnings.push(format!("ROM at {:#05X} overlaps the fonts at 0x000-{:#05X}", start, FONT_AREA_END - 1));
+        } else if size > 0 && start < 0x200 {
+            warnings.push(format!("ROM at {:#05X} overlaps the interpreter area at {:#05X}-0x1FF", start, FONT_AREA_END));
+        }
+
+        let limit = self.quirks.memory_size.min(self.memory.len());
+        if end > self.memory.len() {
+            warnings.push(format!(
+                "ROM is {} bytes but only {} fit at {:#06X}, the last {} are cut off",
+                size,
+                self.memory.len().saturating_sub(start),
+                start,
+                end - self.memory.len()
+            ));
+        } else if end > limit {
+            warnings.push(format!(
+                "ROM is {} bytes, {} past the {}K this interpreter addresses (at most {} at {:#05X}); XO-CHIP ROMs need --profile xochip",
+                size,
+                end - limit,
+                limit / 1024,
+                limit.saturating_sub(start),
+                start
+            ));
+        }
+
+        warnings
+    }
+
+    // Uses a cycle-cost timing model instead of the fixed CPU rate passed to run()
+    pub fn set_timing(&mut self, timing: Option<TimingModel>) {
+        self.timing = timing;
+    }
+
+    // Host time the next instruction takes, from the timing model if one is set
+    fn next_cycle_duration(&self, cpu_target: Duration, timer_target: Duration) -> Duration {
+        match self.timing {
+            Some(ref timing) => {
+                let pc = self.registers.pc as usize;
+                let opcode = (*self.memory.get(pc).unwrap_or(&0) as u16) << 8 | *self.memory.get(pc + 1).unwrap_or(&0) as u16;
+                timer_target * (timing.cost)(opcode) / timing.cycles_per_frame
+            }
+            None => cpu_target,
+        }
+    }
+
+    // Runs one step of the Chip8 emulator
+    pub fn step(&mut self) {
+        if let Some(ref mut timeline) = self.timeline {
+            timeline.record_keys(self.cycles, self.display.keypad.keypad);
+        }
+
+        let pc: u16 = self.registers.pc;
+        let Some(word) = self.fetch() else {
+            self.registers.pc = pc;
+            return;
+        };
+        if pc & 1 == 1 && !self.check_odd_pc(pc) {
+            self.registers.pc = pc;
+            return;
+        }
+        if let Some(ref mut blocks) = self.blocks {
+            blocks.observe(&self.memory, pc, word);
+        }
+        if let Some(ref mut tickrate) = self.tickrate {
+            tickrate.observe(word, self.timers.delay);
+        }
+        if let Some(ref mut trace) = self.trace {
+            trace.record(&TraceEntry::new(pc, word, self.registers.i, self.registers.v));
+        }
+        let instruction: Instruction = self.decode(word);
+        self.check_quirks(pc, &instruction);
+        let started = self.opcode_timing.is_some().then(Instant::now);
+        self.execute(instruction);
+        if let (Some(started), Some(timing)) = (started, self.opcode_timing.as_mut()) {
+            timing.record(word, started.elapsed());
+        }
+        self.cycles += 1;
+
+        if let Some(written) = self.memory.take_written() {
+            if let Some(ref mut blocks) = self.blocks {
+                blocks.invalidate(written);
+            }
+        }
+
+        if let Some(address) = self.memory.take_fault() {
+            self.trap(&format!("access past the end of memory ({:#06X})", address));
+        }
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.timers.delay
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.timers.sound
+    }
+
+    // How much longer the beep lasts at 60hz
+    pub fn sound_remaining(&self) -> Duration {
+        Duration::from_millis(16) * self.timers.sound as u32
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn display_mut(&mut self) -> &mut Display {
+        &mut self.display
+    }
+
+    // Messages for the user since the last call: warnings, reports and status changes. The
+    // core never prints, so frontends show these however suits them.
+    pub fn take_messages(&mut self) -> Vec<String> {
+        self.messages.extend(self.writer.take_failures());
+        std::mem::take(&mut self.messages)
+    }
+
+    // Under --strict, the instructions that depended on a quirk setting
+    pub fn quirk_report(&self) -> Option<String> {
+        self.strict.as_ref().map(QuirkReport::report)
+    }
+
+    // Starts counting basic block executions for block_report
+    pub fn enable_block_stats(&mut self) {
+        self.blocks = Some(BlockMap::new());
+    }
+
+    pub fn block_report(&self) -> Option<String> {
+        self.blocks.as_ref().map(|blocks| blocks.report(16))
+    }
+
+    // Starts measuring the work done per frame for tickrate_report
+    pub fn enable_tickrate_probe(&mut self) {
+        self.tickrate = Some(TickrateProbe::new());
+    }
+
+    // The measurement and a suggested speed, for a run at speed instructions per second
+    pub fn tickrate_report(&self, speed: u32) -> Option<String> {
+        self.tickrate.as_ref().map(|tickrate| tickrate.report(speed))
+    }
+
+    // Starts timing instructions by class for opcode_timing_report
+    pub fn enable_opcode_timing(&mut self) {
+        self.opcode_timing = Some(OpcodeTiming::new());
+    }
+
+    pub fn opcode_timing_report(&self) -> Option<String> {
+        self.opcode_timing.as_ref().map(OpcodeTiming::report)
+    }
+
+    // Starts recording every instruction executed to a trace file, see trace.rs
+    pub fn start_trace(&mut self, path: &str) -> io::Result<()> {
+        self.trace = Some(TraceWriter::create(path)?);
+        Ok(())
+    }
+
+    // Finishes the trace being recorded, returning how many instructions it holds
+    pub fn stop_trace(&mut self) -> Option<io::Result<u64>> {
+        self.trace.take().map(TraceWriter::finish)
+    }
+
+    // Starts keeping the last frames for rewind(), as many as fit in budget bytes
+    pub fn enable_rewind(&mut self, budget: usize) {
+        self.rewind = Some(Rewind::new(budget));
+    }
+
+    fn record_rewind_frame(&mut self) {
+        let frame = RewindFrame {
+            registers: self.registers.clone(),
+            stack: self.stack,
+            timers: self.timers.clone(),
+            size: (self.display.width, self.display.height, self.display.scale),
+            pixel_words: self.display.display.len(),
+            planes: self.display.planes,
+            keys: self.display.keypad.keypad,
+            key_wait: self.display.keypad.key_wait,
+            audio: self.display.audio,
+            megachip: self.megachip.is_some(),
+            colors: self.display.colors.as_ref().map(Vec::len),
+            halted: self.halted,
+            exited: self.exited,
+            cycles: self.cycles,
+            vblank: self.vblank,
+            half_scroll: self.half_scroll,
+            rng: self.rng,
+        };
+        let mut bulk = self.memory.to_vec();
+        bulk.extend(delta::words_to_bytes(&self.display.display));
+        bulk.extend(delta::words_to_bytes(&self.display.plane2));
+        if let Some(colors) = &self.display.colors {
+            bulk.extend(delta::words_to_bytes(colors));
+        }
+        if let Some(mega) = &self.megachip {
+            bulk.extend(mega.to_raw_bytes());
+        }
+        self.rewind.as_mut().unwrap().push(frame, bulk);
+    }
+
+    // Goes back the given number of frames, 0 being the state at the last frame tick. Frames
+    // after it are forgotten. Returns false when rewinding is off or that far back isn't kept.
+    pub fn rewind(&mut self, frames: usize) -> bool {
+        let Some((frame, bulk)) = self.rewind.as_mut().and_then(|rewind| rewind.go_back(frames)) else {
+            return false;
+        };
+        let (memory, screen) = bulk.split_at(self.memory.len().min(bulk.len()));
+        let (pixels, rest) = screen.split_at((frame.pixel_words * 4).min(screen.len()));
+        let (plane2, rest) = rest.split_at((frame.pixel_words * 4).min(rest.len()));
+        let (colors, mega) = rest.split_at((frame.colors.unwrap_or(0) * 4).min(rest.len()));
+
+        self.registers = frame.registers;
+        self.stack = frame.stack;
+        self.timers = frame.timers;
+        self.memory[..memory.len()].copy_from_slice(memory);
+        (self.display.width, self.display.height, self.display.scale) = frame.size;
+        self.display.display = delta::bytes_to_words(pixels);
+        self.display.plane2 = delta::bytes_to_words(plane2);
+        self.display.planes = frame.planes;
+        self.display.mark_all_dirty();
+        self.display.keypad.keypad = frame.keys;
+        self.display.keypad.key_wait = frame.key_wait;
+        self.display.audio = frame.audio;
+        self.megachip = if frame.megachip { MegaChip::from_raw_bytes(mega) } else { None };
+        self.display.colors = frame.colors.map(|_| delta::bytes_to_words(colors));
+        self.halted = frame.halted;
+        self.exited = frame.exited;
+        self.cycles = frame.cycles;
+        self.vblank = frame.vblank;
+        self.half_scroll = frame.half_scroll;
+        self.rng = frame.rng;
+        true
+    }
+
+    // Reseeds CXNN, e.g. so a recording or netplay session starts from a known sequence
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Rng::from_seed(seed);
+    }
+
+    pub fn quick_save(&mut self, path: &str) {
+        self.writer.write(path, self.serialize_state());
+        self.messages.push("wrote savestate!".to_string());
+        self.unsaved_frames = 0;
+        self.flags_written = false;
+    }
+
+    // Whether quitting now would lose play worth keeping: a minute of frames run since the
+    // last savestate, or RPL flags (high scores, usually) stored since
+    pub fn unsaved_progress(&self) -> bool {
+        self.unsaved_frames >= UNSAVED_PROGRESS_FRAMES || self.flags_written
+    }
+
+    pub fn unsaved_frames(&self) -> u64 {
+        self.unsaved_frames
+    }
+
+    // Stops instructions and timers (and the beep) until unpaused
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.update_stopped();
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    // Paused or editing the screen in draw mode: no instructions run and the timers hold still
+    fn stopped(&self) -> bool {
+        self.paused || self.display.draw_mode
+    }
+
+    // Silences the beep and badges the window while stopped, and picks the beep back up from
+    // the sound timer when execution restarts
+    fn update_stopped(&mut self) {
+        self.display.set_beep(!self.stopped() && self.timers.sound > 0);
+        self.display.badge = if self.display.draw_mode {
+            Some("DRAW MODE".to_string())
+        } else if self.paused {
+            Some("PAUSED".to_string())
+        } else {
+            None
+        };
+    }
+
+    // Runs a single instruction while paused, e.g. stepping through a ROM from the window. The
+    // timers stay frozen.
+    pub fn step_paused(&mut self) {
+        if self.paused && !self.halted && panic::catch_unwind(AssertUnwindSafe(|| self.step())).is_err() {
+            self.trap("crash");
+        }
+    }
+
+    pub fn toggle_draw_mode(&mut self) {
+        self.display.draw_mode = !self.display.draw_mode;
+        self.update_stopped();
+        self.messages.push(format!("draw mode {}", if self.display.draw_mode { "on (CPU paused)" } else { "off" }));
+    }
+
+    // Shows or hides the sound timer and XO-CHIP pattern panel, kept up to date every frame
+    pub fn toggle_audio_scope(&mut self) {
+        self.display.scope = match self.display.scope {
+            Some(_) => None,
+            None => Some(AudioScope { sound_timer: self.timers.sound, audio: self.display.audio }),
+        };
+    }
+
+    // Runs every instruction that's due by now, applying queued key events at the emulated
+    // time they happened so FX0A/EX9E/EXA1 see sub-frame accurate key state
+    pub fn run_until(&mut self, now: Instant, cpu_target: Duration) {
+        let timer_target = Duration::from_millis(16); // 60 Hz
+
+        if self.stopped() || self.halted {
+            self.last_cpu_tick = now; // don't catch up on the paused time afterwards
+        }
+
+        loop {
+            // CPU cycle duration (e.g., 700Hz), or the cost of the next instruction with a timing model
+            let next_cpu_tick = self.last_cpu_tick + self.next_cycle_duration(cpu_target, timer_target);
+            if now < next_cpu_tick || self.stopped() || self.halted {
+                break;
+            }
+
+            self.display.keypad.apply_until(next_cpu_tick);
+
+            // A panic inside an instruction (e.g. stack overflow) halts the CPU instead of the emulator
+            if panic::catch_unwind(AssertUnwindSafe(|| self.step())).is_err() {
+                self.trap("crash");
+            }
+            self.last_cpu_tick = next_cpu_tick; // Update to the next target time
+
+            // Drop the backlog rather than spiral if the host can't keep up
+            if now.duration_since(self.last_cpu_tick) > timer_target {
+                self.last_cpu_tick = now;
+            }
+        }
+        self.display.keypad.apply_all();
+    }
+
+    fn tick_timers(&mut self) {
+        self.timers.delay = self.timers.delay.saturating_sub(1);
+        if self.timers.sound > 0 {
+            self.display.set_beep(true);
+            self.timers.sound -= 1;
+        } else {
+            self.display.set_beep(false);
+        }
+    }
+
+    // What a 60hz frame does to the emulated machine, the same for real, run-ahead and replayed
+    // frames: frozen bytes put back, the timers ticked and the vertical blank that display_wait
+    // and scroll_wait hold sprites and scrolls for
+    fn advance_frame(&mut self) {
+        for &(address, value) in self.frozen.iter() {
+            if let Some(byte) = self.memory.get_mut(address as usize) {
+                *byte = value;
+            }
+        }
+        self.tick_timers();
+        self.vblank = true;
+    }
+
+    // Everything that happens once per 60hz frame: timers, flash fade, watches, autosave
+    // and timeline recording
+    pub fn tick_frame(&mut self) {
+        if self.stopped() {
+            self.display.tick_frame(); // toasts still fade
+            return;
+        }
+
+        self.advance_frame();
+        if let Some(ref mut scope) = self.display.scope {
+            *scope = AudioScope { sound_timer: self.timers.sound, audio: self.display.audio };
+        }
+        if !self.halted {
+            self.unsaved_frames += 1;
+        }
+        self.display.tick_frame();
+        if let Some(ref mut tickrate) = self.tickrate {
+            tickrate.end_frame();
+        }
+
+        if !self.watches.is_empty() {
+            let mut watches = std::mem::take(&mut self.watches);
+            if let Some(line) = watches.update(self) {
+                self.messages.push(line);
+            }
+            self.watches = watches;
+        }
+
+        if let Some(mut speedrun) = self.speedrun.take() {
+            speedrun.update(self);
+            self.display.hud = speedrun.format();
+            self.speedrun = Some(speedrun);
+        }
+
+        if !self.achievements.is_empty() {
+            let mut achievements = std::mem::take(&mut self.achievements);
+            for idx in achievements.update(self) {
+                self.unlock_achievement(&achievements.list[idx], idx);
+            }
+            self.achievements = achievements;
+        }
+
+        let autosave_due = self.autosave.as_mut().is_some_and(|autosave| autosave.tick());
+        if autosave_due {
+            let path = &self.autosave.as_ref().unwrap().path;
+            self.writer.write(path, self.serialize_state());
+        }
+
+        if let Some(ref mut timeline) = self.timeline {
+            timeline.end_frame(self.cycles);
+        }
+        if self.rewind.is_some() {
+            self.record_rewind_frame();
+        }
+        if self.timeline.as_ref().is_some_and(|timeline| timeline.keyframe_due()) {
+            let state = self.serialize_state();
+            self.timeline.as_mut().unwrap().add_keyframe(self.cycles, state);
+        }
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.drain(..self.messages.len() - MAX_MESSAGES);
+        }
+
+        #[cfg(all(feature = "shm", unix))]
+        if let Some(mut shared) = self.shared_state.take() {
+            shared.publish(self);
+            self.shared_state = Some(shared);
+        }
+    }
+
+    // Runs without a frontend until one of the conditions holds or the CPU halts, ticking
+    // the timers every cycles_per_frame instructions
+    pub fn run_headless(&mut self, conditions: &[HaltCondition], cycles_per_frame: u64) -> HaltResult {
+        let start_cycles = self.cycles;
+        let mut frames: u64 = 0;
+        let mut loops = LoopDetector::new();
+
+        let reason = loop {
+            if self.halted {
+                break if self.exited { "exited" } else { "halted" }.to_string();
+            }
+
+            if let Some(condition) = conditions.iter().find(|condition| self.halt_condition_met(condition, start_cycles, &mut loops)) {
+                break condition.describe();
+            }
+
+            if panic::catch_unwind(AssertUnwindSafe(|| self.step())).is_err() {
+                self.trap("crash");
+            }
+
+            if (self.cycles - start_cycles).is_multiple_of(cycles_per_frame.max(1)) {
+                self.tick_frame();
+                frames += 1;
+                loops.reset(); // a frame tick can change what the loop does
+            }
+        };
+
+        HaltResult {
+            reason,
+            cycles: self.cycles - start_cycles,
+            frames,
+            pc: self.registers.pc,
+            display_hash: self.display.hash(),
+        }
+    }
+
+    // Registers, timers and a framebuffer hash as one line of JSON, for scripts comparing runs
+    pub fn state_json(&self) -> String {
+        let v: Vec<String> = self.registers.v.iter().map(u8::to_string).collect();
+        format!(
+            "{{\"pc\":{},\"i\":{},\"sp\":{},\"v\":[{}],\"delay\":{},\"sound\":{},\"cycles\":{},\"halted\":{},\"display_hash\":\"{:#018X}\"}}",
+            self.registers.pc,
+            self.registers.i,
+            self.registers.sp,
+            v.join(","),
+            self.timers.delay,
+            self.timers.sound,
+            self.cycles,
+            self.halted,
+            self.display.hash(),
+        )
+    }
+
+    // Whether the next instruction is a draw or scroll that display_wait or scroll_wait holds
+    // until the next frame
+    fn waiting_for_vblank(&self) -> bool {
+        let pc = self.registers.pc as usize;
+        let opcode = (*self.memory.get(pc).unwrap_or(&0) as u16) << 8 | *self.memory.get(pc + 1).unwrap_or(&0) as u16;
+        !self.vblank && ((self.quirks.display_wait && opcode >> 12 == 0xD) || (self.quirks.scroll_wait && is_scroll(opcode)))
+    }
+
+    fn halt_condition_met(&self, condition: &HaltCondition, start_cycles: u64, loops: &mut LoopDetector) -> bool {
+        match condition {
+            HaltCondition::MaxCycles(cycles) => self.cycles - start_cycles >= *cycles,
+            HaltCondition::PcEquals(pc) => self.registers.pc == *pc,
+            HaltCondition::MemoryEquals(address, value) => self.memory.get(*address as usize) == Some(value),
+            HaltCondition::DisplayHash(hash) => self.display.hash() == *hash,
+            HaltCondition::When(_, expr) => expr.eval(self) != 0,
+            HaltCondition::InfiniteLoop => {
+                // With the delay timer running a loop can still be waiting on it, and a draw
+                // stalled for the vertical blank goes ahead on the next frame
+                if self.timers.delay != 0 || self.waiting_for_vblank() {
+                    loops.reset();
+                    return false;
+                }
+
+                let mut hasher = DefaultHasher::new();
+                (self.registers.pc, self.registers.v, self.registers.i, self.registers.sp, self.stack).hash(&mut hasher);
+                loops.observe(hasher.finish())
+            }
+        }
+    }
+
+    fn unlock_achievement(&mut self, achievement: &Achievement, idx: usize) {
+        let text = match achievement.message {
+            Some(ref message) => format!("{}: {}", achievement.name, message),
+            None => achievement.name.clone(),
+        };
+        self.messages.push(format!("achievement unlocked: {}", text));
+        self.display.toast(&format!("Achievement: {}", text));
+
+        if let Some(ref mut config) = self.rom_config {
+            config.set_achievement_unlocked(idx);
+            self.writer.write(&config.path, config.to_toml().into_bytes());
+        }
+    }
+
+    // Starts recording a timeline from the current state, keyframing every interval frames
+    pub fn set_timeline(&mut self, interval: Option<u64>) {
+        self.timeline = interval.map(|interval| {
+            let mut timeline = Timeline::new(interval);
+            timeline.add_keyframe(self.cycles, self.serialize_state());
+            timeline
+        });
+    }
+
+    pub fn frame(&self) -> Option<u64> {
+        self.timeline.as_ref().map(Timeline::frame)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            registers: self.registers.clone(),
+            stack: self.stack,
+            timers: self.timers.clone(),
+            memory: self.memory.clone(),
+            pixels: self.display.display.clone(),
+            plane2: self.display.plane2.clone(),
+            planes: self.display.planes,
+            size: (self.display.width, self.display.height, self.display.scale),
+            keys: self.display.keypad.keypad,
+            key_wait: self.display.keypad.key_wait,
+            beep: self.display.beep(),
+            audio: self.display.audio,
+            megachip: self.megachip.clone(),
+            colors: self.display.colors.clone(),
+            halted: self.halted,
+            exited: self.exited,
+            cycles: self.cycles,
+            vblank: self.vblank,
+            half_scroll: self.half_scroll,
+            rng: self.rng,
+        }
+    }
+
+    // Goes back to a snapshot, which can be restored again later
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.restore_owned(snapshot.clone());
+    }
+
+    fn restore_owned(&mut self, snapshot: Snapshot) {
+        self.registers = snapshot.registers;
+        self.stack = snapshot.stack;
+        self.timers = snapshot.timers;
+        self.memory = snapshot.memory;
+        (self.display.width, self.display.height, self.display.scale) = snapshot.size;
+        self.display.display = snapshot.pixels;
+        self.display.plane2 = snapshot.plane2;
+        self.display.planes = snapshot.planes;
+        self.display.mark_all_dirty();
+        self.display.keypad.keypad = snapshot.keys;
+        self.display.keypad.key_wait = snapshot.key_wait;
+        self.display.set_beep(snapshot.beep);
+        self.display.audio = snapshot.audio;
+        self.megachip = snapshot.megachip;
+        self.display.colors = snapshot.colors;
+        self.halted = snapshot.halted;
+        self.exited = snapshot.exited;
+        self.cycles = snapshot.cycles;
+        self.vblank = snapshot.vblank;
+        self.half_scroll = snapshot.half_scroll;
+        self.rng = snapshot.rng;
+    }
+
+    // Run-ahead: emulates frames past the current one with the input held as it is now, shows
+    // the last of them and rolls back, so a key press reaches the screen that many frames
+    // sooner. The real frames then run as usual, drawing the same random numbers since the
+    // generator rolls back too. Recorders and profilers sit the extra frames out, and traps,
+    // crash captures and warnings there wait for the real frame to report them.
+    pub fn run_ahead(&mut self, frames: u8, cycles_per_frame: u64, show: impl FnOnce(&Display)) {
+        let snapshot = self.snapshot();
+        let timeline = self.timeline.take();
+        let blocks = self.blocks.take();
+        let tickrate = self.tickrate.take();
+        let opcode_timing = self.opcode_timing.take();
+        let trace = self.trace.take();
+        let strict = self.strict.take();
+        self.speculative = true;
+
+        for _ in 0..frames {
+            for _ in 0..cycles_per_frame {
+                if self.halted {
+                    break;
+                }
+                if panic::catch_unwind(AssertUnwindSafe(|| self.step())).is_err() {
+                    self.halted = true;
+                }
+            }
+            self.advance_frame();
+        }
+        show(&self.display);
+
+        self.speculative = false;
+        self.restore_owned(snapshot);
+        self.timeline = timeline;
+        self.blocks = blocks;
+        self.tickrate = tickrate;
+        self.opcode_timing = opcode_timing;
+        self.trace = trace;
+        self.strict = strict;
+    }
+
+    // Jumps to an earlier recorded frame by loading the nearest keyframe and replaying the
+    // recorded input; recording continues from that frame afterwards
+    pub fn goto_frame(&mut self, frame: u64) -> io::Result<()> {
+        let Some(timeline) = self.timeline.take() else {
+            return Err(io::Error::other("no timeline is being recorded"));
+        };
+
+        let result = self.replay(&timeline, frame);
+        self.timeline = Some(timeline);
+        if result.is_ok() {
+            self.timeline.as_mut().unwrap().truncate(frame);
+        }
+        result
+    }
+
+    // The keyframe carries CXNN's generator state, so random numbers repeat during the replay
+    fn replay(&mut self, timeline: &Timeline, frame: u64) -> io::Result<()> {
+        if frame > timeline.frame() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("frame {} hasn't been recorded yet", frame)));
+        }
+
+        let Some(keyframe) = timeline.keyframe_for(frame) else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("no keyframe at or before frame {}", frame)));
+        };
+
+        self.restore_state(&keyframe.state)?;
+        self.cycles = keyframe.cycles;
+        self.halted = false;
+
+        for n in keyframe.frame + 1..=frame {
+            let end = timeline.frame_end(n).unwrap_or(self.cycles);
+            while self.cycles < end && !self.halted {
+                self.set_keys(timeline.keys_at(self.cycles));
+                if panic::catch_unwind(AssertUnwindSafe(|| self.step())).is_err() {
+                    self.halted = true;
+                }
+            }
+            self.advance_frame();
+        }
+
+        self.set_keys(timeline.keys_at(self.cycles));
+        Ok(())
+    }
+
+    fn set_keys(&mut self, keys: [bool; 16]) {
+        for (key, &down) in keys.iter().enumerate() {
+            if down != self.display.keypad.keypad[key] {
+                if down {
+                    self.display.keypad.key_down(key as u8);
+                } else {
+                    self.display.keypad.key_up(key as u8);
+                }
+            }
+        }
+    }
+}
+
+impl ExprContext for Chip8 {
+    fn register(&self, idx: u8) -> u8 {
+        self.registers.v[idx as usize & 0xF]
+    }
+
+    fn index(&self) -> u16 {
+        self.registers.i as u16
+    }
+
+    fn pc(&self) -> u16 {
+        self.registers.pc
+    }
+
+    fn sp(&self) -> i8 {
+        self.registers.sp
+    }
+
+    fn delay(&self) -> u8 {
+        self.timers.delay
+    }
+
+    fn sound(&self) -> u8 {
+        self.timers.sound
+    }
+
+    fn memory(&self, address: u16) -> u8 {
+        *self.memory.get(address as usize).unwrap_or(&0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu_with(address: u16, program: &[u8]) -> Chip8 {
+        let mut cpu = Chip8::new(None, None, None, Display::new(64, 32, 1), None).unwrap();
+        cpu.odd_pc_policy = OddPcPolicy::Allow;
+        cpu.memory[address as usize..address as usize + program.len()].copy_from_slice(program);
+        cpu.registers.pc = address;
+        cpu
+    }
+
+    #[test]
+    fn fetch_at_the_last_word_wraps_pc_to_zero() {
+        let mut cpu = cpu_with(0xFFFE, &[0x60, 0x2A]); // LD V0, 0x2A
+        cpu.step();
+        assert_eq!(cpu.registers.v[0], 0x2A);
+        assert_eq!(cpu.registers.pc, 0x0000);
+        assert!(!cpu.halted);
+    }
+
+    #[test]
+    fn fetch_at_the_last_byte_reads_across_the_wrap() {
+        let mut cpu = cpu_with(0xFFFF, &[0x61]);
+        cpu.memory[0x0000] = 0x07; // LD V1, 0x07 split across the end of memory
+        cpu.step();
+        assert_eq!(cpu.registers.v[1], 0x07);
+        assert_eq!(cpu.registers.pc, 0x0001);
+    }
+
+    #[test]
+    fn skip_at_the_end_of_memory_wraps() {
+        let mut cpu = cpu_with(0xFFFC, &[0x30, 0x00]); // SE V0, 0x00
+        cpu.step();
+        assert_eq!(cpu.registers.pc, 0x0000);
+    }
+
+    // 2x2 block at (8, 8) on an otherwise blank screen
+    fn scrolled(width: u16, height: u16, schip_lores_scroll: bool, program: &[u8]) -> Chip8 {
+        let mut cpu = cpu_with(0x200, program);
+        cpu.quirks.schip_lores_scroll = schip_lores_scroll;
+        cpu.display.resize(width, height, 1);
+        for (x, y) in [(8, 8), (9, 8), (8, 9), (9, 9)] {
+            cpu.display.set_pixel(x, y, 1);
+        }
+        cpu.step();
+        cpu
+    }
+
+    // The 16x16 top-left corner as '#'/'.' rows
+    fn snapshot(cpu: &Chip8) -> Vec<String> {
+        (0..16)
+            .map(|y| (0..16).map(|x| if cpu.display.get_pixel(x, y) != 0 { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    fn block_at(x: usize, y: usize) -> Vec<String> {
+        (0..16)
+            .map(|row| (0..16).map(|col| if (x..x + 2).contains(&col) && (y..y + 2).contains(&row) { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn xochip_lores_scrolls_by_the_literal_amount() {
+        assert_eq!(snapshot(&scrolled(64, 32, false, &[0x00, 0xC4])), block_at(8, 12)); // 00C4
+        assert_eq!(snapshot(&scrolled(64, 32, false, &[0x00, 0xD4])), block_at(8, 4)); // 00D4
+        assert_eq!(snapshot(&scrolled(64, 32, false, &[0x00, 0xB4])), block_at(8, 4)); // 00B4
+        assert_eq!(snapshot(&scrolled(64, 32, false, &[0x00, 0xFB])), block_at(12, 8)); // 00FB
+        assert_eq!(snapshot(&scrolled(64, 32, false, &[0x00, 0xFC])), block_at(4, 8)); // 00FC
+    }
+
+    #[test]
+    fn schip_lores_scrolls_by_half_the_amount() {
+        assert_eq!(snapshot(&scrolled(64, 32, true, &[0x00, 0xC4])), block_at(8, 10));
+        assert_eq!(snapshot(&scrolled(64, 32, true, &[0x00, 0xD4])), block_at(8, 6));
+        assert_eq!(snapshot(&scrolled(64, 32, true, &[0x00, 0xB4])), block_at(8, 6));
+        assert_eq!(snapshot(&scrolled(64, 32, true, &[0x00, 0xFB])), block_at(10, 8));
+        assert_eq!(snapshot(&scrolled(64, 32, true, &[0x00, 0xFC])), block_at(6, 8));
+    }
+
+    #[test]
+    fn schip_lores_odd_scrolls_carry_the_half_pixel() {
+        assert_eq!(snapshot(&scrolled(64, 32, true, &[0x00, 0xC1])), block_at(8, 8)); // half a pixel down
+        let mut cpu = scrolled(64, 32, true, &[0x00, 0xC1, 0x00, 0xC3]);
+        cpu.step();
+        assert_eq!(snapshot(&cpu), block_at(8, 10));
+        let mut cpu = scrolled(64, 32, true, &[0x00, 0xC3, 0x00, 0xB1]);
+        cpu.step();
+        assert_eq!(snapshot(&cpu), block_at(8, 9));
+    }
+
+    #[test]
+    fn hires_scrolls_ignore_the_lores_quirk() {
+        for quirk in [false, true] {
+            assert_eq!(snapshot(&scrolled(128, 64, quirk, &[0x00, 0xC4])), block_at(8, 12));
+            assert_eq!(snapshot(&scrolled(128, 64, quirk, &[0x00, 0xFC])), block_at(4, 8));
+        }
+    }
+
+    // Golden savestates in tests/fixtures. They're what the writer produced when they were
+    // checked in; run with UPDATE_SAVESTATE_FIXTURES=1 to regenerate them after a deliberate
+    // format change (and bump the version in write_state).
+    fn fixture_path(name: &str) -> String {
+        format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    // Hires, a sprite on screen, I, V0-V1, VA, the delay timer and one return address on the
+    // stack, then a CXNN draw spinning in a subroutine
+    const FIXTURE_PROGRAM: [u8; 18] = [
+        0x00, 0xFF, // 0x200 HIGH
+        0xA2, 0x20, // 0x202 LD I, 0x220
+        0x60, 0x05, // 0x204 LD V0, 5
+        0x61, 0x03, // 0x206 LD V1, 3
+        0xD0, 0x15, // 0x208 DRW V0, V1, 5
+        0x6A, 0x3C, // 0x20A LD VA, 60
+        0xFA, 0x15, // 0x20C LD DT, VA
+        0x22, 0x10, // 0x20E CALL 0x210
+        0xC2, 0xFF, // 0x210 RND V2, 0xFF (then runs into the sprite, which is harmless)
+    ];
+
+    fn fixture_states() -> Vec<(&'static str, Vec<u8>)> {
+        let mut cpu = cpu_with(0x200, &FIXTURE_PROGRAM);
+        cpu.memory[0x220..0x225].copy_from_slice(&[0xF0, 0x90, 0xF0, 0x90, 0x90]);
+        cpu.seed_rng(0x5EED);
+        let boot = cpu.serialize_state();
+
+        for _ in 0..9 {
+            cpu.step();
+        }
+        cpu.tick_frame();
+        vec![("boot.sav", boot), ("running.sav", cpu.serialize_state())]
+    }
+
+    #[test]
+    fn writer_reproduces_the_golden_savestates() {
+        for (name, state) in fixture_states() {
+            if std::env::var_os("UPDATE_SAVESTATE_FIXTURES").is_some() {
+                std::fs::create_dir_all(fixture_path("")).unwrap();
+                std::fs::write(fixture_path(name), &state).unwrap();
+            }
+            let golden = std::fs::read(fixture_path(name)).unwrap();
+            assert!(state == golden, "{} no longer matches what the writer produces", name);
+        }
+    }
+
+    #[test]
+    fn golden_savestates_load_back_to_the_same_state() {
+        for (name, _) in fixture_states() {
+            let golden = std::fs::read(fixture_path(name)).unwrap();
+            let mut cpu = cpu_with(0x200, &[]);
+            cpu.load_state(&fixture_path(name)).unwrap();
+            assert!(cpu.serialize_state() == golden, "{} changed on the way through load_state", name);
+        }
+
+        let mut cpu = cpu_with(0x200, &FIXTURE_PROGRAM);
+        cpu.load_state(&fixture_path("running.sav")).unwrap();
+        assert_eq!((cpu.display.width, cpu.registers.sp, cpu.registers.v[0xA]), (128, 0, 60));
+
+        // A cut-short or foreign file is an error and leaves the state alone
+        let state = std::fs::read(fixture_path("running.sav")).unwrap();
+        let before = cpu.serialize_state();
+        assert!(cpu.restore_state(&state[..state.len() - 3]).is_err());
+        assert!(cpu.restore_state(b"PK\x03\x04").is_err());
+        let mut future = state.clone();
+        future[4] = 2;
+        assert!(cpu.restore_state(&future).unwrap_err().to_string().contains("version 2.3.0"));
+        assert!(cpu.serialize_state() == before);
+    }
+
+    #[test]
+    fn format_1_0_savestates_still_load() {
+        // Written as 1.0 did: 0xFFFF bytes of memory, a word-RLE screen and no optional chunks.
+        // Screen word 7 has a zero low byte, which reads like the start of a run.
+        let mut cpu = cpu_with(0x200, &[]);
+        cpu.load_state(&fixture_path("v1_0.sav")).unwrap();
+        assert_eq!((cpu.registers.v[0], cpu.registers.v[0xA], cpu.registers.i, cpu.registers.pc, cpu.registers.sp), (5, 60, 0x220, 0x20E, -1));
+        assert_eq!((cpu.timers.delay, cpu.memory[0x200 + 17], cpu.memory[0x224], cpu.memory[0xFFFF]), (60, 0xFF, 0x90, 0));
+        assert_eq!((cpu.display.width, cpu.display.display[7], cpu.display.display[60]), (64, 0x0F800000, 0xFFFFFFFF));
+        assert_eq!(cpu.display.display.iter().filter(|&&word| word != 0).count(), 2);
+
+        let mut state = std::fs::read(fixture_path("v1_0.sav")).unwrap();
+        state.pop();
+        assert!(cpu.restore_state(&state).is_err());
+    }
+
+    #[test]
+    fn save_load_save_is_byte_identical_and_restores_the_machine() {
+        for (name, saved) in fixture_states() {
+            let mut cpu = cpu_with(0x200, &[]);
+            cpu.restore_state(&saved).unwrap();
+            assert!(cpu.serialize_state() == saved, "{} changed between save, load and save", name);
+        }
+
+        // The machine loaded from the running state is the one that wrote it
+        let (_, running) = fixture_states().pop().unwrap();
+        let mut cpu = cpu_with(0x200, &FIXTURE_PROGRAM);
+        cpu.memory[0x220..0x225].copy_from_slice(&[0xF0, 0x90, 0xF0, 0x90, 0x90]);
+        cpu.seed_rng(0x5EED);
+        for _ in 0..9 {
+            cpu.step();
+        }
+        cpu.tick_frame();
+        let mut loaded = cpu_with(0x200, &[]);
+        loaded.restore_state(&running).unwrap();
+        assert_eq!((loaded.registers.v, loaded.registers.i, loaded.registers.pc, loaded.registers.sp), (cpu.registers.v, cpu.registers.i, cpu.registers.pc, cpu.registers.sp));
+        assert_eq!((loaded.timers.delay, loaded.timers.sound, loaded.stack), (cpu.timers.delay, cpu.timers.sound, cpu.stack));
+        assert!(loaded.memory[..] == cpu.memory[..] && loaded.display.frame() == cpu.display.frame());
+        loaded.step();
+        cpu.step();
+        assert!(loaded.serialize_state() == cpu.serialize_state()); // and runs on the same way
+    }
+
+    #[test]
+    fn serializing_is_deterministic() {
+        let first = fixture_states();
+        let second = fixture_states();
+        for ((name, a), (_, b)) in first.iter().zip(second.iter()) {
+            assert!(a == b, "{} differs between two identical runs", name);
+        }
+    }
+
+    #[test]
+    fn missing_rom_is_an_error() {
+        let result = Chip8::new(None, None, Some("does/not/exist.ch8"), Display::new(64, 32, 1), None);
+        assert!(matches!(result, Err(EmulatorError::Read { .. })));
+    }
+
+    #[test]
+    fn rom_too_large_for_memory_is_an_error() {
+        let path = std::env::temp_dir().join("chip8-too-large.ch8");
+        let mut cpu = cpu_with(0x200, &[]);
+        std::fs::write(&path, vec![0u8; cpu.memory.len()]).unwrap();
+        let result = cpu.read_to_memory(path.to_str().unwrap(), 0x200);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(EmulatorError::TooLarge { max: 0xFE00, .. })));
+    }
+
+    #[test]
+    fn state_json_reports_registers_and_timers() {
+        // LD V3, 0x2A; LD I, 0x300; LD DT, V3
+        let mut cpu = cpu_with(0x200, &[0x63, 0x2A, 0xA3, 0x00, 0xF3, 0x15]);
+        for _ in 0..3 {
+            cpu.step();
+        }
+        let json = cpu.state_json();
+        assert!(json.starts_with("{\"pc\":518,\"i\":768,\"sp\":-1,\"v\":[0,0,0,42,0,"), "{}", json);
+        assert!(json.contains("\"delay\":42,\"sound\":0,\"cycles\":3,\"halted\":false"), "{}", json);
+    }
+
+    #[test]
+    fn pausing_freezes_timers_and_the_beep() {
+        // LD V0, 0x10; LD DT, V0; LD ST, V0
+        let mut cpu = cpu_with(0x200, &[0x60, 0x10, 0xF0, 0x15, 0xF0, 0x18]);
+        for _ in 0..3 {
+            cpu.step();
+        }
+        cpu.tick_frame();
+        assert!(cpu.display().beep());
+
+        cpu.set_paused(true);
+        assert!(!cpu.display().beep());
+        assert_eq!(cpu.display().badge.as_deref(), Some("PAUSED"));
+        for _ in 0..5 {
+            cpu.tick_frame();
+        }
+        assert_eq!((cpu.delay_timer(), cpu.sound_timer()), (0x0F, 0x0F));
+
+        cpu.set_paused(false);
+        assert!(cpu.display().beep());
+        assert_eq!(cpu.display().badge, None);
+    }
+
+    #[test]
+    fn xochip_planes_draw_and_clear_separately() {
+        let mut cpu = cpu_with(0x200, &[
+            0xF3, 0x01, // 0x200 PLANE 3
+            0xA3, 0x00, // 0x202 LD I, 0x300
+            0xD0, 0x01, // 0x204 DRW V0, V0, 1: 0xC0 on plane 1, 0xA0 on plane 2
+            0xF2, 0x01, // 0x206 PLANE 2
+            0x00, 0xE0, // 0x208 CLS
+        ]);
+        cpu.memory[0x300] = 0xC0;
+        cpu.memory[0x301] = 0xA0;
+
+        for _ in 0..3 {
+            cpu.step();
+        }
+        let row = |cpu: &Chip8| (0..3).map(|x| cpu.display.get_pixel(x, 0)).collect::<Vec<u8>>();
+        assert_eq!(row(&cpu), [3, 1, 2]);
+
+        cpu.step();
+        cpu.step();
+        assert_eq!(row(&cpu), [1, 1, 0]);
+    }
+
+    #[test]
+    fn xochip_audio_loads_a_pattern_and_pitch() {
+        let mut cpu = cpu_with(0x200, &[
+            0xA3, 0x00, // 0x200 LD I, 0x300
+            0xF0, 0x02, // 0x202 AUDIO
+            0x60, 0x70, // 0x204 LD V0, 0x70
+            0xF0, 0x3A, // 0x206 PITCH V0
+        ]);
+        cpu.memory[0x300] = 0x80;
+        cpu.memory[0x30F] = 0x01;
+
+        for _ in 0..4 {
+            cpu.step();
+        }
+        let audio = cpu.display().audio;
+        assert!(audio.bit(0) && audio.bit(127) && !audio.bit(1));
+        assert_eq!(audio.pitch, 0x70);
+
+        cpu.reset();
+        assert_eq!(cpu.display().audio, Audio::default());
+    }
+
+    #[test]
+    fn skips_step_over_the_whole_long_index_instruction() {
+        let mut cpu = cpu_with(0x200, &[
+            0x30, 0x00,             // 0x200 SE V0, 0x00
+            0xF0, 0x00, 0x12, 0x34, // 0x202 LD I, 0x1234
+            0xF0, 0x00, 0x56, 0x78, // 0x206 LD I, 0x5678
+        ]);
+        cpu.step();
+        assert_eq!(cpu.registers.pc, 0x206);
+        cpu.step();
+        assert_eq!((cpu.registers.i, cpu.registers.pc), (0x5678, 0x20A));
+    }
+
+    #[test]
+    fn rom_warnings_name_the_overlap_and_the_limit() {
+        let mut cpu = cpu_with(0x200, &[]);
+        cpu.quirks = Quirks::preset("chip8").unwrap();
+        assert!(cpu.rom_warnings(0x200, 0xE00).is_empty());
+
+        let warnings = cpu.rom_warnings(0x100, 0xF01);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("interpreter area"));
+        assert!(warnings[1].starts_with("ROM is 3841 bytes, 1 past the 4K"));
+
+        cpu.quirks = Quirks::preset("xochip").unwrap();
+        assert!(cpu.rom_warnings(0x200, 0xF01).is_empty());
+        assert!(cpu.rom_warnings(0x200, 0xFE01)[0].contains("the last 1 are cut off"));
+    }
+
+    #[test]
+    fn addresses_wrap_at_the_profile_memory_size() {
+        let program = [
+            0xF0, 0x1E, // 0x200 ADD I, V0
+            0xF1, 0x65, // 0x202 LD V1, [I]
+        ];
+        let mut cpu = cpu_with(0x200, &program);
+        cpu.quirks = Quirks::preset("chip8").unwrap();
+        cpu.registers.i = 0xFFF;
+        cpu.registers.v[0] = 2;
+        cpu.step();
+        assert_eq!((cpu.registers.i, cpu.registers.v[0xF]), (0x001, 0)); // the VIP wraps without touching VF
+
+        let mut cpu = cpu_with(0x200, &program);
+        cpu.quirks = Quirks::preset("xochip").unwrap();
+        cpu.memory[0x1001] = 0xAB;
+        cpu.registers.i = 0xFFF;
+        cpu.registers.v[0] = 2;
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.registers.v[0], 0xAB);
+        assert_eq!(cpu.registers.i, 0x1003); // past the 4K boundary, moved on by the FX65 quirk
+    }
+
+    #[test]
+    fn schip_draws_8x16_in_lores_counts_collided_rows_and_exits() {
+        let sprite = [
+            0xA3, 0x00, // 0x200 LD I, 0x300
+            0xD0, 0x00, // 0x202 DRW V0, V0, 0
+            0xD0, 0x00, // 0x204 DRW V0, V0, 0
+            0x00, 0xFD, // 0x206 EXIT
+        ];
+        let mut cpu = cpu_with(0x200, &sprite);
+        cpu.quirks = Quirks::preset("schip1.1").unwrap();
+        cpu.memory[0x300..0x320].fill(0xFF);
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.display.get_pixel(7, 15), 1);
+        assert_eq!(cpu.display.get_pixel(8, 0), 0);
+
+        let mut cpu = cpu_with(0x200, &sprite);
+        cpu.quirks = Quirks::preset("schip1.1").unwrap();
+        cpu.display.resize(128, 64, 1);
+        cpu.memory[0x300..0x320].fill(0xFF);
+        for _ in 0..4 {
+            cpu.step();
+        }
+        assert_eq!(cpu.registers.v[0xF], 16);
+        assert!(cpu.exited && cpu.halted);
+        assert_eq!(cpu.registers.pc, 0x208);
+    }
+
+    #[test]
+    fn megachip_draws_palette_colors_once_00e0_presents_them() {
+        let program = [
+            0x00, 0x11, // 0x200 MEGAON
+            0x01, 0x00, 0x03, 0x00, // 0x202 LDHI 0x000300
+            0x02, 0x01, // 0x206 LDPAL 1
+            0x03, 0x02, // 0x208 SPRW 2
+            0x04, 0x01, // 0x20A SPRH 1
+            0x09, 0x01, // 0x20C collision color 1
+            0xA3, 0x10, // 0x20E LD I, 0x310
+            0x60, 0x05, // 0x210 LD V0, 5
+            0xD0, 0x01, // 0x212 DRW V0, V0
+            0xD0, 0x01, // 0x214 DRW V0, V0
+            0x00, 0xE0, // 0x216 CLS, presenting the frame
+        ];
+        let mut cpu = cpu_with(0x200, &program);
+        cpu.quirks = Quirks::preset("megachip").unwrap();
+        cpu.memory[0x300..0x304].copy_from_slice(&[0xFF, 0x12, 0x34, 0x56]);
+        cpu.memory[0x310..0x312].copy_from_slice(&[1, 1]);
+        for _ in 0..9 {
+            cpu.step();
+        }
+        assert_eq!(cpu.registers.v[0xF], 0);
+        assert!(cpu.display.frame().iter().all(|&color| color == (0, 0, 0))); // not presented yet
+
+        cpu.step();
+        assert_eq!(cpu.registers.v[0xF], 1);
+        cpu.step();
+        let frame = cpu.display.frame();
+        assert_eq!((cpu.display.width, cpu.display.height), (256, 192));
+        assert_eq!(frame[5 * 256 + 6], (0x12, 0x34, 0x56));
+        assert_eq!(frame[5 * 256 + 7], (0, 0, 0));
+    }
+
+    #[test]
+    fn megachip_mode_and_run_state_survive_a_savestate() {
+        let program = [
+            0x00, 0x11, // 0x200 MEGAON
+            0x01, 0x01, 0x03, 0x00, // 0x202 LDHI 0x010300, past 16 bits
+            0x03, 0x02, // 0x206 SPRW 2
+            0x04, 0x01, // 0x208 SPRH 1
+            0x08, 0x04, // 0x20A BMODE add
+        ];
+        let mut cpu = cpu_with(0x200, &program);
+        cpu.quirks = Quirks::preset("megachip").unwrap();
+        for _ in 0..5 {
+            cpu.step();
+        }
+        cpu.display.colors.as_mut().unwrap()[256 * 5 + 6] = 0x123456; // as if presented
+        cpu.display.keypad.key_wait = KeyWait { waiting: true, pressed: Some(0xB), released: None };
+        cpu.half_scroll = 1;
+        cpu.halted = true;
+        let state = cpu.serialize_state();
+
+        let mut loaded = cpu_with(0x200, &[]);
+        loaded.restore_state(&state).unwrap();
+        assert_eq!(loaded.registers.i, 0x010300);
+        assert_eq!((loaded.display.width, loaded.display.height), (256, 192));
+        assert_eq!(loaded.display.frame()[256 * 5 + 6], (0x12, 0x34, 0x56));
+        let mega = loaded.megachip.as_ref().unwrap();
+        assert_eq!((mega.sprite_width, mega.sprite_height, mega.blend), (2, 1, Blend::Add));
+        assert!(loaded.display.keypad.key_wait == cpu.display.keypad.key_wait);
+        assert_eq!((loaded.cycles, loaded.half_scroll, loaded.halted), (cpu.cycles, 1, true));
+        assert!(loaded.serialize_state() == state);
+    }
+
+    #[test]
+    fn two_page_hires_stub_starts_at_0x2c0_on_a_64x64_screen() {
+        let mut rom = vec![0; 0xC2];
+        rom[..2].copy_from_slice(&[0x12, 0x60]); // JP 0x260, into the interpreter patch
+        rom[0xC0..].copy_from_slice(&[0x02, 0x30]); // 0x2C0 CLS
+        let display = Display::new(64, 32, 1);
+        let mut cpu = Chip8::new(None, None, None, display, Quirks::preset("chip8")).unwrap();
+        cpu.load_rom(&rom, 0x200);
+        assert_eq!(cpu.registers.pc, 0x2C0);
+        assert_eq!((cpu.display.width, cpu.display.height), (64, 64));
+
+        cpu.display.set_pixel(0, 63, 1);
+        cpu.step();
+        assert_eq!(cpu.display.get_pixel(0, 63), 0);
+
+        let mut cpu = Chip8::new(None, None, None, Display::new(64, 32, 1), Quirks::preset("schip1.1")).unwrap();
+        cpu.load_rom(&rom, 0x200);
+        assert_eq!(cpu.registers.pc, 0x200); // only the CHIP-8 profile looks for the stub
+    }
+
+    #[test]
+    fn strict_mode_leaves_the_lores_quirks_out_of_hires_chip8() {
+        let mut rom = vec![0; 0xC6];
+        rom[..2].copy_from_slice(&[0x12, 0x60]); // JP 0x260, into the two-page hires stub
+        rom[0xC0..].copy_from_slice(&[
+            0xD0, 0x10, // 0x2C0 DRW V0, V1, 0
+            0x00, 0xC1, // 0x2C2 SCD 1
+            0x12, 0xC0, // 0x2C4 JP 0x2C0
+        ]);
+        let mut cpu = Chip8::new(None, None, None, Display::new(64, 32, 1), Quirks::preset("chip8")).unwrap();
+        cpu.set_strict(true);
+        cpu.load_rom(&rom, 0x200);
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert_eq!((cpu.display.width, cpu.display.height), (64, 64));
+        assert!(cpu.quirk_report().unwrap().contains("no quirk-dependent instructions"));
+
+        // The same code on SCHIP's 64x32 lores screen depends on both
+        let mut cpu = cpu_with(0x2C0, &rom[0xC0..]);
+        cpu.set_strict(true);
+        for _ in 0..3 {
+            cpu.step();
+        }
+        let report = cpu.quirk_report().unwrap();
+        assert!(report.contains("schip_lores_dxy0") && report.contains("schip_lores_scroll"), "{}", report);
+    }
+
+    #[test]
+    fn eti660_programs_load_and_restart_at_0x600() {
+        let mut cpu = Chip8::new(None, None, None, Display::new(64, 32, 1), Quirks::preset("eti660")).unwrap();
+        cpu.load_program(&[0x60, 0x2A]); // LD V0, 0x2A
+        assert_eq!(cpu.registers.pc, 0x600);
+        assert_eq!(cpu.memory[0x600], 0x60);
+        cpu.step();
+        assert_eq!(cpu.registers.v[0], 0x2A);
+
+        cpu.load_program(&[0x00, 0xE0]);
+        assert_eq!(cpu.registers.pc, 0x600);
+    }
+
+    #[test]
+    fn reset_restores_the_rom_as_loaded_and_reloads_diff_against_it() {
+        let mut cpu = Chip8::new(None, None, None, Display::new(64, 32, 1), None).unwrap();
+        cpu.load_program(&[0xA2, 0x00, 0x60, 0x55, 0xF0, 0x55]); // LD I, 0x200; LD V0, 0x55; LD [I], V0
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert_eq!(cpu.memory[0x200], 0x55); // overwrote itself
+
+        cpu.reset();
+        assert_eq!(cpu.memory[0x200], 0xA2);
+        assert_eq!(cpu.rom(), &[0xA2, 0x00, 0x60, 0x55, 0xF0, 0x55]);
+
+        assert_eq!(cpu.rom_changes(&[0xA2, 0x00, 0x60, 0x66, 0xF0, 0x55, 0x00, 0xEE]), vec![0x203..0x204, 0x206..0x208]);
+        assert_eq!(cpu.rom_changes(&[0xA2, 0x00]), vec![0x202..0x206]);
+    }
+
+    #[test]
+    fn display_wait_draws_at_most_one_sprite_per_frame() {
+        let program = [
+            0xD0, 0x15, // 0x200 DRW V0, V1, 5
+            0xD0, 0x15, // 0x202 DRW V0, V1, 5
+        ];
+        let mut cpu = cpu_with(0x200, &program);
+        cpu.quirks.display_wait = true;
+        cpu.step();
+        assert_eq!(cpu.registers.pc, 0x200); // stalled until the first frame ticks
+
+        cpu.tick_frame();
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.registers.pc, 0x202);
+        cpu.tick_frame();
+        cpu.step();
+        assert_eq!(cpu.registers.pc, 0x204);
+
+        // A stalled draw isn't a program stuck in a loop
+        let mut cpu = cpu_with(0x200, &[0xD0, 0x15, 0x12, 0x00]);
+        cpu.quirks.display_wait = true;
+        let result = cpu.run_headless(&[HaltCondition::InfiniteLoop, HaltCondition::MaxCycles(100)], 50);
+        assert_eq!(result.reason, HaltCondition::MaxCycles(100).describe());
+    }
+
+    #[test]
+    fn rewind_goes_back_whole_frames_of_registers_memory_and_screen() {
+        let program = [
+            0x70, 0x01, // 0x200 ADD V0, 1
+            0xA3, 0x00, // 0x202 LD I, 0x300
+            0xF0, 0x55, // 0x204 LD [I], V0
+        ];
+        let mut cpu = cpu_with(0x200, &program);
+        assert!(!cpu.rewind(0));
+        cpu.enable_rewind(1 << 20);
+        for frame in 1..=5 {
+            cpu.registers.pc = 0x200;
+            for _ in 0..3 {
+                cpu.step();
+            }
+            cpu.display.set_pixel(frame, 0, 1);
+            cpu.tick_frame();
+        }
+
+        assert!(cpu.rewind(2));
+        assert_eq!((cpu.registers.v[0], cpu.memory[0x300], cpu.registers.pc), (3, 3, 0x206));
+        assert_eq!((cpu.display.get_pixel(3, 0), cpu.display.get_pixel(4, 0)), (1, 0));
+        assert!(cpu.rewind(2));
+        assert_eq!(cpu.registers.v[0], 1);
+        assert!(!cpu.rewind(1)); // frames after the one gone back to are dropped
+    }
+
+    #[test]
+    fn rewind_keeps_megachip_mode_and_an_exit() {
+        let program = [
+            0x00, 0x11, // 0x200 MEGAON
+            0x01, 0x00, 0x03, 0x00, // 0x202 LDHI 0x000300
+            0x03, 0x02, // 0x206 SPRW 2
+            0x00, 0xFD, // 0x208 EXIT
+        ];
+        let mut cpu = cpu_with(0x200, &program);
+        cpu.quirks = Quirks::preset("megachip").unwrap();
+        cpu.enable_rewind(1 << 22);
+        cpu.tick_frame();
+        for _ in 0..3 {
+            cpu.step();
+        }
+        cpu.display.colors.as_mut().unwrap()[256 * 5 + 6] = 0x123456; // as if presented
+        cpu.half_scroll = 1;
+        cpu.tick_frame();
+        cpu.vblank = false;
+        cpu.step();
+        cpu.tick_frame();
+        assert!(cpu.exited);
+
+        cpu.exited = false;
+        cpu.megachip = None;
+        assert!(cpu.rewind(0));
+        assert!(cpu.exited && cpu.halted && cpu.megachip.is_some());
+
+        assert!(cpu.rewind(1));
+        assert!(!cpu.exited && !cpu.halted && cpu.vblank);
+        assert_eq!((cpu.registers.pc, cpu.registers.i, cpu.half_scroll), (0x208, 0x300, 1));
+        assert_eq!(cpu.megachip.as_ref().unwrap().sprite_width, 2);
+        assert_eq!((cpu.display.width, cpu.display.frame()[256 * 5 + 6]), (256, (0x12, 0x34, 0x56)));
+
+        assert!(cpu.rewind(1)); // back before MEGAON
+        assert!(cpu.megachip.is_none() && cpu.display.colors.is_none());
+        assert_eq!((cpu.display.width, cpu.half_scroll), (64, 0));
+    }
+
+    #[test]
+    fn progress_is_unsaved_after_a_minute_of_play_or_a_flags_write() {
+        let mut cpu = cpu_with(0x200, &[0x12, 0x00]); // JP 0x200
+        for _ in 0..UNSAVED_PROGRESS_FRAMES - 1 {
+            cpu.tick_frame();
+        }
+        assert!(!cpu.unsaved_progress());
+        cpu.tick_frame();
+        assert!(cpu.unsaved_progress());
+
+        cpu.load_program(&[0xF0, 0x75]); // LD R, V0
+        assert!(!cpu.unsaved_progress());
+        cpu.step();
+        assert!(cpu.unsaved_progress());
+        _ = std::fs::remove_file("flags.bin");
+    }
+
+    #[test]
+    fn vf_reset_clears_vf_after_logic_ops() {
+        let program = [
+            0x80, 0x11, // 0x200 OR V0, V1
+            0x80, 0x12, // 0x202 AND V0, V1
+            0x80, 0x13, // 0x204 XOR V0, V1
+        ];
+        for vf_reset in [false, true] {
+            let mut cpu = cpu_with(0x200, &program);
+            cpu.quirks.vf_reset = vf_reset;
+            for _ in 0..3 {
+                cpu.registers.v[0xF] = 0x55;
+                cpu.step();
+                assert_eq!(cpu.registers.v[0xF], if vf_reset { 0 } else { 0x55 });
+            }
+        }
+        assert!(Quirks::preset("chip8").unwrap().vf_reset);
+        assert!(!Quirks::preset("schip1.1").unwrap().vf_reset);
+    }
+
+    #[test]
+    fn fx55_and_fx65_increment_i_independently() {
+        let program = [
+            0xF1, 0x55, // 0x200 LD [I], V1
+            0xF1, 0x65, // 0x202 LD V1, [I]
+        ];
+        let mut cpu = cpu_with(0x200, &program);
+        cpu.quirks.cosmac_fx55 = true;
+        cpu.registers.i = 0x300;
+        cpu.step();
+        assert_eq!(cpu.registers.i, 0x302);
+        cpu.step();
+        assert_eq!(cpu.registers.i, 0x302);
+
+        let config = crate::toml::parse("[quirks]\ncosmac_fx65 = true").unwrap();
+        let quirks = Quirks::from_config(&config).unwrap();
+        assert!(quirks.cosmac_fx65 && !quirks.cosmac_fx55);
+    }
+
+    #[test]
+    fn segments_load_around_the_program_and_survive_reset() {
+        let dir = std::env::temp_dir();
+        let overlay = dir.join("chip8-segment-overlay.bin");
+        std::fs::write(&overlay, [0xAA, 0xBB]).unwrap();
+        let segment = |address: u16| Segment { path: overlay.to_str().unwrap().to_string(), address };
+
+        let mut cpu = Chip8::new(None, None, None, Display::new(64, 32, 1), None).unwrap();
+        cpu.load_rom(&[0x12, 0x00, 0x00, 0xE0], 0x200);
+        cpu.load_segments(&[segment(0x800)]).unwrap();
+        assert_eq!(cpu.memory[0x800..0x802], [0xAA, 0xBB]);
+
+        // Over the program or the first segment, and nothing of a refused list is loaded
+        let refused = [segment(0x900), segment(0x203)];
+        assert!(matches!(cpu.load_segments(&refused), Err(EmulatorError::Overlap { address: 0x203, .. })));
+        assert!(matches!(cpu.load_segments(&[segment(0x801)]), Err(EmulatorError::Overlap { .. })));
+        assert!(matches!(cpu.load_segments(&[segment(0xFFFF)]), Err(EmulatorError::TooLarge { .. })));
+        assert_eq!(cpu.memory[0x900], 0);
+
+        cpu.memory[0x800] = 0;
+        cpu.reset();
+        assert_eq!(cpu.memory[0x800], 0xAA);
+        cpu.set_memory_init(MemoryInit::Fill(0xFF));
+        assert_eq!((cpu.memory[0x7FF], cpu.memory[0x800]), (0xFF, 0xAA));
+        _ = std::fs::remove_file(&overlay);
+    }
+
+    #[test]
+    fn audio_scope_follows_the_sound_timer_and_pattern() {
+        // LD V0, 0x20; LD ST, V0; LD I, 0x300; F002 (load pattern)
+        let mut cpu = cpu_with(0x200, &[0x60, 0x20, 0xF0, 0x18, 0xA3, 0x00, 0xF0, 0x02]);
+        cpu.toggle_audio_scope();
+        let silent = cpu.display().scope.unwrap();
+        assert_eq!(silent.size(), (128, 9));
+
+        cpu.memory[0x300] = 0xF0;
+        for _ in 0..4 {
+            cpu.step();
+        }
+        cpu.tick_frame();
+        let scope = cpu.display().scope.unwrap();
+        assert_eq!(scope.sound_timer, 0x1F);
+        assert!(scope.audio.pattern.is_some());
+        assert_eq!(scope.size(), (128, 30));
+        assert!(scope.pixels().len() > silent.pixels().len());
+
+        cpu.toggle_audio_scope();
+        assert_eq!(cpu.display().scope, None);
+    }
+
+    #[test]
+    fn fx1e_overflow_flags_wraps_or_runs_past_the_end() {
+        let add = |overflow: Fx1eOverflow| {
+            let mut cpu = cpu_with(0x200, &[0xF0, 0x1E]); // ADD I, V0
+            cpu.quirks.memory_size = 0x1000;
+            cpu.quirks.fx1e_overflow = overflow;
+            cpu.registers.i = 0xFFF;
+            cpu.registers.v[0] = 2;
+            cpu.step();
+            (cpu.registers.i, cpu.registers.v[0xF])
+        };
+        assert_eq!(add(Fx1eOverflow::Flag), (0x001, 1));
+        assert_eq!(add(Fx1eOverflow::Wrap), (0x001, 0));
+        assert_eq!(add(Fx1eOverflow::Unbounded), (0x1001, 0));
+
+        // VF is set past 12 bits even when the profile's memory goes on
+        let mut cpu = cpu_with(0x200, &[0xF0, 0x1E, 0xF0, 0x1E]); // ADD I, V0 twice
+        cpu.quirks.memory_size = 0x10000;
+        cpu.quirks.fx1e_overflow = Fx1eOverflow::Flag;
+        cpu.registers.i = 0xFFE;
+        cpu.registers.v[0] = 1;
+        cpu.step();
+        assert_eq!((cpu.registers.i, cpu.registers.v[0xF]), (0xFFF, 0));
+        cpu.step();
+        assert_eq!((cpu.registers.i, cpu.registers.v[0xF]), (0x1000, 1));
+
+        let quirks = Quirks::from_config(&crate::toml::parse("[quirks]\nfx1e_overflow = \"none\"").unwrap()).unwrap();
+        assert_eq!(quirks.fx1e_overflow, Fx1eOverflow::Unbounded);
+        let quirks = Quirks::from_config(&crate::toml::parse("[quirks]\ncosmac_fx1e = true").unwrap()).unwrap();
+        assert_eq!(quirks.fx1e_overflow, Fx1eOverflow::Wrap);
+        assert!(Quirks::from_config(&crate::toml::parse("[quirks]\nfx1e_overflow = \"trap\"").unwrap()).is_err());
+    }
+
+    #[test]
+    fn messages_wait_for_the_frontend_instead_of_printing() {
+        let mut cpu = cpu_with(0x200, &[0x80, 0x16, 0x12, 0x00]); // SHR V0, V1; JP 0x200
+        cpu.registers.v[1] = 0x08;
+        cpu.set_strict(true);
+        for _ in 0..4 {
+            cpu.step();
+        }
+        cpu.toggle_quirk("vf_reset");
+        assert_eq!(cpu.take_messages(), ["strict: 0x8016 at 0x0200 diverges on quirk cosmac_shift", "quirk vf_reset on"]);
+        assert!(cpu.take_messages().is_empty());
+        assert!(cpu.quirk_report().unwrap().ends_with("0x0200  0x8016  cosmac_shift   2"));
+        assert_eq!(cpu.block_report(), None);
+
+        for _ in 0..MAX_MESSAGES + 10 {
+            cpu.toggle_quirk("vf_reset");
+        }
+        cpu.tick_frame();
+        let messages = cpu.take_messages();
+        assert_eq!((messages.len(), messages[0].as_str()), (MAX_MESSAGES, "quirk vf_reset off"));
+    }
+
+    #[test]
+    fn quirks_flip_while_running() {
+        // SHR V0, V1 twice, flipping cosmac_shift in between
+        let mut cpu = cpu_with(0x200, &[0x80, 0x16, 0x80, 0x16]);
+        cpu.registers.v[1] = 0x08;
+        cpu.registers.v[0] = 0x40;
+        cpu.step();
+        assert_eq!(cpu.registers.v[0], 0x20);
+        assert_eq!(cpu.toggle_quirk("cosmac_shift"), Some("on"));
+        cpu.step();
+        assert_eq!(cpu.registers.v[0], 0x04);
+
+        for name in RUNTIME_QUIRKS {
+            assert!(cpu.quirks().setting(name).is_some(), "{}", name);
+        }
+        assert_eq!(cpu.toggle_quirk("fx1e_overflow"), Some("wrap"));
+        assert_eq!(cpu.toggle_quirk("fx1e_overflow"), Some("none"));
+        assert_eq!(cpu.toggle_quirk("fx1e_overflow"), Some("flag"));
+        assert_eq!(cpu.toggle_quirk("memory_size"), None);
+    }
+
+    #[test]
+    fn scroll_wait_moves_the_screen_between_frames() {
+        let program = [
+            0x00, 0xC4, // 0x200 SCD 4
+            0x00, 0xFB, // 0x202 SCR
+            0x12, 0x04, // 0x204 JP 0x204
+        ];
+        let mut cpu = cpu_with(0x200, &program);
+        cpu.quirks.scroll_wait = true;
+        for (x, y) in [(8, 8), (9, 8), (8, 9), (9, 9)] {
+            cpu.display.set_pixel(x, y, 1);
+        }
+        cpu.step();
+        assert_eq!((cpu.registers.pc, snapshot(&cpu)), (0x200, block_at(8, 8))); // held until the first frame ticks
+
+        cpu.tick_frame();
+        cpu.step();
+        cpu.step();
+        assert_eq!((cpu.registers.pc, snapshot(&cpu)), (0x202, block_at(8, 12)));
+        cpu.tick_frame();
+        cpu.step();
+        assert_eq!((cpu.registers.pc, snapshot(&cpu)), (0x204, block_at(12, 12)));
+
+        // Replayed frames land the held scrolls where the recorded ones did
+        let mut cpu = cpu_with(0x200, &program);
+        cpu.quirks.scroll_wait = true;
+        cpu.display.set_pixel(8, 8, 1);
+        cpu.set_timeline(Some(100));
+        run_frames(&mut cpu, 2);
+        let recorded = (cpu.registers.pc, snapshot(&cpu));
+        run_frames(&mut cpu, 2);
+        cpu.goto_frame(2).unwrap();
+        assert_eq!((cpu.registers.pc, snapshot(&cpu)), recorded);
+        assert_eq!(recorded.0, 0x202); // SCD landed in the second frame, SCR waits for the third
+
+        // A held scroll isn't a program stuck in a loop
+        let mut cpu = cpu_with(0x200, &[0x00, 0xC1, 0x12, 0x00]);
+        cpu.quirks.scroll_wait = true;
+        let result = cpu.run_headless(&[HaltCondition::InfiniteLoop, HaltCondition::MaxCycles(100)], 50);
+        assert_eq!(result.reason, HaltCondition::MaxCycles(100).describe());
+    }
+
+    #[test]
+    fn resize_clear_off_keeps_the_screen_scaled() {
+        let mut cpu = cpu_with(0x200, &[0x00, 0xFF, 0x00, 0xFE]); // HIGH, LOW
+        cpu.quirks.resize_clear = false;
+        cpu.display.set_pixel(3, 2, 1);
+        cpu.step();
+        assert_eq!((cpu.display.width, cpu.display.get_pixel(6, 4), cpu.display.get_pixel(7, 5), cpu.display.get_pixel(8, 4)), (128, 1, 1, 0));
+        cpu.display.set_pixel(9, 4, 1); // between lores pixels, gone going back
+        cpu.step();
+        assert_eq!((cpu.display.width, cpu.display.get_pixel(3, 2), cpu.display.get_pixel(4, 2)), (64, 1, 0));
+
+        let mut cpu = cpu_with(0x200, &[0x00, 0xFF]);
+        cpu.display.set_pixel(3, 2, 1);
+        cpu.step();
+        assert_eq!(cpu.display.get_pixel(6, 4), 0);
+    }
+
+    #[test]
+    fn fx0a_stores_the_key_pressed_or_released_while_waiting() {
+        let program = [
+            0xF3, 0x0A, // 0x200 LD V3, K
+        ];
+        for release in [false, true] {
+            let mut cpu = cpu_with(0x200, &program);
+            cpu.quirks.fx0a_release = release;
+            cpu.display.keypad.key_down(0x4); // held from before, doesn't count
+            cpu.step();
+            cpu.display.keypad.key_down(0xB);
+            cpu.display.keypad.key_up(0x4);
+            cpu.step();
+            assert_eq!((cpu.registers.pc, cpu.registers.v[3]), if release { (0x200, 0) } else { (0x202, 0xB) });
+
+            if release {
+                cpu.display.keypad.key_up(0xB);
+                cpu.step();
+                assert_eq!((cpu.registers.pc, cpu.registers.v[3]), (0x202, 0xB));
+            }
+        }
+    }
+
+    // A sprite drawn one place further along each frame, display_wait holding each draw for
+    // the next vertical blank
+    const SPRITE_PER_FRAME: [u8; 8] = [
+        0xA2, 0x20, // 0x200 LD I, 0x220
+        0xD0, 0x01, // 0x202 DRW V0, V0, 1
+        0x70, 0x01, // 0x204 ADD V0, 1
+        0x12, 0x02, // 0x206 JP 0x202
+    ];
+
+    fn sprite_per_frame() -> Chip8 {
+        let mut cpu = cpu_with(0x200, &SPRITE_PER_FRAME);
+        cpu.memory[0x220] = 0x80;
+        cpu.quirks.display_wait = true;
+        cpu
+    }
+
+    fn run_frames(cpu: &mut Chip8, frames: u64) {
+        for _ in 0..frames {
+            for _ in 0..10 {
+                cpu.step();
+            }
+            cpu.tick_frame();
+        }
+    }
+
+    #[test]
+    fn run_ahead_and_replays_see_the_vertical_blank() {
+        let mut cpu = sprite_per_frame();
+        let mut ahead = Vec::new();
+        cpu.run_ahead(3, 10, |display| ahead = display.frame());
+        run_frames(&mut cpu, 3);
+        assert_eq!(cpu.registers.v[0], 2); // the first draw waits for the first blank
+        assert!(ahead == cpu.display.frame(), "run-ahead stalled on display_wait");
+
+        let mut cpu = sprite_per_frame();
+        cpu.set_timeline(Some(100));
+        run_frames(&mut cpu, 3);
+        let (pc, v0, frame) = (cpu.registers.pc, cpu.registers.v[0], cpu.display.frame());
+        run_frames(&mut cpu, 2);
+        cpu.goto_frame(3).unwrap();
+        assert_eq!((cpu.registers.pc, cpu.registers.v[0]), (pc, v0));
+        assert!(cpu.display.frame() == frame, "the replay diverged from the recorded run");
+    }
+
+    #[test]
+    fn run_ahead_frames_report_nothing_until_they_really_run() {
+        let program = [
+            0xA0, 0xF0, // 0x200 LD I, 0x0F0
+            0xF1, 0x55, // 0x202 LD [I], V1, into the watched bytes
+            0xAF, 0xFF, // 0x204 LD I, 0xFFFF
+            0xF1, 0x65, // 0x206 LD V1, [I], past the end of memory
+        ];
+        let mut cpu = cpu_with(0x200, &program);
+        cpu.memory.wrap = MemoryWrap::Trap;
+        cpu.watch_writes(0xF0..0xF2);
+
+        let mut shown = false;
+        cpu.run_ahead(1, 10, |_| shown = true); // runs into the trap
+        assert!(shown);
+        assert!(!cpu.halted && cpu.crash_captures.is_empty());
+        assert!(cpu.take_write_hits().is_empty());
+
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.take_write_hits().len(), 2); // the real frame still reports its stores
+    }
+
+    #[test]
+    fn snapshots_restore_the_whole_machine_any_number_of_times() {
+        let mut cpu = cpu_with(0x200, &FIXTURE_PROGRAM);
+        cpu.memory[0x220..0x225].copy_from_slice(&[0xF0, 0x90, 0xF0, 0x90, 0x90]);
+        for _ in 0..4 {
+            cpu.step();
+        }
+        let snapshot = cpu.snapshot();
+        let state = cpu.serialize_state();
+
+        for _ in 0..2 {
+            for _ in 0..6 {
+                cpu.step();
+            }
+            cpu.tick_frame();
+            assert!(cpu.serialize_state() != state);
+            cpu.restore(&snapshot);
+            assert!(cpu.serialize_state() == state);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::cpu::Quirks;
+use crate::json;
+use crate::palette::parse_color;
+use crate::toml::{Table, Value};
+use std::collections::HashMap;
+use std::io;
+
+// The community CHIP-8 program database (github.com/chip-8/chip-8-database), read from its
+// programs.json: known ROMs by SHA-1, with the platform they were written for, quirks that
+// differ from that platform, the instructions per frame they want and their colors. Point
+// paths.database in config.toml at a copy and ROMs it knows start with those settings.
+pub struct Database {
+    roms: HashMap<String, DatabaseEntry>, // by lowercase hex SHA-1
+}
+
+// What the database recommends for one ROM
+#[derive(Clone)]
+pub struct DatabaseEntry {
+    pub title: String,
+    pub platform: String, // the database's id, e.g. superchip1
+    pub quirks: Quirks,
+    pub speed: Option<u32>, // instructions per second, from the tickrate
+    pub colors: Option<[u32; 4]>,
+}
+
+// Our preset for each of the database's platforms. Its modern CHIP-8 is what Quirks::default()
+// describes; CHIP-8X isn't supported, so ROMs only listed for it aren't matched.
+fn platform_quirks(id: &str) -> Option<Quirks> {
+    match id {
+        "originalChip8" | "hybridVIP" => Quirks::preset("chip8"),
+        "modernChip8" => Some(Quirks::default()),
+        "chip48" => Quirks::preset("chip48"),
+        "superchip1" | "superchip" => Quirks::preset("schip1.1"),
+        "megachip8" => Quirks::preset("megachip"),
+        "xochip" => Quirks::preset("xochip"),
+        _ => None,
+    }
+}
+
+// Applies a quirkyPlatforms table, the database's names for the quirks a ROM needs changed
+// from its platform's. Ones we don't emulate (memoryIncrementByX, wrap) are skipped.
+fn apply_quirky(quirks: &mut Quirks, table: &Table) {
+    for (name, value) in table {
+        let Some(on) = value.as_bool() else {
+            continue;
+        };
+        match name.as_str() {
+            "shift" => quirks.cosmac_shift = !on,
+            "memoryLeaveIUnchanged" => (quirks.cosmac_fx55, quirks.cosmac_fx65) = (!on, !on),
+            "jump" => quirks.cosmac_bnnn = !on,
+            "vblank" => quirks.display_wait = on,
+            "logic" => quirks.vf_reset = on,
+            _ => {}
+        }
+    }
+}
+
+impl Database {
+    pub fn load(path: &str) -> io::Result<Database> {
+        Database::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> io::Result<Database> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let programs = json::parse(text)?;
+        let programs = programs.as_array().ok_or_else(|| invalid("expected a list of programs"))?;
+
+        let mut roms = HashMap::new();
+        for program in programs.iter().filter_map(Value::as_table) {
+            let title = program.get("title").and_then(Value::as_str).unwrap_or("untitled");
+            let Some(program_roms) = program.get("roms").and_then(Value::as_table) else {
+                continue;
+            };
+            for (hash, rom) in program_roms {
+                let Some(rom) = rom.as_table() else {
+                    continue;
+                };
+                if let Some(entry) = DatabaseEntry::from_rom(title, rom) {
+                    roms.insert(hash.to_ascii_lowercase(), entry);
+                }
+            }
+        }
+
+        Ok(Database { roms })
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&DatabaseEntry> {
+        let hash: String = sha1(rom).iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.roms.get(&hash)
+    }
+}
+
+impl DatabaseEntry {
+    // The first of the ROM's platforms we emulate, with its quirk overrides
+    fn from_rom(title: &str, rom: &Table) -> Option<DatabaseEntry> {
+        let platforms = rom.get("platforms").and_then(Value::as_array)?;
+        let (platform, mut quirks) = platforms
+            .iter()
+            .filter_map(Value::as_str)
+            .find_map(|id| platform_quirks(id).map(|quirks| (id.to_string(), quirks)))?;
+        if let Some(quirky) = rom.get("quirkyPlatforms").and_then(Value::as_table).and_then(|quirky| quirky.get(&platform)).and_then(Value::as_table) {
+            apply_quirky(&mut quirks, quirky);
+        }
+
+        let speed = rom
+            .get("tickrate")
+            .and_then(Value::as_int)
+            .and_then(|tickrate| u32::try_from(tickrate).ok())
+            .filter(|&tickrate| tickrate > 0)
+            .map(|tickrate| tickrate * 60);
+
+        // Background and the planes' colors; a ROM with only two still gets the second plane's
+        // defaults filled in from the background and foreground
+        let colors = rom.get("colors").and_then(Value::as_table).and_then(|colors| colors.get("pixels")).and_then(Value::as_array).and_then(|pixels| {
+            let pixels: Vec<u32> = pixels.iter().filter_map(Value::as_str).filter_map(parse_color).collect();
+            match pixels[..] {
+                [background, foreground] => Some([background, foreground, foreground, foreground]),
+                [background, one, two, both, ..] => Some([background, one, two, both]),
+                _ => None,
+            }
+        });
+
+        Some(DatabaseEntry { title: title.to_string(), platform, quirks, speed, colors })
+    }
+}
+
+// SHA-1, which the database keys ROMs by
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend(((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut words = [0u32; 80];
+        for (idx, word) in block.chunks_exact(4).enumerate() {
+            words[idx] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for idx in 16..80 {
+            words[idx] = (words[idx - 3] ^ words[idx - 8] ^ words[idx - 14] ^ words[idx - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (idx, &word) in words.iter().enumerate() {
+            let (f, k) = match idx {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, temp);
+        }
+
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (idx, value) in state.iter().enumerate() {
+        digest[idx * 4..idx * 4 + 4].copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha1_matches_reference() {
+        let hex = |digest: [u8; 20]| digest.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+        assert_eq!(hex(sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex(sha1(&[b'a'; 1000])), "291e9a6c66994949b57ba5e650361e98fc36b1ba");
+    }
+
+    #[test]
+    fn roms_are_matched_by_hash_with_their_platform_quirks_and_tickrate() {
+        // sha1 of "abc" standing in for a ROM
+        let database = Database::parse(
+            r##"[{"title": "Test", "roms": {"A9993E364706816ABA3E25717850C26C9CD0D89D": {
+                "platforms": ["chip8x", "superchip1"],
+                "quirkyPlatforms": {"superchip1": {"shift": false, "wrap": true}},
+                "tickrate": 30,
+                "colors": {"pixels": ["#000000", "#FF8000"]}
+            }}}, {"title": "X only", "roms": {"00": {"platforms": ["chip8x"]}}}]"##,
+        )
+        .unwrap();
+        assert_eq!(database.len(), 1);
+
+        let entry = database.lookup(b"abc").unwrap();
+        assert_eq!((entry.title.as_str(), entry.platform.as_str(), entry.speed), ("Test", "superchip1", Some(1800)));
+        assert!(entry.quirks.cosmac_shift && entry.quirks.schip_lores_dxy0);
+        assert_eq!(entry.colors, Some([0x000000, 0xFF8000, 0xFF8000, 0xFF8000]));
+        assert!(database.lookup(b"abd").is_none());
+    }
+}
@@ -0,0 +1,73 @@
+use crate::rle::{decode_rle, encode_rle};
+
+// Frame-to-frame deltas for rewinding: the new bytes XORed with the previous ones, so whatever
+// didn't change is zero, then run-length encoded. A CHIP-8 frame touches a handful of bytes of
+// memory and screen, so a delta is usually a few dozen bytes where the state is kilobytes.
+// The new length comes first, so a delta can grow or shrink the state, e.g. on a hires switch.
+pub fn encode(previous: &[u8], current: &[u8]) -> Vec<u8> {
+    let xored: Vec<u8> = current
+        .iter()
+        .enumerate()
+        .map(|(idx, &byte)| byte ^ previous.get(idx).copied().unwrap_or(0))
+        .collect();
+
+    let mut delta = (current.len() as u32).to_le_bytes().to_vec();
+    delta.extend(encode_rle(&xored));
+    delta
+}
+
+// The state a delta was encoded from previous into, None if the delta is malformed
+pub fn apply(previous: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let len = u32::from_le_bytes(delta.get(..4)?.try_into().ok()?) as usize;
+    let xored = decode_rle(&delta[4..]);
+    if xored.len() != len {
+        return None;
+    }
+
+    Some(
+        xored
+            .iter()
+            .enumerate()
+            .map(|(idx, &byte)| byte ^ previous.get(idx).copied().unwrap_or(0))
+            .collect(),
+    )
+}
+
+// Packs screen words into bytes to diff them along with memory
+pub fn words_to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+pub fn bytes_to_words(bytes: &[u8]) -> Vec<u32> {
+    bytes.chunks_exact(4).map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deltas_round_trip_and_stay_small() {
+        let previous = vec![0xAA; 4096];
+        let mut current = previous.clone();
+        current[0x200] = 0x12;
+        current[0xF00] = 0x34;
+
+        let delta = encode(&previous, &current);
+        assert!(delta.len() < 32, "{} bytes", delta.len());
+        assert_eq!(apply(&previous, &delta), Some(current.clone()));
+        assert_eq!(apply(&current, &encode(&current, &current)), Some(current.clone()));
+    }
+
+    #[test]
+    fn deltas_can_change_the_length() {
+        let lores = words_to_bytes(&[0xFFFF_0000; 64]);
+        let hires = words_to_bytes(&[0x0000_FFFF; 256]);
+        assert_eq!(apply(&lores, &encode(&lores, &hires)), Some(hires.clone()));
+        assert_eq!(apply(&hires, &encode(&hires, &lores)), Some(lores.clone()));
+        assert_eq!(bytes_to_words(&lores), vec![0xFFFF_0000; 64]);
+
+        assert_eq!(apply(&lores, &[1, 0]), None); // cut off in the length
+        assert_eq!(apply(&lores, &[9, 0, 0, 0, 7]), None); // one byte where there should be nine
+    }
+}
@@ -0,0 +1,318 @@
+use crate::annotations::Annotations;
+use std::collections::HashSet;
+
+#[derive(Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Format {
+    Plain,
+    Ansi,
+    Json,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "plain" | "text" => Some(Format::Plain),
+            "ansi" | "color" => Some(Format::Ansi),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+pub struct DisasmOptions {
+    pub format: Format,
+    pub start: Option<u16>,
+    pub end: Option<u16>,
+    pub follow: bool,
+    pub annotations: Option<Annotations>,
+}
+
+pub struct Line {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub text: String,
+    pub is_data: bool,
+    pub label: Option<String>,
+    pub comment: Option<String>,
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_ADDRESS: &str = "\x1b[90m";
+const ANSI_BYTES: &str = "\x1b[2m";
+const ANSI_MNEMONIC: &str = "\x1b[36m";
+const ANSI_DATA: &str = "\x1b[33m";
+const ANSI_LABEL: &str = "\x1b[1;32m";
+const ANSI_COMMENT: &str = "\x1b[32m";
+
+// Returns the size in bytes of the instruction starting with this word
+pub fn instruction_size(word: u16) -> u16 {
+    if word == 0xF000 { 4 } else { 2 }
+}
+
+// Turns a single instruction into its mnemonic, long is the second word of F000 NNNN
+pub fn disassemble(word: u16, long: u16) -> String {
+    let x = (word & 0x0F00) >> 8;
+    let y = (word & 0x00F0) >> 4;
+    let n = word & 0x000F;
+    let nn = word & 0x00FF;
+    let nnn = word & 0x0FFF;
+
+    match word >> 12 {
+        0x0 => match word {
+            0x00E0 => "CLS".to_string(),
+            0x00EE => "RET".to_string(),
+            0x00FB => "SCR".to_string(),
+            0x00FC => "SCL".to_string(),
+            0x00FD => "EXIT".to_string(),
+            0x00FE => "LOW".to_string(),
+            0x00FF => "HIGH".to_string(),
+            _ if word & 0xFFF0 == 0x00C0 => format!("SCD {}", n),
+            _ if word & 0xFFF0 == 0x00D0 => format!("SCU {}", n),
+            _ => format!("SYS {:#05X}", nnn),
+        },
+        0x1 => format!("JP {:#05X}", nnn),
+        0x2 => format!("CALL {:#05X}", nnn),
+        0x3 => format!("SE V{:X}, {:#04X}", x, nn),
+        0x4 => format!("SNE V{:X}, {:#04X}", x, nn),
+        0x5 => match n {
+            0x0 => format!("SE V{:X}, V{:X}", x, y),
+            0x2 => format!("SAVE V{:X}-V{:X}", x, y),
+            0x3 => format!("LOAD V{:X}-V{:X}", x, y),
+            _ => format!("DW {:#06X}", word),
+        },
+        0x6 => format!("LD V{:X}, {:#04X}", x, nn),
+        0x7 => format!("ADD V{:X}, {:#04X}", x, nn),
+        0x8 => match n {
+            0x0 => format!("LD V{:X}, V{:X}", x, y),
+            0x1 => format!("OR V{:X}, V{:X}", x, y),
+            0x2 => format!("AND V{:X}, V{:X}", x, y),
+            0x3 => format!("XOR V{:X}, V{:X}", x, y),
+            0x4 => format!("ADD V{:X}, V{:X}", x, y),
+            0x5 => format!("SUB V{:X}, V{:X}", x, y),
+            0x6 => format!("SHR V{:X}, V{:X}", x, y),
+            0x7 => format!("SUBN V{:X}, V{:X}", x, y),
+            0xE => format!("SHL V{:X}, V{:X}", x, y),
+            _ => format!("DW {:#06X}", word),
+        },
+        0x9 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA => format!("LD I, {:#05X}", nnn),
+        0xB => format!("JP V0, {:#05X}", nnn),
+        0xC => format!("RND V{:X}, {:#04X}", x, nn),
+        0xD => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        0xE => match nn {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            _ => format!("DW {:#06X}", word),
+        },
+        _ => match nn {
+            0x00 if word == 0xF000 => format!("LD I, {:#06X}", long),
+            0x01 => format!("PLANE {}", x),
+            0x02 if word == 0xF002 => "AUDIO".to_string(),
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x30 => format!("LD HF, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x3A => format!("PITCH V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            0x75 => format!("LD R, V{:X}", x),
+            0x85 => format!("LD V{:X}, R", x),
+            _ => format!("DW {:#06X}", word),
+        },
+    }
+}
+
+// Memory address an instruction refers to, if any
+pub fn target_address(word: u16, long: u16) -> Option<u16> {
+    match word >> 12 {
+        0x1 | 0x2 | 0xA | 0xB => Some(word & 0x0FFF),
+        0xF if word == 0xF000 => Some(long),
+        _ => None,
+    }
+}
+
+// Reads a big-endian word from the ROM, treating bytes past the end as zero
+fn word_at(rom: &[u8], offset: usize) -> u16 {
+    let hi = *rom.get(offset).unwrap_or(&0) as u16;
+    let lo = *rom.get(offset + 1).unwrap_or(&0) as u16;
+    hi << 8 | lo
+}
+
+// Walks the control flow from the entry point and returns every byte offset reached as code
+pub fn find_code(rom: &[u8], base: u16) -> HashSet<usize> {
+    let mut code: HashSet<usize> = HashSet::new();
+    let mut pending: Vec<u16> = vec![base];
+
+    while let Some(address) = pending.pop() {
+        if address < base || (address - base) as usize >= rom.len() {
+            continue;
+        }
+
+        let offset = (address - base) as usize;
+        if code.contains(&offset) {
+            continue;
+        }
+
+        let word = word_at(rom, offset);
+        let size = instruction_size(word);
+        for i in 0..size as usize {
+            code.insert(offset + i);
+        }
+
+        let next = address.wrapping_add(size);
+        let skip_target = |next: u16| {
+            let skipped = word_at(rom, next.wrapping_sub(base) as usize);
+            next.wrapping_add(instruction_size(skipped))
+        };
+
+        match word >> 12 {
+            0x0 if word == 0x00EE || word == 0x00FD => {}
+            0x1 => pending.push(word & 0x0FFF),
+            0x2 => {
+                pending.push(word & 0x0FFF);
+                pending.push(next);
+            }
+            0xB => {} // computed jump, target unknown
+            0x3 | 0x4 | 0x5 | 0x9 => {
+                pending.push(next);
+                pending.push(skip_target(next));
+            }
+            0xE if matches!(word & 0x00FF, 0x9E | 0xA1) => {
+                pending.push(next);
+                pending.push(skip_target(next));
+            }
+            _ => pending.push(next),
+        }
+    }
+
+    code
+}
+
+// Disassembles a ROM loaded at base into lines, marking unreached bytes as data when following
+pub fn disassemble_rom(rom: &[u8], base: u16, options: &DisasmOptions) -> Vec<Line> {
+    let code: Option<HashSet<usize>> = if options.follow { Some(find_code(rom, base)) } else { None };
+    let start = options.start.unwrap_or(base).max(base);
+    let end = options.end.unwrap_or(u16::MAX);
+
+    let mut lines: Vec<Line> = Vec::new();
+    let mut offset = (start - base) as usize;
+
+    while offset < rom.len() {
+        let address = base + offset as u16;
+        if address > end {
+            break;
+        }
+
+        let is_code = match code {
+            Some(ref code) => code.contains(&offset),
+            None => offset + 1 < rom.len(),
+        };
+
+        let region = options.annotations.as_ref().and_then(|annotations| annotations.region_at(address));
+        let label = region.filter(|region| region.start == address).map(|region| region.name.clone());
+        let mut comment = region.filter(|region| region.start == address).and_then(|region| region.comment.clone());
+
+        if is_code {
+            let word = word_at(rom, offset);
+            let long = word_at(rom, offset + 2);
+            let size = instruction_size(word) as usize;
+            let end_offset = (offset + size).min(rom.len());
+
+            let target_label = target_address(word, long)
+                .and_then(|target| options.annotations.as_ref().and_then(|annotations| annotations.label(target)));
+            if target_label.is_some() {
+                comment = target_label;
+            }
+
+            lines.push(Line {
+                address,
+                bytes: rom[offset..end_offset].to_vec(),
+                text: disassemble(word, long),
+                is_data: false,
+                label,
+                comment,
+            });
+            offset += size;
+        } else {
+            lines.push(Line {
+                address,
+                bytes: vec![rom[offset]],
+                text: format!("DB {:#04X}", rom[offset]),
+                is_data: true,
+                label,
+                comment,
+            });
+            offset += 1;
+        }
+    }
+
+    lines
+}
+
+// Quotes and escapes an optional string for JSON output
+fn json_string(text: &Option<String>) -> String {
+    match text {
+        Some(text) => format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"")),
+        None => "null".to_string(),
+    }
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+// Renders disassembled lines in the requested output format
+pub fn format_lines(lines: &[Line], format: Format) -> String {
+    let mut out = String::new();
+
+    match format {
+        Format::Plain => for line in lines {
+            if let Some(ref label) = line.label {
+                out += &format!("{}:\n", label);
+            }
+
+            let comment = line.comment.as_ref().map(|comment| format!("  ; {}", comment)).unwrap_or_default();
+            out += &format!("{:#06X}  {:<8}  {}{}\n", line.address, hex_bytes(&line.bytes), line.text, comment);
+        }
+
+        Format::Ansi => for line in lines {
+            if let Some(ref label) = line.label {
+                out += &format!("{}{}:{}\n", ANSI_LABEL, label, ANSI_RESET);
+            }
+
+            let comment = line.comment.as_ref().map(|comment| format!(" {}; {}{}", ANSI_COMMENT, comment, ANSI_RESET)).unwrap_or_default();
+            let color = if line.is_data { ANSI_DATA } else { ANSI_MNEMONIC };
+            let (mnemonic, operands) = line.text.split_once(' ').unwrap_or((&line.text, ""));
+            out += &format!(
+                "{}{:#06X}{}  {}{:<8}{}  {}{:<5}{} {}{}\n",
+                ANSI_ADDRESS, line.address, ANSI_RESET,
+                ANSI_BYTES, hex_bytes(&line.bytes), ANSI_RESET,
+                color, mnemonic, ANSI_RESET, operands, comment,
+            );
+        }
+
+        Format::Json => {
+            out += "[\n";
+            for (i, line) in lines.iter().enumerate() {
+                out += &format!(
+                    "  {{\"address\": {}, \"bytes\": \"{}\", \"text\": \"{}\", \"kind\": \"{}\", \"label\": {}, \"comment\": {}}}{}\n",
+                    line.address,
+                    hex_bytes(&line.bytes),
+                    line.text,
+                    if line.is_data { "data" } else { "code" },
+                    json_string(&line.label),
+                    json_string(&line.comment),
+                    if i + 1 < lines.len() { "," } else { "" },
+                );
+            }
+            out += "]\n";
+        }
+    }
+
+    out
+}
@@ -0,0 +1,854 @@
+use crate::audio::Audio;
+use crate::keypad::Keypad;
+use std::ops::Range;
+use crate::overlay::{AudioScope, Overlay};
+use crate::palette::{builtin_palettes, Palette};
+
+// A horizontal run of same-colored pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Run {
+    pub x: u16,
+    pub y: u16,
+    pub len: u16,
+    pub color: (u8, u8, u8),
+}
+
+// Clockwise rotation of the framebuffer on screen, e.g. for ROMs meant for a portrait display
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Rotation {
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+impl Rotation {
+    pub fn from_degrees(degrees: u16) -> Option<Rotation> {
+        match degrees {
+            0 => Some(Rotation::None),
+            90 => Some(Rotation::Clockwise90),
+            180 => Some(Rotation::Clockwise180),
+            270 => Some(Rotation::Clockwise270),
+            _ => None,
+        }
+    }
+
+    fn quarter_turn(&self) -> bool {
+        matches!(self, Rotation::Clockwise90 | Rotation::Clockwise270)
+    }
+}
+
+// How Display::to_ascii_art draws the screen as text
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum AsciiStyle {
+    Hash,    // '#' or ' ' per pixel
+    Braille, // one braille character per 2x4 pixels, so a 64x32 screen fits in 32x8
+}
+
+impl AsciiStyle {
+    pub fn from_name(name: &str) -> Option<AsciiStyle> {
+        match name {
+            "hash" => Some(AsciiStyle::Hash),
+            "braille" => Some(AsciiStyle::Braille),
+            _ => None,
+        }
+    }
+}
+
+// How the drawing methods for embedders combine with what's already on screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum BlitMode {
+    Xor,       // flip pixels as DXYN does, reporting whether a lit one went out
+    Overwrite, // replace pixels, lit or not
+}
+
+// Where the rotated framebuffer is drawn inside the window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub scale: u16,
+}
+
+pub struct Display {
+    pub display: Vec<u32>,  // Each u32 holds 32 pixels (1 bit per pixel)
+    pub plane2: Vec<u32>,   // XO-CHIP's second bit plane, laid out like display
+    pub planes: u8,         // bit planes that drawing, clearing and scrolling affect (FN01)
+    pub width: u16,
+    pub height: u16,
+    pub keypad: Keypad,
+    pub scale: u16,
+    pub original_scale: u16,
+    pub palettes: Vec<Palette>,
+    pub palette_index: usize,
+    pub brightness: f32,
+    pub gamma: f32,
+    pub flash_reduction: bool, // Limit how fast pixels can change brightness
+    shown: Vec<[f32; 3]>,      // Per-pixel color on screen while flash reduction fades it
+    pub draw_mode: bool,     // Mouse edits the framebuffer directly
+    paint_value: u8,
+    beep: bool,
+    pub audio: Audio, // XO-CHIP pattern and pitch played in place of the beep
+    pub colors: Option<Vec<u32>>, // 0xRRGGBB per pixel in MEGACHIP's color mode, shown instead of the planes
+    pub title: String,
+    pub overlay: Option<Overlay>,
+    pub hud: Vec<String>, // small text kept in the top right corner, e.g. the speedrun timer
+    pub badge: Option<String>, // shown in the top left corner while set, e.g. PAUSED
+    pub toasts: Vec<(String, u32)>, // short-lived messages and the frames they stay up for
+    pub scope: Option<AudioScope>, // audio panel in the bottom right corner while shown
+    pub rotation: Rotation,
+    pub window: Option<(u32, u32)>, // actual window size when it differs from window_size(), letterboxed
+    dirty_rows: Vec<u64>, // bitmap of rows changed since take_damage
+    fingerprint: u64,     // see fingerprint()
+    fingerprint_stale: bool,
+}
+
+impl Display {
+    pub fn new(width: u16, height: u16, scale: u16) -> Display {
+        Display {
+            display: vec![0; ((width * height) as usize).div_ceil(32)],  // Initialize with enough u32s to hold all bits
+            plane2: vec![0; ((width * height) as usize).div_ceil(32)],
+            planes: 1,
+            width,
+            height,
+            keypad: Keypad::new(),
+            scale,
+            original_scale: scale,
+            palettes: builtin_palettes(),
+            palette_index: 0,
+            brightness: 1.0,
+            gamma: 1.0,
+            flash_reduction: false,
+            shown: Vec::new(),
+            draw_mode: false,
+            paint_value: 1,
+            beep: false,
+            audio: Audio::default(),
+            colors: None,
+            title: "Rust Chip-8".to_string(),
+            overlay: None,
+            hud: Vec::new(),
+            badge: None,
+            toasts: Vec::new(),
+            scope: None,
+            rotation: Rotation::None,
+            window: None,
+            dirty_rows: vec![u64::MAX; (height as usize).div_ceil(64)],
+            fingerprint: 0,
+            fingerprint_stale: true,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        let num_u32s = ((self.width * self.height) as usize).div_ceil(32);
+        self.display = vec![0; num_u32s];  // 32 bits per u32
+        self.plane2 = vec![0; num_u32s];
+        self.mark_all_dirty();
+    }
+
+    // 00E0: clears only the selected planes
+    pub fn clear_planes(&mut self) {
+        for plane in 0..2 {
+            if self.planes & (1 << plane) != 0 {
+                self.plane_mut(plane).fill(0);
+            }
+        }
+        self.mark_all_dirty();
+    }
+
+    fn plane(&self, plane: u8) -> &Vec<u32> {
+        if plane == 0 { &self.display } else { &self.plane2 }
+    }
+
+    fn plane_mut(&mut self, plane: u8) -> &mut Vec<u32> {
+        if plane == 0 { &mut self.display } else { &mut self.plane2 }
+    }
+
+    fn mark_row_dirty(&mut self, y: u16) {
+        if let Some(bits) = self.dirty_rows.get_mut(y as usize / 64) {
+            *bits |= 1 << (y % 64);
+        }
+    }
+
+    // For code that replaces the framebuffer wholesale, e.g. restoring a state
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty_rows = vec![u64::MAX; (self.height as usize).div_ceil(64)];
+        self.fingerprint_stale = true;
+    }
+
+    // Marks the rows covered by a changed word of the framebuffer
+    pub fn mark_word_dirty(&mut self, idx: usize) {
+        self.fingerprint_stale = true;
+        let width = self.width.max(1) as usize;
+        for y in idx * 32 / width..=(idx * 32 + 31) / width {
+            self.mark_row_dirty(y as u16);
+        }
+    }
+
+    // Damage since the last call as spans of changed rows, so a frontend only has to redraw
+    // those. Covers the framebuffer's contents; a palette change marks everything.
+    pub fn take_damage(&mut self) -> Vec<Range<u16>> {
+        let mut spans: Vec<Range<u16>> = Vec::new();
+        for y in 0..self.height {
+            if self.dirty_rows[y as usize / 64] & (1 << (y % 64)) == 0 {
+                continue;
+            }
+            match spans.last_mut() {
+                Some(span) if span.end == y => span.end = y + 1,
+                _ => spans.push(y..y + 1),
+            }
+        }
+
+        self.dirty_rows.iter_mut().for_each(|bits| *bits = 0);
+        spans
+    }
+
+    // Color index of a pixel: bit 0 from the first plane, bit 1 from the second
+    pub fn get_pixel(&self, x: u16, y: u16) -> u8 {
+        self.get_plane_pixel(0, x, y) | self.get_plane_pixel(1, x, y) << 1
+    }
+
+    pub fn set_pixel(&mut self, x: u16, y: u16, v: u8) {
+        self.set_plane_pixel(0, x, y, v & 1);
+        self.set_plane_pixel(1, x, y, v >> 1 & 1);
+    }
+
+    pub fn get_plane_pixel(&self, plane: u8, x: u16, y: u16) -> u8 {
+        let index = (y * self.width + x) as usize;
+        let u32_index = index / 32;
+        let bit_index = index % 32;
+
+        // Shift the bit into the least significant bit and mask with 1
+        ((self.plane(plane)[u32_index] >> (31 - bit_index)) & 1) as u8
+    }
+
+    pub fn set_plane_pixel(&mut self, plane: u8, x: u16, y: u16, v: u8) {
+        let index = (y * self.width + x) as usize;
+        let u32_index = index / 32;
+        let bit_index = index % 32;
+
+        if self.get_plane_pixel(plane, x, y) != v {
+            self.mark_row_dirty(y);
+            self.fingerprint ^= pixel_key(plane, index);
+        }
+
+        let words = self.plane_mut(plane);
+        if v == 1 {
+            words[u32_index] |= 1 << (31 - bit_index); // Set bit
+        } else {
+            words[u32_index] &= !(1 << (31 - bit_index)); // Clear bit
+        }
+    }
+
+    // Drawing for embedders, e.g. a UI drawn underneath a ROM's output, that works without
+    // the CPU. Whatever falls outside the screen is clipped, so any position is safe. Each
+    // returns true when XOR turned a lit pixel off, the collision DXYN puts in VF.
+
+    // Draws an 8-pixel-wide sprite, one byte per row, on the selected planes like DXYN does
+    pub fn draw_sprite_at(&mut self, x: u16, y: u16, rows: &[u8], mode: BlitMode) -> bool {
+        let mut collided = false;
+        let planes = self.planes;
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..8 {
+                let bit = bits >> (7 - column) & 1;
+                for plane in (0..2).filter(|plane| planes & (1 << plane) != 0) {
+                    collided |= self.blit_pixel(plane, x as usize + column, y as usize + row, bit, mode);
+                }
+            }
+        }
+        collided
+    }
+
+    // Draws a bitmap of color indices (0-3, bit 0 the first plane and bit 1 the second), width
+    // pixels per row, on both planes. A partial last row is left out.
+    pub fn blit_bitmap(&mut self, x: u16, y: u16, width: u16, bitmap: &[u8], mode: BlitMode) -> bool {
+        let mut collided = false;
+        if width == 0 {
+            return collided;
+        }
+        for (row, pixels) in bitmap.chunks_exact(width as usize).enumerate() {
+            for (column, &color) in pixels.iter().enumerate() {
+                for plane in 0..2 {
+                    collided |= self.blit_pixel(plane, x as usize + column, y as usize + row, color >> plane & 1, mode);
+                }
+            }
+        }
+        collided
+    }
+
+    // Turns off every pixel of a rectangle on both planes
+    pub fn clear_region(&mut self, x: u16, y: u16, width: u16, height: u16) {
+        let right = (x as usize + width as usize).min(self.width as usize);
+        let bottom = (y as usize + height as usize).min(self.height as usize);
+        for row in y as usize..bottom {
+            for column in x as usize..right {
+                self.set_pixel(column as u16, row as u16, 0);
+            }
+        }
+    }
+
+    fn blit_pixel(&mut self, plane: u8, x: usize, y: usize, bit: u8, mode: BlitMode) -> bool {
+        if x >= self.width as usize || y >= self.height as usize {
+            return false;
+        }
+        let (x, y) = (x as u16, y as u16);
+        let lit = self.get_plane_pixel(plane, x, y);
+        match mode {
+            BlitMode::Xor => {
+                self.set_plane_pixel(plane, x, y, lit ^ bit);
+                lit == 1 && bit == 1
+            }
+            BlitMode::Overwrite => {
+                self.set_plane_pixel(plane, x, y, bit);
+                false
+            }
+        }
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palettes[self.palette_index]
+    }
+
+    // Switches to the next palette, returning its name
+    pub fn cycle_palette(&mut self) -> &str {
+        self.palette_index = (self.palette_index + 1) % self.palettes.len();
+        self.mark_all_dirty();
+        &self.palettes[self.palette_index].name
+    }
+
+    // Selects a palette by name, returns false if there's no such palette
+    pub fn select_palette(&mut self, name: &str) -> bool {
+        match self.palettes.iter().position(|palette| palette.name == name) {
+            Some(idx) => {
+                self.palette_index = idx;
+                self.mark_all_dirty();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Adds (or replaces) a palette and selects it
+    pub fn add_palette(&mut self, palette: Palette) {
+        self.palette_index = match self.palettes.iter().position(|existing| existing.name == palette.name) {
+            Some(idx) => {
+                self.palettes[idx] = palette;
+                idx
+            }
+            None => {
+                self.palettes.push(palette);
+                self.palettes.len() - 1
+            }
+        };
+        self.mark_all_dirty();
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+    }
+
+    pub fn set_beep(&mut self, flag: bool) {
+        self.beep = flag;
+    }
+
+    pub fn beep(&self) -> bool {
+        self.beep
+    }
+
+    // Shows a message for a few seconds
+    pub fn toast(&mut self, message: &str) {
+        self.toasts.push((message.to_string(), 4 * 60));
+    }
+
+    // Mouse editing in draw mode: a press toggles the pixel under the cursor and
+    // dragging keeps painting that same value
+    pub fn paint(&mut self, x: i32, y: i32, pressed: bool) {
+        if !self.draw_mode {
+            return;
+        }
+
+        if let Some((x, y)) = self.window_to_pixel(x, y) {
+            if pressed {
+                self.paint_value = self.get_pixel(x, y) ^ 1;
+            }
+            self.set_pixel(x, y, self.paint_value);
+        }
+    }
+
+    // Framebuffer size as it appears on screen, in pixels
+    fn rotated_size(&self) -> (u16, u16) {
+        if self.rotation.quarter_turn() { (self.height, self.width) } else { (self.width, self.height) }
+    }
+
+    // The window size this display asks for
+    pub fn window_size(&self) -> (u32, u32) {
+        let (width, height) = self.rotated_size();
+        (width as u32 * self.scale as u32, height as u32 * self.scale as u32)
+    }
+
+    // Largest whole scale that fits the window, centered with bars on the remaining sides
+    pub fn viewport(&self) -> Viewport {
+        let Some((window_width, window_height)) = self.window else {
+            return Viewport { x: 0, y: 0, scale: self.scale };
+        };
+
+        let (width, height) = self.rotated_size();
+        let scale = (window_width / width as u32).min(window_height / height as u32).max(1);
+        Viewport {
+            x: (window_width as i32 - (width as u32 * scale) as i32) / 2,
+            y: (window_height as i32 - (height as u32 * scale) as i32) / 2,
+            scale: scale as u16,
+        }
+    }
+
+    // Converts window coordinates to a framebuffer pixel, None over the letterbox bars
+    pub fn window_to_pixel(&self, x: i32, y: i32) -> Option<(u16, u16)> {
+        let viewport = self.viewport();
+        let (x, y) = (x - viewport.x, y - viewport.y);
+        if x < 0 || y < 0 {
+            return None;
+        }
+
+        let (width, height) = self.rotated_size();
+        let x = (x / viewport.scale as i32) as u16;
+        let y = (y / viewport.scale as i32) as u16;
+        if x >= width || y >= height {
+            return None;
+        }
+
+        Some(match self.rotation {
+            Rotation::None => (x, y),
+            Rotation::Clockwise90 => (y, self.height - 1 - x),
+            Rotation::Clockwise180 => (self.width - 1 - x, self.height - 1 - y),
+            Rotation::Clockwise270 => (self.width - 1 - y, x),
+        })
+    }
+
+    // Window rectangle (x, y, width, height) covered by a run, the inverse of window_to_pixel
+    pub fn run_rect(&self, run: &Run) -> (i32, i32, u32, u32) {
+        let viewport = self.viewport();
+        let scale = viewport.scale as i32;
+        let (x, y, len) = (run.x as i32, run.y as i32, run.len as i32);
+        let (width, height) = (self.width as i32, self.height as i32);
+
+        // top-left screen pixel of the run and its extent on screen
+        let (left, top, columns, rows) = match self.rotation {
+            Rotation::None => (x, y, len, 1),
+            Rotation::Clockwise90 => (height - 1 - y, x, 1, len),
+            Rotation::Clockwise180 => (width - x - len, height - 1 - y, len, 1),
+            Rotation::Clockwise270 => (y, width - x - len, 1, len),
+        };
+
+        (viewport.x + left * scale, viewport.y + top * scale, (columns * scale) as u32, (rows * scale) as u32)
+    }
+
+    // The framebuffer one byte per pixel (its color index, 0-3 with XO-CHIP planes), row by
+    // row, for scripts and tests that inspect the screen without a frontend
+    pub fn pixels(&self) -> Vec<u8> {
+        (0..self.height).flat_map(|y| (0..self.width).map(move |x| self.get_pixel(x, y))).collect()
+    }
+
+    // The framebuffer as text, '#' for lit pixels
+    pub fn to_ascii(&self) -> String {
+        let rows: Vec<String> = (0..self.height)
+            .map(|y| (0..self.width).map(|x| if self.get_pixel(x, y) != 0 { '#' } else { '.' }).collect())
+            .collect();
+        rows.join("\n")
+    }
+
+    // The framebuffer as text for terminals and snapshot tests, a pixel lit on any plane
+    // counting as lit. Rows keep their trailing spaces so every row is as wide as the screen.
+    pub fn to_ascii_art(&self, style: AsciiStyle) -> String {
+        let lit = |x: u16, y: u16| x < self.width && y < self.height && self.get_pixel(x, y) != 0;
+
+        let rows: Vec<String> = match style {
+            AsciiStyle::Hash => (0..self.height)
+                .map(|y| (0..self.width).map(|x| if lit(x, y) { '#' } else { ' ' }).collect())
+                .collect(),
+            AsciiStyle::Braille => {
+                // Dot bits of a braille cell, by row then column
+                const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+                (0..self.height.div_ceil(4))
+                    .map(|row| {
+                        (0..self.width.div_ceil(2))
+                            .map(|column| {
+                                let mut bits = 0;
+                                for (dy, dots) in DOTS.iter().enumerate() {
+                                    for (dx, dot) in dots.iter().enumerate() {
+                                        if lit(column * 2 + dx as u16, row * 4 + dy as u16) {
+                                            bits |= dot;
+                                        }
+                                    }
+                                }
+                                char::from_u32(0x2800 + bits).unwrap_or(' ')
+                            })
+                            .collect()
+                    })
+                    .collect()
+            }
+        };
+        rows.join("\n")
+    }
+
+    // Dumps the lit pixels' bounding box as sprite bytes, in 8 pixel wide columns
+    pub fn sprite_dump(&self) -> String {
+        let mut min_x = self.width;
+        let mut min_y = self.height;
+        let mut max_x = 0;
+        let mut max_y = 0;
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.get_pixel(x, y) != 0 {
+                    min_x = min_x.min(x);
+                    min_y = min_y.min(y);
+                    max_x = max_x.max(x);
+                    max_y = max_y.max(y);
+                }
+            }
+        }
+
+        if min_x > max_x {
+            return "; no pixels set".to_string();
+        }
+
+        let width = max_x - min_x + 1;
+        let height = max_y - min_y + 1;
+        let mut out = format!("; sprite {}x{} at ({}, {})", width, height, min_x, min_y);
+
+        for column in (min_x..=max_x).step_by(8) {
+            out += &format!("\n; columns {}-{}\n", column, (column + 7).min(max_x));
+            let rows: Vec<String> = (min_y..=max_y)
+                .map(|y| {
+                    let mut byte: u8 = 0;
+                    for bit in 0..8 {
+                        if column + bit < self.width && self.get_pixel(column + bit, y) != 0 {
+                            byte |= 0x80 >> bit;
+                        }
+                    }
+                    format!("{:#04X}", byte)
+                })
+                .collect();
+            out += &rows.join(" ");
+        }
+
+        out
+    }
+
+    pub fn resize(&mut self, new_width: u16, new_height: u16, new_scale: u16) {
+        self.width = new_width;
+        self.height = new_height;
+        self.scale = new_scale;
+        let num_u32s = ((self.width * self.height) as usize).div_ceil(32);
+        self.display = vec![0; num_u32s];
+        self.plane2 = vec![0; num_u32s];
+        self.mark_all_dirty();
+    }
+
+    // Like resize, but keeps what's on screen, each pixel taken from where it was on the old
+    // screen: doubled going to hires, every other one going back to lores
+    pub fn rescale(&mut self, new_width: u16, new_height: u16, new_scale: u16) {
+        let (old_width, old_height) = (self.width as u32, self.height as u32);
+        let old = [self.display.clone(), self.plane2.clone()];
+        self.resize(new_width, new_height, new_scale);
+
+        for (plane, pixels) in old.iter().enumerate() {
+            for y in 0..new_height {
+                for x in 0..new_width {
+                    let from = (y as u32 * old_height / new_height as u32) * old_width + x as u32 * old_width / new_width as u32;
+                    if pixels[from as usize / 32] >> (31 - from % 32) & 1 == 1 {
+                        self.set_plane_pixel(plane as u8, x, y, 1);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn shift_up(&mut self) {
+        self.shift(0, 1);
+    }
+
+    pub fn shift_down(&mut self) {
+        self.shift(0, -1);
+    }
+
+    pub fn shift_left(&mut self) {
+        self.shift(1, 0);
+    }
+
+    pub fn shift_right(&mut self) {
+        self.shift(-1, 0);
+    }
+
+    // Moves the selected planes by one pixel, each pixel taking the value from (dx, dy) away;
+    // the row or column scrolled in is blank
+    fn shift(&mut self, dx: i32, dy: i32) {
+        self.mark_all_dirty();
+        let (width, height) = (self.width as i32, self.height as i32);
+
+        for plane in 0..2 {
+            if self.planes & (1 << plane) == 0 {
+                continue;
+            }
+
+            let mut shifted = vec![0u32; self.plane(plane).len()];
+            for y in 0..height {
+                for x in 0..width {
+                    let (from_x, from_y) = (x + dx, y + dy);
+                    if from_x < 0 || from_y < 0 || from_x >= width || from_y >= height {
+                        continue;
+                    }
+                    if self.get_plane_pixel(plane, from_x as u16, from_y as u16) == 1 {
+                        let idx = (y * width + x) as usize;
+                        shifted[idx / 32] |= 1 << (31 - idx % 32);
+                    }
+                }
+            }
+            *self.plane_mut(plane) = shifted;
+        }
+    }
+
+    // Palette color for a pixel value as linear 0..1 RGB
+    fn pixel_rgb(&self, pixel: u8) -> [f32; 3] {
+        let (r, g, b) = self.palette().rgb(pixel as usize);
+        [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0]
+    }
+
+    // Applies gamma and brightness to a 0..1 RGB color
+    fn rasterize(&self, rgb: [f32; 3]) -> (u8, u8, u8) {
+        let channel = |value: f32| (value.powf(1.0 / self.gamma) * self.brightness * 255.0).clamp(0.0, 255.0) as u8;
+        (channel(rgb[0]), channel(rgb[1]), channel(rgb[2]))
+    }
+
+    // Advances the flash reduction fade by one 60hz frame: each pixel may only move a third
+    // of the way between colors per frame, so 00E0 + redraw flashes and full-screen
+    // inversions become a short fade instead of a strobe
+    pub fn tick_frame(&mut self) {
+        self.toasts.retain_mut(|(_, frames)| {
+            *frames = frames.saturating_sub(1);
+            *frames > 0
+        });
+
+        if !self.flash_reduction {
+            return;
+        }
+
+        const MAX_STEP: f32 = 1.0 / 3.0;
+        let pixels = (self.width * self.height) as usize;
+        if self.shown.len() != pixels {
+            self.shown = vec![self.pixel_rgb(0); pixels];
+        }
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let target = self.pixel_rgb(self.get_pixel(x, y));
+                let shown = &mut self.shown[(y * self.width + x) as usize];
+                for channel in 0..3 {
+                    let delta = (target[channel] - shown[channel]).clamp(-MAX_STEP, MAX_STEP);
+                    shown[channel] += delta;
+                }
+            }
+        }
+    }
+
+    // Final on-screen colors, row by row, for a frontend to blit
+    pub fn frame(&self) -> Vec<(u8, u8, u8)> {
+        if let Some(ref colors) = self.colors {
+            return colors.iter().map(|&color| ((color >> 16) as u8, (color >> 8) as u8, color as u8)).collect();
+        }
+
+        let colors: Vec<(u8, u8, u8)> = (0..4).map(|idx| self.rasterize(self.pixel_rgb(idx))).collect();
+        let pixels = (self.width * self.height) as usize;
+
+        if self.flash_reduction && self.shown.len() == pixels {
+            return self.shown.iter().map(|&rgb| self.rasterize(rgb)).collect();
+        }
+
+        let bit = |words: &[u32], idx: usize| ((words[idx / 32] >> (31 - idx % 32)) & 1) as usize;
+        (0..pixels)
+            .map(|idx| colors[bit(&self.display, idx) | bit(&self.plane2, idx) << 1])
+            .collect()
+    }
+
+    // Background color, used to clear the window
+    pub fn background(&self) -> (u8, u8, u8) {
+        if self.colors.is_some() {
+            return (0, 0, 0);
+        }
+        self.rasterize(self.pixel_rgb(0))
+    }
+
+    // The frame as horizontal runs of same-colored pixels, skipping background runs, so a
+    // frontend can clear to background() and fill one rect per run instead of one per pixel
+    pub fn runs(&self) -> Vec<Run> {
+        let frame = self.frame();
+        let background = self.background();
+        let mut runs: Vec<Run> = Vec::new();
+
+        for y in 0..self.height {
+            let row = &frame[(y * self.width) as usize..((y + 1) * self.width) as usize];
+            let mut x = 0;
+            while x < self.width {
+                let color = row[x as usize];
+                let start = x;
+                while x < self.width && row[x as usize] == color {
+                    x += 1;
+                }
+
+                if color != background {
+                    runs.push(Run { x: start, y, len: x - start, color });
+                }
+            }
+        }
+
+        runs
+    }
+
+    // FNV-1a hash of the framebuffer, to compare screens without storing them. The second
+    // plane only counts once something is drawn on it, so single-plane hashes stay the same.
+    pub fn hash(&self) -> u64 {
+        let mut hash: u64 = 0xCBF29CE484222325;
+        let second = if self.plane2.iter().any(|&word| word != 0) { &self.plane2[..] } else { &[] };
+        let colors = self.colors.as_deref().unwrap_or(&[]);
+        for word in self.display.iter().chain(second).chain(colors) {
+            for byte in word.to_be_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001B3);
+            }
+        }
+        hash
+    }
+
+    // A cheap stand-in for hash() to tell whether the screen changed, e.g. to skip sending or
+    // drawing a frame that's the same as the last. Each lit pixel contributes its own key, so
+    // setting a pixel updates it in O(1); only wholesale changes like a clear, scroll or resize
+    // recompute it, on the next call. Unlike hash() it isn't meant to be kept across runs.
+    pub fn fingerprint(&mut self) -> u64 {
+        if !self.fingerprint_stale {
+            return self.fingerprint;
+        }
+
+        let mut fingerprint = mix((self.width as u64) << 16 | self.height as u64);
+        for plane in 0..2 {
+            for (idx, &word) in self.plane(plane).iter().enumerate() {
+                let mut bits = word;
+                while bits != 0 {
+                    let bit = bits.leading_zeros() as usize;
+                    fingerprint ^= pixel_key(plane, idx * 32 + bit);
+                    bits &= !(1 << (31 - bit));
+                }
+            }
+        }
+        for (idx, &color) in self.colors.iter().flatten().enumerate() {
+            if color != 0 {
+                fingerprint ^= mix(pixel_key(2, idx) ^ color as u64);
+            }
+        }
+
+        self.fingerprint = fingerprint;
+        self.fingerprint_stale = false;
+        fingerprint
+    }
+
+    // A frame worth using as an icon: between 5% and 60% of the pixels lit, so it's neither
+    // a blank/cleared screen nor a full-screen flash
+    pub fn is_interesting(&self) -> bool {
+        let lit: u32 = match self.colors {
+            Some(ref colors) => colors.iter().filter(|&&color| color != 0).count() as u32,
+            None => self.display.iter().zip(&self.plane2).map(|(first, second)| (first | second).count_ones()).sum(),
+        };
+        let pixels = self.width as u32 * self.height as u32;
+        lit * 20 >= pixels && lit * 5 <= pixels * 3
+    }
+
+    // The current frame as a size x size RGBA image, letterboxed onto the background
+    pub fn icon(&self, size: u16) -> Vec<u8> {
+        let frame = self.frame();
+        let background = self.background();
+        let scale = (size as f32 / self.width.max(self.height) as f32).max(f32::MIN_POSITIVE);
+        let offset_x = (size as f32 - self.width as f32 * scale) / 2.0;
+        let offset_y = (size as f32 - self.height as f32 * scale) / 2.0;
+
+        let mut rgba: Vec<u8> = Vec::with_capacity(size as usize * size as usize * 4);
+        for y in 0..size {
+            for x in 0..size {
+                let frame_x = ((x as f32 + 0.5 - offset_x) / scale).floor();
+                let frame_y = ((y as f32 + 0.5 - offset_y) / scale).floor();
+                let inside = frame_x >= 0.0 && frame_y >= 0.0 && frame_x < self.width as f32 && frame_y < self.height as f32;
+                let (r, g, b) = if inside { frame[frame_y as usize * self.width as usize + frame_x as usize] } else { background };
+                rgba.extend_from_slice(&[r, g, b, 255]);
+            }
+        }
+
+        rgba
+    }
+}
+
+// SplitMix64's finalizer, spreading nearby inputs over the whole range
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D049BB133111EB);
+    value ^ (value >> 31)
+}
+
+fn pixel_key(plane: u8, index: usize) -> u64 {
+    mix((plane as u64) << 32 | index as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_art_styles() {
+        let mut display = Display::new(4, 4, 1);
+        display.set_pixel(0, 0, 1);
+        display.set_pixel(3, 3, 1);
+
+        assert_eq!(display.to_ascii_art(AsciiStyle::Hash), "#   \n    \n    \n   #");
+        // top-left dot of the first cell, bottom-right dot of the second
+        assert_eq!(display.to_ascii_art(AsciiStyle::Braille), "\u{2801}\u{2880}");
+    }
+
+    #[test]
+    fn fingerprint_follows_pixel_writes_and_wholesale_changes() {
+        let mut display = Display::new(64, 32, 1);
+        let blank = display.fingerprint();
+        display.set_pixel(10, 5, 3);
+        let drawn = display.fingerprint();
+        assert_ne!(drawn, blank);
+
+        display.mark_all_dirty(); // recomputed from scratch, it should agree
+        assert_eq!(display.fingerprint(), drawn);
+
+        display.set_pixel(10, 5, 0);
+        assert_eq!(display.fingerprint(), blank);
+        display.set_pixel(10, 5, 1);
+        display.clear();
+        assert_eq!(display.fingerprint(), blank);
+    }
+
+    #[test]
+    fn embedder_drawing_clips_and_reports_xor_collisions() {
+        let mut display = Display::new(64, 32, 1);
+        assert!(!display.draw_sprite_at(60, 30, &[0xFF, 0x81, 0xFF], BlitMode::Xor)); // clipped at the corner
+        assert_eq!((display.get_pixel(63, 30), display.get_pixel(63, 31)), (1, 0));
+        assert!(display.draw_sprite_at(56, 30, &[0x0F], BlitMode::Xor));
+        assert_eq!(display.get_pixel(60, 30), 0);
+        assert!(!display.draw_sprite_at(56, 30, &[0x00], BlitMode::Overwrite));
+        assert_eq!(display.get_pixel(62, 30), 0);
+
+        // Two rows of a 3-wide bitmap, the dangling pixel left out
+        assert!(!display.blit_bitmap(0, 0, 3, &[1, 2, 3, 0, 3, 0, 1], BlitMode::Overwrite));
+        assert_eq!((display.get_pixel(1, 0), display.get_pixel(2, 0), display.get_pixel(0, 2)), (2, 3, 0));
+        assert!(display.blit_bitmap(2, 0, 1, &[1], BlitMode::Xor));
+        assert_eq!(display.get_pixel(2, 0), 2);
+        assert!(!display.blit_bitmap(u16::MAX, u16::MAX, 0, &[1], BlitMode::Xor));
+
+        display.clear_region(1, 0, u16::MAX, 1);
+        assert_eq!((display.get_pixel(0, 0), display.get_pixel(2, 0), display.get_pixel(1, 1)), (1, 0, 3));
+    }
+}
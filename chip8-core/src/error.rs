@@ -0,0 +1,59 @@
+use std::fmt;
+use std::io;
+
+// Why the emulator couldn't start or load something, worded so the user knows what to fix
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EmulatorError {
+    Read { path: String, source: io::Error }, // a ROM or font file couldn't be read
+    TooLarge { path: String, size: usize, max: usize }, // doesn't fit in memory from its load address
+    Overlap { path: String, address: u16, other: String }, // a segment loads over the program or another segment
+    InvalidFont { path: String, reason: String },
+    Renderer(String), // the window, renderer or audio device couldn't be set up
+}
+
+impl EmulatorError {
+    pub fn read(path: &str, source: io::Error) -> EmulatorError {
+        EmulatorError::Read { path: path.to_string(), source }
+    }
+
+    // Process exit code for a frontend giving up on this error (2 is left for usage errors)
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            EmulatorError::Renderer(_) => 3,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmulatorError::Read { path, source } if source.kind() == io::ErrorKind::NotFound => {
+                write!(f, "{} doesn't exist, check the path", path)
+            }
+            EmulatorError::Read { path, source } => write!(f, "can't read {}: {}", path, source),
+            EmulatorError::TooLarge { path, size, max } => {
+                write!(f, "{} is {} bytes but only {} fit in memory, is it really a CHIP-8 ROM?", path, size, max)
+            }
+            EmulatorError::Overlap { path, address, other } => {
+                write!(f, "{} at {:#05X} overlaps {}, load it somewhere else", path, address, other)
+            }
+            EmulatorError::InvalidFont { path, reason } => {
+                write!(f, "{} isn't a usable font ({}), fix or remove it under [paths] in config.toml", path, reason)
+            }
+            EmulatorError::Renderer(reason) => {
+                write!(f, "can't open the window: {} (is a display available? c8 headless runs without one)", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EmulatorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EmulatorError::Read { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::display::Display;
+use crate::keypad::KeyEvent;
+use std::time::Instant;
+
+// Emulator actions a platform layer binds host keys to. What one does can depend on what's
+// open: Digit opens a recent ROM or types into the RAM search, and LoadState also submits
+// the value typed there. Mute only comes from media keys.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hotkey {
+    About,
+    DrawMode,
+    SpriteDump,
+    Palette,
+    Memory,
+    Controls,
+    InputProfile,
+    RamSearch,
+    Reload,
+    Recent,
+    Pause,
+    Mute,
+    Step,
+    SaveState,
+    LoadState,
+    Up,
+    Down,
+    Select,
+    Back,
+    Digit(u8),
+    More,
+    Less,
+    Changed,
+    Same,
+    Erase,
+    Pin,
+    Unpin,
+    Restart,
+    Rewind,
+    AudioScope,
+    Quirks,
+}
+
+// What a platform layer reports to the emulator loop. Frontends turn their own input and
+// window events into these, so the loop never sees a windowing library's types.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EmuEvent {
+    Quit,
+    KeyChip8 { key: u8, down: bool, repeat: bool, time: Instant },
+    Hotkey(Hotkey),
+    DropFile(String),
+    FocusChanged { focused: bool, time: Instant },
+    WindowResized(u32, u32),
+    Paint { x: i32, y: i32, start: bool }, // mouse held down in draw mode, start on the press
+}
+
+// Applies the events that only concern the emulated machine and its screen: CHIP-8 keys,
+// focus loss letting go of them, window size and painting. The rest are left to the loop.
+pub fn apply(display: &mut Display, event: &EmuEvent) {
+    match *event {
+        // Held-down repeats would flip a sticky key back and forth
+        EmuEvent::KeyChip8 { key, down, repeat, time } if !(repeat && display.keypad.sticky[key as usize & 0xF]) => {
+            display.keypad.queue(KeyEvent { time, key, down });
+        }
+
+        // Key ups go to whichever window has focus now, so let go of everything held rather
+        // than leave keys stuck down
+        EmuEvent::FocusChanged { focused: false, time } => {
+            for key in 0..16 {
+                if display.keypad.keypad[key as usize] {
+                    display.keypad.queue(KeyEvent { time, key, down: false });
+                }
+            }
+        }
+
+        // A user-resized window letterboxes the framebuffer instead of stretching it
+        EmuEvent::WindowResized(width, height) => {
+            let window = (width, height);
+            display.window = if window == display.window_size() { None } else { Some(window) };
+        }
+
+        EmuEvent::Paint { x, y, start } => display.paint(x, y, start),
+        _ => {}
+    }
+}
+
+// Hotkeys pressed since the last poll, each taken by the first part of the loop to act on it
+#[derive(Default)]
+pub struct Hotkeys(Vec<Hotkey>);
+
+impl Hotkeys {
+    pub fn push(&mut self, hotkey: Hotkey) {
+        self.0.push(hotkey);
+    }
+
+    pub fn take(&mut self, hotkey: Hotkey) -> bool {
+        match self.0.iter().position(|&pressed| pressed == hotkey) {
+            Some(idx) => {
+                self.0.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn take_digit(&mut self) -> Option<u8> {
+        let idx = self.0.iter().position(|pressed| matches!(pressed, Hotkey::Digit(_)))?;
+        match self.0.remove(idx) {
+            Hotkey::Digit(digit) => Some(digit),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sticky_keys_ignore_repeats_and_focus_loss_lets_go_of_held_keys() {
+        let mut display = Display::new(64, 32, 1);
+        display.keypad.sticky[5] = true;
+        let time = Instant::now();
+        for repeat in [false, true] {
+            apply(&mut display, &EmuEvent::KeyChip8 { key: 5, down: true, repeat, time });
+            apply(&mut display, &EmuEvent::KeyChip8 { key: 6, down: true, repeat, time });
+        }
+        display.keypad.apply_all();
+        assert!(display.keypad.keypad[5] && display.keypad.keypad[6]);
+
+        apply(&mut display, &EmuEvent::FocusChanged { focused: false, time });
+        display.keypad.apply_all();
+        assert!(display.keypad.keypad[5] && !display.keypad.keypad[6]); // latched keys stay down
+    }
+
+    #[test]
+    fn presses_count_for_the_heatmap_but_repeats_dont() {
+        let mut display = Display::new(64, 32, 1);
+        let time = Instant::now();
+        for (key, down, repeat) in [(5, true, false), (5, true, true), (5, false, false), (5, true, false), (0xA, true, false)] {
+            apply(&mut display, &EmuEvent::KeyChip8 { key, down, repeat, time });
+        }
+        display.keypad.apply_all();
+        assert_eq!((display.keypad.presses[5], display.keypad.presses[0xA]), (2, 1));
+
+        let lines = display.keypad.heatmap_lines();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].contains("5 ██     2") && lines[3].starts_with("A ▒▒     1"), "{:?}", lines);
+        assert!(display.keypad.presses_json().starts_with("{\"presses\":{\"0\":0,"));
+        assert!(display.keypad.presses_json().ends_with("\"F\":0},\"total\":3}"));
+    }
+}
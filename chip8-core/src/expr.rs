@@ -0,0 +1,295 @@
+// Expression evaluator for watches, conditional breakpoints and halt conditions.
+// Understands V0-VF, I, PC, SP, DT, ST, mem[addr], integer literals and C-like operators:
+//   "V3", "mem[0x3F0]", "I+V2", "mem[I] == 5 && DT == 0"
+
+// Machine state an expression can read
+pub trait ExprContext {
+    fn register(&self, idx: u8) -> u8;
+    fn index(&self) -> u16;
+    fn pc(&self) -> u16;
+    fn sp(&self) -> i8;
+    fn delay(&self) -> u8;
+    fn sound(&self) -> u8;
+    fn memory(&self, address: u16) -> u8;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Number(i64),
+    Register(u8),
+    Index,
+    Pc,
+    Sp,
+    Delay,
+    Sound,
+    Memory(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Op(String),
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        let c = chars[idx];
+
+        if c.is_whitespace() {
+            idx += 1;
+        } else if c.is_ascii_digit() {
+            let start = idx;
+            while idx < chars.len() && (chars[idx].is_ascii_alphanumeric() || chars[idx] == '_') {
+                idx += 1;
+            }
+
+            let word: String = chars[start..idx].iter().filter(|&&c| c != '_').collect();
+            let value = if let Some(hex) = word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+                i64::from_str_radix(hex, 16)
+            } else if let Some(bin) = word.strip_prefix("0b") {
+                i64::from_str_radix(bin, 2)
+            } else {
+                word.parse::<i64>()
+            };
+            tokens.push(Token::Number(value.map_err(|_| format!("invalid number '{}'", word))?));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = idx;
+            while idx < chars.len() && (chars[idx].is_ascii_alphanumeric() || chars[idx] == '_') {
+                idx += 1;
+            }
+            tokens.push(Token::Ident(chars[start..idx].iter().collect()));
+        } else {
+            idx += 1;
+            match c {
+                '[' => tokens.push(Token::LBracket),
+                ']' => tokens.push(Token::RBracket),
+                '(' => tokens.push(Token::LParen),
+                ')' => tokens.push(Token::RParen),
+                _ => {
+                    let pair: String = [c, *chars.get(idx).unwrap_or(&' ')].iter().collect();
+                    if matches!(pair.as_str(), "==" | "!=" | "<=" | ">=" | "&&" | "||" | "<<" | ">>") {
+                        idx += 1;
+                        tokens.push(Token::Op(pair));
+                    } else if "+-*/%&|^<>!~".contains(c) {
+                        tokens.push(Token::Op(c.to_string()));
+                    } else {
+                        return Err(format!("unexpected character '{}'", c));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn binary_op(op: &str) -> Option<(BinaryOp, u8)> {
+    Some(match op {
+        "||" => (BinaryOp::Or, 1),
+        "&&" => (BinaryOp::And, 2),
+        "|" => (BinaryOp::BitOr, 3),
+        "^" => (BinaryOp::BitXor, 4),
+        "&" => (BinaryOp::BitAnd, 5),
+        "==" => (BinaryOp::Eq, 6),
+        "!=" => (BinaryOp::Ne, 6),
+        "<" => (BinaryOp::Lt, 7),
+        "<=" => (BinaryOp::Le, 7),
+        ">" => (BinaryOp::Gt, 7),
+        ">=" => (BinaryOp::Ge, 7),
+        "<<" => (BinaryOp::Shl, 8),
+        ">>" => (BinaryOp::Shr, 8),
+        "+" => (BinaryOp::Add, 9),
+        "-" => (BinaryOp::Sub, 9),
+        "*" => (BinaryOp::Mul, 10),
+        "/" => (BinaryOp::Div, 10),
+        "%" => (BinaryOp::Rem, 10),
+        _ => return None,
+    })
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    idx: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.idx).cloned();
+        self.idx += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            _ => Err(format!("expected '{}'", what)),
+        }
+    }
+
+    // Precedence climbing over binary operators
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut left = self.parse_unary()?;
+
+        while let Some(Token::Op(op)) = self.tokens.get(self.idx) {
+            let Some((op, precedence)) = binary_op(op) else { break };
+            if precedence < min_precedence {
+                break;
+            }
+
+            self.idx += 1;
+            let right = self.parse_binary(precedence + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Op(op)) if op == "-" => Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.parse_unary()?))),
+            Some(Token::Op(op)) if op == "!" => Ok(Expr::Unary(UnaryOp::Not, Box::new(self.parse_unary()?))),
+            Some(Token::Op(op)) if op == "~" => Ok(Expr::Unary(UnaryOp::BitNot, Box::new(self.parse_unary()?))),
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+
+            Some(Token::LParen) => {
+                let inner = self.parse_binary(0)?;
+                self.expect(Token::RParen, ")")?;
+                Ok(inner)
+            }
+
+            Some(Token::Ident(name)) => {
+                let upper = name.to_ascii_uppercase();
+                match upper.as_str() {
+                    "I" => Ok(Expr::Index),
+                    "PC" => Ok(Expr::Pc),
+                    "SP" => Ok(Expr::Sp),
+                    "DT" => Ok(Expr::Delay),
+                    "ST" => Ok(Expr::Sound),
+                    "MEM" => {
+                        self.expect(Token::LBracket, "[")?;
+                        let address = self.parse_binary(0)?;
+                        self.expect(Token::RBracket, "]")?;
+                        Ok(Expr::Memory(Box::new(address)))
+                    }
+                    _ => match upper.strip_prefix('V').map(|idx| u8::from_str_radix(idx, 16)) {
+                        Some(Ok(idx)) if idx < 16 && upper.len() == 2 => Ok(Expr::Register(idx)),
+                        _ => Err(format!("unknown name '{}'", name)),
+                    },
+                }
+            }
+
+            Some(token) => Err(format!("unexpected {:?}", token)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        idx: 0,
+    };
+
+    let expr = parser.parse_binary(0)?;
+    if parser.idx < parser.tokens.len() {
+        return Err(format!("unexpected {:?}", parser.tokens[parser.idx]));
+    }
+
+    Ok(expr)
+}
+
+impl Expr {
+    pub fn eval(&self, ctx: &dyn ExprContext) -> i64 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Register(idx) => ctx.register(*idx) as i64,
+            Expr::Index => ctx.index() as i64,
+            Expr::Pc => ctx.pc() as i64,
+            Expr::Sp => ctx.sp() as i64,
+            Expr::Delay => ctx.delay() as i64,
+            Expr::Sound => ctx.sound() as i64,
+            Expr::Memory(address) => ctx.memory(address.eval(ctx) as u16) as i64,
+
+            Expr::Unary(op, value) => {
+                let value = value.eval(ctx);
+                match op {
+                    UnaryOp::Neg => value.wrapping_neg(),
+                    UnaryOp::Not => (value == 0) as i64,
+                    UnaryOp::BitNot => !value,
+                }
+            }
+
+            Expr::Binary(op, left, right) => {
+                let left = left.eval(ctx);
+
+                // short-circuit the logical operators
+                match op {
+                    BinaryOp::Or if left != 0 => return 1,
+                    BinaryOp::And if left == 0 => return 0,
+                    _ => {}
+                }
+
+                let right = right.eval(ctx);
+                match op {
+                    BinaryOp::Or | BinaryOp::And => (right != 0) as i64,
+                    BinaryOp::BitOr => left | right,
+                    BinaryOp::BitXor => left ^ right,
+                    BinaryOp::BitAnd => left & right,
+                    BinaryOp::Eq => (left == right) as i64,
+                    BinaryOp::Ne => (left != right) as i64,
+                    BinaryOp::Lt => (left < right) as i64,
+                    BinaryOp::Le => (left <= right) as i64,
+                    BinaryOp::Gt => (left > right) as i64,
+                    BinaryOp::Ge => (left >= right) as i64,
+                    BinaryOp::Shl => left.wrapping_shl(right as u32),
+                    BinaryOp::Shr => left.wrapping_shr(right as u32),
+                    BinaryOp::Add => left.wrapping_add(right),
+                    BinaryOp::Sub => left.wrapping_sub(right),
+                    BinaryOp::Mul => left.wrapping_mul(right),
+                    BinaryOp::Div => left.checked_div(right).unwrap_or(0),
+                    BinaryOp::Rem => left.checked_rem(right).unwrap_or(0),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::io::{self, Error, ErrorKind};
+
+// Just enough GIF for Octocarts: reading the color indices of every frame, and writing one
+// frame from indices and a palette. Interlaced frames aren't supported.
+const MAX_CODES: usize = 4096;
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    idx: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> io::Result<&[u8]> {
+        let bytes = self.data.get(self.idx..self.idx + len).ok_or_else(|| invalid("GIF is cut short"))?;
+        self.idx += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    // A run of data sub-blocks, each a length byte and that many bytes, up to an empty one
+    fn sub_blocks(&mut self) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        loop {
+            let len = self.u8()? as usize;
+            if len == 0 {
+                return Ok(data);
+            }
+            data.extend_from_slice(self.take(len)?);
+        }
+    }
+}
+
+// The color indices of each frame, row by row
+pub fn decode_frames(data: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    if !(data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")) {
+        return Err(invalid("not a GIF"));
+    }
+    let mut gif = Reader { data, idx: 6 };
+    gif.take(4)?; // screen size
+    let flags = gif.u8()?;
+    gif.take(2)?; // background color, aspect ratio
+    if flags & 0x80 != 0 {
+        gif.take(3 << ((flags & 7) + 1))?;
+    }
+
+    let mut frames = Vec::new();
+    loop {
+        match gif.u8()? {
+            0x21 => {
+                gif.u8()?; // extension label
+                gif.sub_blocks()?;
+            }
+            0x2C => {
+                gif.take(4)?; // position
+                let (width, height) = (gif.u16()? as usize, gif.u16()? as usize);
+                let flags = gif.u8()?;
+                if flags & 0x40 != 0 {
+                    return Err(invalid("interlaced GIF frames aren't supported"));
+                }
+                if flags & 0x80 != 0 {
+                    gif.take(3 << ((flags & 7) + 1))?;
+                }
+                let min_code_size = gif.u8()?;
+                let mut pixels = decode_lzw(&gif.sub_blocks()?, min_code_size)?;
+                if pixels.len() < width * height {
+                    return Err(invalid("GIF frame has fewer pixels than its size"));
+                }
+                pixels.truncate(width * height);
+                frames.push(pixels);
+            }
+            0x3B => return Ok(frames),
+            _ => return Err(invalid("unknown GIF block")),
+        }
+    }
+}
+
+// GIF's variable-width LZW: codes packed least significant bit first, growing a bit each time
+// the table fills its width, up to 12 bits
+fn decode_lzw(data: &[u8], min_code_size: u8) -> io::Result<Vec<u8>> {
+    if !(2..=8).contains(&min_code_size) {
+        return Err(invalid("bad GIF code size"));
+    }
+    let clear = 1usize << min_code_size;
+    let end = clear + 1;
+    let reset = || -> Vec<Vec<u8>> { (0..clear + 2).map(|code| vec![code as u8]).collect() };
+
+    let mut table = reset();
+    let mut size = min_code_size as u32 + 1;
+    let mut previous: Option<usize> = None;
+    let mut out = Vec::new();
+    let (mut bits, mut count, mut idx) = (0u32, 0u32, 0);
+
+    loop {
+        while count < size {
+            let Some(&byte) = data.get(idx) else {
+                return Ok(out); // some encoders leave out the end code
+            };
+            bits |= (byte as u32) << count;
+            count += 8;
+            idx += 1;
+        }
+        let code = (bits & ((1 << size) - 1)) as usize;
+        bits >>= size;
+        count -= size;
+
+        if code == clear {
+            table = reset();
+            size = min_code_size as u32 + 1;
+            previous = None;
+            continue;
+        }
+        if code == end {
+            return Ok(out);
+        }
+
+        let entry = match (table.get(code), previous) {
+            (Some(entry), _) => entry.clone(),
+            (None, Some(previous)) if code == table.len() => {
+                let mut entry = table[previous].clone();
+                entry.push(entry[0]);
+                entry
+            }
+            _ => return Err(invalid("bad GIF code")),
+        };
+        out.extend_from_slice(&entry);
+
+        if let Some(previous) = previous {
+            if table.len() < MAX_CODES {
+                let mut added = table[previous].clone();
+                added.push(entry[0]);
+                table.push(added);
+                if table.len() == 1 << size && size < 12 {
+                    size += 1;
+                }
+            }
+        }
+        previous = Some(code);
+    }
+}
+
+// A single-frame GIF of width x height color indices into palette (0xRRGGBB, at most 256
+// colors, padded to a power of two)
+pub fn encode(width: u16, height: u16, palette: &[u32], pixels: &[u8]) -> Vec<u8> {
+    let bits = (palette.len().max(4) as u32).next_power_of_two().trailing_zeros().min(8);
+
+    let mut gif = Vec::new();
+    gif.extend(b"GIF89a");
+    gif.extend(width.to_le_bytes());
+    gif.extend(height.to_le_bytes());
+    gif.push(0x80 | ((bits as u8 - 1) << 4) | (bits as u8 - 1)); // global color table
+    gif.extend([0, 0]);
+    for idx in 0..1 << bits {
+        let color = palette.get(idx).copied().unwrap_or(0);
+        gif.extend([(color >> 16) as u8, (color >> 8) as u8, color as u8]);
+    }
+
+    gif.push(0x2C);
+    gif.extend([0; 4]);
+    gif.extend(width.to_le_bytes());
+    gif.extend(height.to_le_bytes());
+    gif.push(0);
+    gif.push(bits as u8);
+    for block in encode_lzw(pixels, bits as u8).chunks(255) {
+        gif.push(block.len() as u8);
+        gif.extend(block);
+    }
+    gif.push(0);
+    gif.push(0x3B);
+    gif
+}
+
+fn encode_lzw(pixels: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1usize << min_code_size;
+    let mut out = Vec::new();
+    let (mut bits, mut count) = (0u32, 0u32);
+    let mut emit = |code: usize, size: u32| {
+        bits |= (code as u32) << count;
+        count += size;
+        while count >= 8 {
+            out.push(bits as u8);
+            bits >>= 8;
+            count -= 8;
+        }
+    };
+
+    let mut table: HashMap<(usize, u8), usize> = HashMap::new();
+    let mut next = clear + 2;
+    let mut size = min_code_size as u32 + 1;
+    emit(clear, size);
+
+    let Some((&first, rest)) = pixels.split_first() else {
+        emit(clear + 1, size);
+        if count > 0 {
+            out.push(bits as u8);
+        }
+        return out;
+    };
+    let mut prefix = first as usize;
+    for &pixel in rest {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        emit(prefix, size);
+        if next < MAX_CODES {
+            table.insert((prefix, pixel), next);
+            next += 1;
+            // The decoder adds its entry a code later, so it widens once this one is past the width
+            if next > 1 << size {
+                size += 1;
+            }
+        } else {
+            emit(clear, size);
+            table.clear();
+            next = clear + 2;
+            size = min_code_size as u32 + 1;
+        }
+        prefix = pixel as usize;
+    }
+    emit(prefix, size);
+    emit(clear + 1, size);
+    if count > 0 {
+        out.push(bits as u8);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_come_back_as_written() {
+        // Long enough to fill the code table and start over
+        let pixels: Vec<u8> = (0..40_000u32).map(|n| (n.wrapping_mul(2_654_435_761) >> 28) as u8).collect();
+        let gif = encode(200, 200, &[0; 16], &pixels);
+        assert_eq!(decode_frames(&gif).unwrap(), vec![pixels]);
+
+        let flat = vec![3; 100];
+        assert_eq!(decode_frames(&encode(10, 10, &[0; 4], &flat)).unwrap(), vec![flat]);
+        assert!(decode_frames(&gif[..gif.len() / 2]).is_err());
+        assert!(decode_frames(b"PNG").is_err());
+    }
+
+    #[test]
+    fn lzw_matches_a_known_stream() {
+        // The 10x10 sample from "What's in a GIF", four colors
+        let stream = [0x8C, 0x2D, 0x99, 0x87, 0x2A, 0x1C, 0xDC, 0x33, 0xA0, 0x02, 0x75, 0xEC, 0x95, 0xFA, 0xA8, 0xDE, 0x60, 0x8C, 0x04, 0x91, 0x4C, 0x01];
+        let rows = ["1111122222", "1111122222", "1111122222", "1110000222", "1110000222", "2220000111", "2220000111", "2222211111", "2222211111", "2222211111"];
+        let pixels: Vec<u8> = rows.concat().bytes().map(|digit| digit - b'0').collect();
+        assert_eq!(decode_lzw(&stream, 2).unwrap(), pixels);
+        assert_eq!(encode_lzw(&pixels, 2), stream);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::expr::{self, Expr};
+use std::collections::VecDeque;
+
+// End conditions for headless runs
+pub enum HaltCondition {
+    MaxCycles(u64),
+    PcEquals(u16),
+    MemoryEquals(u16, u8),
+    DisplayHash(u64),
+    When(String, Expr), // a watch expression evaluating to non-zero
+    InfiniteLoop,       // PC revisits an unchanged CPU state with no delay timer pending
+}
+
+// Why a headless run stopped, with enough state to tell runs apart
+pub struct HaltResult {
+    pub reason: String,
+    pub cycles: u64,
+    pub frames: u64,
+    pub pc: u16,
+    pub display_hash: u64,
+}
+
+// Parses a decimal or 0x-prefixed hex number
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+impl HaltCondition {
+    // Parses "cycles=N", "pc=ADDR", "mem[ADDR]=VALUE", "display=HASH", "when=EXPR" or "loop"
+    pub fn parse(text: &str) -> Result<HaltCondition, String> {
+        if text == "loop" {
+            return Ok(HaltCondition::InfiniteLoop);
+        }
+
+        let Some((key, value)) = text.split_once('=') else {
+            return Err(format!("invalid halt condition {}", text));
+        };
+        let number = || parse_number(value.trim()).ok_or_else(|| format!("invalid number {} in {}", value, text));
+
+        match key.trim() {
+            "cycles" => Ok(HaltCondition::MaxCycles(number()?)),
+            "pc" => Ok(HaltCondition::PcEquals(number()? as u16)),
+            "display" => Ok(HaltCondition::DisplayHash(number()?)),
+            "when" => Ok(HaltCondition::When(value.to_string(), expr::parse(value)?)),
+            key => {
+                let address = key
+                    .strip_prefix("mem[")
+                    .and_then(|rest| rest.strip_suffix(']'))
+                    .and_then(parse_number)
+                    .ok_or_else(|| format!("unknown halt condition {}", text))?;
+                Ok(HaltCondition::MemoryEquals(address as u16, number()? as u8))
+            }
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            HaltCondition::MaxCycles(cycles) => format!("cycles={}", cycles),
+            HaltCondition::PcEquals(pc) => format!("pc={:#05X}", pc),
+            HaltCondition::MemoryEquals(address, value) => format!("mem[{:#05X}]={:#04X}", address, value),
+            HaltCondition::DisplayHash(hash) => format!("display={:#018X}", hash),
+            HaltCondition::When(source, _) => format!("when={}", source),
+            HaltCondition::InfiniteLoop => "loop".to_string(),
+        }
+    }
+}
+
+impl HaltResult {
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"reason\":\"{}\",\"cycles\":{},\"frames\":{},\"pc\":{},\"display_hash\":\"{:#018X}\"}}",
+            self.reason.replace('\\', "\\\\").replace('"', "\\\""),
+            self.cycles,
+            self.frames,
+            self.pc,
+            self.display_hash,
+        )
+    }
+}
+
+// Spots the CPU revisiting a state it was in a few instructions ago
+pub struct LoopDetector {
+    recent: VecDeque<u64>,
+}
+
+impl Default for LoopDetector {
+    fn default() -> LoopDetector {
+        LoopDetector::new()
+    }
+}
+
+impl LoopDetector {
+    const WINDOW: usize = 16;
+
+    pub fn new() -> LoopDetector {
+        LoopDetector {
+            recent: VecDeque::with_capacity(LoopDetector::WINDOW),
+        }
+    }
+
+    // Feeds the hash of the CPU state after an instruction, returns true on a repeat
+    pub fn observe(&mut self, state: u64) -> bool {
+        if self.recent.contains(&state) {
+            return true;
+        }
+
+        if self.recent.len() == LoopDetector::WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(state);
+        false
+    }
+
+    pub fn reset(&mut self) {
+        self.recent.clear();
+    }
+}
@@ -0,0 +1,83 @@
+use crate::memory::{MemoryBus, WriteHit};
+
+const COLUMNS: u16 = 8;
+const ROWS: u16 = 8;
+const HIGHLIGHT_FRAMES: u32 = 60;
+
+// A page of memory as overlay text. Bytes hit by a write watchpoint are bracketed for a second
+// after the write, and the most recent hits are listed with the PC that wrote them.
+pub struct HexView {
+    pub start: u16,
+    recent: Vec<(WriteHit, u32)>, // hit and the frames its highlight stays up for
+}
+
+impl HexView {
+    pub fn new(start: u16) -> HexView {
+        HexView {
+            start: start & !(COLUMNS - 1),
+            recent: Vec::new(),
+        }
+    }
+
+    // Takes new watchpoint hits, following the page to the latest one
+    pub fn record(&mut self, hits: &[WriteHit]) {
+        for hit in hits {
+            self.recent.retain(|(recent, _)| recent.address != hit.address);
+            self.recent.push((*hit, HIGHLIGHT_FRAMES));
+        }
+
+        if let Some(hit) = hits.last() {
+            let page = COLUMNS * ROWS;
+            if !(self.start..self.start.saturating_add(page)).contains(&hit.address) {
+                self.start = (hit.address & !(COLUMNS - 1)).saturating_sub(page / 2);
+            }
+        }
+    }
+
+    pub fn tick_frame(&mut self) {
+        for (_, frames) in self.recent.iter_mut() {
+            *frames = frames.saturating_sub(1);
+        }
+        self.recent.retain(|&(_, frames)| frames > 0);
+    }
+
+    fn highlighted(&self, address: u16) -> bool {
+        self.recent.iter().any(|(hit, _)| hit.address == address)
+    }
+
+    pub fn lines(&self, memory: &MemoryBus) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for row in 0..ROWS {
+            let address = self.start.wrapping_add(row * COLUMNS);
+            let mut line = format!("{:04X}:", address);
+            for column in 0..COLUMNS {
+                let address = address.wrapping_add(column);
+                let byte = memory.get(address as usize).copied().unwrap_or(0);
+                let separator = match (column > 0 && self.highlighted(address.wrapping_sub(1)), self.highlighted(address)) {
+                    (false, true) => '[',
+                    (true, false) => ']',
+                    _ => ' ',
+                };
+                line += &format!("{}{:02X}", separator, byte);
+            }
+            if self.highlighted(address.wrapping_add(COLUMNS - 1)) {
+                line.push(']');
+            }
+            lines.push(line);
+        }
+
+        if !self.recent.is_empty() {
+            lines.push(String::new());
+        }
+        for (hit, _) in self.recent.iter().rev().take(4) {
+            let writer = match memory.last_writer(hit.address) {
+                Some(pc) => format!("{:04X}", pc),
+                None => format!("{:04X}", hit.pc),
+            };
+            lines.push(format!("{:04X} = {:02X} BY PC {}", hit.address, hit.value, writer));
+        }
+
+        lines
+    }
+}
@@ -0,0 +1,49 @@
+use crate::keypad;
+use crate::toml::Table;
+
+// A named set of key remaps, e.g. for a two-player ROM whose keys are spread awkwardly over
+// the hex pad. In the ROM config:
+//
+//   input = "pong-2p"        # the selected profile
+//
+//   [input_profiles.pong-2p]
+//   1 = "W"                  # CHIP-8 key = host key; keys not listed keep their usual binding
+//   4 = "S"
+//   C = "Up"
+//   D = "Down"
+pub struct InputProfile {
+    pub name: String,
+    pub bindings: Vec<(u8, String)>, // (CHIP-8 key, host key name)
+}
+
+impl InputProfile {
+    pub fn all_from_config(table: &Table) -> Result<Vec<InputProfile>, String> {
+        let Some(profiles) = table.get("input_profiles") else {
+            return Ok(Vec::new());
+        };
+        let profiles = profiles.as_table().ok_or("input_profiles should be a table of profiles")?;
+
+        let mut all = Vec::new();
+        for (name, entries) in profiles {
+            let entries = entries.as_table().ok_or_else(|| format!("input profile {} should be a table", name))?;
+
+            let bindings = keypad::bindings_from_config(entries).map_err(|e| format!("input profile {}: {}", name, e))?;
+            all.push(InputProfile { name: name.clone(), bindings });
+        }
+
+        Ok(all)
+    }
+}
+
+// The profile after `current` in order, None after the last one so cycling passes through the
+// usual bindings
+pub fn next_profile<'a>(profiles: &'a [InputProfile], current: Option<&str>) -> Option<&'a InputProfile> {
+    match current.and_then(|name| profiles.iter().position(|profile| profile.name == name)) {
+        Some(idx) => profiles.get(idx + 1),
+        None => profiles.first(),
+    }
+}
+
+pub fn find_profile<'a>(profiles: &'a [InputProfile], name: &str) -> Option<&'a InputProfile> {
+    profiles.iter().find(|profile| profile.name == name)
+}
@@ -0,0 +1,208 @@
+use crate::toml::{Table, Value};
+use std::io::{self, Error, ErrorKind};
+
+// A small JSON reader for data files such as the CHIP-8 program database. Values come back as
+// the TOML reader's, objects as tables, so the same accessors read both. Members set to null
+// are left out, as TOML has no null.
+pub fn parse(text: &str) -> io::Result<Value> {
+    let mut parser = Parser { chars: text.chars().collect(), idx: 0 };
+    let value = parser.value()?.ok_or_else(|| parser.error("null at the top level"))?;
+    parser.skip_whitespace();
+    if parser.idx < parser.chars.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+// A JSON string literal holding text
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+struct Parser {
+    chars: Vec<char>,
+    idx: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> Error {
+        let line = self.chars[..self.idx.min(self.chars.len())].iter().filter(|&&c| c == '\n').count() + 1;
+        Error::new(ErrorKind::InvalidData, format!("line {}: {}", line, message))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.idx).is_some_and(|c| c.is_whitespace()) {
+            self.idx += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> io::Result<()> {
+        self.skip_whitespace();
+        if self.chars.get(self.idx) != Some(&expected) {
+            return Err(self.error(&format!("expected {}", expected)));
+        }
+        self.idx += 1;
+        Ok(())
+    }
+
+    // The next value, None for null
+    fn value(&mut self) -> io::Result<Option<Value>> {
+        self.skip_whitespace();
+        let value = match self.chars.get(self.idx).copied() {
+            Some('{') => Value::Table(self.object()?),
+            Some('[') => Value::Array(self.array()?),
+            Some('"') => Value::Str(self.string()?),
+            Some('t') if self.word("true") => Value::Bool(true),
+            Some('f') if self.word("false") => Value::Bool(false),
+            Some('n') if self.word("null") => return Ok(None),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number()?,
+            _ => return Err(self.error("expected a value")),
+        };
+        Ok(Some(value))
+    }
+
+    fn word(&mut self, word: &str) -> bool {
+        let matches = word.chars().enumerate().all(|(offset, c)| self.chars.get(self.idx + offset) == Some(&c));
+        if matches {
+            self.idx += word.len();
+        }
+        matches
+    }
+
+    fn object(&mut self) -> io::Result<Table> {
+        let mut table = Table::new();
+        self.idx += 1;
+        self.skip_whitespace();
+        if self.chars.get(self.idx) == Some(&'}') {
+            self.idx += 1;
+            return Ok(table);
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.chars.get(self.idx) != Some(&'"') {
+                return Err(self.error("expected a key"));
+            }
+            let key = self.string()?;
+            self.expect(':')?;
+            if let Some(value) = self.value()? {
+                table.insert(key, value);
+            }
+
+            self.skip_whitespace();
+            match self.chars.get(self.idx) {
+                Some(',') => self.idx += 1,
+                Some('}') => {
+                    self.idx += 1;
+                    return Ok(table);
+                }
+                _ => return Err(self.error("expected , or }")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> io::Result<Vec<Value>> {
+        let mut values = Vec::new();
+        self.idx += 1;
+        self.skip_whitespace();
+        if self.chars.get(self.idx) == Some(&']') {
+            self.idx += 1;
+            return Ok(values);
+        }
+
+        loop {
+            values.extend(self.value()?);
+            self.skip_whitespace();
+            match self.chars.get(self.idx) {
+                Some(',') => self.idx += 1,
+                Some(']') => {
+                    self.idx += 1;
+                    return Ok(values);
+                }
+                _ => return Err(self.error("expected , or ]")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let mut text = String::new();
+        self.idx += 1;
+        loop {
+            let Some(&c) = self.chars.get(self.idx) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.idx += 1;
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escaped = self.chars.get(self.idx).copied();
+                    self.idx += 1;
+                    match escaped {
+                        Some('n') => text.push('\n'),
+                        Some('t') => text.push('\t'),
+                        Some('r') => text.push('\r'),
+                        Some('b') => text.push('\u{8}'),
+                        Some('f') => text.push('\u{c}'),
+                        Some('u') => {
+                            let hex: String = self.chars.iter().skip(self.idx).take(4).collect();
+                            let code = u32::from_str_radix(&hex, 16).map_err(|_| self.error("bad \\u escape"))?;
+                            self.idx += 4;
+                            // Surrogate pairs aren't put back together, non-BMP characters come out as U+FFFD
+                            text.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        }
+                        Some(c @ ('"' | '\\' | '/')) => text.push(c),
+                        _ => return Err(self.error("bad escape")),
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> io::Result<Value> {
+        let start = self.idx;
+        while self.chars.get(self.idx).is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+            self.idx += 1;
+        }
+        let text: String = self.chars[start..self.idx].iter().collect();
+        if let Ok(int) = text.parse::<i64>() {
+            return Ok(Value::Int(int));
+        }
+        text.parse::<f64>().map(Value::Float).map_err(|_| self.error(&format!("bad number {}", text)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objects_arrays_and_scalars_read_as_toml_values() {
+        let value = parse(r#"{"title": "Brix \"2\"", "tickrate": 15, "scale": -1.5e0, "tags": [true, null, {}], "gone": null}"#).unwrap();
+        let table = value.as_table().unwrap();
+        assert_eq!(table["title"].as_str(), Some("Brix \"2\""));
+        assert_eq!(table["tickrate"].as_int(), Some(15));
+        assert_eq!(table["scale"].as_float(), Some(-1.5));
+        assert_eq!(table["tags"].as_array().unwrap().len(), 2);
+        assert!(!table.contains_key("gone"));
+
+        assert!(parse("[1, 2").is_err());
+        assert!(parse("{\"a\": 1} x").is_err());
+
+        let text = "line \"one\"\n\ttab \\ \u{1}";
+        assert_eq!(parse(&quote(text)).unwrap().as_str(), Some(text));
+    }
+}
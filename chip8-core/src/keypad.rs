@@ -0,0 +1,184 @@
+use crate::toml::{Table, Value};
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// The COSMAC VIP keypad as laid out on the device, rows top to bottom
+pub const KEYPAD_LAYOUT: [[usize; 4]; 4] = [[0x1, 0x2, 0x3, 0xC], [0x4, 0x5, 0x6, 0xD], [0x7, 0x8, 0x9, 0xE], [0xA, 0x0, 0xB, 0xF]];
+
+pub struct Keypad {
+    pub keypad: [bool; 16],
+    pub key_wait: KeyWait,
+    pub sticky: [bool; 16], // keys that toggle on each press instead of being held down
+    pub presses: [u64; 16], // presses from the host this session, held-down repeats not counted
+    pending: VecDeque<KeyEvent>,
+}
+
+// FX0A's wait for a key. Only keys pressed once it started waiting count: the first to go
+// down, or with fx0a_release the first to go down and come back up.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct KeyWait {
+    pub waiting: bool,
+    pub pressed: Option<u8>,
+    pub released: Option<u8>,
+}
+
+// A CHIP-8 key (0x0-0xF) press or release, timestamped with when the host saw it
+pub struct KeyEvent {
+    pub time: Instant,
+    pub key: u8,
+    pub down: bool,
+}
+
+impl Default for Keypad {
+    fn default() -> Keypad {
+        Keypad::new()
+    }
+}
+
+impl Keypad {
+    pub fn new() -> Keypad {
+        Keypad {
+            keypad: [false; 16],
+            key_wait: KeyWait::default(),
+            sticky: [false; 16],
+            presses: [0; 16],
+            pending: VecDeque::new(),
+        }
+    }
+
+    // Queues an event to be applied once emulation reaches its timestamp
+    pub fn queue(&mut self, event: KeyEvent) {
+        self.pending.push_back(event);
+    }
+
+    // Applies queued events that happened at or before time
+    pub fn apply_until(&mut self, time: Instant) {
+        while self.pending.front().is_some_and(|event| event.time <= time) {
+            let event = self.pending.pop_front().unwrap();
+            self.apply(&event);
+        }
+    }
+
+    pub fn apply_all(&mut self) {
+        while let Some(event) = self.pending.pop_front() {
+            self.apply(&event);
+        }
+    }
+
+    fn apply(&mut self, event: &KeyEvent) {
+        let key = event.key as usize & 0xF;
+        if event.down && (self.sticky[key] || !self.keypad[key]) {
+            self.presses[key] += 1;
+        }
+
+        // A sticky key latches on one press and lets go on the next; releasing it does nothing
+        if self.sticky[event.key as usize & 0xF] {
+            if event.down && self.keypad[event.key as usize & 0xF] {
+                self.key_up(event.key);
+            } else if event.down {
+                self.key_down(event.key);
+            }
+            return;
+        }
+
+        if event.down {
+            self.key_down(event.key);
+        } else {
+            self.key_up(event.key);
+        }
+    }
+
+    // The keypad drawn with each key's share of the presses, darkest for the most pressed, so
+    // playtesters can see which keys a control scheme wears out:
+    //
+    //   1 ░░    12  2 ██   240  3 ▒▒   130  C        0
+    pub fn heatmap_lines(&self) -> Vec<String> {
+        const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+        let most = self.presses.iter().copied().max().unwrap_or(0).max(1);
+
+        KEYPAD_LAYOUT
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&key| {
+                        let count = self.presses[key];
+                        let shade = match count {
+                            0 => SHADES[0],
+                            _ => SHADES[1 + (count * 3 / most) as usize],
+                        };
+                        format!("{:X} {}{} {:5}", key, shade, shade, count)
+                    })
+                    .collect::<Vec<String>>()
+                    .join("  ")
+            })
+            .collect()
+    }
+
+    // The press counts as JSON, keyed by hex digit
+    pub fn presses_json(&self) -> String {
+        let keys: Vec<String> = self.presses.iter().enumerate().map(|(key, count)| format!("\"{:X}\":{}", key, count)).collect();
+        format!("{{\"presses\":{{{}}},\"total\":{}}}", keys.join(","), self.presses.iter().sum::<u64>())
+    }
+
+    // Starts FX0A's wait if it isn't waiting already and returns the key it got, if any yet,
+    // which ends the wait
+    pub fn wait_for_key(&mut self, on_release: bool) -> Option<u8> {
+        if !self.key_wait.waiting {
+            self.key_wait = KeyWait { waiting: true, pressed: None, released: None };
+        }
+        let key = if on_release { self.key_wait.released } else { self.key_wait.pressed };
+        if key.is_some() {
+            self.key_wait.waiting = false;
+        }
+        key
+    }
+
+    pub fn key_down(&mut self, key: u8) {
+        let key = key & 0xF;
+        self.keypad[key as usize] = true;
+        if self.key_wait.waiting && self.key_wait.pressed.is_none() {
+            self.key_wait.pressed = Some(key);
+        }
+    }
+
+    pub fn key_up(&mut self, key: u8) {
+        let key = key & 0xF;
+        self.keypad[key as usize] = false;
+        if self.key_wait.waiting && self.key_wait.pressed == Some(key) {
+            self.key_wait.released = Some(key);
+        }
+    }
+}
+
+// Reads a list of CHIP-8 keys as hex digits, e.g. sticky_keys = ["5", "A"]
+pub fn keys_from_config(values: &[Value]) -> Result<Vec<u8>, String> {
+    values
+        .iter()
+        .map(|value| {
+            value
+                .as_str()
+                .and_then(|key| u8::from_str_radix(key, 16).ok())
+                .filter(|&key| key < 16)
+                .ok_or_else(|| "keys should be CHIP-8 keys written \"0\" to \"F\"".to_string())
+        })
+        .collect()
+}
+
+// Reads a table of CHIP-8 key (hex digit) = host key name, the way config files bind keys:
+//
+//   [keys]
+//   C = "4"
+//   F = "V"
+pub fn bindings_from_config(table: &Table) -> Result<Vec<(u8, String)>, String> {
+    let mut bindings = Vec::new();
+    for (key, host_key) in table {
+        let chip8_key = u8::from_str_radix(key, 16)
+            .ok()
+            .filter(|&key| key < 16)
+            .ok_or_else(|| format!("{} isn't a CHIP-8 key (0-F)", key))?;
+        let host_key = host_key.as_str().ok_or_else(|| format!("key {} should name a host key", key))?;
+        bindings.push((chip8_key, host_key.to_string()));
+    }
+
+    Ok(bindings)
+}
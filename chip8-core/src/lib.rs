@@ -0,0 +1,59 @@
+pub mod achievements;
+pub mod annotations;
+pub mod audio;
+pub mod autosave;
+pub mod backtrace;
+pub mod blocks;
+pub mod bundle;
+pub mod config;
+pub mod compat;
+pub mod cpu;
+pub mod database;
+pub mod delta;
+pub mod disasm;
+pub mod display;
+pub mod error;
+pub mod event;
+pub mod expr;
+pub mod font;
+pub mod gif;
+pub mod halt;
+pub mod hexview;
+pub mod input_profile;
+pub mod json;
+pub mod keypad;
+pub mod megachip;
+pub mod memory;
+pub mod menu;
+pub mod octo;
+pub mod opcode_timing;
+pub mod overlay;
+pub mod palette;
+pub mod patch;
+pub mod persist;
+pub mod quirk_report;
+pub mod ramsearch;
+pub mod recent;
+pub mod rewind;
+pub mod rle;
+pub mod rng;
+pub mod rom_config;
+pub mod romfix;
+pub mod savestate;
+pub mod screenshot;
+pub mod segment;
+#[cfg(all(feature = "shm", unix))]
+pub mod shm;
+pub mod spectate;
+pub mod splash;
+pub mod speedrun;
+pub mod tickrate;
+pub mod timeline;
+pub mod timing;
+pub mod toml;
+pub mod trace;
+pub mod watch;
+
+pub use cpu::{Chip8, Quirks, Snapshot};
+pub use display::Display;
+pub use error::EmulatorError;
@@ -0,0 +1,208 @@
+use crate::delta;
+use crate::rle::{decode_rle_len, encode_rle};
+
+// MEGACHIP's color mode, entered with 0011: a 256x192 screen drawn with sprites of one byte
+// per pixel, each a color index into a palette loaded from memory. Sprites are drawn into a
+// back buffer that 00E0 presents and clears, so the screen only changes once per 00E0.
+pub const WIDTH: u16 = 256;
+pub const HEIGHT: u16 = 192;
+
+// The palette (256 colors), sprite size, alpha, blend mode and collision color in a savestate
+const SETTINGS_LEN: usize = 1031;
+
+// How a sprite pixel combines with the one under it, set with 080N
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Blend {
+    Normal,
+    Alpha(u8), // 25, 50 or 75 percent of the sprite
+    Add,
+    Multiply,
+}
+
+impl Blend {
+    pub fn from_mode(mode: u8) -> Blend {
+        match mode {
+            1 => Blend::Alpha(25),
+            2 => Blend::Alpha(50),
+            3 => Blend::Alpha(75),
+            4 => Blend::Add,
+            5 => Blend::Multiply,
+            _ => Blend::Normal,
+        }
+    }
+
+    // The 080N mode that selects this blend
+    pub fn mode(self) -> u8 {
+        match self {
+            Blend::Normal => 0,
+            Blend::Alpha(25) => 1,
+            Blend::Alpha(50) => 2,
+            Blend::Alpha(_) => 3,
+            Blend::Add => 4,
+            Blend::Multiply => 5,
+        }
+    }
+
+    // Combines two 0xRRGGBB colors, channel by channel
+    fn apply(self, under: u32, over: u32) -> u32 {
+        let channel = |shift: u32| {
+            let (under, over) = ((under >> shift) & 0xFF, (over >> shift) & 0xFF);
+            let mixed = match self {
+                Blend::Normal => over,
+                Blend::Alpha(percent) => (over * percent as u32 + under * (100 - percent as u32)) / 100,
+                Blend::Add => (under + over).min(0xFF),
+                Blend::Multiply => under * over / 0xFF,
+            };
+            mixed << shift
+        };
+        channel(16) | channel(8) | channel(0)
+    }
+}
+
+#[derive(Clone)]
+pub struct MegaChip {
+    pub palette: [u32; 256], // 0xRRGGBB by color index; index 0 is transparent
+    pub sprite_width: u16,   // 03NN, 0 meaning 256
+    pub sprite_height: u16,  // 04NN, 0 meaning 256
+    pub alpha: u8,           // 05NN, how opaque the presented screen is
+    pub blend: Blend,
+    pub collision_color: u8, // 09NN, drawing over this index sets VF
+    back: Vec<u32>,          // the screen being drawn, 0xRRGGBB
+    indices: Vec<u8>,        // color index drawn last at each pixel, for collisions
+}
+
+impl Default for MegaChip {
+    fn default() -> MegaChip {
+        let pixels = WIDTH as usize * HEIGHT as usize;
+        MegaChip {
+            palette: [0; 256],
+            sprite_width: 0,
+            sprite_height: 0,
+            alpha: 0xFF,
+            blend: Blend::Normal,
+            collision_color: 0,
+            back: vec![0; pixels],
+            indices: vec![0; pixels],
+        }
+    }
+}
+
+impl MegaChip {
+    // 02NN: count colors from memory as 4 bytes each, alpha first and ignored, into
+    // indices 1 onwards
+    pub fn load_palette(&mut self, bytes: &[u8]) {
+        for (idx, color) in bytes.chunks_exact(4).enumerate().take(255) {
+            self.palette[idx + 1] = u32::from_be_bytes([0, color[1], color[2], color[3]]);
+        }
+    }
+
+    pub fn sprite_size(&self) -> (u16, u16) {
+        let size = |size: u16| if size == 0 { 256 } else { size };
+        (size(self.sprite_width), size(self.sprite_height))
+    }
+
+    // DXYN: a sprite of sprite_size() color indices, row by row, clipped at the screen edges.
+    // Returns whether it drew over the collision color.
+    pub fn draw(&mut self, x: u16, y: u16, sprite: &[u8]) -> bool {
+        let (width, height) = self.sprite_size();
+        let mut collided = false;
+        for row in 0..height {
+            for column in 0..width {
+                let (px, py) = (x as usize + column as usize, y as usize + row as usize);
+                let index = sprite.get(row as usize * width as usize + column as usize).copied().unwrap_or(0);
+                if index == 0 || px >= WIDTH as usize || py >= HEIGHT as usize {
+                    continue;
+                }
+
+                let pixel = py * WIDTH as usize + px;
+                collided |= self.indices[pixel] == self.collision_color && self.collision_color != 0;
+                self.indices[pixel] = index;
+                self.back[pixel] = self.blend.apply(self.back[pixel], self.palette[index as usize]);
+            }
+        }
+        collided
+    }
+
+    // 00BN: moves the back buffer up, blank rows coming in at the bottom
+    pub fn scroll_up(&mut self, rows: u8) {
+        let offset = (rows as usize).min(HEIGHT as usize) * WIDTH as usize;
+        self.back.drain(..offset);
+        self.back.resize(WIDTH as usize * HEIGHT as usize, 0);
+        self.indices.drain(..offset);
+        self.indices.resize(WIDTH as usize * HEIGHT as usize, 0);
+    }
+
+    // The mode's state for a savestate: the palette and sprite settings, then the back buffer
+    // and the color indices drawn, run-length encoded
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.settings_bytes();
+        bytes.extend(encode_rle(&delta::words_to_bytes(&self.back)));
+        bytes.extend(encode_rle(&self.indices));
+        bytes
+    }
+
+    // Reads to_bytes() output from the start of data, returning the mode and the bytes it took
+    pub fn from_bytes(data: &[u8]) -> Option<(MegaChip, usize)> {
+        let pixels = WIDTH as usize * HEIGHT as usize;
+        let settings = data.get(..SETTINGS_LEN)?;
+        let (back, back_len) = decode_rle_len(&data[SETTINGS_LEN..], pixels * 4)?;
+        let (indices, indices_len) = decode_rle_len(&data[SETTINGS_LEN + back_len..], pixels)?;
+        let mega = MegaChip::with_settings(settings, delta::bytes_to_words(&back), indices)?;
+        Some((mega, SETTINGS_LEN + back_len + indices_len))
+    }
+
+    // The same state uncompressed and always the same length, for rewind's frame deltas to
+    // line up
+    pub fn to_raw_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.settings_bytes();
+        bytes.extend(delta::words_to_bytes(&self.back));
+        bytes.extend(&self.indices);
+        bytes
+    }
+
+    pub fn from_raw_bytes(data: &[u8]) -> Option<MegaChip> {
+        let pixels = WIDTH as usize * HEIGHT as usize;
+        if data.len() != SETTINGS_LEN + pixels * 5 {
+            return None;
+        }
+        let (settings, screen) = data.split_at(SETTINGS_LEN);
+        let (back, indices) = screen.split_at(pixels * 4);
+        MegaChip::with_settings(settings, delta::bytes_to_words(back), indices.to_vec())
+    }
+
+    fn settings_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self.palette.iter().flat_map(|color| color.to_le_bytes()).collect();
+        bytes.extend(self.sprite_width.to_le_bytes());
+        bytes.extend(self.sprite_height.to_le_bytes());
+        bytes.extend([self.alpha, self.blend.mode(), self.collision_color]);
+        bytes
+    }
+
+    fn with_settings(settings: &[u8], back: Vec<u32>, indices: Vec<u8>) -> Option<MegaChip> {
+        let mut palette = [0; 256];
+        for (color, bytes) in palette.iter_mut().zip(settings.chunks_exact(4)) {
+            *color = u32::from_le_bytes(bytes.try_into().ok()?);
+        }
+        Some(MegaChip {
+            palette,
+            sprite_width: u16::from_le_bytes([settings[1024], settings[1025]]),
+            sprite_height: u16::from_le_bytes([settings[1026], settings[1027]]),
+            alpha: settings[1028],
+            blend: Blend::from_mode(settings[1029]),
+            collision_color: settings[1030],
+            back,
+            indices,
+        })
+    }
+
+    // 00E0: the finished screen, faded by the screen alpha, leaving a clear back buffer to
+    // draw the next one into
+    pub fn present(&mut self) -> Vec<u32> {
+        let fade = Blend::Alpha((self.alpha as u32 * 100 / 0xFF) as u8);
+        let frame = self.back.iter().map(|&color| fade.apply(0, color)).collect();
+        self.back.fill(0);
+        self.indices.fill(0);
+        frame
+    }
+}
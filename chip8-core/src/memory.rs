@@ -0,0 +1,309 @@
+use crate::rng::Rng;
+use std::ops::{Deref, DerefMut, Range};
+
+// What an access past the end of memory does
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum MemoryWrap {
+    Wrap, // continue from address 0
+    Trap, // read 0 and record a fault for the CPU to act on
+}
+
+impl MemoryWrap {
+    pub fn from_name(name: &str) -> Option<MemoryWrap> {
+        match name {
+            "wrap" => Some(MemoryWrap::Wrap),
+            "trap" => Some(MemoryWrap::Trap),
+            _ => None,
+        }
+    }
+}
+
+// What memory holds where nothing was loaded, and what a trapped read past the end sees
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum MemoryInit {
+    Fill(u8),    // the same byte everywhere, Fill(0) being zeroed RAM
+    OpenBus,     // the high byte of the address, as a floating 8-bit bus tends to read back
+    Random(u64), // noise from a seed, like RAM at power-on
+}
+
+impl MemoryInit {
+    // "zero", "open-bus", "random", "random:SEED" or a byte such as 0xFF. Plain "random" picks
+    // a new seed each run.
+    pub fn parse(text: &str) -> Result<MemoryInit, String> {
+        let number = |text: &str| match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => text.parse().ok(),
+        };
+
+        match text {
+            "zero" => Ok(MemoryInit::Fill(0)),
+            "open-bus" => Ok(MemoryInit::OpenBus),
+            "random" => Ok(MemoryInit::Random(rand::random())),
+            _ => match text.strip_prefix("random:") {
+                Some(seed) => number(seed).map(MemoryInit::Random).ok_or_else(|| format!("invalid seed {}", seed)),
+                None => number(text)
+                    .and_then(|value| u8::try_from(value).ok())
+                    .map(MemoryInit::Fill)
+                    .ok_or_else(|| format!("unknown memory init {}, expected zero, open-bus, random[:seed] or a byte", text)),
+            },
+        }
+    }
+
+    // The byte at an address, the same every time it's asked for
+    pub fn byte(&self, address: u32) -> u8 {
+        match *self {
+            MemoryInit::Fill(value) => value,
+            MemoryInit::OpenBus => (address >> 8) as u8,
+            MemoryInit::Random(seed) => Rng::from_seed(seed.wrapping_add(address as u64)).next_u8(),
+        }
+    }
+}
+
+// A CPU write that landed inside a write watchpoint
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WriteHit {
+    pub address: u16,
+    pub value: u8,
+    pub pc: u16,
+}
+
+// The address space. Instruction fetches go through read_word so a corrupted PC can't index
+// past the end, and CPU stores go through write so they can be watched; everything else sees
+// it as a plain byte slice. The CPU's accesses end at the limit, the memory the interpreter
+// addresses, which can be less than what's allocated.
+#[derive(Clone)]
+pub struct MemoryBus {
+    data: Vec<u8>,
+    limit: u32,
+    pub wrap: MemoryWrap,
+    pub init: MemoryInit,
+    fault: Option<u32>,
+    writers: Option<Vec<Option<u16>>>, // PC of the last store to each byte, when tracked
+    watchpoints: Vec<Range<u32>>,
+    hits: Vec<WriteHit>,
+    written: Option<Range<u32>>, // span stored to since take_written
+}
+
+impl MemoryBus {
+    pub fn new(size: usize, wrap: MemoryWrap) -> MemoryBus {
+        MemoryBus {
+            data: vec![0; size],
+            limit: size as u32,
+            wrap,
+            init: MemoryInit::Fill(0),
+            fault: None,
+            writers: None,
+            watchpoints: Vec::new(),
+            hits: Vec::new(),
+            written: None,
+        }
+    }
+
+    // Where the CPU's accesses end, e.g. 4K for CHIP-8, at most the allocated size
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.min(self.data.len()) as u32;
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit as usize
+    }
+
+    // Maps an address past the limit according to the wrap policy, None (and a fault) on a trap
+    fn resolve(&mut self, address: u32) -> Option<usize> {
+        let size = self.limit;
+        if address < size {
+            return Some(address as usize);
+        }
+
+        match self.wrap {
+            MemoryWrap::Wrap if size > 0 => Some((address % size) as usize),
+            _ => {
+                self.fault.get_or_insert(address);
+                None
+            }
+        }
+    }
+
+    pub fn read(&mut self, address: u32) -> u8 {
+        match self.resolve(address) {
+            Some(idx) => self.data[idx],
+            None => self.init.byte(address),
+        }
+    }
+
+    // Resets a range to what unloaded memory holds under the init policy
+    pub fn initialize(&mut self, range: Range<usize>) {
+        let init = self.init;
+        for (idx, byte) in self.data[range.clone()].iter_mut().enumerate() {
+            *byte = init.byte((range.start + idx) as u32);
+        }
+    }
+
+    // A store by the instruction at pc, recorded in the writer map and checked against the
+    // watchpoints
+    pub fn write(&mut self, address: u32, value: u8, pc: u16) {
+        let Some(idx) = self.resolve(address) else {
+            return;
+        };
+
+        self.data[idx] = value;
+        let idx32 = idx as u32;
+        self.written = Some(match self.written.take() {
+            Some(span) => span.start.min(idx32)..span.end.max(idx32 + 1),
+            None => idx32..idx32 + 1,
+        });
+        if let Some(ref mut writers) = self.writers {
+            writers[idx] = Some(pc);
+        }
+        if self.watchpoints.iter().any(|range| range.contains(&(idx as u32))) {
+            self.hits.push(WriteHit { address: idx as u16, value, pc });
+        }
+    }
+
+    // Starts (or stops) remembering which instruction last wrote each byte. Costs a shadow
+    // entry per byte, so it's off unless something asks for it.
+    pub fn track_writers(&mut self, enabled: bool) {
+        self.writers = match (enabled, self.writers.take()) {
+            (true, Some(writers)) => Some(writers),
+            (true, None) => Some(vec![None; self.data.len()]),
+            (false, _) => None,
+        };
+    }
+
+    // PC of the instruction that last wrote the byte, if writers are tracked and one did
+    pub fn last_writer(&self, address: u16) -> Option<u16> {
+        self.writers.as_ref()?.get(address as usize).copied().flatten()
+    }
+
+    pub fn watch_writes(&mut self, range: Range<u32>) {
+        self.watchpoints.push(range);
+    }
+
+    // Smallest range covering every store since the last call
+    pub fn take_written(&mut self) -> Option<Range<u32>> {
+        self.written.take()
+    }
+
+    // Watchpoint hits since the last call, oldest first
+    pub fn take_hits(&mut self) -> Vec<WriteHit> {
+        std::mem::take(&mut self.hits)
+    }
+
+    // Big-endian word, as instructions are stored
+    pub fn read_word(&mut self, address: u16) -> u16 {
+        (self.read(address as u32) as u16) << 8 | self.read(address as u32 + 1) as u16
+    }
+
+    // Address of the first access that ran past the end since the last call
+    pub fn take_fault(&mut self) -> Option<u32> {
+        self.fault.take()
+    }
+}
+
+impl Deref for MemoryBus {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl DerefMut for MemoryBus {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_inside_memory() {
+        let mut memory = MemoryBus::new(0x1000, MemoryWrap::Trap);
+        memory[0x200] = 0x12;
+        memory[0x201] = 0x34;
+        assert_eq!(memory.read_word(0x200), 0x1234);
+        assert_eq!(memory.take_fault(), None);
+    }
+
+    #[test]
+    fn word_at_the_last_byte_wraps_to_address_zero() {
+        let mut memory = MemoryBus::new(0x1000, MemoryWrap::Wrap);
+        memory[0xFFF] = 0xAB;
+        memory[0x000] = 0xCD;
+        assert_eq!(memory.read_word(0xFFF), 0xABCD);
+        assert_eq!(memory.take_fault(), None);
+    }
+
+    #[test]
+    fn addresses_past_the_end_wrap_modulo_size() {
+        let mut memory = MemoryBus::new(0x1000, MemoryWrap::Wrap);
+        memory[0x010] = 0x60;
+        memory[0x011] = 0x07;
+        assert_eq!(memory.read_word(0x1010), 0x6007);
+    }
+
+    #[test]
+    fn word_at_the_last_byte_traps() {
+        let mut memory = MemoryBus::new(0x1000, MemoryWrap::Trap);
+        memory[0xFFF] = 0xAB;
+        assert_eq!(memory.read_word(0xFFF), 0xAB00);
+        assert_eq!(memory.take_fault(), Some(0x1000));
+        assert_eq!(memory.take_fault(), None);
+    }
+
+    #[test]
+    fn trapped_reads_see_the_open_bus() {
+        let mut memory = MemoryBus::new(0x1000, MemoryWrap::Trap);
+        memory.init = MemoryInit::OpenBus;
+        memory.initialize(0xF00..0x1000);
+        assert_eq!(memory[0xF80], 0x0F);
+        assert_eq!(memory.read_word(0x1234), 0x1212);
+    }
+
+    #[test]
+    fn random_init_repeats_with_its_seed() {
+        let init = MemoryInit::parse("random:42").unwrap();
+        assert_eq!(init, MemoryInit::Random(42));
+        let bytes: Vec<u8> = (0..16).map(|address| init.byte(address)).collect();
+        assert_eq!(bytes, (0..16).map(|address| init.byte(address)).collect::<Vec<u8>>());
+        assert!(bytes.iter().any(|&byte| byte != bytes[0]));
+        assert_eq!(MemoryInit::parse("0xFF"), Ok(MemoryInit::Fill(0xFF)));
+    }
+
+    #[test]
+    fn full_address_space_wraps_at_ffff() {
+        let mut memory = MemoryBus::new(0x10000, MemoryWrap::Wrap);
+        memory[0xFFFF] = 0x00;
+        memory[0x0000] = 0xE0;
+        assert_eq!(memory.read_word(0xFFFF), 0x00E0);
+    }
+
+    #[test]
+    fn writes_inside_a_watchpoint_are_reported() {
+        let mut memory = MemoryBus::new(0x1000, MemoryWrap::Wrap);
+        memory.watch_writes(0x3F0..0x3F4);
+        memory.write(0x3EF, 1, 0x200);
+        memory.write(0x3F2, 2, 0x202);
+        memory.write(0x3F4, 3, 0x204);
+        assert_eq!(memory.take_hits(), vec![WriteHit { address: 0x3F2, value: 2, pc: 0x202 }]);
+        assert!(memory.take_hits().is_empty());
+    }
+
+    #[test]
+    fn last_writer_is_only_kept_when_tracked() {
+        let mut memory = MemoryBus::new(0x1000, MemoryWrap::Wrap);
+        memory.write(0x300, 1, 0x200);
+        assert_eq!(memory.last_writer(0x300), None);
+
+        memory.track_writers(true);
+        memory.write(0x300, 1, 0x200);
+        memory.write(0x300, 2, 0x2A0);
+        assert_eq!(memory.last_writer(0x300), Some(0x2A0));
+        assert_eq!(memory.last_writer(0x301), None);
+        assert_eq!(memory[0x300], 2);
+    }
+}
@@ -0,0 +1,101 @@
+use std::io;
+use std::path::Path;
+
+// File extensions listed by the menu; other files in the directory are left out
+pub const ROM_EXTENSIONS: [&str; 5] = ["ch8", "c8", "sc8", "xo8", "c8b"];
+
+// A directory of ROMs to pick from on the emulated screen, drawn with splash::render_text
+pub struct RomMenu {
+    pub dir: String,
+    pub roms: Vec<String>, // paths, sorted by file name
+    pub selected: usize,
+}
+
+impl RomMenu {
+    // Rows of the 128x64 hires text screen: a header, a blank line, then the entries
+    const VISIBLE: usize = 8;
+    const COLUMNS: usize = 30; // 32 columns less the selection marker
+
+    // Lists the ROMs in a directory, not descending into subdirectories
+    pub fn scan(dir: &str) -> io::Result<RomMenu> {
+        let mut roms: Vec<String> = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let extension = path.extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+            if path.is_file() && extension.is_some_and(|extension| ROM_EXTENSIONS.contains(&extension.as_str())) {
+                roms.push(path.to_string_lossy().into_owned());
+            }
+        }
+        roms.sort_by_key(|path| name(path).to_ascii_lowercase());
+
+        Ok(RomMenu {
+            dir: dir.to_string(),
+            roms,
+            selected: 0,
+        })
+    }
+
+    // Moves the selection, wrapping around at either end
+    pub fn up(&mut self) {
+        if !self.roms.is_empty() {
+            self.selected = (self.selected + self.roms.len() - 1) % self.roms.len();
+        }
+    }
+
+    pub fn down(&mut self) {
+        if !self.roms.is_empty() {
+            self.selected = (self.selected + 1) % self.roms.len();
+        }
+    }
+
+    pub fn selected_path(&self) -> Option<&str> {
+        self.roms.get(self.selected).map(String::as_str)
+    }
+
+    // The page of entries around the selection, which is marked with >
+    pub fn lines(&self) -> Vec<String> {
+        if self.roms.is_empty() {
+            return vec!["NO ROMS IN".to_string(), self.dir.clone()];
+        }
+
+        let mut lines = vec![format!("ROMS {}/{}", self.selected + 1, self.roms.len()), String::new()];
+        let first = self.selected.saturating_sub(RomMenu::VISIBLE - 1);
+        for (idx, path) in self.roms.iter().enumerate().skip(first).take(RomMenu::VISIBLE) {
+            let marker = if idx == self.selected { "> " } else { "  " };
+            lines.push(format!("{}{}", marker, name(path).chars().take(RomMenu::COLUMNS).collect::<String>()));
+        }
+        lines
+    }
+}
+
+fn name(path: &str) -> String {
+    Path::new(path).file_name().map_or(path.to_string(), |name| name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu(count: usize) -> RomMenu {
+        RomMenu {
+            dir: "roms".to_string(),
+            roms: (0..count).map(|idx| format!("roms/{:02}.ch8", idx)).collect(),
+            selected: 0,
+        }
+    }
+
+    #[test]
+    fn selection_wraps_and_the_page_follows_it() {
+        let mut menu = menu(12);
+        menu.up();
+        assert_eq!(menu.selected_path(), Some("roms/11.ch8"));
+
+        let lines = menu.lines();
+        assert_eq!(lines[0], "ROMS 12/12");
+        assert_eq!(lines.len(), 2 + RomMenu::VISIBLE);
+        assert_eq!(lines.last().unwrap(), "> 11.ch8");
+
+        menu.down();
+        assert_eq!(menu.lines()[2], "> 00.ch8");
+    }
+}
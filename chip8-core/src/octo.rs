@@ -0,0 +1,244 @@
+use crate::cpu::Quirks;
+use crate::gif;
+use crate::json;
+use crate::palette::{format_color, parse_color, Palette};
+use crate::toml::{Table, Value};
+use std::io::{self, Error, ErrorKind};
+
+// Octocarts are the GIF cartridges Octo shares programs in. The low nybble of each pixel's
+// color index carries a payload, two pixels to a byte (high nybble first) across every frame:
+// a 32-bit big-endian length, then that much JSON, {"options": {...}, "program": "source"}.
+// The program is Octo source, so only carts whose source is plain bytes after `: main`, as
+// export_cart writes, run without compiling them in Octo first.
+const CART_WIDTH: usize = 128;
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+// The subset of Octo's per-program options this emulator understands
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OctoOptions {
+    pub tickrate: Option<u32>, // instructions per frame
+    pub shift_quirks: Option<bool>,
+    pub load_store_quirks: Option<bool>,
+    pub jump_quirks: Option<bool>,
+    pub logic_quirks: Option<bool>,
+    pub vblank_quirks: Option<bool>,
+    pub background_color: Option<u32>,
+    pub fill_color: Option<u32>,
+    pub fill_color2: Option<u32>,
+    pub blend_color: Option<u32>,
+}
+
+impl OctoOptions {
+    // Reads a cart's options object; keys this emulator has no use for are ignored
+    pub fn from_json(options: &Table) -> OctoOptions {
+        let flag = |key: &str| options.get(key).and_then(Value::as_bool);
+        let color = |key: &str| options.get(key).and_then(Value::as_str).and_then(parse_color);
+
+        OctoOptions {
+            tickrate: options.get("tickrate").and_then(Value::as_int).and_then(|rate| u32::try_from(rate).ok()).filter(|&rate| rate > 0),
+            shift_quirks: flag("shiftQuirks"),
+            load_store_quirks: flag("loadStoreQuirks"),
+            jump_quirks: flag("jumpQuirks"),
+            logic_quirks: flag("logicQuirks"),
+            vblank_quirks: flag("vBlankQuirks"),
+            background_color: color("backgroundColor"),
+            fill_color: color("fillColor"),
+            fill_color2: color("fillColor2"),
+            blend_color: color("blendColor"),
+        }
+    }
+
+    // The options that run a program the way it runs here: quirks, speed (instructions per
+    // second) and colors
+    pub fn from_settings(quirks: &Quirks, speed: u32, palette: Option<&Palette>) -> OctoOptions {
+        let colors = palette.map(|palette| palette.colors);
+        OctoOptions {
+            tickrate: Some((speed / 60).max(1)),
+            shift_quirks: Some(!quirks.cosmac_shift),
+            load_store_quirks: Some(!quirks.cosmac_fx55),
+            jump_quirks: Some(!quirks.cosmac_bnnn),
+            logic_quirks: Some(quirks.vf_reset),
+            vblank_quirks: Some(quirks.display_wait),
+            background_color: colors.map(|colors| colors[0]),
+            fill_color: colors.map(|colors| colors[1]),
+            fill_color2: colors.map(|colors| colors[2]),
+            blend_color: colors.map(|colors| colors[3]),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let mut members = Vec::new();
+        if let Some(rate) = self.tickrate {
+            members.push(format!("\"tickrate\":{}", rate));
+        }
+        let flags = [
+            ("shiftQuirks", self.shift_quirks),
+            ("loadStoreQuirks", self.load_store_quirks),
+            ("jumpQuirks", self.jump_quirks),
+            ("logicQuirks", self.logic_quirks),
+            ("vBlankQuirks", self.vblank_quirks),
+        ];
+        for (key, flag) in flags {
+            if let Some(flag) = flag {
+                members.push(format!("\"{}\":{}", key, flag));
+            }
+        }
+        let colors = [
+            ("backgroundColor", self.background_color),
+            ("fillColor", self.fill_color),
+            ("fillColor2", self.fill_color2),
+            ("blendColor", self.blend_color),
+        ];
+        for (key, color) in colors {
+            if let Some(color) = color {
+                members.push(format!("\"{}\":{}", key, json::quote(&format_color(color))));
+            }
+        }
+        format!("{{{}}}", members.join(","))
+    }
+
+    // Octo's quirk flags enable the non-COSMAC behavior, so most map inverted onto ours
+    pub fn apply_quirks(&self, quirks: &mut Quirks) {
+        if let Some(shift) = self.shift_quirks {
+            quirks.cosmac_shift = !shift;
+        }
+        if let Some(load_store) = self.load_store_quirks {
+            quirks.cosmac_fx55 = !load_store;
+            quirks.cosmac_fx65 = !load_store;
+        }
+        if let Some(jump) = self.jump_quirks {
+            quirks.cosmac_bnnn = !jump;
+        }
+        if let Some(logic) = self.logic_quirks {
+            quirks.vf_reset = logic;
+        }
+        if let Some(vblank) = self.vblank_quirks {
+            quirks.display_wait = vblank;
+        }
+    }
+
+    // Instructions per second for the cart's tickrate
+    pub fn speed(&self) -> Option<u32> {
+        self.tickrate.map(|rate| rate.saturating_mul(60))
+    }
+
+    // The cart's colors, when it sets at least the background and fill
+    pub fn palette(&self) -> Option<Palette> {
+        let (background, fill) = (self.background_color?, self.fill_color?);
+        let fill2 = self.fill_color2.unwrap_or(fill);
+        Some(Palette::new("octocart", [background, fill, fill2, self.blend_color.unwrap_or(fill2)]))
+    }
+}
+
+pub struct Octocart {
+    pub rom: Vec<u8>,
+    pub options: OctoOptions,
+}
+
+pub fn is_octocart(data: &[u8]) -> bool {
+    data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")
+}
+
+// Reads the program and options out of an Octocart
+pub fn parse_cart(data: &[u8]) -> io::Result<Octocart> {
+    let nybbles: Vec<u8> = gif::decode_frames(data)?.concat();
+    let bytes: Vec<u8> = nybbles.chunks_exact(2).map(|pair| (pair[0] & 0xF) << 4 | (pair[1] & 0xF)).collect();
+    let len = bytes.get(..4).map(|len| u32::from_be_bytes(len.try_into().unwrap()) as usize).ok_or_else(|| invalid("Octocart has no payload"))?;
+    let payload = bytes.get(4..4 + len).ok_or_else(|| invalid("Octocart payload is cut short"))?;
+    let text = std::str::from_utf8(payload).map_err(|_| invalid("Octocart payload isn't text"))?;
+
+    let json = json::parse(text)?;
+    let cart = json.as_table().ok_or_else(|| invalid("Octocart payload isn't an object"))?;
+    let source = cart.get("program").and_then(Value::as_str).ok_or_else(|| invalid("Octocart has no program"))?;
+    let options = match cart.get("options") {
+        Some(options) => OctoOptions::from_json(options.as_table().ok_or_else(|| invalid("Octocart options aren't an object"))?),
+        None => OctoOptions::default(),
+    };
+    Ok(Octocart { rom: assemble_bytes(source)?, options })
+}
+
+// An Octocart holding the ROM as Octo source, one byte literal at a time, so Octo opens and
+// runs it too
+pub fn export_cart(rom: &[u8], options: &OctoOptions) -> Vec<u8> {
+    let mut source = String::from(": main\n");
+    for line in rom.chunks(16) {
+        let bytes: Vec<String> = line.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+        source.push_str(&bytes.join(" "));
+        source.push('\n');
+    }
+    let payload = format!("{{\"options\":{},\"program\":{}}}", options.to_json(), json::quote(&source));
+
+    let mut bytes = (payload.len() as u32).to_be_bytes().to_vec();
+    bytes.extend(payload.as_bytes());
+    let mut pixels: Vec<u8> = bytes.iter().flat_map(|byte| [byte >> 4, byte & 0xF]).collect();
+    let height = pixels.len().div_ceil(CART_WIDTH);
+    pixels.resize(height * CART_WIDTH, 0);
+
+    let palette: Vec<u32> = (0..16).map(|shade| shade * 0x111111).collect();
+    gif::encode(CART_WIDTH as u16, height as u16, &palette, &pixels)
+}
+
+// The bytes of Octo source that's nothing but `: main` and byte literals (decimal, 0x hex or
+// 0b binary, with # comments). Anything else needs Octo's compiler.
+fn assemble_bytes(source: &str) -> io::Result<Vec<u8>> {
+    let mut rom = Vec::new();
+    let mut tokens = source.lines().flat_map(|line| line.split('#').next().unwrap_or("").split_whitespace());
+    while let Some(token) = tokens.next() {
+        if token == ":" && rom.is_empty() && tokens.next() == Some("main") {
+            continue;
+        }
+        let value = if let Some(hex) = token.strip_prefix("0x") {
+            i64::from_str_radix(hex, 16).ok()
+        } else if let Some(binary) = token.strip_prefix("0b") {
+            i64::from_str_radix(binary, 2).ok()
+        } else {
+            token.parse::<i64>().ok()
+        };
+        match value {
+            Some(value @ -128..=255) => rom.push(value as u8),
+            _ => return Err(invalid(&format!("Octocart holds Octo source (\"{}\"), which needs to be compiled with Octo first", token))),
+        }
+    }
+    Ok(rom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carts_round_trip_the_rom_and_options() {
+        let rom: Vec<u8> = (0..=255).chain([0x00, 0xE0, 0x12, 0x00]).collect();
+        let quirks = Quirks::preset("chip8").unwrap();
+        let palette = Palette::new("amber", [0x1A0F00, 0xFFB000, 0xB36B00, 0x664000]);
+        let options = OctoOptions::from_settings(&quirks, 900, Some(&palette));
+
+        let cart = parse_cart(&export_cart(&rom, &options)).unwrap();
+        assert_eq!(cart.rom, rom);
+        assert_eq!(cart.options, options);
+        assert_eq!((cart.options.speed(), cart.options.palette()), (Some(900), Some(Palette::new("octocart", palette.colors))));
+
+        let mut applied = Quirks::preset("xochip").unwrap();
+        cart.options.apply_quirks(&mut applied);
+        assert_eq!((applied.cosmac_shift, applied.cosmac_fx55, applied.cosmac_bnnn, applied.vf_reset), (true, true, true, true));
+    }
+
+    #[test]
+    fn options_come_from_octos_json() {
+        let json = json::parse(r##"{"tickrate": 20, "shiftQuirks": true, "jumpQuirks": false, "fillColor": "#FFCC00", "backgroundColor": "#996600", "screenRotation": 0}"##).unwrap();
+        let options = OctoOptions::from_json(json.as_table().unwrap());
+        assert_eq!((options.speed(), options.shift_quirks, options.jump_quirks, options.load_store_quirks), (Some(1200), Some(true), Some(false), None));
+        assert_eq!(options.palette().unwrap().colors, [0x996600, 0xFFCC00, 0xFFCC00, 0xFFCC00]);
+    }
+
+    #[test]
+    fn only_byte_literal_source_assembles() {
+        assert_eq!(assemble_bytes(": main # start\n0xA2 0b1010 255 -1\n").unwrap(), [0xA2, 0x0A, 0xFF, 0xFF]);
+        let e = assemble_bytes(": main\n  v0 := 5\n").unwrap_err();
+        assert!(e.to_string().contains("\"v0\""));
+        assert!(assemble_bytes("0x100").is_err());
+    }
+}
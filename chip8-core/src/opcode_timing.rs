@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Host time spent executing each class of instruction over a session, to see where the
+// interpreter's time goes on a real ROM before optimizing it
+#[derive(Default)]
+pub struct OpcodeTiming {
+    classes: HashMap<&'static str, (u64, Duration)>, // class -> instructions, time spent
+}
+
+// The class an instruction is timed under: what the interpreter does for it rather than
+// exactly which opcode it is
+pub fn class(instruction: u16) -> &'static str {
+    match instruction >> 12 {
+        0x0 => match instruction {
+            0x00E0 => "clear",
+            0x00EE => "return",
+            0x00FB | 0x00FC => "scroll",
+            0x00FE | 0x00FF | 0x0010 | 0x0011 => "display mode",
+            _ if instruction & 0xFF00 == 0 && matches!(instruction & 0xF0, 0xB0 | 0xC0 | 0xD0) => "scroll",
+            _ => "system",
+        },
+        0x1 | 0xB => "jump",
+        0x2 => "call",
+        0x3 | 0x4 | 0x5 | 0x9 | 0xE => "skip",
+        0x6 | 0x7 | 0xA => "load",
+        0x8 => "alu",
+        0xC => "random",
+        0xD => "draw",
+        _ => match instruction & 0xFF {
+            0x07 | 0x15 | 0x18 => "timers",
+            0x0A => "key wait",
+            0x02 | 0x3A => "audio",
+            0x01 => "display mode",
+            0x00 => "load",
+            _ => "memory",
+        },
+    }
+}
+
+impl OpcodeTiming {
+    pub fn new() -> OpcodeTiming {
+        OpcodeTiming::default()
+    }
+
+    pub fn record(&mut self, instruction: u16, elapsed: Duration) {
+        let entry = self.classes.entry(class(instruction)).or_insert((0, Duration::ZERO));
+        entry.0 += 1;
+        entry.1 += elapsed;
+    }
+
+    // Classes by total time, most first
+    pub fn sorted(&self) -> Vec<(&'static str, u64, Duration)> {
+        let mut classes: Vec<_> = self.classes.iter().map(|(&class, &(count, time))| (class, count, time)).collect();
+        classes.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(b.0)));
+        classes
+    }
+
+    pub fn report(&self) -> String {
+        let total: Duration = self.classes.values().map(|&(_, time)| time).sum();
+        let mut out = format!("{:.1} ms executing instructions\n", total.as_secs_f64() * 1000.0);
+        out += "  class          instructions    total ms  ns/instr  share\n";
+        for (class, count, time) in self.sorted() {
+            out += &format!(
+                "  {:<13} {:>13} {:>11.2} {:>9.0} {:>5.1}%\n",
+                class,
+                count,
+                time.as_secs_f64() * 1000.0,
+                time.as_nanos() as f64 / count as f64,
+                time.as_secs_f64() * 100.0 / total.as_secs_f64().max(f64::MIN_POSITIVE)
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classes_sort_by_total_time() {
+        assert_eq!(class(0xD015), "draw");
+        assert_eq!(class(0x00C4), "scroll");
+        assert_eq!(class(0x8124), "alu");
+        assert_eq!(class(0xF265), "memory");
+
+        let mut timing = OpcodeTiming::new();
+        for _ in 0..100 {
+            timing.record(0x8124, Duration::from_nanos(10));
+        }
+        timing.record(0xD015, Duration::from_micros(5));
+        timing.record(0xD125, Duration::from_micros(5));
+        let sorted = timing.sorted();
+        assert_eq!(sorted[0], ("draw", 2, Duration::from_micros(10)));
+        assert_eq!(sorted[1], ("alu", 100, Duration::from_micros(1)));
+        assert!(timing.report().contains("draw"));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::audio::Audio;
+
+// Text pages drawn over the emulated screen by a frontend, e.g. the "About this ROM" page.
+// Glyphs are 3x5 pixels, one row per byte in the low 3 bits; lowercase prints as uppercase.
+pub struct Overlay {
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+pub const GLYPH_WIDTH: u16 = 3;
+pub const GLYPH_HEIGHT: u16 = 5;
+
+impl Overlay {
+    pub fn new(title: &str, lines: Vec<String>) -> Overlay {
+        Overlay {
+            title: title.to_string(),
+            lines,
+        }
+    }
+
+    fn rows(&self) -> Vec<String> {
+        let mut rows = vec![self.title.clone(), String::new()];
+        rows.extend(self.lines.iter().cloned());
+        rows
+    }
+
+    // Lit pixels of the page, title first and then a blank line
+    pub fn pixels(&self) -> Vec<(u16, u16)> {
+        text_pixels(&self.rows())
+    }
+
+    // Size of the page in overlay pixels
+    pub fn size(&self) -> (u16, u16) {
+        text_size(&self.rows())
+    }
+}
+
+// The audio panel for music ROMs: the sound timer as a number and a bar, then for an XO-CHIP
+// pattern its 128 bits drawn as a square wave and the pitch on a slider. The CPU refreshes it
+// every frame from the timer and the audio state it hands the backend.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioScope {
+    pub sound_timer: u8,
+    pub audio: Audio,
+}
+
+const SCOPE_WIDTH: u16 = Audio::BITS as u16; // one pixel per pattern bit
+const WAVE_HEIGHT: u16 = 5;
+
+impl AudioScope {
+    // Text, then a bar as long as the timer (full at 128 ticks, two seconds)
+    fn timer_rows(&self) -> Vec<(u16, u16)> {
+        let mut pixels = text_pixels(&[format!("SOUND {:3}", self.sound_timer)]);
+        let bar = (self.sound_timer as u16).min(SCOPE_WIDTH);
+        pixels.extend((0..bar).flat_map(|x| (7..9).map(move |y| (x, y))));
+        pixels
+    }
+
+    pub fn pixels(&self) -> Vec<(u16, u16)> {
+        let mut pixels = self.timer_rows();
+        if self.audio.pattern.is_none() {
+            return pixels;
+        }
+
+        let text = format!("PITCH {:3} {:5.0}HZ", self.audio.pitch, self.audio.rate());
+        pixels.extend(text_pixels(&[text]).into_iter().map(|(x, y)| (x, y + 12)));
+
+        // High bits along the top, low along the bottom, joined where the level changes
+        let wave_top = 19;
+        for x in 0..SCOPE_WIDTH {
+            let bit = self.audio.bit(x as usize);
+            pixels.push((x, if bit { wave_top } else { wave_top + WAVE_HEIGHT - 1 }));
+            if x > 0 && bit != self.audio.bit(x as usize - 1) {
+                pixels.extend((wave_top + 1..wave_top + WAVE_HEIGHT - 1).map(|y| (x, y)));
+            }
+        }
+
+        // Pitch 0-255 on a track the width of the panel
+        let slider = wave_top + WAVE_HEIGHT + 3;
+        pixels.extend((0..SCOPE_WIDTH).map(|x| (x, slider + 1)));
+        let knob = self.audio.pitch as u16 * (SCOPE_WIDTH - 1) / 255;
+        pixels.extend((slider..slider + 3).map(|y| (knob, y)));
+        pixels
+    }
+
+    // Size of the panel in overlay pixels
+    pub fn size(&self) -> (u16, u16) {
+        match self.audio.pattern {
+            Some(_) => (SCOPE_WIDTH, 30),
+            None => (SCOPE_WIDTH, 9),
+        }
+    }
+}
+
+// Lit pixels of lines of text as (x, y), each character cell 4x6 so glyphs get a pixel of spacing
+pub fn text_pixels(lines: &[String]) -> Vec<(u16, u16)> {
+    let mut pixels: Vec<(u16, u16)> = Vec::new();
+
+    for (row, line) in lines.iter().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            for (y, bits) in glyph(c).iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if bits & (0b100 >> x) != 0 {
+                        pixels.push((column as u16 * (GLYPH_WIDTH + 1) + x, row as u16 * (GLYPH_HEIGHT + 1) + y as u16));
+                    }
+                }
+            }
+        }
+    }
+
+    pixels
+}
+
+pub fn text_size(lines: &[String]) -> (u16, u16) {
+    let columns = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    (columns as u16 * (GLYPH_WIDTH + 1), lines.len() as u16 * (GLYPH_HEIGHT + 1))
+}
+
+pub fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '[' => [0b011, 0b010, 0b010, 0b010, 0b011],
+        ']' => [0b110, 0b010, 0b010, 0b010, 0b110],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '?' => [0b110, 0b001, 0b010, 0b000, 0b010],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '"' => [0b101, 0b101, 0b000, 0b000, 0b000],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        ' ' => [0; 5],
+        _ => [0b111; 5], // unknown: solid block
+    }
+}
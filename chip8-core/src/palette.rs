@@ -0,0 +1,69 @@
+use crate::toml::{Table, Value};
+
+// Display colors indexed by plane bits: 0 = background, 1 = plane 1, 2 = plane 2, 3 = both planes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    pub colors: [u32; 4], // 0xRRGGBB
+}
+
+impl Palette {
+    pub fn new(name: &str, colors: [u32; 4]) -> Palette {
+        Palette {
+            name: name.to_string(),
+            colors,
+        }
+    }
+
+    // The palette a config asks for: custom `colors` (background, plane 1, plane 2, both) or
+    // a builtin by `palette` name. None if it sets neither.
+    pub fn from_config(config: &Table) -> Result<Option<Palette>, String> {
+        if let Some(values) = config.get("colors") {
+            let values = values.as_array().filter(|values| (2..=4).contains(&values.len())).ok_or("colors should list 2 to 4 colors")?;
+            let mut colors = [0; 4];
+            for (idx, value) in values.iter().enumerate() {
+                colors[idx] = value.as_str().and_then(parse_color).ok_or("colors should be \"#RRGGBB\" strings")?;
+            }
+            return Ok(Some(Palette::new("custom", colors)));
+        }
+
+        match config.get("palette").map(Value::as_str) {
+            Some(Some(name)) => builtin_palettes()
+                .into_iter()
+                .find(|palette| palette.name == name)
+                .map(Some)
+                .ok_or_else(|| format!("unknown palette {}", name)),
+            Some(None) => Err("palette should be a palette name".to_string()),
+            None => Ok(None),
+        }
+    }
+
+    // Split a color into its red, green and blue channels
+    pub fn rgb(&self, idx: usize) -> (u8, u8, u8) {
+        let color = self.colors[idx & 3];
+        ((color >> 16) as u8, (color >> 8) as u8, color as u8)
+    }
+}
+
+pub fn builtin_palettes() -> Vec<Palette> {
+    vec![
+        Palette::new("classic", [0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555]),
+        Palette::new("octo", [0x996600, 0xFFCC00, 0xFF6600, 0x662200]),
+        Palette::new("amber", [0x1A0F00, 0xFFB000, 0xB36B00, 0x664000]),
+        Palette::new("phosphor", [0x001A00, 0x33FF66, 0x1FA33F, 0x0F5C22]),
+        Palette::new("gameboy", [0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F]),
+    ]
+}
+
+// Parses "#RRGGBB" or "0xRRGGBB"
+pub fn parse_color(text: &str) -> Option<u32> {
+    let hex = text.trim_start_matches('#').trim_start_matches("0x");
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
+
+pub fn format_color(color: u32) -> String {
+    format!("#{:06X}", color & 0xFFFFFF)
+}
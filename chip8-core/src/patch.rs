@@ -0,0 +1,328 @@
+use std::io::{self, Error, ErrorKind};
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+// Standard CRC-32 (IEEE), used by BPS to validate source, target and patch
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFFFFFF;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+// Applies an IPS or BPS patch to a ROM, detecting the format from its header
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    if patch.starts_with(b"PATCH") {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(b"BPS1") {
+        apply_bps(rom, patch)
+    } else {
+        Err(invalid("unknown patch format (expected IPS or BPS header)"))
+    }
+}
+
+// Reads the ROM and patch files and returns the patched ROM
+pub fn apply_patch_file(rom_path: &str, patch_path: &str) -> io::Result<Vec<u8>> {
+    let rom = std::fs::read(rom_path)?;
+    let patch = std::fs::read(patch_path)?;
+    apply_patch(&rom, &patch)
+}
+
+// IPS: "PATCH", then records of offset (3 bytes) + size (2 bytes) + data, until "EOF"
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    if !patch.starts_with(b"PATCH") {
+        return Err(invalid("missing IPS header"));
+    }
+
+    let mut output = rom.to_vec();
+    let mut idx = 5;
+
+    loop {
+        if patch.len() < idx + 3 {
+            return Err(invalid("IPS patch ends without EOF marker"));
+        }
+
+        if &patch[idx..idx + 3] == b"EOF" {
+            idx += 3;
+            break;
+        }
+
+        if patch.len() < idx + 5 {
+            return Err(invalid("truncated IPS record header"));
+        }
+
+        let offset = (patch[idx] as usize) << 16 | (patch[idx + 1] as usize) << 8 | patch[idx + 2] as usize;
+        let size = (patch[idx + 3] as usize) << 8 | patch[idx + 4] as usize;
+        idx += 5;
+
+        if size == 0 {
+            // RLE record: run length (2 bytes) + fill byte
+            if patch.len() < idx + 3 {
+                return Err(invalid("truncated IPS RLE record"));
+            }
+
+            let length = (patch[idx] as usize) << 8 | patch[idx + 1] as usize;
+            let value = patch[idx + 2];
+            idx += 3;
+
+            if output.len() < offset + length {
+                output.resize(offset + length, 0);
+            }
+            output[offset..offset + length].fill(value);
+        } else {
+            if patch.len() < idx + size {
+                return Err(invalid("truncated IPS record data"));
+            }
+
+            if output.len() < offset + size {
+                output.resize(offset + size, 0);
+            }
+            output[offset..offset + size].copy_from_slice(&patch[idx..idx + size]);
+            idx += size;
+        }
+    }
+
+    // Optional truncation extension
+    if patch.len() == idx + 3 {
+        let length = (patch[idx] as usize) << 16 | (patch[idx + 1] as usize) << 8 | patch[idx + 2] as usize;
+        output.truncate(length);
+    } else if patch.len() != idx {
+        return Err(invalid("trailing data after IPS EOF marker"));
+    }
+
+    Ok(output)
+}
+
+// Reads a BPS variable-length number
+fn read_varint(patch: &[u8], idx: &mut usize) -> io::Result<u64> {
+    let mut data: u64 = 0;
+    let mut shift: u64 = 1;
+
+    loop {
+        let Some(&byte) = patch.get(*idx) else {
+            return Err(invalid("truncated BPS number"));
+        };
+        *idx += 1;
+
+        data = data
+            .checked_add((byte & 0x7F) as u64 * shift)
+            .ok_or_else(|| invalid("BPS number overflow"))?;
+        if byte & 0x80 != 0 {
+            break;
+        }
+
+        shift = shift.checked_shl(7).ok_or_else(|| invalid("BPS number overflow"))?;
+        data += shift;
+    }
+
+    Ok(data)
+}
+
+// Reads a BPS signed offset and applies it to a relative position
+fn read_relative(patch: &[u8], idx: &mut usize, position: usize) -> io::Result<usize> {
+    let data = read_varint(patch, idx)?;
+    let delta = (data >> 1) as usize;
+
+    let moved = if data & 1 == 1 { position.checked_sub(delta) } else { position.checked_add(delta) };
+    moved.ok_or_else(|| invalid("BPS relative offset out of range"))
+}
+
+fn read_crc(patch: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([patch[at], patch[at + 1], patch[at + 2], patch[at + 3]])
+}
+
+// BPS: "BPS1", sizes, metadata, copy/read actions, then source/target/patch CRC-32s
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    if !patch.starts_with(b"BPS1") {
+        return Err(invalid("missing BPS header"));
+    }
+
+    if patch.len() < 4 + 12 {
+        return Err(invalid("BPS patch too short"));
+    }
+
+    let footer = patch.len() - 12;
+    if crc32(&patch[..footer + 8]) != read_crc(patch, footer + 8) {
+        return Err(invalid("BPS patch checksum mismatch (corrupted patch)"));
+    }
+
+    if crc32(rom) != read_crc(patch, footer) {
+        return Err(invalid("BPS source checksum mismatch (patch is for a different ROM)"));
+    }
+
+    let mut idx = 4;
+    let source_size = read_varint(patch, &mut idx)? as usize;
+    let target_size = read_varint(patch, &mut idx)? as usize;
+    let metadata_size = read_varint(patch, &mut idx)? as usize;
+    idx += metadata_size;
+
+    if source_size != rom.len() {
+        return Err(invalid("BPS source size does not match ROM"));
+    }
+
+    let mut output: Vec<u8> = Vec::with_capacity(target_size);
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+
+    while idx < footer {
+        let data = read_varint(patch, &mut idx)?;
+        let command = data & 3;
+        let length = (data >> 2) as usize + 1;
+
+        if output.len() + length > target_size {
+            return Err(invalid("BPS action writes past target size"));
+        }
+
+        match command {
+            // SourceRead
+            0 => {
+                let start = output.len();
+                let bytes = rom.get(start..start + length).ok_or_else(|| invalid("BPS source read out of range"))?;
+                output.extend_from_slice(bytes);
+            }
+
+            // TargetRead
+            1 => {
+                if idx + length > footer {
+                    return Err(invalid("BPS target read out of range"));
+                }
+                output.extend_from_slice(&patch[idx..idx + length]);
+                idx += length;
+            }
+
+            // SourceCopy
+            2 => {
+                source_offset = read_relative(patch, &mut idx, source_offset)?;
+                let bytes = rom
+                    .get(source_offset..source_offset + length)
+                    .ok_or_else(|| invalid("BPS source copy out of range"))?;
+                output.extend_from_slice(bytes);
+                source_offset += length;
+            }
+
+            // TargetCopy, byte by byte since the ranges may overlap
+            _ => {
+                target_offset = read_relative(patch, &mut idx, target_offset)?;
+                for _ in 0..length {
+                    let byte = *output.get(target_offset).ok_or_else(|| invalid("BPS target copy out of range"))?;
+                    output.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if output.len() != target_size {
+        return Err(invalid("BPS output size does not match target size"));
+    }
+
+    if crc32(&output) != read_crc(patch, footer + 4) {
+        return Err(invalid("BPS target checksum mismatch"));
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_varint(out: &mut Vec<u8>, mut data: u64) {
+        loop {
+            let x = (data & 0x7F) as u8;
+            data >>= 7;
+            if data == 0 {
+                out.push(0x80 | x);
+                break;
+            }
+            out.push(x);
+            data -= 1;
+        }
+    }
+
+    fn finish_bps(source: &[u8], target: &[u8], mut patch: Vec<u8>) -> Vec<u8> {
+        patch.extend(crc32(source).to_le_bytes());
+        patch.extend(crc32(target).to_le_bytes());
+        let patch_crc = crc32(&patch);
+        patch.extend(patch_crc.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn crc32_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn ips_applies_records_and_rle() {
+        let rom = [0u8; 8];
+        let mut patch = b"PATCH".to_vec();
+        patch.extend([0, 0, 1, 0, 2, 0xAA, 0xBB]); // 2 bytes at offset 1
+        patch.extend([0, 0, 5, 0, 0, 0, 4, 0x11]); // RLE 4x 0x11 at offset 5, grows ROM
+        patch.extend(b"EOF");
+
+        let patched = apply_patch(&rom, &patch).unwrap();
+        assert_eq!(patched, vec![0, 0xAA, 0xBB, 0, 0, 0x11, 0x11, 0x11, 0x11]);
+    }
+
+    #[test]
+    fn ips_truncate_extension() {
+        let mut patch = b"PATCH".to_vec();
+        patch.extend(b"EOF");
+        patch.extend([0, 0, 2]);
+
+        assert_eq!(apply_patch(&[1, 2, 3, 4], &patch).unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn ips_rejects_malformed() {
+        assert!(apply_patch(&[0; 4], b"PATCH").is_err()); // no EOF
+        assert!(apply_patch(&[0; 4], b"PATCH\x00\x00\x01\x00\x04\xAA").is_err()); // truncated data
+        assert!(apply_patch(&[0; 4], b"PATCHEOFjunk").is_err());
+        assert!(apply_patch(&[0; 4], b"NOTAPATCH").is_err());
+    }
+
+    #[test]
+    fn bps_applies_all_actions() {
+        let source = [1u8, 2, 3, 4];
+        let target = [1u8, 2, 9, 9, 3, 4, 9, 9];
+
+        let mut patch = b"BPS1".to_vec();
+        write_varint(&mut patch, source.len() as u64);
+        write_varint(&mut patch, target.len() as u64);
+        write_varint(&mut patch, 0);
+        write_varint(&mut patch, 1 << 2); // SourceRead 2
+        write_varint(&mut patch, (1 << 2) | 1); // TargetRead 2
+        patch.extend([9, 9]);
+        write_varint(&mut patch, (1 << 2) | 2); // SourceCopy 2 from +2
+        write_varint(&mut patch, 2 << 1);
+        write_varint(&mut patch, (1 << 2) | 3); // TargetCopy 2 from +2
+        write_varint(&mut patch, 2 << 1);
+        let patch = finish_bps(&source, &target, patch);
+
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target.to_vec());
+    }
+
+    #[test]
+    fn bps_validates_checksums() {
+        let source = [1u8, 2, 3, 4];
+        let mut patch = b"BPS1".to_vec();
+        write_varint(&mut patch, 4);
+        write_varint(&mut patch, 4);
+        write_varint(&mut patch, 0);
+        write_varint(&mut patch, 3 << 2); // SourceRead 4
+        let mut patch = finish_bps(&source, &source, patch);
+
+        assert!(apply_patch(&[1, 2, 3, 5], &patch).is_err()); // wrong source ROM
+
+        let last = patch.len() - 1;
+        patch[last] ^= 0xFF;
+        assert!(apply_patch(&source, &patch).is_err()); // corrupted patch
+    }
+}
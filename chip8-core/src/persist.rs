@@ -0,0 +1,210 @@
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+const MAX_NAME_LEN: usize = 64;
+
+struct WriteJob {
+    path: String,
+    data: Vec<u8>,
+}
+
+// Writes serialized blobs (savestates, recordings...) to disk on a worker thread
+// so file I/O never blocks the emulation loop. Writes that fail are reported through
+// take_failures rather than printed.
+pub struct DiskWriter {
+    sender: Option<Sender<WriteJob>>,
+    worker: Option<JoinHandle<()>>,
+    failures: Receiver<String>,
+    failed: Sender<String>,
+}
+
+impl Default for DiskWriter {
+    fn default() -> DiskWriter {
+        DiskWriter::new()
+    }
+}
+
+impl DiskWriter {
+    pub fn new() -> DiskWriter {
+        let (sender, receiver) = channel::<WriteJob>();
+        let (failed, failures) = channel::<String>();
+
+        let worker_failed = failed.clone();
+        let worker = thread::spawn(move || {
+            for job in receiver {
+                if let Err(e) = write_atomically(&job.path, &job.data) {
+                    _ = worker_failed.send(format!("failed to write {}: {}", job.path, e));
+                }
+            }
+        });
+
+        DiskWriter {
+            sender: Some(sender),
+            worker: Some(worker),
+            failures,
+            failed,
+        }
+    }
+
+    // Queues a blob to be written to a path relative to dir, dropping it (with a failure)
+    // when the path would end up outside dir. For paths built from anything a ROM supplies,
+    // e.g. a bundle title, after passing that through sanitize_file_name.
+    pub fn write_in(&self, dir: &Path, relative: &str, data: Vec<u8>) {
+        match contained_path(dir, relative) {
+            Ok(path) => self.write(&path.to_string_lossy(), data),
+            Err(e) => _ = self.failed.send(format!("refusing to write {}: {}", relative, e)),
+        }
+    }
+
+    // Queues a blob to be written to path
+    pub fn write(&self, path: &str, data: Vec<u8>) {
+        if let Some(ref sender) = self.sender {
+            let job = WriteJob { path: path.to_string(), data };
+            if sender.send(job).is_err() {
+                _ = self.failed.send(format!("disk writer thread is gone, dropped write to {}", path));
+            }
+        }
+    }
+
+    // The writes that failed or were refused since the last call
+    pub fn take_failures(&self) -> Vec<String> {
+        self.failures.try_iter().collect()
+    }
+}
+
+impl Drop for DiskWriter {
+    // Flushes pending writes before exiting
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            _ = worker.join();
+        }
+    }
+}
+
+// Writes to a temporary file beside path and renames it over path, so a crash or full disk
+// mid-write leaves the old file rather than a truncated one
+fn write_atomically(path: &str, data: &[u8]) -> io::Result<()> {
+    let temp = format!("{}.tmp", path);
+    std::fs::write(&temp, data)?;
+    std::fs::rename(&temp, path).inspect_err(|_| _ = std::fs::remove_file(&temp))
+}
+
+// A file name made from untrusted text, e.g. a ROM bundle's title: only letters, digits,
+// '-', '_' and '.' are kept (anything else becomes '_'), leading dots are dropped so it can't
+// be "." or ".." or a hidden file, and it's cut to 64 characters. Never empty.
+pub fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .skip_while(|&c| c == '.')
+        .take(MAX_NAME_LEN)
+        .collect();
+
+    if cleaned.is_empty() { "_".to_string() } else { cleaned }
+}
+
+// The savestate file for one of the ten slots: named after the ROM and the slot, in the
+// directory of the configured savestate path, e.g. roms/brix.ch8's slot 3 with the default
+// savestate.sav is brix.3.sav. Without a ROM it's the configured name with the slot added.
+pub fn slot_path(savestate_path: &str, rom_path: Option<&str>, slot: u8) -> String {
+    let configured = Path::new(savestate_path);
+    let stem = rom_path.map(Path::new).unwrap_or(configured).file_stem().map_or("savestate".into(), |stem| stem.to_string_lossy());
+    let name = format!("{}.{}.sav", sanitize_file_name(&stem), slot);
+    configured.parent().unwrap_or(Path::new("")).join(name).to_string_lossy().into_owned()
+}
+
+// The autosave file, autosave.sav in the directory of the configured savestate path
+pub fn autosave_path(savestate_path: &str) -> String {
+    Path::new(savestate_path).parent().unwrap_or(Path::new("")).join("autosave.sav").to_string_lossy().into_owned()
+}
+
+// Joins a relative path onto dir. Absolute paths, drive or UNC prefixes and ".." components
+// are refused, so the result is always inside dir.
+pub fn contained_path(dir: &Path, relative: &str) -> io::Result<PathBuf> {
+    let refuse = |why: &str| Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is {}", relative, why)));
+    if relative.is_empty() {
+        return refuse("empty");
+    }
+    // Backslashes separate components on Windows, so they'd let "..\.." through elsewhere
+    if relative.contains('\\') || relative.contains('\0') {
+        return refuse("not a plain relative path");
+    }
+
+    let mut path = dir.to_path_buf();
+    for component in Path::new(relative).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => return refuse("outside the directory"),
+            Component::RootDir | Component::Prefix(_) => return refuse("an absolute path"),
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitized_names_stay_a_single_plain_file() {
+        assert_eq!(sanitize_file_name("Space Invaders (1978)"), "Space_Invaders__1978_");
+        assert_eq!(sanitize_file_name("../../etc/passwd"), "_.._etc_passwd");
+        assert_eq!(sanitize_file_name(".."), "_");
+        assert_eq!(sanitize_file_name(".hidden"), "hidden");
+        assert_eq!(sanitize_file_name("C:\\Windows"), "C__Windows");
+        assert_eq!(sanitize_file_name(""), "_");
+        assert_eq!(sanitize_file_name(&"x".repeat(300)).len(), MAX_NAME_LEN);
+    }
+
+    #[test]
+    fn slots_are_named_after_the_rom_beside_the_configured_savestate() {
+        assert_eq!(slot_path("savestate.sav", Some("roms/Brix (1990).ch8"), 3), "Brix__1990_.3.sav");
+        assert_eq!(slot_path("saves/state.sav", Some("pong.ch8"), 0), Path::new("saves").join("pong.0.sav").to_string_lossy());
+        assert_eq!(slot_path("saves/state.sav", None, 9), Path::new("saves").join("state.9.sav").to_string_lossy());
+    }
+
+    #[test]
+    fn autosaves_go_beside_the_configured_savestate_and_replace_the_old_file() {
+        assert_eq!(autosave_path("savestate.sav"), "autosave.sav");
+        assert_eq!(autosave_path("saves/state.sav"), Path::new("saves").join("autosave.sav").to_string_lossy());
+
+        let path = std::env::temp_dir().join("chip8-atomic-write.sav");
+        let path = path.to_string_lossy();
+        write_atomically(&path, b"first").unwrap();
+        write_atomically(&path, b"second").unwrap();
+        assert_eq!(std::fs::read(&*path).unwrap(), b"second");
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
+        _ = std::fs::remove_file(&*path);
+    }
+
+    #[test]
+    fn refused_and_failed_writes_come_back_as_failures() {
+        let writer = DiskWriter::new();
+        writer.write_in(Path::new("saves"), "../game.sav", vec![1]);
+        assert!(writer.take_failures()[0].starts_with("refusing to write ../game.sav"));
+
+        let path = std::env::temp_dir().join("chip8-no-such-dir").join("game.sav");
+        writer.write(&path.to_string_lossy(), vec![1]);
+        let failure = writer.failures.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        assert!(failure.starts_with("failed to write"));
+        assert!(writer.take_failures().is_empty());
+    }
+
+    #[test]
+    fn traversal_out_of_the_directory_is_refused() {
+        let dir = Path::new("saves");
+        assert_eq!(contained_path(dir, "game.sav").unwrap(), dir.join("game.sav"));
+        assert_eq!(contained_path(dir, "./slot/1.sav").unwrap(), dir.join("slot").join("1.sav"));
+
+        for path in ["../game.sav", "slot/../../game.sav", "/etc/passwd", "..\\game.sav", "", "a\0b"] {
+            assert!(contained_path(dir, path).is_err(), "{:?} should be refused", path);
+        }
+        // whatever the title, the sanitized name lands inside
+        let name = sanitize_file_name("../../../../tmp/evil");
+        assert!(contained_path(dir, &name).unwrap().starts_with(dir));
+    }
+}
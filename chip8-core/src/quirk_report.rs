@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+// Records instructions whose result depends on the quirk settings
+pub struct QuirkReport {
+    // (pc, quirk name) -> (opcode, times executed)
+    divergences: HashMap<(u16, &'static str), (u16, u64)>,
+}
+
+impl Default for QuirkReport {
+    fn default() -> QuirkReport {
+        QuirkReport::new()
+    }
+}
+
+impl QuirkReport {
+    pub fn new() -> QuirkReport {
+        QuirkReport {
+            divergences: HashMap::new(),
+        }
+    }
+
+    // Counts a divergence, returning a line describing it the first time it's seen at a PC
+    pub fn record(&mut self, pc: u16, opcode: u16, quirk: &'static str) -> Option<String> {
+        let entry = self.divergences.entry((pc, quirk)).or_insert((opcode, 0));
+        entry.1 += 1;
+        (entry.1 == 1).then(|| format!("strict: {:#06X} at {:#06X} diverges on quirk {}", opcode, pc, quirk))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.divergences.is_empty()
+    }
+
+    // The quirk sensitivity report, sorted by PC
+    pub fn report(&self) -> String {
+        if self.is_empty() {
+            return "quirk sensitivity report: no quirk-dependent instructions executed".to_string();
+        }
+
+        let mut entries: Vec<_> = self.divergences.iter().collect();
+        entries.sort_by_key(|((pc, quirk), _)| (*pc, *quirk));
+
+        let mut lines = vec!["quirk sensitivity report:".to_string(), "  PC      OPCODE  QUIRK          COUNT".to_string()];
+        for ((pc, quirk), (opcode, count)) in entries {
+            lines.push(format!("  {:#06X}  {:#06X}  {:<13}  {}", pc, opcode, quirk, count));
+        }
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,103 @@
+// Cheat search: start from every address, then narrow the candidates down by how each byte
+// compares with its value at the previous step until only the variable you're after is left,
+// e.g. "decreased" after losing a life. Found addresses can be pinned to keep an eye on them,
+// and pins can be frozen through Chip8::freeze.
+#[derive(Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Comparison {
+    Increased,
+    Decreased,
+    Changed,
+    Unchanged,
+    EqualTo(u8),
+}
+
+impl Comparison {
+    fn matches(self, previous: u8, current: u8) -> bool {
+        match self {
+            Comparison::Increased => current > previous,
+            Comparison::Decreased => current < previous,
+            Comparison::Changed => current != previous,
+            Comparison::Unchanged => current == previous,
+            Comparison::EqualTo(value) => current == value,
+        }
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            Comparison::Increased => "increased".to_string(),
+            Comparison::Decreased => "decreased".to_string(),
+            Comparison::Changed => "changed".to_string(),
+            Comparison::Unchanged => "unchanged".to_string(),
+            Comparison::EqualTo(value) => format!("equal to {}", value),
+        }
+    }
+}
+
+const LISTED: usize = 8; // candidates shown on the panel
+
+pub struct RamSearch {
+    snapshot: Vec<u8>, // memory as of the last step
+    pub candidates: Vec<u16>,
+    pub pins: Vec<u16>,
+    pub steps: Vec<Comparison>,
+}
+
+impl RamSearch {
+    // Starts a search with every address as a candidate
+    pub fn new(memory: &[u8]) -> RamSearch {
+        RamSearch {
+            snapshot: memory.to_vec(),
+            candidates: (0..memory.len().min(0x10000)).map(|address| address as u16).collect(),
+            pins: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    // Keeps the candidates whose byte compares with the snapshot as asked, then snapshots
+    // memory again for the next step
+    pub fn filter(&mut self, memory: &[u8], comparison: Comparison) {
+        let byte = |memory: &[u8], address: u16| memory.get(address as usize).copied().unwrap_or(0);
+        self.candidates.retain(|&address| comparison.matches(byte(&self.snapshot, address), byte(memory, address)));
+        self.snapshot = memory.to_vec();
+        self.steps.push(comparison);
+    }
+
+    pub fn pin(&mut self, address: u16) {
+        if !self.pins.contains(&address) {
+            self.pins.push(address);
+        }
+    }
+
+    pub fn unpin(&mut self, address: u16) {
+        self.pins.retain(|&pin| pin != address);
+    }
+
+    // Panel text: the search so far, the first few candidates with their previous and current
+    // values, and the pins with a marker for frozen ones
+    pub fn lines(&self, memory: &[u8], frozen: &[(u16, u8)]) -> Vec<String> {
+        let byte = |memory: &[u8], address: u16| memory.get(address as usize).copied().unwrap_or(0);
+
+        let mut lines = vec![match self.steps.last() {
+            Some(step) => format!("{} LEFT AFTER {} STEPS, LAST {}", self.candidates.len(), self.steps.len(), step.describe()),
+            None => format!("{} ADDRESSES", self.candidates.len()),
+        }];
+        for &address in self.candidates.iter().take(LISTED) {
+            lines.push(format!("{:04X}: {:3} -> {:3}", address, byte(&self.snapshot, address), byte(memory, address)));
+        }
+        if self.candidates.len() > LISTED {
+            lines.push("...".to_string());
+        }
+
+        if !self.pins.is_empty() {
+            lines.push(String::new());
+            lines.push("PINNED".to_string());
+        }
+        for &address in self.pins.iter() {
+            let marker = if frozen.iter().any(|&(frozen, _)| frozen == address) { " FROZEN" } else { "" };
+            lines.push(format!("{:04X}: {:3}{}", address, byte(memory, address), marker));
+        }
+
+        lines
+    }
+}
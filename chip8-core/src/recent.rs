@@ -0,0 +1,76 @@
+use std::io;
+use std::path::Path;
+
+pub const RECENT_PATH: &str = ".c8_recent";
+
+// ROMs opened lately, most recent first, kept one path per line between sessions
+#[derive(Default)]
+pub struct RecentRoms {
+    pub paths: Vec<String>,
+}
+
+impl RecentRoms {
+    pub const LIMIT: usize = 9; // one per keypad digit
+
+    // Loads the list, starting empty when the file doesn't exist
+    pub fn load(path: &str) -> io::Result<RecentRoms> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                let paths = text.lines().filter(|line| !line.is_empty()).take(RecentRoms::LIMIT).map(str::to_string).collect();
+                Ok(RecentRoms { paths })
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(RecentRoms::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut text = self.paths.join("\n");
+        text.push('\n');
+        std::fs::write(path, text)
+    }
+
+    // Moves a ROM to the front, dropping the oldest past the limit. Paths are made absolute
+    // so the list still works when c8 is started from another directory.
+    pub fn add(&mut self, path: &str) {
+        let path = std::path::absolute(path).map_or(path.to_string(), |path| path.to_string_lossy().into_owned());
+        self.paths.retain(|recent| *recent != path);
+        self.paths.insert(0, path);
+        self.paths.truncate(RecentRoms::LIMIT);
+    }
+
+    // Numbered file names for the Recent ROMs page
+    pub fn lines(&self) -> Vec<String> {
+        if self.paths.is_empty() {
+            return vec!["NOTHING OPENED YET".to_string()];
+        }
+        self.paths
+            .iter()
+            .enumerate()
+            .map(|(idx, path)| {
+                let name = Path::new(path).file_name().map_or(path.clone(), |name| name.to_string_lossy().into_owned());
+                format!("{} {}", idx + 1, name)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reopening_moves_a_rom_to_the_front_and_the_oldest_falls_off() {
+        let mut recent = RecentRoms::default();
+        for idx in 0..=RecentRoms::LIMIT {
+            recent.add(&format!("/roms/{}.ch8", idx));
+        }
+        recent.add("/roms/5.ch8");
+
+        assert_eq!(recent.paths.len(), RecentRoms::LIMIT);
+        assert_eq!(recent.paths[0], "/roms/5.ch8");
+        assert_eq!(recent.paths[1], "/roms/9.ch8");
+        assert!(!recent.paths.contains(&"/roms/0.ch8".to_string()));
+        assert_eq!(recent.lines()[0], "1 5.ch8");
+    }
+}
@@ -0,0 +1,158 @@
+use crate::delta;
+use std::collections::VecDeque;
+
+// Frames between full copies of the state. Going back loads the keyframe before the frame
+// and applies the deltas after it, so this bounds the work as well as what a lost delta costs.
+const KEYFRAME_INTERVAL: usize = 600;
+
+// The last frames of a session, to step back through. Each frame keeps its small state (T,
+// registers and the like) as is, and its bulk (memory and screen) as a delta against the frame
+// before, with a full copy every KEYFRAME_INTERVAL frames. When the frames take more than the
+// budget the oldest keyframe goes, along with the deltas up to the next one.
+pub struct Rewind<T> {
+    budget: usize, // bytes
+    frames: VecDeque<Frame<T>>,
+    latest: Vec<u8>, // bulk of the newest frame, which the next delta is against
+    since_keyframe: usize,
+    bytes: usize,
+}
+
+struct Frame<T> {
+    state: T,
+    bulk: Bulk,
+}
+
+enum Bulk {
+    Key(Vec<u8>),
+    Delta(Vec<u8>),
+}
+
+impl Bulk {
+    fn len(&self) -> usize {
+        match self {
+            Bulk::Key(bytes) | Bulk::Delta(bytes) => bytes.len(),
+        }
+    }
+}
+
+impl<T: Clone> Rewind<T> {
+    pub fn new(budget: usize) -> Rewind<T> {
+        Rewind {
+            budget,
+            frames: VecDeque::new(),
+            latest: Vec::new(),
+            since_keyframe: 0,
+            bytes: 0,
+        }
+    }
+
+    // Frames that can be gone back to
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // Memory the frames take, roughly
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn push(&mut self, state: T, bulk: Vec<u8>) {
+        let stored = if self.frames.is_empty() || self.since_keyframe + 1 >= KEYFRAME_INTERVAL {
+            self.since_keyframe = 0;
+            Bulk::Key(bulk.clone())
+        } else {
+            self.since_keyframe += 1;
+            Bulk::Delta(delta::encode(&self.latest, &bulk))
+        };
+        self.latest = bulk;
+        self.bytes += Rewind::<T>::cost(&stored);
+        self.frames.push_back(Frame { state, bulk: stored });
+
+        // Whole keyframe groups at a time, so every frame left still has a keyframe before it
+        while self.bytes > self.budget {
+            let Some(next) = self.frames.iter().skip(1).position(|frame| matches!(frame.bulk, Bulk::Key(_))) else {
+                break;
+            };
+            for frame in self.frames.drain(..next + 1) {
+                self.bytes -= Rewind::<T>::cost(&frame.bulk);
+            }
+        }
+    }
+
+    // Goes back the given number of frames from the newest, forgetting the ones after it, and
+    // returns that frame's state and bulk. None when fewer frames than that are recorded.
+    pub fn go_back(&mut self, frames: usize) -> Option<(T, Vec<u8>)> {
+        let target = self.frames.len().checked_sub(frames + 1)?;
+        let key = (0..=target).rev().find(|&idx| matches!(self.frames[idx].bulk, Bulk::Key(_)))?;
+
+        let mut bulk = Vec::new();
+        for frame in self.frames.range(key..=target) {
+            bulk = match &frame.bulk {
+                Bulk::Key(bytes) => bytes.clone(),
+                Bulk::Delta(delta) => delta::apply(&bulk, delta)?,
+            };
+        }
+
+        for frame in self.frames.drain(target + 1..) {
+            self.bytes -= Rewind::<T>::cost(&frame.bulk);
+        }
+        self.since_keyframe = target - key;
+        self.latest = bulk.clone();
+        Some((self.frames[target].state.clone(), bulk))
+    }
+
+    fn cost(bulk: &Bulk) -> usize {
+        std::mem::size_of::<Frame<T>>() + bulk.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(frame: u8) -> Vec<u8> {
+        let mut memory = vec![0; 4096];
+        memory[0x300] = frame;
+        memory
+    }
+
+    #[test]
+    fn going_back_rebuilds_the_frame_from_its_keyframe() {
+        let mut rewind = Rewind::new(usize::MAX);
+        for frame in 0..=200u8 {
+            rewind.push(frame, memory(frame));
+        }
+        assert!(rewind.bytes() < 4096 + 200 * 128, "{} bytes", rewind.bytes());
+
+        assert_eq!(rewind.go_back(60), Some((140, memory(140))));
+        assert_eq!(rewind.len(), 141);
+        rewind.push(141, memory(99)); // a different future this time
+        assert_eq!(rewind.go_back(0), Some((141, memory(99))));
+        assert_eq!(rewind.go_back(141), Some((0, memory(0))));
+        assert_eq!(rewind.go_back(1), None);
+    }
+
+    #[test]
+    fn the_budget_drops_the_oldest_keyframe_group() {
+        let push = |rewind: &mut Rewind<usize>, frames: usize| {
+            for frame in 0..frames {
+                rewind.push(frame, memory(frame as u8));
+            }
+        };
+
+        // Room for two groups of a keyframe and its deltas but not three
+        let mut two_groups = Rewind::new(usize::MAX);
+        push(&mut two_groups, KEYFRAME_INTERVAL * 2);
+        let mut rewind = Rewind::new(two_groups.bytes());
+        push(&mut rewind, KEYFRAME_INTERVAL * 3);
+        assert!(rewind.bytes() <= two_groups.bytes());
+        assert_eq!(rewind.len(), KEYFRAME_INTERVAL * 2); // the first group went
+
+        let oldest = rewind.len() - 1;
+        assert_eq!(rewind.go_back(oldest).map(|(frame, _)| frame), Some(KEYFRAME_INTERVAL));
+    }
+}
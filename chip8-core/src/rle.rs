@@ -29,12 +29,12 @@ pub fn decode_rle(data: &[u8]) -> Vec<u8> {
 
     while idx < data.len() {
         if data[idx] == 0x00 {
-            if idx + 4 <= data.len() {
+            if idx + 4 < data.len() {
                 let length = u32::from_le_bytes([data[idx + 1], data[idx + 2], data[idx + 3], data[idx + 4]]) as usize;
                 decoded.resize(decoded.len() + length, 0x00);
                 idx += 5;
             } else {
-                continue;
+                break; // Handle malformed input gracefully
             }
         } else {
             decoded.push(data[idx]);
@@ -45,6 +45,26 @@ pub fn decode_rle(data: &[u8]) -> Vec<u8> {
     decoded
 }
 
+// Decodes encode_rle output up to len bytes, for data that goes on past it as in savestates.
+// Returns the bytes and how much of data they took, or None if data runs out first.
+pub fn decode_rle_len(data: &[u8], len: usize) -> Option<(Vec<u8>, usize)> {
+    let mut decoded = Vec::with_capacity(len);
+    let mut idx = 0;
+
+    while decoded.len() < len {
+        if *data.get(idx)? == 0x00 {
+            let length = u32::from_le_bytes(data.get(idx + 1..idx + 5)?.try_into().ok()?) as usize;
+            decoded.resize(decoded.len() + length, 0x00);
+            idx += 5;
+        } else {
+            decoded.push(data[idx]);
+            idx += 1;
+        }
+    }
+
+    (decoded.len() == len).then_some((decoded, idx))
+}
+
 pub fn encode_rle_u32(data: &[u32]) -> Vec<u8> {
     let mut encoded = Vec::new();
     let mut count: u32 = 0;
@@ -93,4 +113,23 @@ pub fn decode_rle_u32(data: &[u8]) -> Vec<u32> {
     }
 
     decoded
-}
\ No newline at end of file
+}
+
+// decode_rle_len for encode_rle_u32 output, len being in words
+pub fn decode_rle_u32_len(data: &[u8], len: usize) -> Option<(Vec<u32>, usize)> {
+    let mut decoded = Vec::with_capacity(len);
+    let mut idx = 0;
+
+    while decoded.len() < len {
+        if *data.get(idx)? == 0 {
+            let length = u32::from_le_bytes(data.get(idx + 1..idx + 5)?.try_into().ok()?);
+            decoded.resize(decoded.len() + length as usize, 0);
+            idx += 5;
+        } else {
+            decoded.push(u32::from_le_bytes(data.get(idx..idx + 4)?.try_into().ok()?));
+            idx += 4;
+        }
+    }
+
+    (decoded.len() == len).then_some((decoded, idx))
+}
@@ -0,0 +1,29 @@
+// Seedable generator behind CXNN (SplitMix64). Its whole state is one u64, so a savestate can
+// carry it and a restored state draws the same numbers again.
+#[derive(Clone, Copy)]
+pub struct Rng {
+    pub state: u64,
+}
+
+impl Rng {
+    pub fn from_seed(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    // Seeded from the OS, for a run that isn't meant to be reproduced
+    pub fn from_entropy() -> Rng {
+        Rng::from_seed(rand::random())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+}
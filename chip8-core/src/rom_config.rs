@@ -0,0 +1,91 @@
+use crate::palette::{format_color, parse_color};
+use crate::toml::{self, Table, Value};
+use std::io;
+
+// Per-ROM settings stored next to the ROM as "<rom>.toml" and written back when changed:
+//
+//   palette = "amber"
+//   colors = ["#000000", "#FFFFFF", "#FF0000", "#00FF00"]  # background, plane 1, plane 2, both
+//   font = "vip"       # octo, vip, dream6800 or eti660
+//   bigfont = "schip"  # octo or schip
+//   input = "pong-2p"  # selected [input_profiles.*] table, see input_profile.rs
+pub struct RomConfig {
+    pub path: String,
+    pub table: Table,
+}
+
+impl RomConfig {
+    pub fn path_for(rom_path: &str) -> String {
+        format!("{}.toml", rom_path)
+    }
+
+    // Loads the ROM's config, starting empty when it doesn't exist yet
+    pub fn load(rom_path: &str) -> io::Result<RomConfig> {
+        let path = RomConfig::path_for(rom_path);
+        let table = match std::fs::read_to_string(&path) {
+            Ok(text) => toml::parse(&text)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Table::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(RomConfig { path, table })
+    }
+
+    pub fn palette(&self) -> Option<&str> {
+        self.table.get("palette").and_then(Value::as_str)
+    }
+
+    pub fn set_palette(&mut self, name: &str) {
+        self.table.insert("palette".to_string(), Value::Str(name.to_string()));
+    }
+
+    pub fn input_profile(&self) -> Option<&str> {
+        self.table.get("input").and_then(Value::as_str)
+    }
+
+    pub fn set_input_profile(&mut self, name: Option<&str>) {
+        match name {
+            Some(name) => _ = self.table.insert("input".to_string(), Value::Str(name.to_string())),
+            None => _ = self.table.remove("input"),
+        }
+    }
+
+    pub fn font(&self) -> Option<&str> {
+        self.table.get("font").and_then(Value::as_str)
+    }
+
+    pub fn bigfont(&self) -> Option<&str> {
+        self.table.get("bigfont").and_then(Value::as_str)
+    }
+
+    // Custom per-plane colors for this ROM
+    pub fn colors(&self) -> Option<[u32; 4]> {
+        let values = self.table.get("colors").and_then(Value::as_array)?;
+        let mut colors = [0; 4];
+        for (idx, value) in values.iter().take(4).enumerate() {
+            colors[idx] = parse_color(value.as_str()?)?;
+        }
+        if values.len() >= 2 { Some(colors) } else { None }
+    }
+
+    pub fn set_colors(&mut self, colors: [u32; 4]) {
+        let values = colors.iter().map(|&color| Value::Str(format_color(color))).collect();
+        self.table.insert("colors".to_string(), Value::Array(values));
+    }
+
+    // Marks the idx'th [[achievement]] as unlocked
+    pub fn set_achievement_unlocked(&mut self, idx: usize) {
+        let entry = match self.table.get_mut("achievement") {
+            Some(Value::Array(entries)) => entries.get_mut(idx),
+            _ => None,
+        };
+
+        if let Some(Value::Table(entry)) = entry {
+            entry.insert("unlocked".to_string(), Value::Bool(true));
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(&self.table)
+    }
+}
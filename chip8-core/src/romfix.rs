@@ -0,0 +1,56 @@
+use crate::disasm::find_code;
+
+pub struct FixOptions {
+    pub trim: bool,
+    pub pad_to: Option<usize>,
+    pub align: Option<usize>,
+}
+
+pub struct RomReport {
+    pub original_size: usize,
+    pub effective_size: usize,
+    pub code_bytes: usize,
+    pub data_bytes: usize,
+}
+
+// Size of the ROM without its trailing zero padding, never cutting reachable code
+pub fn effective_size(rom: &[u8], base: u16) -> usize {
+    let last_data = rom.iter().rposition(|&byte| byte != 0).map_or(0, |idx| idx + 1);
+    let last_code = find_code(rom, base).into_iter().max().map_or(0, |idx| idx + 1);
+    last_data.max(last_code).min(rom.len())
+}
+
+pub fn analyze(rom: &[u8], base: u16) -> RomReport {
+    let effective_size = effective_size(rom, base);
+    let code_bytes = find_code(rom, base).into_iter().filter(|&idx| idx < effective_size).count();
+
+    RomReport {
+        original_size: rom.len(),
+        effective_size,
+        code_bytes,
+        data_bytes: effective_size - code_bytes,
+    }
+}
+
+// Trims trailing padding, then pads to a minimum size and/or aligns to a multiple
+pub fn fix_rom(rom: &[u8], base: u16, options: &FixOptions) -> Vec<u8> {
+    let mut fixed = rom.to_vec();
+
+    if options.trim {
+        fixed.truncate(effective_size(rom, base));
+    }
+
+    if let Some(size) = options.pad_to {
+        if fixed.len() < size {
+            fixed.resize(size, 0);
+        }
+    }
+
+    if let Some(align) = options.align {
+        if align > 0 {
+            fixed.resize(fixed.len().div_ceil(align) * align, 0);
+        }
+    }
+
+    fixed
+}
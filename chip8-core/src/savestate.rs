@@ -0,0 +1,63 @@
+use std::io;
+
+// State read from another emulator's savestate or memory dump. Anything the format doesn't
+// carry stays None and keeps its current value when imported.
+pub struct ImportedState {
+    pub memory: Vec<u8>,
+    pub pc: Option<u16>,
+    pub i: Option<u16>,
+    pub v: Option<[u8; 16]>,
+    pub delay: Option<u8>,
+    pub sound: Option<u8>,
+}
+
+pub trait StateReader {
+    fn name(&self) -> &'static str;
+    fn detect(&self, data: &[u8]) -> bool;
+    fn read(&self, data: &[u8]) -> io::Result<ImportedState>;
+}
+
+// Raw memory dumps, which most emulators' debuggers can write: the whole 4K (or 64K for
+// XO-CHIP) address space with the font at 0x000 and the program at 0x200.
+// They carry no registers, so execution restarts at 0x200.
+pub struct RamDumpReader;
+
+impl StateReader for RamDumpReader {
+    fn name(&self) -> &'static str {
+        "ram dump"
+    }
+
+    fn detect(&self, data: &[u8]) -> bool {
+        data.len() == 0x1000 || data.len() == 0x10000
+    }
+
+    fn read(&self, data: &[u8]) -> io::Result<ImportedState> {
+        if !self.detect(data) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "memory dumps must be 4096 or 65536 bytes"));
+        }
+
+        Ok(ImportedState {
+            memory: data.to_vec(),
+            pc: Some(0x200),
+            i: None,
+            v: None,
+            delay: None,
+            sound: None,
+        })
+    }
+}
+
+pub fn readers() -> Vec<Box<dyn StateReader>> {
+    vec![Box::new(RamDumpReader)]
+}
+
+// Reads a foreign savestate with the first reader that recognizes it
+pub fn import(data: &[u8]) -> io::Result<(&'static str, ImportedState)> {
+    for reader in readers() {
+        if reader.detect(data) {
+            return Ok((reader.name(), reader.read(data)?));
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::InvalidData, "unrecognized savestate format"))
+}
@@ -0,0 +1,43 @@
+use crate::display::Display;
+
+// Encodes the framebuffer as a 24-bit BMP, each CHIP-8 pixel drawn as a scale x scale block
+pub fn encode_bmp(display: &Display, scale: u16) -> Vec<u8> {
+    let scale = scale.max(1) as u32;
+    let width = display.width as u32 * scale;
+    let height = display.height as u32 * scale;
+    let row_size = (width * 3).div_ceil(4) * 4;
+    let image_size = row_size * height;
+    let file_size = 54 + image_size;
+
+    let mut bmp: Vec<u8> = Vec::with_capacity(file_size as usize);
+
+    // BITMAPFILEHEADER
+    bmp.extend(b"BM");
+    bmp.extend(file_size.to_le_bytes());
+    bmp.extend([0; 4]);
+    bmp.extend(54u32.to_le_bytes());
+
+    // BITMAPINFOHEADER
+    bmp.extend(40u32.to_le_bytes());
+    bmp.extend((width as i32).to_le_bytes());
+    bmp.extend((height as i32).to_le_bytes()); // positive height: rows stored bottom-up
+    bmp.extend(1u16.to_le_bytes());
+    bmp.extend(24u16.to_le_bytes());
+    bmp.extend(0u32.to_le_bytes());
+    bmp.extend(image_size.to_le_bytes());
+    bmp.extend(2835i32.to_le_bytes());
+    bmp.extend(2835i32.to_le_bytes());
+    bmp.extend([0; 8]);
+
+    for row in (0..height).rev() {
+        let y = (row / scale) as u16;
+        for column in 0..width {
+            let x = (column / scale) as u16;
+            let value = if display.get_pixel(x, y) != 0 { 0xFF } else { 0x00 };
+            bmp.extend([value; 3]);
+        }
+        bmp.resize(bmp.len() + (row_size - width * 3) as usize, 0);
+    }
+
+    bmp
+}
@@ -0,0 +1,44 @@
+use crate::toml::Value;
+
+// A file loaded into memory besides the program, e.g. a data overlay or a patched interpreter
+// stub, written `overlay.bin@0x800` on the command line (--segment) and in config.toml:
+//
+//   segments = ["overlay.bin@0x800", "stub.bin@0x100"]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub path: String,
+    pub address: u16,
+}
+
+impl Segment {
+    pub fn parse(text: &str) -> Result<Segment, String> {
+        let invalid = || format!("invalid segment {}, expected file@address, e.g. overlay.bin@0x800", text);
+        let (path, address) = text.rsplit_once('@').ok_or_else(invalid)?;
+        let address = u16::from_str_radix(address.trim_start_matches("0x").trim_start_matches("0X"), 16).map_err(|_| invalid())?;
+        if path.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Segment { path: path.to_string(), address })
+    }
+}
+
+pub fn segments_from_config(values: &[Value]) -> Result<Vec<Segment>, String> {
+    values
+        .iter()
+        .map(|value| value.as_str().ok_or_else(|| "segments should be strings like \"overlay.bin@0x800\"".to_string()).and_then(Segment::parse))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_parse_from_file_at_address() {
+        assert_eq!(Segment::parse("data/overlay.bin@0x800"), Ok(Segment { path: "data/overlay.bin".to_string(), address: 0x800 }));
+        assert_eq!(Segment::parse("me@home.bin@A00").map(|segment| segment.address), Ok(0xA00));
+        assert!(Segment::parse("overlay.bin").is_err());
+        assert!(Segment::parse("@0x800").is_err());
+        assert!(Segment::parse("overlay.bin@0x10000").is_err());
+    }
+}
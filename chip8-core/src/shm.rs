@@ -0,0 +1,88 @@
+use crate::cpu::Chip8;
+use crate::expr::ExprContext;
+use std::fs::OpenOptions;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+// Machine state mapped into a file (e.g. under /dev/shm) that external visualizers and OBS
+// overlays map too, so they read each frame without a socket in between. Little-endian:
+//
+//   0   "C8SM" magic, u32 layout version
+//   8   u64 sequence: odd while a frame is being written, so readers retry until they see the
+//       same even value before and after copying
+//   16  u64 frame number
+//   24  u16 width, u16 height, u16 PC, u16 I
+//   32  i8 SP, u8 delay, u8 sound, u8 flags (bit 0 halted, bit 1 beeping)
+//   36  V0-VF
+//   64  framebuffer, one byte per pixel row by row, width * height bytes
+const MAGIC: &[u8; 4] = b"C8SM";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 64;
+const SIZE: usize = HEADER_SIZE + 128 * 64;
+
+pub struct SharedState {
+    map: *mut u8,
+    frame: u64,
+}
+
+impl SharedState {
+    // Creates (or reuses) the file and maps it shared
+    pub fn create(path: &str) -> io::Result<SharedState> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        file.set_len(SIZE as u64)?;
+
+        // The mapping stays valid after the file is closed
+        let map = unsafe {
+            libc::mmap(std::ptr::null_mut(), SIZE, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, file.as_raw_fd(), 0)
+        };
+        if map == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut shared = SharedState { map: map as *mut u8, frame: 0 };
+        shared.write(0, MAGIC);
+        shared.write(4, &VERSION.to_le_bytes());
+        Ok(shared)
+    }
+
+    fn write(&mut self, offset: usize, bytes: &[u8]) {
+        assert!(offset + bytes.len() <= SIZE);
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), self.map.add(offset), bytes.len()) };
+    }
+
+    fn sequence(&self) -> &AtomicU64 {
+        // Offset 8 of a page-aligned mapping is 8-byte aligned
+        unsafe { &*(self.map.add(8) as *const AtomicU64) }
+    }
+
+    // Writes this frame's state, bumping the sequence around it
+    pub fn publish(&mut self, cpu: &Chip8) {
+        let display = cpu.display();
+        let pixels = display.pixels();
+        let flags = cpu.halted as u8 | (display.beep() as u8) << 1;
+        let v: Vec<u8> = (0..16).map(|idx| cpu.register(idx)).collect();
+
+        self.sequence().fetch_add(1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        self.frame += 1;
+        self.write(16, &self.frame.to_le_bytes());
+        self.write(24, &display.width.to_le_bytes());
+        self.write(26, &display.height.to_le_bytes());
+        self.write(28, &cpu.pc().to_le_bytes());
+        self.write(30, &cpu.index().to_le_bytes());
+        self.write(32, &[cpu.sp() as u8, cpu.delay(), cpu.sound(), flags]);
+        self.write(36, &v);
+        self.write(HEADER_SIZE, &pixels[..pixels.len().min(SIZE - HEADER_SIZE)]);
+
+        fence(Ordering::Release);
+        self.sequence().fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for SharedState {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.map as *mut libc::c_void, SIZE) };
+    }
+}
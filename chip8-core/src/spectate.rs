@@ -0,0 +1,173 @@
+use crate::display::Display;
+use crate::palette::Palette;
+use crate::rle::{decode_rle, encode_rle};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+// Spectator streaming: the playing emulator broadcasts each frame as an RLE-compressed XOR
+// against the previous one, and read-only spectators apply the deltas to their own Display.
+// No input flows back, so spectators can join and leave at any time.
+//
+// Frame message, little endian:
+//   "FRAM" width:u16 height:u16 colors:[u32; 4] flags:u8 (bit 0 = beep) length:u32 delta
+// where delta is encode_rle() of the framebuffer words (big endian bytes) XOR the previous
+// frame, or XOR all zeros for a keyframe.
+const MAGIC: &[u8; 4] = b"FRAM";
+const HEADER_SIZE: usize = 4 + 2 + 2 + 16 + 1 + 4;
+
+fn frame_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
+
+fn encode_frame(display: &Display, previous: Option<&[u32]>) -> Vec<u8> {
+    let delta: Vec<u32> = match previous {
+        Some(previous) if previous.len() == display.display.len() => {
+            display.display.iter().zip(previous).map(|(current, previous)| current ^ previous).collect()
+        }
+        _ => display.display.clone(),
+    };
+    let payload = encode_rle(&frame_bytes(&delta));
+
+    let mut message = Vec::with_capacity(HEADER_SIZE + payload.len());
+    message.extend_from_slice(MAGIC);
+    message.extend_from_slice(&display.width.to_le_bytes());
+    message.extend_from_slice(&display.height.to_le_bytes());
+    for color in display.palette().colors {
+        message.extend_from_slice(&color.to_le_bytes());
+    }
+    message.push(display.beep() as u8);
+    message.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    message.extend_from_slice(&payload);
+    message
+}
+
+pub struct Broadcaster {
+    listener: TcpListener,
+    clients: Vec<TcpStream>,
+    last: Option<(u16, u16, Vec<u32>)>,
+    sent: Option<(u64, [u32; 4], bool)>, // fingerprint, palette and beep of the last frame sent
+}
+
+impl Broadcaster {
+    pub fn bind(address: &str) -> io::Result<Broadcaster> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Broadcaster {
+            listener,
+            clients: Vec::new(),
+            last: None,
+            sent: None,
+        })
+    }
+
+    pub fn spectators(&self) -> usize {
+        self.clients.len()
+    }
+
+    // Sends the frame to every spectator, call once per frame. Frames that look the same as
+    // the last one sent are skipped. Returns the addresses of spectators who joined.
+    pub fn broadcast(&mut self, display: &mut Display) -> Vec<SocketAddr> {
+        let sent = (display.fingerprint(), display.palette().colors, display.beep());
+        let unchanged = self.sent == Some(sent);
+        self.sent = Some(sent);
+
+        let previous = match self.last {
+            Some((width, height, ref words)) if width == display.width && height == display.height => Some(words.as_slice()),
+            _ => None,
+        };
+
+        if !self.clients.is_empty() && !unchanged {
+            let message = encode_frame(display, previous);
+            self.clients.retain_mut(|client| client.write_all(&message).is_ok());
+        }
+
+        // New spectators start from a keyframe
+        let mut joined = Vec::new();
+        while let Ok((mut stream, address)) = self.listener.accept() {
+            let keyframe = encode_frame(display, None);
+            if stream.set_nodelay(true).and_then(|_| stream.write_all(&keyframe)).is_ok() {
+                joined.push(address);
+                self.clients.push(stream);
+            }
+        }
+
+        self.last = Some((display.width, display.height, display.display.clone()));
+        joined
+    }
+}
+
+pub struct Spectator {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+}
+
+impl Spectator {
+    pub fn connect(address: &str) -> io::Result<Spectator> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nonblocking(true)?;
+        Ok(Spectator {
+            stream,
+            buffer: Vec::new(),
+        })
+    }
+
+    // Applies every frame that has arrived, returns false once the broadcaster is gone
+    pub fn poll(&mut self, display: &mut Display) -> io::Result<bool> {
+        let mut chunk = [0; 4096];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Ok(false),
+                Ok(read) => self.buffer.extend_from_slice(&chunk[..read]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        while self.buffer.len() >= HEADER_SIZE {
+            if &self.buffer[..4] != MAGIC {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "not a spectator stream"));
+            }
+
+            let length = u32::from_le_bytes([self.buffer[25], self.buffer[26], self.buffer[27], self.buffer[28]]) as usize;
+            if self.buffer.len() < HEADER_SIZE + length {
+                break;
+            }
+
+            let message: Vec<u8> = self.buffer.drain(..HEADER_SIZE + length).collect();
+            apply_frame(display, &message);
+        }
+
+        Ok(true)
+    }
+}
+
+fn apply_frame(display: &mut Display, message: &[u8]) {
+    let width = u16::from_le_bytes([message[4], message[5]]);
+    let height = u16::from_le_bytes([message[6], message[7]]);
+    let mut colors = [0; 4];
+    for (i, color) in colors.iter_mut().enumerate() {
+        let at = 8 + i * 4;
+        *color = u32::from_le_bytes([message[at], message[at + 1], message[at + 2], message[at + 3]]);
+    }
+    let flags = message[24];
+
+    if width != display.width || height != display.height {
+        let scale = (display.original_scale * 64 / width.max(1)).max(1);
+        display.resize(width, height, scale);
+    }
+
+    if display.palette().colors != colors {
+        display.add_palette(Palette::new("broadcast", colors));
+    }
+    display.set_beep(flags & 1 != 0);
+
+    let delta = decode_rle(&message[HEADER_SIZE..]);
+    for i in 0..display.display.len() {
+        let bytes = delta.get(i * 4..i * 4 + 4).unwrap_or(&[0; 4]);
+        let changed = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if changed != 0 {
+            display.display[i] ^= changed;
+            display.mark_word_dirty(i);
+        }
+    }
+}
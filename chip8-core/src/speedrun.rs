@@ -0,0 +1,155 @@
+use crate::expr::{self, Expr, ExprContext};
+use crate::toml::{Table, Value};
+
+// Speedrun timer with start, split and reset conditions from the per-ROM config:
+//
+//   [speedrun]
+//   start = "mem[0x3F0] == 1"
+//   reset = "mem[0x3F0] == 0"   # optional
+//
+//   [[speedrun.split]]
+//   name = "Level 1"
+//   when = "mem[0x3F1] == 2"
+//
+// Conditions trigger when they become true. Time is counted in 60hz frames, so it matches
+// emulated time rather than the host clock.
+pub struct SpeedrunTimer {
+    start: Expr,
+    reset: Option<Expr>,
+    pub splits: Vec<Split>,
+    pub frames: u64,
+    running: bool,
+    next_split: usize,
+    was_start: bool,
+    was_reset: bool,
+    was_split: bool,
+    events: Vec<SplitEvent>,
+}
+
+pub struct Split {
+    pub name: String,
+    when: Expr,
+    pub time: Option<u64>, // frame it was reached at
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SplitEvent {
+    Start,
+    Split(usize, u64),
+    Finish(u64),
+    Reset,
+}
+
+pub fn format_time(frames: u64) -> String {
+    let centiseconds = frames * 100 / 60;
+    format!("{}:{:02}.{:02}", centiseconds / 6000, centiseconds / 100 % 60, centiseconds % 100)
+}
+
+impl SpeedrunTimer {
+    pub fn from_config(config: &Table) -> Result<Option<SpeedrunTimer>, String> {
+        let Some(speedrun) = config.get("speedrun").and_then(Value::as_table) else {
+            return Ok(None);
+        };
+
+        let condition = |table: &Table, key: &str| -> Result<Option<Expr>, String> {
+            match table.get(key).map(|value| value.as_str().ok_or(format!("speedrun {} must be a string", key))) {
+                Some(source) => Ok(Some(expr::parse(source?).map_err(|e| format!("speedrun {}: {}", key, e))?)),
+                None => Ok(None),
+            }
+        };
+
+        let start = condition(speedrun, "start")?.ok_or("speedrun needs a start condition")?;
+        let reset = condition(speedrun, "reset")?;
+
+        let mut splits = Vec::new();
+        for (i, entry) in speedrun.get("split").and_then(Value::as_array).into_iter().flatten().enumerate() {
+            let entry = entry.as_table().ok_or(format!("split {}: expected a table", i + 1))?;
+            splits.push(Split {
+                name: entry.get("name").and_then(Value::as_str).map_or(format!("Split {}", i + 1), str::to_string),
+                when: condition(entry, "when")?.ok_or(format!("split {}: missing when", i + 1))?,
+                time: None,
+            });
+        }
+
+        Ok(Some(SpeedrunTimer {
+            start,
+            reset,
+            splits,
+            frames: 0,
+            running: false,
+            next_split: 0,
+            was_start: false,
+            was_reset: false,
+            was_split: false,
+            events: Vec::new(),
+        }))
+    }
+
+    pub fn running(&self) -> bool {
+        self.running
+    }
+
+    // Called once per frame
+    pub fn update(&mut self, ctx: &dyn ExprContext) {
+        let reset = self.reset.as_ref().is_some_and(|reset| reset.eval(ctx) != 0);
+        if reset && !self.was_reset && (self.running || self.frames > 0) {
+            self.running = false;
+            self.frames = 0;
+            self.next_split = 0;
+            self.splits.iter_mut().for_each(|split| split.time = None);
+            self.events.push(SplitEvent::Reset);
+        }
+        self.was_reset = reset;
+
+        if self.running {
+            self.frames += 1;
+        }
+
+        let start = self.start.eval(ctx) != 0;
+        if start && !self.was_start && !self.running {
+            self.running = true;
+            self.frames = 0;
+            self.next_split = 0;
+            self.splits.iter_mut().for_each(|split| split.time = None);
+            self.events.push(SplitEvent::Start);
+        }
+        self.was_start = start;
+
+        if !self.running {
+            return;
+        }
+
+        let Some(split) = self.splits.get_mut(self.next_split) else {
+            return;
+        };
+
+        let reached = split.when.eval(ctx) != 0;
+        if reached && !self.was_split {
+            split.time = Some(self.frames);
+            self.next_split += 1;
+            if self.next_split == self.splits.len() {
+                self.running = false;
+                self.events.push(SplitEvent::Finish(self.frames));
+            } else {
+                self.events.push(SplitEvent::Split(self.next_split - 1, self.frames));
+            }
+            // The next split's condition has to become true on its own
+            self.was_split = true;
+            return;
+        }
+        self.was_split = reached;
+    }
+
+    pub fn take_events(&mut self) -> Vec<SplitEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    // Timer and the latest split for an on-screen display
+    pub fn format(&self) -> Vec<String> {
+        let mut lines = vec![format_time(self.frames)];
+        if let Some(split) = self.next_split.checked_sub(1).and_then(|idx| self.splits.get(idx)) {
+            lines.push(format!("{} {}", split.name, format_time(split.time.unwrap_or(0))));
+        }
+        lines
+    }
+}
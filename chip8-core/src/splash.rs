@@ -0,0 +1,72 @@
+use crate::display::Display;
+use crate::overlay::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+// Boot splash shown when there's no ROM to run
+pub fn splash_lines() -> Vec<String> {
+    vec![
+        "RUST CHIP-8".to_string(),
+        String::new(),
+        "DROP A ROM ON".to_string(),
+        "THIS WINDOW".to_string(),
+    ]
+}
+
+// Error screen for a ROM that couldn't be loaded
+pub fn error_lines(path: &str, reason: &str) -> Vec<String> {
+    vec![
+        "CAN'T LOAD".to_string(),
+        path.to_string(),
+        String::new(),
+        reason.to_string(),
+        String::new(),
+        "DROP ANOTHER ROM".to_string(),
+    ]
+}
+
+// Breaks lines at spaces (or mid-word if a word doesn't fit) so none is wider than columns
+fn wrap(lines: &[String], columns: usize) -> Vec<String> {
+    let mut wrapped = Vec::new();
+
+    for line in lines {
+        let mut current = String::new();
+        for word in line.split(' ') {
+            let mut word = word.to_string();
+            if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > columns {
+                wrapped.push(std::mem::take(&mut current));
+            }
+            while word.chars().count() > columns {
+                let rest = word.split_off(word.char_indices().nth(columns).map_or(word.len(), |(idx, _)| idx));
+                wrapped.push(std::mem::replace(&mut word, rest));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current += &word;
+        }
+        wrapped.push(current);
+    }
+
+    wrapped
+}
+
+// Draws text into the framebuffer with the overlay font, each line centered and the block
+// centered vertically. Lines that don't fit are wrapped, and cut off at the bottom.
+pub fn render_text(display: &mut Display, lines: &[String]) {
+    display.clear();
+
+    let cell_width = GLYPH_WIDTH + 1;
+    let cell_height = GLYPH_HEIGHT + 1;
+    let lines = wrap(lines, (display.width / cell_width) as usize);
+    let top = display.height.saturating_sub(lines.len() as u16 * cell_height) / 2;
+
+    for (row, line) in lines.iter().enumerate() {
+        let (width, _) = overlay::text_size(std::slice::from_ref(line));
+        let left = display.width.saturating_sub(width) / 2;
+        for (x, y) in overlay::text_pixels(std::slice::from_ref(line)) {
+            let (x, y) = (left + x, top + row as u16 * cell_height + y);
+            if x < display.width && y < display.height {
+                display.set_pixel(x, y, 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+
+// Measures how many instructions a ROM works through each frame before it settles into
+// waiting on the delay timer (FX07 reading a timer that hasn't run out), to suggest a speed
+// where the work fits in a frame without the rest being spent spinning
+#[derive(Default)]
+pub struct TickrateProbe {
+    frame_cycles: u64,      // instructions run so far this frame
+    busy: Option<u64>,      // instructions before the first wait this frame
+    histogram: BTreeMap<u64, u64>, // busy instructions -> frames
+    starved: u64,           // frames that ran out of instructions before waiting
+    frames: u64,
+}
+
+impl TickrateProbe {
+    const PERCENTILE: f64 = 0.95;
+    const HEADROOM: f64 = 1.25;
+    const BUCKETS: u64 = 8;
+
+    pub fn new() -> TickrateProbe {
+        TickrateProbe::default()
+    }
+
+    // Called before each instruction runs, with the delay timer as it is then
+    pub fn observe(&mut self, instruction: u16, delay: u8) {
+        if self.busy.is_none() && instruction & 0xF0FF == 0xF007 && delay != 0 {
+            self.busy = Some(self.frame_cycles);
+        }
+        self.frame_cycles += 1;
+    }
+
+    pub fn end_frame(&mut self) {
+        match self.busy.take() {
+            Some(busy) => *self.histogram.entry(busy).or_insert(0) += 1,
+            None if self.waited() > 0 => self.starved += 1,
+            None => {} // nothing to measure until the ROM first waits
+        }
+        self.frames += 1;
+        self.frame_cycles = 0;
+    }
+
+    fn waited(&self) -> u64 {
+        self.histogram.values().sum()
+    }
+
+    // Busy instructions that the given share of waiting frames stay within
+    fn percentile(&self, share: f64) -> u64 {
+        let target = (self.waited() as f64 * share).ceil() as u64;
+        let mut seen = 0;
+        for (&busy, &frames) in &self.histogram {
+            seen += frames;
+            if seen >= target {
+                return busy;
+            }
+        }
+        0
+    }
+
+    // Instructions per second that fit 95% of frames' work with some headroom, rounded up to
+    // 10hz. None until the ROM has waited on the delay timer, or while it still runs out of
+    // instructions too often for the measurement to mean anything.
+    pub fn suggested_speed(&self) -> Option<u32> {
+        let waited = self.waited();
+        if waited == 0 || self.starved * 20 > waited + self.starved {
+            return None;
+        }
+        let hz = self.percentile(TickrateProbe::PERCENTILE) as f64 * 60.0 * TickrateProbe::HEADROOM;
+        Some(((hz / 10.0).ceil() as u32 * 10).max(60))
+    }
+
+    // Histogram of busy instructions per frame and the suggestion, for a run at speed hz
+    pub fn report(&self, speed: u32) -> String {
+        let waited = self.waited();
+        let mut out = format!(
+            "{} frames, {} waited on the delay timer, {} ran out of instructions first\n",
+            self.frames, waited, self.starved
+        );
+
+        let max = self.histogram.keys().next_back().copied().unwrap_or(0);
+        let width = max / TickrateProbe::BUCKETS + 1;
+        out += "  busy instructions  frames\n";
+        for bucket in 0..TickrateProbe::BUCKETS.min(max / width + 1) {
+            let range = bucket * width..(bucket + 1) * width;
+            let frames: u64 = self.histogram.range(range.clone()).map(|(_, &frames)| frames).sum();
+            let bar = "#".repeat((frames * 40 / waited.max(1)) as usize);
+            out += &format!("  {:>6}-{:<6}  {:>10}  {}\n", range.start, range.end - 1, frames, bar);
+        }
+
+        out += &match self.suggested_speed() {
+            Some(hz) => format!(
+                "suggested speed: {} (95% of frames finish their work in {} instructions, {} at the current {})",
+                hz,
+                self.percentile(TickrateProbe::PERCENTILE),
+                speed / 60,
+                speed
+            ),
+            None if waited == 0 => "no suggestion: the ROM never waited on the delay timer".to_string(),
+            None => format!("no suggestion: the ROM ran out of instructions too often, try a speed above {} and measure again", speed),
+        };
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A frame that works for `busy` instructions, then spins on FX07 until the frame ends
+    fn frame(probe: &mut TickrateProbe, busy: u64, per_frame: u64) {
+        for cycle in 0..per_frame {
+            let instruction = if cycle < busy { 0x6000 } else { 0xF007 };
+            probe.observe(instruction, 1);
+        }
+        probe.end_frame();
+    }
+
+    #[test]
+    fn suggests_a_speed_covering_most_frames() {
+        let mut probe = TickrateProbe::new();
+        for idx in 0..100 {
+            frame(&mut probe, if idx < 95 { 8 } else { 15 }, 20);
+        }
+        // 8 instructions * 60 frames * 1.25 headroom
+        assert_eq!(probe.suggested_speed(), Some(600));
+    }
+
+    #[test]
+    fn no_suggestion_while_starved() {
+        let mut probe = TickrateProbe::new();
+        frame(&mut probe, 8, 20);
+        for _ in 0..10 {
+            frame(&mut probe, 30, 20);
+        }
+        assert_eq!(probe.suggested_speed(), None);
+    }
+}
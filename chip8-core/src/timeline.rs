@@ -0,0 +1,80 @@
+// Recording for jumping back to an earlier frame: savestate keyframes every few frames,
+// the instruction count at each 60hz tick and every keypad change with the instruction it
+// happened before. Going to frame N loads the nearest keyframe at or before N and replays
+// the recorded input instruction by instruction.
+pub struct Timeline {
+    pub interval: u64, // frames between keyframes
+    frame_ends: Vec<u64>, // instruction count at each frame tick
+    keyframes: Vec<Keyframe>,
+    key_changes: Vec<(u64, [bool; 16])>,
+}
+
+pub struct Keyframe {
+    pub frame: u64,
+    pub cycles: u64,
+    pub state: Vec<u8>,
+}
+
+impl Timeline {
+    pub fn new(interval: u64) -> Timeline {
+        Timeline {
+            interval: interval.max(1),
+            frame_ends: Vec::new(),
+            keyframes: Vec::new(),
+            key_changes: Vec::new(),
+        }
+    }
+
+    // Frames recorded so far
+    pub fn frame(&self) -> u64 {
+        self.frame_ends.len() as u64
+    }
+
+    pub fn keyframe_due(&self) -> bool {
+        self.frame().is_multiple_of(self.interval)
+    }
+
+    pub fn add_keyframe(&mut self, cycles: u64, state: Vec<u8>) {
+        self.keyframes.push(Keyframe { frame: self.frame(), cycles, state });
+    }
+
+    // Records a keypad change before the instruction at the given count
+    pub fn record_keys(&mut self, cycles: u64, keypad: [bool; 16]) {
+        if self.keys_at(cycles) != keypad {
+            self.key_changes.push((cycles, keypad));
+        }
+    }
+
+    pub fn end_frame(&mut self, cycles: u64) {
+        self.frame_ends.push(cycles);
+    }
+
+    // Instruction count at the end of a frame (1-based)
+    pub fn frame_end(&self, frame: u64) -> Option<u64> {
+        self.frame_ends.get(frame.checked_sub(1)? as usize).copied()
+    }
+
+    // Keypad state in effect before the instruction at the given count
+    pub fn keys_at(&self, cycles: u64) -> [bool; 16] {
+        match self.key_changes.iter().rposition(|&(at, _)| at <= cycles) {
+            Some(idx) => self.key_changes[idx].1,
+            None => [false; 16],
+        }
+    }
+
+    // Latest keyframe at or before a frame
+    pub fn keyframe_for(&self, frame: u64) -> Option<&Keyframe> {
+        self.keyframes.iter().rev().find(|keyframe| keyframe.frame <= frame)
+    }
+
+    // Forgets everything after a frame, so recording continues from there
+    pub fn truncate(&mut self, frame: u64) {
+        let Some(cycles) = (if frame == 0 { Some(0) } else { self.frame_end(frame) }) else {
+            return;
+        };
+
+        self.frame_ends.truncate(frame as usize);
+        self.keyframes.retain(|keyframe| keyframe.frame <= frame);
+        self.key_changes.retain(|&(at, _)| at <= cycles);
+    }
+}
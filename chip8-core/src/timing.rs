@@ -0,0 +1,59 @@
+// Cycle-cost timing models, so speed-sensitive games run at the pace of the interpreter
+// they were written for instead of a flat instructions-per-second rate
+pub struct TimingModel {
+    pub name: &'static str,
+    pub cycles_per_frame: u32,  // cycle budget of one 60hz frame
+    pub cost: fn(u16) -> u32,   // cycles taken by an opcode
+}
+
+// Approximate COSMAC VIP interpreter costs in 1802 machine cycles, the VIP runs
+// ~3668 of them between two 60hz interrupts
+fn vip_cost(opcode: u16) -> u32 {
+    let x = ((opcode & 0x0F00) >> 8) as u32;
+    let n = (opcode & 0x000F) as u32;
+
+    match opcode >> 12 {
+        0x0 => match opcode {
+            0x00E0 => 24 + 3078,
+            0x00EE => 10,
+            _ => 12,
+        },
+        0x1 => 12,
+        0x2 => 26,
+        0x3 | 0x4 => 14,
+        0x5 | 0x9 => 18,
+        0x6 => 6,
+        0x7 => 10,
+        0x8 => 44,
+        0xA => 12,
+        0xB => 22,
+        0xC => 36,
+        0xD => 26 + 68 * n.max(1) + 2700, // blits and waits for the display interrupt
+        0xE => 16,
+        _ => match opcode & 0x00FF {
+            0x07 | 0x15 | 0x18 => 10,
+            0x0A => 20,
+            0x1E | 0x29 => 16,
+            0x33 => 84 + 16 * 3,
+            0x55 | 0x65 => 14 + 14 * (x + 1),
+            _ => 12,
+        },
+    }
+}
+
+// Interpreters that spend about the same time on every instruction
+fn flat_cost(_opcode: u16) -> u32 {
+    1
+}
+
+pub const PRESETS: [&str; 4] = ["vip", "chip48", "schip", "xochip"];
+
+pub fn preset(name: &str) -> Option<TimingModel> {
+    match name {
+        "vip" | "cosmac" => Some(TimingModel { name: "vip", cycles_per_frame: 3668, cost: vip_cost }),
+        "chip48" => Some(TimingModel { name: "chip48", cycles_per_frame: 15, cost: flat_cost }),
+        "schip" | "hp48" => Some(TimingModel { name: "schip", cycles_per_frame: 30, cost: flat_cost }),
+        "xochip" | "octo" => Some(TimingModel { name: "xochip", cycles_per_frame: 1000, cost: flat_cost }),
+        _ => None,
+    }
+}
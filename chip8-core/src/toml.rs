@@ -0,0 +1,457 @@
+use std::collections::BTreeMap;
+use std::io::{self, Error, ErrorKind};
+
+// A small TOML reader covering what our config files use: tables, arrays of tables,
+// strings, integers (dec/hex/bin), floats, booleans, arrays and inline tables
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+pub type Table = BTreeMap<String, Value>;
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(value) => Some(*value),
+            Value::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(flag) => Some(*flag),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&Table> {
+        match self {
+            Value::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+}
+
+fn error(line: usize, message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("line {}: {}", line, message))
+}
+
+struct Parser {
+    chars: Vec<char>,
+    idx: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.idx).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) {
+            self.idx += 1;
+        }
+    }
+
+    // Skips whitespace, newlines and comments
+    fn skip_blank(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ') | Some('\t') | Some('\r') => self.idx += 1,
+                Some('\n') => {
+                    self.line += 1;
+                    self.idx += 1;
+                }
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while !matches!(self.peek(), Some('\n') | None) {
+            self.idx += 1;
+        }
+    }
+
+    // Expects the rest of the line to be empty or a comment
+    fn end_of_line(&mut self) -> io::Result<()> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('#') => {
+                self.skip_comment();
+                Ok(())
+            }
+            Some('\r') | Some('\n') | None => Ok(()),
+            Some(c) => Err(error(self.line, &format!("unexpected '{}'", c))),
+        }
+    }
+
+    fn parse_key(&mut self) -> io::Result<Vec<String>> {
+        let mut parts = Vec::new();
+
+        loop {
+            self.skip_spaces();
+            let part = match self.peek() {
+                Some('"') => self.parse_string()?,
+                _ => {
+                    let start = self.idx;
+                    while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                        self.idx += 1;
+                    }
+                    if start == self.idx {
+                        return Err(error(self.line, "expected a key"));
+                    }
+                    self.chars[start..self.idx].iter().collect()
+                }
+            };
+            parts.push(part);
+
+            self.skip_spaces();
+            if self.peek() == Some('.') {
+                self.idx += 1;
+            } else {
+                return Ok(parts);
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> io::Result<String> {
+        let quote = self.peek().unwrap();
+        self.idx += 1;
+        let mut out = String::new();
+
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(error(self.line, "unterminated string"));
+            };
+            self.idx += 1;
+
+            match c {
+                '\n' => return Err(error(self.line, "newline in string")),
+                _ if c == quote => return Ok(out),
+                '\\' if quote == '"' => {
+                    let escaped = self.peek().ok_or_else(|| error(self.line, "unterminated string"))?;
+                    self.idx += 1;
+                    match escaped {
+                        'n' => out.push('\n'),
+                        't' => out.push('\t'),
+                        'r' => out.push('\r'),
+                        '"' => out.push('"'),
+                        '\\' => out.push('\\'),
+                        'u' => {
+                            let hex: String = self.chars.iter().skip(self.idx).take(4).collect();
+                            let code = u32::from_str_radix(&hex, 16).map_err(|_| error(self.line, "bad \\u escape"))?;
+                            out.push(char::from_u32(code).unwrap_or('?'));
+                            self.idx += 4;
+                        }
+                        _ => return Err(error(self.line, "unknown escape")),
+                    }
+                }
+                _ => out.push(c),
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> io::Result<Value> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('"') | Some('\'') => Ok(Value::Str(self.parse_string()?)),
+
+            Some('[') => {
+                self.idx += 1;
+                let mut values = Vec::new();
+                loop {
+                    self.skip_blank();
+                    match self.peek() {
+                        Some(']') => {
+                            self.idx += 1;
+                            return Ok(Value::Array(values));
+                        }
+                        Some(',') => self.idx += 1,
+                        None => return Err(error(self.line, "unterminated array")),
+                        _ => values.push(self.parse_value()?),
+                    }
+                }
+            }
+
+            Some('{') => {
+                self.idx += 1;
+                let mut table = Table::new();
+                loop {
+                    self.skip_spaces();
+                    match self.peek() {
+                        Some('}') => {
+                            self.idx += 1;
+                            return Ok(Value::Table(table));
+                        }
+                        Some(',') => self.idx += 1,
+                        None | Some('\n') => return Err(error(self.line, "unterminated inline table")),
+                        _ => {
+                            let key = self.parse_key()?;
+                            self.skip_spaces();
+                            if self.peek() != Some('=') {
+                                return Err(error(self.line, "expected '='"));
+                            }
+                            self.idx += 1;
+                            let value = self.parse_value()?;
+                            insert(&mut table, &key, value, self.line)?;
+                        }
+                    }
+                }
+            }
+
+            Some(_) => {
+                let start = self.idx;
+                while matches!(self.peek(), Some(c) if !matches!(c, ',' | ']' | '}' | '#' | '\n' | '\r' | ' ' | '\t')) {
+                    self.idx += 1;
+                }
+
+                let word: String = self.chars[start..self.idx].iter().filter(|&&c| c != '_').collect();
+                parse_scalar(&word).ok_or_else(|| error(self.line, &format!("invalid value '{}'", word)))
+            }
+
+            None => Err(error(self.line, "expected a value")),
+        }
+    }
+}
+
+fn parse_scalar(word: &str) -> Option<Value> {
+    match word {
+        "true" => return Some(Value::Bool(true)),
+        "false" => return Some(Value::Bool(false)),
+        _ => {}
+    }
+
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, word.strip_prefix('+').unwrap_or(word)),
+    };
+
+    let int = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()
+    } else if let Some(oct) = digits.strip_prefix("0o") {
+        i64::from_str_radix(oct, 8).ok()
+    } else {
+        digits.parse::<i64>().ok()
+    };
+
+    match int {
+        Some(value) => Some(Value::Int(if negative { -value } else { value })),
+        None => word.parse::<f64>().ok().map(Value::Float),
+    }
+}
+
+// Inserts a value at a dotted key, creating intermediate tables
+fn insert(table: &mut Table, key: &[String], value: Value, line: usize) -> io::Result<()> {
+    let (last, parents) = key.split_last().unwrap();
+    let mut current = table;
+
+    for part in parents {
+        let entry = current.entry(part.clone()).or_insert_with(|| Value::Table(Table::new()));
+        current = match entry {
+            Value::Table(table) => table,
+            _ => return Err(error(line, &format!("'{}' is not a table", part))),
+        };
+    }
+
+    if current.contains_key(last) {
+        return Err(error(line, &format!("duplicate key '{}'", last)));
+    }
+    current.insert(last.clone(), value);
+    Ok(())
+}
+
+// Finds the table a [header] or [[header]] refers to, creating it if needed
+fn open_table<'a>(root: &'a mut Table, path: &[String], array: bool, line: usize) -> io::Result<&'a mut Table> {
+    let mut current = root;
+
+    for (i, part) in path.iter().enumerate() {
+        let last = i + 1 == path.len();
+        let entry = current.entry(part.clone()).or_insert_with(|| {
+            if last && array { Value::Array(Vec::new()) } else { Value::Table(Table::new()) }
+        });
+
+        current = match entry {
+            Value::Table(table) => table,
+            Value::Array(values) => {
+                if last && array {
+                    values.push(Value::Table(Table::new()));
+                }
+                match values.last_mut() {
+                    Some(Value::Table(table)) => table,
+                    _ => return Err(error(line, &format!("'{}' is not an array of tables", part))),
+                }
+            }
+            _ => return Err(error(line, &format!("'{}' is not a table", part))),
+        };
+    }
+
+    Ok(current)
+}
+
+pub fn parse(text: &str) -> io::Result<Table> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        idx: 0,
+        line: 1,
+    };
+
+    let mut root = Table::new();
+    let mut current_path: Vec<String> = Vec::new();
+
+    loop {
+        parser.skip_blank();
+        match parser.peek() {
+            None => return Ok(root),
+
+            Some('[') => {
+                parser.idx += 1;
+                let array = parser.peek() == Some('[');
+                if array {
+                    parser.idx += 1;
+                }
+
+                let path = parser.parse_key()?;
+                let closing = if array { "]]" } else { "]" };
+                for expected in closing.chars() {
+                    if parser.peek() != Some(expected) {
+                        return Err(error(parser.line, &format!("expected '{}'", closing)));
+                    }
+                    parser.idx += 1;
+                }
+                parser.end_of_line()?;
+
+                open_table(&mut root, &path, array, parser.line)?;
+                current_path = path;
+            }
+
+            Some(_) => {
+                let key = parser.parse_key()?;
+                parser.skip_spaces();
+                if parser.peek() != Some('=') {
+                    return Err(error(parser.line, "expected '='"));
+                }
+                parser.idx += 1;
+
+                let value = parser.parse_value()?;
+                parser.end_of_line()?;
+
+                // Re-open the current table without pushing a new array element
+                let line = parser.line;
+                let table = open_table(&mut root, &current_path, false, line)?;
+                insert(table, &key, value, line)?;
+            }
+        }
+    }
+}
+
+pub fn load(path: &str) -> io::Result<Table> {
+    parse(&std::fs::read_to_string(path)?)
+}
+
+fn format_key(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        key.to_string()
+    } else {
+        format_string(key)
+    }
+}
+
+fn format_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\t' => out += "\\t",
+            '\r' => out += "\\r",
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn format_inline(value: &Value) -> String {
+    match value {
+        Value::Str(text) => format_string(text),
+        Value::Int(value) => value.to_string(),
+        Value::Float(value) => format!("{:?}", value),
+        Value::Bool(flag) => flag.to_string(),
+        Value::Array(values) => format!("[{}]", values.iter().map(format_inline).collect::<Vec<_>>().join(", ")),
+        Value::Table(table) => {
+            let entries: Vec<String> = table.iter().map(|(key, value)| format!("{} = {}", format_key(key), format_inline(value))).collect();
+            format!("{{ {} }}", entries.join(", "))
+        }
+    }
+}
+
+fn is_table_array(value: &Value) -> bool {
+    matches!(value, Value::Array(values) if !values.is_empty() && values.iter().all(|value| matches!(value, Value::Table(_))))
+}
+
+fn write_table(out: &mut String, table: &Table, path: &[String]) {
+    for (key, value) in table {
+        if !matches!(value, Value::Table(_)) && !is_table_array(value) {
+            *out += &format!("{} = {}\n", format_key(key), format_inline(value));
+        }
+    }
+
+    for (key, value) in table {
+        let mut child_path = path.to_vec();
+        child_path.push(format_key(key));
+
+        match value {
+            Value::Table(child) => {
+                *out += &format!("\n[{}]\n", child_path.join("."));
+                write_table(out, child, &child_path);
+            }
+            Value::Array(values) if is_table_array(value) => {
+                for child in values.iter().filter_map(Value::as_table) {
+                    *out += &format!("\n[[{}]]\n", child_path.join("."));
+                    write_table(out, child, &child_path);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Serializes a table back to TOML text, used for config write-back
+pub fn to_string(table: &Table) -> String {
+    let mut out = String::new();
+    write_table(&mut out, table, &[]);
+    out.trim_start().to_string()
+}
@@ -0,0 +1,341 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+
+// Execution traces on disk, one fixed-size record per instruction so any instruction can be
+// read without the ones before it. Every BLOCK records get a summary of what they executed
+// and wrote, kept after the records as an index; a search only reads the blocks whose summary
+// could match, so a trace of millions of instructions is searched without loading it.
+//
+// File: "C8TR" and a version, the records, a summary per block, then the record count and
+// "C8TX" so a reader finds the index from the end.
+const MAGIC: &[u8; 4] = b"C8TR";
+const INDEX_MAGIC: &[u8; 4] = b"C8TX";
+const VERSION: u8 = 1;
+const HEADER_LEN: u64 = 8;
+const RECORD_LEN: usize = 32;
+const BLOCK: u64 = 4096; // records per summary
+const SUMMARY_LEN: usize = 16 * 8 * 2 + 2;
+
+// One executed instruction and the state it ran in
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: u16,
+    pub i: u32,
+    pub v: [u8; 16],
+    pub writes: Option<Range<u32>>, // memory the instruction stores to
+    pub registers: u16,             // bit per V register the instruction can set
+}
+
+impl TraceEntry {
+    pub fn new(pc: u16, opcode: u16, i: u32, v: [u8; 16]) -> TraceEntry {
+        let (x, y) = ((opcode >> 8 & 0xF) as u32, (opcode >> 4 & 0xF) as u32);
+        let span = |from: u32, to: u32| (1u32 << (to + 1)) as u16 - (1u32 << from) as u16;
+        let registers = match (opcode >> 12, opcode & 0xF, opcode & 0xFF) {
+            (0x6 | 0x7 | 0xC, _, _) | (0x8, 0x0, _) | (0xF, _, 0x07 | 0x0A) => 1 << x,
+            (0x8, _, _) => 1 << x | 1 << 0xF,
+            (0xD, _, _) => 1 << 0xF,
+            (0x5, 0x3, _) if y > x => span(x, y - 1), // flags into Vx up to Vy
+            (0xF, _, 0x65 | 0x85) => span(0, x),
+            _ => 0,
+        };
+        let writes = match (opcode >> 12, opcode & 0xF, opcode & 0xFF) {
+            (0xF, _, 0x33) => Some(i..i + 3),
+            (0xF, _, 0x55) => Some(i..i + x + 1),
+            _ => None,
+        };
+        TraceEntry { pc, opcode, i, v, writes, registers }
+    }
+
+    fn encode(&self) -> [u8; RECORD_LEN] {
+        let mut record = [0; RECORD_LEN];
+        record[0..2].copy_from_slice(&self.pc.to_le_bytes());
+        record[2..4].copy_from_slice(&self.opcode.to_le_bytes());
+        record[4..8].copy_from_slice(&self.i.to_le_bytes());
+        record[8..24].copy_from_slice(&self.v);
+        if let Some(ref writes) = self.writes {
+            record[24..28].copy_from_slice(&writes.start.to_le_bytes());
+            record[28] = (writes.end - writes.start) as u8;
+        }
+        record[29..31].copy_from_slice(&self.registers.to_le_bytes());
+        record
+    }
+
+    fn decode(record: &[u8]) -> TraceEntry {
+        let start = u32::from_le_bytes([record[24], record[25], record[26], record[27]]);
+        TraceEntry {
+            pc: u16::from_le_bytes([record[0], record[1]]),
+            opcode: u16::from_le_bytes([record[2], record[3]]),
+            i: u32::from_le_bytes([record[4], record[5], record[6], record[7]]),
+            v: record[8..24].try_into().unwrap(),
+            writes: (record[28] > 0).then(|| start..start + record[28] as u32),
+            registers: u16::from_le_bytes([record[29], record[30]]),
+        }
+    }
+}
+
+// What a block of records did, as 1024-bit sets of hashed PCs and written addresses (which
+// can say maybe but never miss) and the V registers set
+#[derive(Clone, Default)]
+struct Summary {
+    pcs: [u64; 16],
+    writes: [u64; 16],
+    registers: u16,
+}
+
+fn bit(value: u32) -> (usize, u64) {
+    let hash = value.wrapping_mul(0x9E37_79B1) >> 22; // 10 bits
+    ((hash >> 6) as usize, 1 << (hash & 63))
+}
+
+impl Summary {
+    fn add(&mut self, entry: &TraceEntry) {
+        let (word, mask) = bit(entry.pc as u32);
+        self.pcs[word] |= mask;
+        for address in entry.writes.clone().unwrap_or(0..0) {
+            let (word, mask) = bit(address);
+            self.writes[word] |= mask;
+        }
+        self.registers |= entry.registers;
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out: Vec<u8> = self.pcs.iter().chain(self.writes.iter()).flat_map(|word| word.to_le_bytes()).collect();
+        out.extend(self.registers.to_le_bytes());
+        out
+    }
+
+    fn decode(bytes: &[u8]) -> Summary {
+        let word = |idx: usize| u64::from_le_bytes(bytes[idx * 8..idx * 8 + 8].try_into().unwrap());
+        Summary {
+            pcs: std::array::from_fn(word),
+            writes: std::array::from_fn(|idx| word(16 + idx)),
+            registers: u16::from_le_bytes([bytes[SUMMARY_LEN - 2], bytes[SUMMARY_LEN - 1]]),
+        }
+    }
+}
+
+// What to look for in a trace: `pc=0x2A4`, `V5` or `write=0x300`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceQuery {
+    Pc(u16),
+    Register(u8),
+    Write(u32),
+}
+
+impl TraceQuery {
+    pub fn parse(text: &str) -> Result<TraceQuery, String> {
+        let hex = |value: &str| u32::from_str_radix(value.trim().trim_start_matches("0x").trim_start_matches("0X"), 16).ok();
+        let query = match text.trim().split_once('=') {
+            Some(("pc", address)) => hex(address).and_then(|address| u16::try_from(address).ok()).map(TraceQuery::Pc),
+            Some(("write", address)) => hex(address).map(TraceQuery::Write),
+            None => text.trim().strip_prefix(['V', 'v']).and_then(hex).filter(|&x| x < 16).map(|x| TraceQuery::Register(x as u8)),
+            _ => None,
+        };
+        query.ok_or_else(|| format!("invalid search {}, expected pc=addr, Vx or write=addr", text))
+    }
+
+    pub fn matches(&self, entry: &TraceEntry) -> bool {
+        match *self {
+            TraceQuery::Pc(pc) => entry.pc == pc,
+            TraceQuery::Register(x) => entry.registers & 1 << x != 0,
+            TraceQuery::Write(address) => entry.writes.as_ref().is_some_and(|writes| writes.contains(&address)),
+        }
+    }
+
+    fn may_match(&self, summary: &Summary) -> bool {
+        let test = |set: &[u64; 16], value: u32| {
+            let (word, mask) = bit(value);
+            set[word] & mask != 0
+        };
+        match *self {
+            TraceQuery::Pc(pc) => test(&summary.pcs, pc as u32),
+            TraceQuery::Register(x) => summary.registers & 1 << x != 0,
+            TraceQuery::Write(address) => test(&summary.writes, address),
+        }
+    }
+}
+
+// Records a trace as the CPU runs. Write errors are kept for finish() rather than stopping
+// the CPU.
+pub struct TraceWriter {
+    out: BufWriter<File>,
+    count: u64,
+    summaries: Vec<Summary>,
+    error: Option<io::Error>,
+}
+
+impl TraceWriter {
+    pub fn create(path: &str) -> io::Result<TraceWriter> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION, 0, 0, 0])?;
+        Ok(TraceWriter { out, count: 0, summaries: Vec::new(), error: None })
+    }
+
+    pub fn record(&mut self, entry: &TraceEntry) {
+        if self.count.is_multiple_of(BLOCK) {
+            self.summaries.push(Summary::default());
+        }
+        self.summaries.last_mut().unwrap().add(entry);
+        self.count += 1;
+
+        if self.error.is_none() {
+            self.error = self.out.write_all(&entry.encode()).err();
+        }
+    }
+
+    // Writes the index and returns how many instructions were recorded
+    pub fn finish(mut self) -> io::Result<u64> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        for summary in self.summaries.iter() {
+            self.out.write_all(&summary.encode())?;
+        }
+        self.out.write_all(&self.count.to_le_bytes())?;
+        self.out.write_all(INDEX_MAGIC)?;
+        self.out.flush()?;
+        Ok(self.count)
+    }
+}
+
+// Reads a finished trace, record by record, keeping only the index in memory
+pub struct TraceReader {
+    file: File,
+    count: u64,
+    summaries: Vec<Summary>,
+    pub position: u64, // the record last looked at, where searches start from
+}
+
+impl TraceReader {
+    pub fn open(path: &str) -> io::Result<TraceReader> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{} isn't a finished trace: {}", path, message));
+        let mut file = File::open(path)?;
+
+        let mut header = [0; HEADER_LEN as usize];
+        file.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(invalid("wrong header"));
+        }
+
+        let mut trailer = [0; 12];
+        file.seek(SeekFrom::End(-12))?;
+        file.read_exact(&mut trailer)?;
+        if &trailer[8..] != INDEX_MAGIC {
+            return Err(invalid("no index, was recording stopped?"));
+        }
+        let count = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+
+        let mut index = vec![0; count.div_ceil(BLOCK) as usize * SUMMARY_LEN];
+        file.seek(SeekFrom::Start(HEADER_LEN + count * RECORD_LEN as u64))?;
+        file.read_exact(&mut index).map_err(|_| invalid("index cut short"))?;
+        let summaries = index.chunks_exact(SUMMARY_LEN).map(Summary::decode).collect();
+
+        Ok(TraceReader { file, count, summaries, position: 0 })
+    }
+
+    // Instructions in the trace
+    pub fn len(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn read(&mut self, n: u64) -> io::Result<TraceEntry> {
+        Ok(self.read_range(n..n + 1)?.remove(0))
+    }
+
+    fn read_range(&mut self, range: Range<u64>) -> io::Result<Vec<TraceEntry>> {
+        let range = range.start..range.end.min(self.count);
+        if range.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("the trace has {} instructions", self.count)));
+        }
+
+        let mut records = vec![0; (range.end - range.start) as usize * RECORD_LEN];
+        self.file.seek(SeekFrom::Start(HEADER_LEN + range.start * RECORD_LEN as u64))?;
+        self.file.read_exact(&mut records)?;
+        Ok(records.chunks_exact(RECORD_LEN).map(TraceEntry::decode).collect())
+    }
+
+    // The nearest record after (or before, going backward) the given one that matches, read
+    // a block at a time and skipping the blocks whose summary rules them out
+    pub fn find(&mut self, query: &TraceQuery, from: u64, backward: bool) -> io::Result<Option<u64>> {
+        let blocks: Vec<u64> = if backward {
+            (0..=from.min(self.count) / BLOCK).rev().collect()
+        } else {
+            (from / BLOCK..self.count.div_ceil(BLOCK)).collect()
+        };
+
+        for block in blocks {
+            if !query.may_match(&self.summaries[block as usize]) {
+                continue;
+            }
+
+            // Only the part of the block past the starting record
+            let range = if backward { block * BLOCK..(block * BLOCK + BLOCK).min(from) } else { (block * BLOCK).max(from + 1)..block * BLOCK + BLOCK };
+            if range.start >= range.end.min(self.count) {
+                continue;
+            }
+            let entries = self.read_range(range.clone())?;
+            let found = if backward { entries.iter().rposition(|entry| query.matches(entry)) } else { entries.iter().position(|entry| query.matches(entry)) };
+            if let Some(idx) = found {
+                return Ok(Some(range.start + idx as u64));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_know_what_they_write() {
+        let entry = TraceEntry::new(0x200, 0xF355, 0x300, [0; 16]);
+        assert_eq!((entry.writes, entry.registers), (Some(0x300..0x304), 0));
+        assert_eq!(TraceEntry::new(0x200, 0x8124, 0, [0; 16]).registers, 1 << 1 | 1 << 0xF);
+        assert_eq!(TraceEntry::new(0x200, 0xF265, 0, [0; 16]).registers, 0b111);
+        let entry = TraceEntry::new(0x2A4, 0xF533, 0x123456, [7; 16]);
+        assert_eq!(TraceEntry::decode(&entry.encode()), entry);
+
+        assert_eq!(TraceQuery::parse("V5"), Ok(TraceQuery::Register(5)));
+        assert_eq!(TraceQuery::parse("pc=0x2A4"), Ok(TraceQuery::Pc(0x2A4)));
+        assert_eq!(TraceQuery::parse("write=300"), Ok(TraceQuery::Write(0x300)));
+        assert!(TraceQuery::parse("VG").is_err());
+    }
+
+    #[test]
+    fn searches_find_the_nearest_match_across_blocks() {
+        let path = std::env::temp_dir().join("chip8-trace-search.c8t");
+        let path = path.to_str().unwrap();
+        let mut writer = TraceWriter::create(path).unwrap();
+        for n in 0..3 * BLOCK {
+            // A loop of LD V1, 1 and ADD I, V0, with a store to 0x300 twice
+            let (pc, opcode) = match n {
+                100 | 9000 => (0x2A4, 0xF055),
+                _ if n % 2 == 0 => (0x200, 0x6101),
+                _ => (0x202, 0xF01E),
+            };
+            writer.record(&TraceEntry::new(pc, opcode, 0x300, [0; 16]));
+        }
+        assert_eq!(writer.finish().unwrap(), 3 * BLOCK);
+
+        let mut reader = TraceReader::open(path).unwrap();
+        assert_eq!(reader.len(), 3 * BLOCK);
+        assert_eq!(reader.read(9000).unwrap().opcode, 0xF055);
+        let store = TraceQuery::Write(0x300);
+        assert_eq!(reader.find(&store, 3 * BLOCK - 1, true).unwrap(), Some(9000));
+        assert_eq!(reader.find(&store, 9000, true).unwrap(), Some(100));
+        assert_eq!(reader.find(&store, 100, false).unwrap(), Some(9000));
+        assert_eq!(reader.find(&store, 9000, false).unwrap(), None);
+        assert_eq!(reader.find(&TraceQuery::Pc(0x2A4), 50, false).unwrap(), Some(100));
+        assert_eq!(reader.find(&TraceQuery::Register(1), 9000, true).unwrap(), Some(8998));
+        assert_eq!(reader.find(&TraceQuery::Register(2), 9000, true).unwrap(), None);
+        _ = std::fs::remove_file(path);
+    }
+}
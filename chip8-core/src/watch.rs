@@ -0,0 +1,53 @@
+use crate::expr::{self, Expr, ExprContext};
+
+pub struct Watch {
+    pub source: String,
+    expr: Expr,
+    last: Option<i64>,
+}
+
+// Expressions re-evaluated every frame, e.g. "V3", "mem[0x3F0]", "I+V2"
+#[derive(Default)]
+pub struct WatchPanel {
+    pub watches: Vec<Watch>,
+}
+
+impl WatchPanel {
+    pub fn add(&mut self, source: &str) -> Result<(), String> {
+        let expr = expr::parse(source)?;
+        self.watches.push(Watch {
+            source: source.to_string(),
+            expr,
+            last: None,
+        });
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    // Re-evaluates every watch, returning the panel line if any value changed
+    pub fn update(&mut self, ctx: &dyn ExprContext) -> Option<String> {
+        let mut changed = false;
+        for watch in self.watches.iter_mut() {
+            let value = watch.expr.eval(ctx);
+            changed |= watch.last != Some(value);
+            watch.last = Some(value);
+        }
+
+        if changed { Some(self.format()) } else { None }
+    }
+
+    pub fn format(&self) -> String {
+        let values: Vec<String> = self
+            .watches
+            .iter()
+            .map(|watch| match watch.last {
+                Some(value) => format!("{} = {} ({:#X})", watch.source, value, value),
+                None => format!("{} = ?", watch.source),
+            })
+            .collect();
+        format!("watch: {}", values.join(" | "))
+    }
+}
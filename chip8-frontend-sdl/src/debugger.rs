@@ -0,0 +1,368 @@
+use crate::print_messages;
+use chip8_core::cpu::RUNTIME_QUIRKS;
+use chip8_core::disasm;
+use chip8_core::expr::ExprContext;
+use chip8_core::halt::HaltCondition;
+use chip8_core::trace::{TraceEntry, TraceQuery, TraceReader};
+use chip8_core::Chip8;
+use std::io;
+
+const CYCLES_PER_FRAME: u64 = 11; // ~700hz
+const CONTINUE_LIMIT: u64 = 10_000_000; // cycles before `continue` gives up waiting for a breakpoint
+
+const HELP: &str = "\
+step [n]          (s)  run n instructions
+continue          (c)  run until a breakpoint, a tight loop or the CPU halts
+break <addr|cond> (b)  stop at an address or a halt condition, e.g. when=V3==2
+delete <n>             remove breakpoint n
+breakpoints            list breakpoints
+watch <expr>           print expr whenever it changes
+regs              (r)  registers and timers
+x <addr> [len]         dump memory
+disasm [addr] [n] (d)  disassemble n instructions, from PC by default
+screen                 print the framebuffer
+reset                  restart the program as it was loaded
+reload                 load a rebuilt ROM, keeping breakpoints on unchanged code
+quirks                 list the quirks that can be flipped while running
+quirk <name>           flip a quirk, e.g. quirk vf_reset
+blocks                 start counting basic blocks, then print the hottest
+trace record <file>    record every instruction from here on, until trace stop
+trace open <file>      open a recorded trace to search
+trace [n]              show instruction n of the open trace, or the current one
+trace prev|next <what> search for pc=addr, Vx (a write to it) or write=addr
+alias [name = cmd]     define or list aliases
+history                list earlier commands, rerun them with !! or !n
+source <file>          run commands from a file
+quit              (q)";
+
+// Parses a decimal or 0x-prefixed hex number
+fn parse_number(text: &str) -> Option<u16> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+// Terminal debugger command interpreter. Lines go through history recall ("!!", "!n") and
+// aliases before running; `c8 debug` feeds it lines and keeps history between sessions.
+#[derive(Default)]
+pub struct Debugger {
+    pub breakpoints: Vec<HaltCondition>,
+    pub aliases: Vec<(String, String)>,
+    pub history: Vec<String>,
+    pub rom_path: Option<String>, // what reload reads
+    pub trace: Option<TraceReader>, // the trace being searched
+}
+
+impl Debugger {
+    // Runs a typed line, recording it in the history. Returns false once the session should end.
+    pub fn execute(&mut self, cpu: &mut Chip8, line: &str) -> bool {
+        let line = line.trim();
+        let line = match self.recall(line) {
+            Ok(line) => line,
+            Err(e) => {
+                println!("{}", e);
+                return true;
+            }
+        };
+
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        self.run(cpu, &line)
+    }
+
+    // Runs every line of a script, e.g. a .dbgrc, without touching the history
+    pub fn source(&mut self, cpu: &mut Chip8, path: &str) -> io::Result<bool> {
+        let script = std::fs::read_to_string(path)?;
+        for line in script.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            if !self.run(cpu, line) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn recall(&self, line: &str) -> Result<String, String> {
+        let Some(reference) = line.strip_prefix('!') else {
+            return Ok(line.to_string());
+        };
+
+        let entry = if reference == "!" {
+            self.history.last()
+        } else {
+            reference.parse::<usize>().ok().and_then(|n| self.history.get(n.wrapping_sub(1)))
+        };
+
+        match entry {
+            Some(entry) => {
+                println!("{}", entry);
+                Ok(entry.clone())
+            }
+            None => Err(format!("no history entry {}", line)),
+        }
+    }
+
+    // Replaces the first word if it names an alias
+    fn expand(&self, line: &str) -> String {
+        let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match self.aliases.iter().find(|(name, _)| name == word) {
+            Some((_, command)) => format!("{} {}", command, rest).trim().to_string(),
+            None => line.to_string(),
+        }
+    }
+
+    fn run(&mut self, cpu: &mut Chip8, line: &str) -> bool {
+        let line = self.expand(line);
+        let (command, args) = line.split_once(' ').map_or((line.as_str(), ""), |(command, args)| (command, args.trim()));
+        let number = |text: &str, default: u16| if text.is_empty() { Some(default) } else { parse_number(text) };
+
+        match command {
+            "" => {}
+            "s" | "step" => match number(args, 1) {
+                Some(count) => {
+                    for _ in 0..count {
+                        cpu.step();
+                    }
+                    self.stopped(cpu);
+                }
+                None => println!("step expects a count"),
+            },
+            "c" | "continue" => {
+                cpu.step(); // leave a breakpoint at the current PC
+                let mut conditions = std::mem::take(&mut self.breakpoints);
+                conditions.push(HaltCondition::InfiniteLoop);
+                conditions.push(HaltCondition::MaxCycles(CONTINUE_LIMIT));
+                let result = cpu.run_headless(&conditions, CYCLES_PER_FRAME);
+                conditions.truncate(conditions.len() - 2);
+                self.breakpoints = conditions;
+
+                println!("stopped: {} after {} cycles", result.reason, result.cycles + 1);
+                self.stopped(cpu);
+            }
+            "b" | "break" => {
+                let condition = match parse_number(args) {
+                    Some(address) => Ok(HaltCondition::PcEquals(address)),
+                    None => HaltCondition::parse(args),
+                };
+                match condition {
+                    Ok(condition) => {
+                        println!("breakpoint {}: {}", self.breakpoints.len() + 1, condition.describe());
+                        self.breakpoints.push(condition);
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "delete" => match args.parse::<usize>() {
+                Ok(n) if (1..=self.breakpoints.len()).contains(&n) => _ = self.breakpoints.remove(n - 1),
+                _ => println!("no breakpoint {}", args),
+            },
+            "breakpoints" => {
+                for (idx, condition) in self.breakpoints.iter().enumerate() {
+                    println!("{}: {}", idx + 1, condition.describe());
+                }
+            }
+            "watch" => match cpu.watches.add(args) {
+                Ok(()) => self.print_watches(cpu),
+                Err(e) => println!("{}", e),
+            },
+            "r" | "regs" => print_registers(cpu),
+            "x" => {
+                let mut args = args.split_whitespace();
+                match (args.next().and_then(parse_number), number(args.next().unwrap_or(""), 16)) {
+                    (Some(address), Some(len)) => print_memory(cpu, address, len),
+                    _ => println!("x expects an address and an optional length"),
+                }
+            }
+            "d" | "disasm" => {
+                let mut args = args.split_whitespace();
+                match (number(args.next().unwrap_or(""), cpu.pc()), number(args.next().unwrap_or(""), 8)) {
+                    (Some(address), Some(count)) => print_disassembly(cpu, address, count),
+                    _ => println!("disasm expects an address and an optional count"),
+                }
+            }
+            "screen" => println!("{}", cpu.display().to_ascii()),
+            "reset" => {
+                cpu.reset();
+                self.stopped(cpu);
+            }
+            "reload" => match self.rom_path.as_deref().map(std::fs::read) {
+                Some(Ok(rom)) => self.reload(cpu, &rom),
+                Some(Err(e)) => println!("can't read the ROM: {}", e),
+                None => println!("no ROM to reload"),
+            },
+            "blocks" => match cpu.block_report() {
+                Some(report) => println!("{}", report),
+                None => {
+                    cpu.enable_block_stats();
+                    println!("counting basic blocks");
+                }
+            },
+            "quirks" => {
+                for name in RUNTIME_QUIRKS {
+                    println!("{:<21} {}", name, cpu.quirks().setting(name).unwrap_or("?"));
+                }
+            }
+            "quirk" => {
+                if cpu.toggle_quirk(args).is_none() {
+                    println!("{} can't be flipped while running, see quirks", args);
+                }
+            }
+            "trace" => self.trace(cpu, args),
+            "alias" => match args.split_once('=') {
+                Some((name, command)) => {
+                    let (name, command) = (name.trim().to_string(), command.trim().to_string());
+                    self.aliases.retain(|(existing, _)| *existing != name);
+                    self.aliases.push((name, command));
+                }
+                None => {
+                    for (name, command) in self.aliases.iter() {
+                        println!("{} = {}", name, command);
+                    }
+                }
+            },
+            "history" => {
+                for (idx, entry) in self.history.iter().enumerate() {
+                    println!("{:>4}  {}", idx + 1, entry);
+                }
+            }
+            "source" => match self.source(cpu, args) {
+                Ok(keep_going) => return keep_going,
+                Err(e) => println!("can't read {}: {}", args, e),
+            },
+            "help" | "?" => println!("{}", HELP),
+            "q" | "quit" => return false,
+            _ => println!("unknown command {}, try help", command),
+        }
+
+        print_messages(cpu);
+        true
+    }
+
+    // Restarts on a new build of the ROM. Address breakpoints inside code that changed are
+    // dropped, since whatever they stopped at may have moved.
+    fn reload(&mut self, cpu: &mut Chip8, rom: &[u8]) {
+        let changes = cpu.rom_changes(rom);
+        cpu.load_program(rom);
+        self.breakpoints.retain(|condition| match condition {
+            HaltCondition::PcEquals(address) if changes.iter().any(|range| range.contains(address)) => {
+                println!("dropped {}, the code there changed", condition.describe());
+                false
+            }
+            _ => true,
+        });
+
+        let changed: usize = changes.iter().map(|range| range.len()).sum();
+        println!("reloaded, {} bytes changed", changed);
+        self.stopped(cpu);
+    }
+
+    fn trace(&mut self, cpu: &mut Chip8, args: &str) {
+        let (command, args) = args.split_once(' ').map_or((args, ""), |(command, args)| (command, args.trim()));
+        match command {
+            "record" if !args.is_empty() => match cpu.start_trace(args) {
+                Ok(()) => println!("recording to {}", args),
+                Err(e) => println!("can't create {}: {}", args, e),
+            },
+            "stop" => match cpu.stop_trace() {
+                Some(Ok(count)) => println!("recorded {} instructions", count),
+                Some(Err(e)) => println!("recording failed: {}", e),
+                None => println!("not recording"),
+            },
+            "open" if !args.is_empty() => match TraceReader::open(args) {
+                Ok(trace) => {
+                    println!("{} instructions", trace.len());
+                    self.trace = Some(trace);
+                }
+                Err(e) => println!("can't open {}: {}", args, e),
+            },
+            "prev" | "next" => {
+                let Some(ref mut trace) = self.trace else {
+                    println!("no trace open, try trace open <file>");
+                    return;
+                };
+                let query = match TraceQuery::parse(args) {
+                    Ok(query) => query,
+                    Err(e) => {
+                        println!("{}", e);
+                        return;
+                    }
+                };
+                match trace.find(&query, trace.position, command == "prev") {
+                    Ok(Some(n)) => {
+                        trace.position = n;
+                        print_trace_entry(trace, n);
+                    }
+                    Ok(None) => println!("not found"),
+                    Err(e) => println!("can't read the trace: {}", e),
+                }
+            }
+            _ => match (self.trace.as_mut(), args.is_empty()) {
+                (Some(trace), true) => {
+                    let n = if command.is_empty() { Some(trace.position) } else { command.parse().ok() };
+                    match n {
+                        Some(n) => {
+                            trace.position = n;
+                            print_trace_entry(trace, n);
+                        }
+                        None => println!("trace expects record, stop, open, prev, next or an instruction number"),
+                    }
+                }
+                _ => println!("no trace open, try trace open <file>"),
+            },
+        }
+    }
+
+    // Shows where execution stopped and any watches that changed
+    fn stopped(&self, cpu: &mut Chip8) {
+        print_disassembly(cpu, cpu.pc(), 1);
+        self.print_watches(cpu);
+    }
+
+    fn print_watches(&self, cpu: &mut Chip8) {
+        let mut watches = std::mem::take(&mut cpu.watches);
+        if let Some(line) = watches.update(cpu) {
+            println!("{}", line);
+        }
+        cpu.watches = watches;
+    }
+}
+
+fn print_registers(cpu: &Chip8) {
+    let v: Vec<String> = (0..16).map(|idx| format!("V{:X}={:02X}", idx, cpu.register(idx))).collect();
+    println!("{}", v[..8].join(" "));
+    println!("{}", v[8..].join(" "));
+    println!("PC={:#06X} I={:#06X} SP={} DT={} ST={}", cpu.pc(), cpu.index(), cpu.sp(), cpu.delay(), cpu.sound());
+}
+
+fn print_memory(cpu: &Chip8, address: u16, len: u16) {
+    for row in (0..len).step_by(16) {
+        let start = address.wrapping_add(row);
+        let bytes: Vec<String> = (0..16.min(len - row)).map(|idx| format!("{:02X}", cpu.memory(start.wrapping_add(idx)))).collect();
+        println!("{:#06X}  {}", start, bytes.join(" "));
+    }
+}
+
+fn print_trace_entry(trace: &mut TraceReader, n: u64) {
+    let entry: TraceEntry = match trace.read(n) {
+        Ok(entry) => entry,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    println!("#{} of {}  {:#06X}  {:04X}  {}", n, trace.len(), entry.pc, entry.opcode, disasm::disassemble(entry.opcode, 0));
+    let v: Vec<String> = entry.v.iter().enumerate().map(|(idx, value)| format!("V{:X}={:02X}", idx, value)).collect();
+    println!("{}", v[..8].join(" "));
+    println!("{} I={:#06X}", v[8..].join(" "), entry.i);
+}
+
+fn print_disassembly(cpu: &Chip8, mut address: u16, count: u16) {
+    let word = |address: u16| (cpu.memory(address) as u16) << 8 | cpu.memory(address.wrapping_add(1)) as u16;
+    for _ in 0..count {
+        let (instruction, long) = (word(address), word(address.wrapping_add(2)));
+        let marker = if address == cpu.pc() { "->" } else { "  " };
+        println!("{} {:#06X}  {:04X}  {}", marker, address, instruction, disasm::disassemble(instruction, long));
+        address = address.wrapping_add(disasm::instruction_size(instruction));
+    }
+}
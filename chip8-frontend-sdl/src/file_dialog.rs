@@ -0,0 +1,44 @@
+use std::process::Command;
+
+// Asks the desktop for a ROM through its own file picker, without linking a GUI toolkit:
+// zenity or kdialog on Linux, AppleScript on macOS and a WinForms dialog through PowerShell
+// on Windows. None if the user cancels or no picker is available.
+pub fn pick_rom() -> Option<String> {
+    const TITLE: &str = "Open a CHIP-8 ROM";
+
+    let pickers: Vec<Command> = if cfg!(target_os = "macos") {
+        vec![command("osascript", &["-e", &format!("POSIX path of (choose file with prompt \"{}\")", TITLE)])]
+    } else if cfg!(windows) {
+        let script = format!(
+            "Add-Type -AssemblyName System.Windows.Forms; $d = New-Object System.Windows.Forms.OpenFileDialog; \
+             $d.Title = '{}'; $d.Filter = 'CHIP-8 ROMs|*.ch8;*.c8b;*.sc8;*.xo8|All files|*.*'; \
+             if ($d.ShowDialog() -eq 'OK') {{ $d.FileName }}",
+            TITLE
+        );
+        vec![command("powershell", &["-NoProfile", "-Command", &script])]
+    } else {
+        vec![
+            command("zenity", &["--file-selection", &format!("--title={}", TITLE), "--file-filter=CHIP-8 ROMs | *.ch8 *.c8b *.sc8 *.xo8", "--file-filter=All files | *"]),
+            command("kdialog", &["--getopenfilename", ".", "*.ch8 *.c8b *.sc8 *.xo8|CHIP-8 ROMs", "--title", TITLE]),
+        ]
+    };
+
+    for mut picker in pickers {
+        // A picker that isn't installed fails to spawn, so try the next one; one that ran and
+        // printed nothing was cancelled
+        let Ok(output) = picker.output() else {
+            continue;
+        };
+        let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        return if output.status.success() && !path.is_empty() { Some(path) } else { None };
+    }
+
+    println!("no file picker available, drop a ROM on the window instead");
+    None
+}
+
+fn command(program: &str, args: &[&str]) -> Command {
+    let mut command = Command::new(program);
+    command.args(args);
+    command
+}
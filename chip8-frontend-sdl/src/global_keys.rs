@@ -0,0 +1,87 @@
+use chip8_core::event::Hotkey;
+use std::io;
+use std::sync::mpsc::Receiver;
+
+// Media keys pressed anywhere on the desktop, to pause or mute c8 while another window has
+// focus, e.g. when it runs on a second display during a demo. Built with --features
+// global-hotkeys and turned on with global_hotkeys = true in config.toml. The keys are read
+// straight from the kernel's input devices, which takes read access to /dev/input (usually
+// membership of the input group).
+pub struct GlobalKeys {
+    receiver: Receiver<Hotkey>,
+}
+
+impl GlobalKeys {
+    // Listens on every readable /dev/input/event* device, one thread each
+    #[cfg(all(feature = "global-hotkeys", target_os = "linux"))]
+    pub fn listen() -> io::Result<GlobalKeys> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut listening = 0;
+        for entry in std::fs::read_dir("/dev/input")? {
+            let path = entry?.path();
+            if !path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("event")) {
+                continue;
+            }
+            let Ok(device) = std::fs::File::open(&path) else {
+                continue;
+            };
+            let sender = sender.clone();
+            std::thread::spawn(move || evdev::forward(device, sender));
+            listening += 1;
+        }
+
+        if listening == 0 {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "no readable devices in /dev/input, is the user in the input group?"));
+        }
+        Ok(GlobalKeys { receiver })
+    }
+
+    #[cfg(not(all(feature = "global-hotkeys", target_os = "linux")))]
+    pub fn listen() -> io::Result<GlobalKeys> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "c8 was built without the global-hotkeys feature (Linux only)"))
+    }
+
+    // Keys pressed since the last call
+    pub fn pressed(&self) -> Vec<Hotkey> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+#[cfg(all(feature = "global-hotkeys", target_os = "linux"))]
+mod evdev {
+    use chip8_core::event::Hotkey;
+    use std::io::Read;
+    use std::sync::mpsc::Sender;
+
+    // struct input_event: a timeval of two longs, then type, code and value
+    const TIME_SIZE: usize = 2 * std::mem::size_of::<std::ffi::c_long>();
+    const EVENT_SIZE: usize = TIME_SIZE + 8;
+
+    const EV_KEY: u16 = 0x01;
+    const KEY_MUTE: u16 = 113;
+    const KEY_PLAYPAUSE: u16 = 164;
+    const KEY_PLAYCD: u16 = 200;
+    const KEY_PAUSECD: u16 = 201;
+
+    // Sends the hotkeys for media key presses until the device goes away or c8 stops listening
+    pub fn forward(mut device: std::fs::File, sender: Sender<Hotkey>) {
+        let mut event = [0; EVENT_SIZE];
+        while device.read_exact(&mut event).is_ok() {
+            let kind = u16::from_ne_bytes([event[TIME_SIZE], event[TIME_SIZE + 1]]);
+            let code = u16::from_ne_bytes([event[TIME_SIZE + 2], event[TIME_SIZE + 3]]);
+            let value = i32::from_ne_bytes([event[TIME_SIZE + 4], event[TIME_SIZE + 5], event[TIME_SIZE + 6], event[TIME_SIZE + 7]]);
+            if kind != EV_KEY || value != 1 {
+                continue; // only presses, not releases or autorepeat
+            }
+
+            let hotkey = match code {
+                KEY_PLAYPAUSE | KEY_PLAYCD | KEY_PAUSECD => Hotkey::Pause,
+                KEY_MUTE => Hotkey::Mute,
+                _ => continue,
+            };
+            if sender.send(hotkey).is_err() {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::{Duration, Instant};
+
+// Diagnostic for --latency: every key press flashes the next presented frame, and the time
+// from the SDL event to that present is recorded
+#[derive(Default)]
+pub struct LatencyProbe {
+    pending: Option<Instant>,
+    samples: Vec<Duration>,
+}
+
+impl LatencyProbe {
+    pub fn key_pressed(&mut self, time: Instant) {
+        // Keep the earliest press if several arrive before a present
+        self.pending.get_or_insert(time);
+    }
+
+    // Whether the frame about to be drawn should flash
+    pub fn flash_due(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn presented(&mut self, time: Instant) {
+        if let Some(pressed) = self.pending.take() {
+            self.samples.push(time.saturating_duration_since(pressed));
+        }
+    }
+
+    pub fn report(&self) -> String {
+        if self.samples.is_empty() {
+            return "latency: no key presses measured".to_string();
+        }
+
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        let percentile = |p: usize| sorted[(sorted.len() - 1) * p / 100];
+        let average = sorted.iter().sum::<Duration>() / sorted.len() as u32;
+
+        format!(
+            "latency over {} presses: avg {:.1?}, p50 {:.1?}, p95 {:.1?}, p99 {:.1?}, max {:.1?}",
+            sorted.len(),
+            average,
+            percentile(50),
+            percentile(95),
+            percentile(99),
+            sorted[sorted.len() - 1],
+        )
+    }
+}
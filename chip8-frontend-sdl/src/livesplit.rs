@@ -0,0 +1,179 @@
+use chip8_core::speedrun::SplitEvent;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+// WebSocket server that LiveSplit One connects to ("Connect to Server"), sending its JSON
+// timer commands so splits follow the emulator's speedrun timer
+pub struct LiveSplitServer {
+    listener: TcpListener,
+    clients: Vec<TcpStream>,
+}
+
+impl LiveSplitServer {
+    pub fn bind(address: &str) -> io::Result<LiveSplitServer> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(LiveSplitServer {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    // Accepts pending connections, call once per frame
+    pub fn poll(&mut self) {
+        while let Ok((stream, address)) = self.listener.accept() {
+            match handshake(stream) {
+                Ok(stream) => {
+                    println!("livesplit: {} connected", address);
+                    self.clients.push(stream);
+                }
+                Err(e) => println!("livesplit: handshake with {} failed: {}", address, e),
+            }
+        }
+    }
+
+    pub fn send(&mut self, event: SplitEvent) {
+        let commands: Vec<String> = match event {
+            SplitEvent::Start => vec![
+                command("start"),
+                command("initializeGameTime"),
+                command("pauseGameTime"),
+            ],
+            SplitEvent::Split(_, frames) | SplitEvent::Finish(frames) => vec![
+                format!("{{\"command\":\"setGameTime\",\"time\":\"{}\"}}", game_time(frames)),
+                command("split"),
+            ],
+            SplitEvent::Reset => vec![command("reset")],
+        };
+
+        self.clients.retain_mut(|client| commands.iter().all(|text| client.write_all(&text_frame(text)).is_ok()));
+    }
+}
+
+fn command(name: &str) -> String {
+    format!("{{\"command\":\"{}\"}}", name)
+}
+
+// Frames as LiveSplit's h:mm:ss.fff time span
+fn game_time(frames: u64) -> String {
+    let millis = frames * 1000 / 60;
+    format!("{}:{:02}:{:02}.{:03}", millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60, millis % 1000)
+}
+
+// Reads the HTTP upgrade request and answers it
+fn handshake(mut stream: TcpStream) -> io::Result<TcpStream> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))?;
+
+    let mut request: Vec<u8> = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 || request.len() > 16 * 1024 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "incomplete upgrade request"));
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let key = request
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-key"))
+        .map(|(_, value)| value.trim().to_string())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a websocket upgrade"))?;
+
+    let accept = base64(&sha1(format!("{}258EAFA5-E914-47DA-95CA-C5AB0DC85B11", key).as_bytes()));
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    )?;
+
+    stream.set_nonblocking(true)?;
+    Ok(stream)
+}
+
+// Unmasked server-to-client text frame
+fn text_frame(text: &str) -> Vec<u8> {
+    let mut frame = vec![0x81];
+    match text.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len < 0x10000 => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(text.as_bytes());
+    frame
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([chunk[i * 4], chunk[i * 4 + 1], chunk[i * 4 + 2], chunk[i * 4 + 3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (i, word) in h.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let triple = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(triple >> (18 - i * 6) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
@@ -0,0 +1,1794 @@
+mod debugger;
+mod file_dialog;
+mod global_keys;
+mod latency;
+mod livesplit;
+mod sdl;
+
+use chip8_core::annotations::Annotations;
+use chip8_core::compat::{self, CompatLimits};
+use chip8_core::config::{Config, CONFIG_PATH};
+use chip8_core::database::{Database, DatabaseEntry};
+use chip8_core::disasm::{self, DisasmOptions, Format};
+use chip8_core::display::{AsciiStyle, Rotation};
+use chip8_core::event::{self, EmuEvent, Hotkey, Hotkeys};
+use chip8_core::expr::ExprContext;
+use chip8_core::halt::HaltCondition;
+use chip8_core::hexview::HexView;
+use chip8_core::keypad::Keypad;
+use chip8_core::memory::{MemoryInit, MemoryWrap};
+use chip8_core::menu::RomMenu;
+use chip8_core::overlay::Overlay;
+use chip8_core::palette::Palette;
+use chip8_core::persist::{autosave_path, slot_path};
+use chip8_core::ramsearch::{Comparison, RamSearch};
+use chip8_core::recent::{RecentRoms, RECENT_PATH};
+use chip8_core::rom_config::RomConfig;
+use chip8_core::romfix::{self, FixOptions};
+use chip8_core::segment::Segment;
+use chip8_core::{bundle, octo, patch, savestate, screenshot, splash};
+use chip8_core::{Chip8, Display, Quirks};
+use chip8_core::cpu::{QUIRK_PRESETS, RUNTIME_QUIRKS};
+use chip8_core::spectate::{Broadcaster, Spectator};
+use chip8_core::speedrun::{format_time, SplitEvent};
+use debugger::Debugger;
+use global_keys::GlobalKeys;
+use latency::LatencyProbe;
+use livesplit::LiveSplitServer;
+use rayon::prelude::*;
+use sdl::{Key, SdlFrontend};
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+// Parses a hex address like 0x200 or 200
+fn parse_address(text: &str) -> Option<u16> {
+    u16::from_str_radix(text.trim_start_matches("0x").trim_start_matches("0X"), 16).ok()
+}
+
+// c8 disasm <rom> [--format plain|ansi|json] [--range start:end] [--follow] [--annotations file.toml]
+fn run_disasm(args: &[String]) -> i32 {
+    let mut path: Option<&str> = None;
+    let mut options = DisasmOptions {
+        format: Format::Plain,
+        start: None,
+        end: None,
+        follow: false,
+        annotations: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().and_then(|name| Format::from_name(name)) {
+                Some(format) => options.format = format,
+                None => {
+                    eprintln!("--format expects plain, ansi or json");
+                    return 2;
+                }
+            },
+
+            "--range" => {
+                let range = args.next().and_then(|range| range.split_once(':'));
+                match range.map(|(start, end)| (parse_address(start), parse_address(end))) {
+                    Some((Some(start), Some(end))) => {
+                        options.start = Some(start);
+                        options.end = Some(end);
+                    }
+                    _ => {
+                        eprintln!("--range expects start:end in hex, e.g. 0x200:0x2FF");
+                        return 2;
+                    }
+                }
+            }
+
+            "--follow" => options.follow = true,
+
+            "--annotations" => {
+                let Some(annotations_path) = args.next() else {
+                    eprintln!("--annotations expects a TOML file");
+                    return 2;
+                };
+
+                match Annotations::load(annotations_path) {
+                    Ok(annotations) => options.annotations = Some(annotations),
+                    Err(e) => {
+                        eprintln!("failed to load annotations {}: {}", annotations_path, e);
+                        return 1;
+                    }
+                }
+            }
+
+            _ => path = Some(arg),
+        }
+    }
+
+    let Some(path) = path else {
+        eprintln!("usage: c8 disasm <rom> [--format plain|ansi|json] [--range start:end] [--follow] [--annotations file.toml]");
+        return 2;
+    };
+
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("failed to read {}: {}", path, e);
+            return 1;
+        }
+    };
+
+    let lines = disasm::disassemble_rom(&rom, 0x200, &options);
+    print!("{}", disasm::format_lines(&lines, options.format));
+    0
+}
+
+// c8 rom fix <rom> [-o out] [--pad size] [--align size] [--no-trim]
+fn run_rom_fix(args: &[String]) -> i32 {
+    let mut path: Option<&str> = None;
+    let mut output: Option<&str> = None;
+    let mut options = FixOptions {
+        trim: true,
+        pad_to: None,
+        align: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = args.next().map(String::as_str),
+            "--no-trim" => options.trim = false,
+
+            "--pad" | "--align" => {
+                let Some(size) = args.next().and_then(|size| size.parse::<usize>().ok()) else {
+                    eprintln!("{} expects a size in bytes", arg);
+                    return 2;
+                };
+
+                if arg == "--pad" {
+                    options.pad_to = Some(size);
+                } else {
+                    options.align = Some(size);
+                }
+            }
+
+            _ => path = Some(arg),
+        }
+    }
+
+    let Some(path) = path else {
+        eprintln!("usage: c8 rom fix <rom> [-o out] [--pad size] [--align size] [--no-trim]");
+        return 2;
+    };
+
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("failed to read {}: {}", path, e);
+            return 1;
+        }
+    };
+
+    let report = romfix::analyze(&rom, 0x200);
+    println!("size:           {} bytes", report.original_size);
+    println!("effective size: {} bytes ({} bytes of padding)", report.effective_size, report.original_size - report.effective_size);
+    println!("code:           {} bytes", report.code_bytes);
+    println!("data:           {} bytes", report.data_bytes);
+
+    if let Some(output) = output {
+        let fixed = romfix::fix_rom(&rom, 0x200, &options);
+        if let Err(e) = std::fs::write(output, &fixed) {
+            eprintln!("failed to write {}: {}", output, e);
+            return 1;
+        }
+        println!("wrote {} bytes to {}", fixed.len(), output);
+    }
+
+    0
+}
+
+// c8 rom octocart <rom> -o out.gif [--profile name] [--speed hz]
+// Wraps a ROM in an Octocart with the quirks, speed and colors it runs with here (config.toml's
+// unless given), for Octo and other emulators that read them
+fn run_rom_octocart(args: &[String]) -> i32 {
+    const USAGE: &str = "usage: c8 rom octocart <rom> -o out.gif [--profile name] [--speed hz]";
+    let mut config = Config::load(CONFIG_PATH).unwrap_or_else(|e| {
+        eprintln!("ignoring invalid {}: {}", CONFIG_PATH, e);
+        Config::default()
+    });
+    let mut path: Option<&str> = None;
+    let mut output: Option<&str> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = args.next().map(String::as_str),
+            "--speed" => match args.next().and_then(|hz| hz.parse().ok()) {
+                Some(hz) if hz > 0 => config.speed = hz,
+                _ => {
+                    eprintln!("--speed expects instructions per second, e.g. 1000");
+                    return 2;
+                }
+            },
+            "--profile" => match quirk_preset(args.next().map_or("", String::as_str)) {
+                Ok(quirks) => config.quirks = quirks,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+            },
+            other if path.is_none() && !other.starts_with('-') => path = Some(other),
+            other => {
+                eprintln!("unexpected argument {}", other);
+                return 2;
+            }
+        }
+    }
+
+    let (Some(path), Some(output)) = (path, output) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("failed to read {}: {}", path, e);
+            return 1;
+        }
+    };
+
+    let options = octo::OctoOptions::from_settings(&config.quirks, config.speed, config.palette.as_ref());
+    let cart = octo::export_cart(&rom, &options);
+    if let Err(e) = std::fs::write(output, &cart) {
+        eprintln!("failed to write {}: {}", output, e);
+        return 1;
+    }
+    println!("wrote {} ({} bytes of ROM) to {}", path, rom.len(), output);
+    0
+}
+
+// c8 debug <rom>: terminal debugger. Commands in ./.dbgrc run first, and the history is
+// kept in ./.c8_history between sessions.
+fn run_debug(args: &[String]) -> i32 {
+    const HISTORY_PATH: &str = ".c8_history";
+    const HISTORY_LIMIT: usize = 1000;
+
+    let Some(path) = args.first() else {
+        eprintln!("usage: c8 debug <rom>");
+        return 2;
+    };
+
+    let mut cpu = match Chip8::new(None, None, Some(path), Display::new(64, 32, 1), None) {
+        Ok(cpu) => cpu,
+        Err(e) => {
+            eprintln!("{}", e);
+            return e.exit_code();
+        }
+    };
+    print_messages(&mut cpu);
+    let mut debugger = Debugger {
+        rom_path: Some(path.clone()),
+        ..Debugger::default()
+    };
+    if let Ok(history) = std::fs::read_to_string(HISTORY_PATH) {
+        debugger.history = history.lines().map(str::to_string).collect();
+    }
+
+    let mut running = match debugger.source(&mut cpu, ".dbgrc") {
+        Ok(keep_going) => keep_going,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => true,
+        Err(e) => {
+            eprintln!("failed to run .dbgrc: {}", e);
+            true
+        }
+    };
+
+    let stdin = std::io::stdin();
+    while running {
+        print!("(c8) ");
+        _ = std::io::stdout().flush();
+
+        let mut line = String::new();
+        match stdin.read_line(&mut line) {
+            Ok(0) | Err(_) => break, // end of input
+            Ok(_) => running = debugger.execute(&mut cpu, &line),
+        }
+    }
+
+    // A trace still recording gets its index, or it couldn't be opened later
+    if let Some(Err(e)) = cpu.stop_trace() {
+        eprintln!("failed to finish the trace: {}", e);
+    }
+
+    let skip = debugger.history.len().saturating_sub(HISTORY_LIMIT);
+    let mut history = debugger.history[skip..].join("\n");
+    history.push('\n');
+    if let Err(e) = std::fs::write(HISTORY_PATH, history) {
+        eprintln!("failed to save {}: {}", HISTORY_PATH, e);
+    }
+
+    0
+}
+
+// c8 spectate <host:port>: watch another emulator's --broadcast
+fn run_spectate(args: &[String]) -> i32 {
+    let Some(address) = args.first() else {
+        eprintln!("usage: c8 spectate <host:port>");
+        return 2;
+    };
+
+    let mut spectator = match Spectator::connect(address) {
+        Ok(spectator) => spectator,
+        Err(e) => {
+            eprintln!("failed to connect to {}: {}", address, e);
+            return 1;
+        }
+    };
+
+    let mut display = Display::new(64, 32, 26);
+    display.set_title(&format!("Spectating {} - Rust Chip-8", address));
+    let mut frontend = match SdlFrontend::new(&display) {
+        Ok(frontend) => frontend,
+        Err(e) => {
+            eprintln!("{}", e);
+            return e.exit_code();
+        }
+    };
+
+    loop {
+        for event in frontend.poll_events() {
+            if event == EmuEvent::Quit {
+                return 0;
+            }
+            event::apply(&mut display, &event);
+        }
+
+        match spectator.poll(&mut display) {
+            Ok(true) => {}
+            Ok(false) => {
+                println!("broadcast ended");
+                return 0;
+            }
+            Err(e) => {
+                eprintln!("spectating {} failed: {}", address, e);
+                return 1;
+            }
+        }
+
+        frontend.update_audio(&display);
+        frontend.draw(&display);
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+// The style after --print-screen, hash unless a style name follows
+fn print_screen_style(args: &mut std::iter::Peekable<std::slice::Iter<String>>) -> AsciiStyle {
+    match args.peek().and_then(|name| AsciiStyle::from_name(name)) {
+        Some(style) => {
+            args.next();
+            style
+        }
+        None => AsciiStyle::Hash,
+    }
+}
+
+// c8 headless <rom> [--until condition]... [--speed hz] [--profile name] [--screen] [--print-screen [hash|braille]] [--screenshot out.bmp] [--json] [--suggest-speed] [--opcode-timing] [--start address] [--memory-init init] [--segment file@address]...
+// Runs a ROM without opening a window or audio device, e.g. in CI, until a halt condition
+// (by default a tight loop or a minute of emulated time), then reports why it stopped.
+fn run_headless(args: &[String]) -> i32 {
+    const DEFAULT_FRAMES: u64 = 60 * 60;
+
+    let mut path: Option<&str> = None;
+    let mut conditions: Vec<HaltCondition> = Vec::new();
+    let mut screen = false;
+    let mut print_screen: Option<AsciiStyle> = None;
+    let mut screenshot: Option<&str> = None;
+    let mut json = false;
+    let mut suggest_speed = false;
+    let mut opcode_timing = false;
+    let mut start: Option<u16> = None;
+    let mut config = Config::load(CONFIG_PATH).unwrap_or_else(|e| {
+        eprintln!("ignoring invalid {}: {}", CONFIG_PATH, e);
+        Config::default()
+    });
+
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--until" => match args.next().map(|condition| HaltCondition::parse(condition)) {
+                Some(Ok(condition)) => conditions.push(condition),
+                Some(Err(e)) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+                None => {
+                    eprintln!("--until expects a condition, e.g. pc=0x2F0, cycles=100000 or loop");
+                    return 2;
+                }
+            },
+            "--speed" => match args.next().and_then(|hz| hz.parse().ok()) {
+                Some(hz) if hz > 0 => config.speed = hz,
+                _ => {
+                    eprintln!("--speed expects instructions per second, e.g. 1000");
+                    return 2;
+                }
+            },
+            "--profile" => match quirk_preset(args.next().map_or("", String::as_str)) {
+                Ok(quirks) => config.quirks = quirks,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+            },
+            "--start" => match args.next().and_then(|address| parse_address(address)) {
+                Some(address) => start = Some(address),
+                None => {
+                    eprintln!("{}", START_HELP);
+                    return 2;
+                }
+            },
+            "--memory-init" => match args.next().map(|init| MemoryInit::parse(init)) {
+                Some(Ok(init)) => config.memory_init = init,
+                Some(Err(e)) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+                None => {
+                    eprintln!("{}", MEMORY_INIT_HELP);
+                    return 2;
+                }
+            },
+            "--segment" => match args.next().map(|text| Segment::parse(text)) {
+                Some(Ok(segment)) => config.segments.push(segment),
+                Some(Err(e)) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+                None => {
+                    eprintln!("{}", SEGMENT_HELP);
+                    return 2;
+                }
+            },
+            "--screen" => screen = true,
+            "--print-screen" => print_screen = Some(print_screen_style(&mut args)),
+            "--screenshot" => match args.next() {
+                Some(out) => screenshot = Some(out),
+                None => {
+                    eprintln!("--screenshot expects an output file");
+                    return 2;
+                }
+            },
+            "--json" => json = true,
+            "--suggest-speed" => suggest_speed = true,
+            "--opcode-timing" => opcode_timing = true,
+            other if path.is_none() && !other.starts_with('-') => path = Some(other),
+            other => {
+                eprintln!("unexpected argument {}", other);
+                return 2;
+            }
+        }
+    }
+
+    let Some(path) = path else {
+        eprintln!("usage: c8 headless <rom> [--until condition]... [--speed hz] [--profile name] [--screen] [--print-screen [hash|braille]] [--screenshot out.bmp] [--json] [--suggest-speed] [--opcode-timing] [--start address] [--memory-init init] [--segment file@address]...");
+        return 2;
+    };
+
+    let cycles_per_frame = (config.speed as u64 / 60).max(1);
+    if conditions.is_empty() {
+        conditions.push(HaltCondition::InfiniteLoop);
+        conditions.push(HaltCondition::MaxCycles(DEFAULT_FRAMES * cycles_per_frame));
+    }
+
+    let mut cpu = match windowless_cpu(path, start, &mut config) {
+        Ok(cpu) => cpu,
+        Err(code) => return code,
+    };
+    if suggest_speed {
+        cpu.enable_tickrate_probe();
+    }
+    if opcode_timing {
+        cpu.enable_opcode_timing();
+    }
+    let result = cpu.run_headless(&conditions, cycles_per_frame);
+    print_messages(&mut cpu);
+
+    if json {
+        println!("{}", result.to_json());
+    } else {
+        println!("stopped: {} after {} cycles ({} frames) at {:#06X}", result.reason, result.cycles, result.frames, result.pc);
+    }
+    if screen {
+        println!("{}", cpu.display().to_ascii());
+    }
+    if let Some(style) = print_screen {
+        println!("{}", cpu.display().to_ascii_art(style));
+    }
+    print_reports(&mut cpu, config.speed);
+    if let Some(out) = screenshot {
+        if let Err(e) = std::fs::write(out, screenshot::encode_bmp(cpu.display(), 4)) {
+            eprintln!("failed to write {}: {}", out, e);
+            return 1;
+        }
+    }
+
+    0
+}
+
+// The windowless machine c8 headless and c8 run start from: the ROM at the profile's (or
+// --start) address, [memory] applied and the segments from config.toml and --segment loaded.
+// Err is the exit code, after printing why.
+fn windowless_cpu(path: &str, start: Option<u16>, config: &mut Config) -> Result<Chip8, i32> {
+    let mut quirks = std::mem::take(&mut config.quirks);
+    quirks.start_address = start.unwrap_or(quirks.start_address);
+    let mut cpu = Chip8::new(config.font_path.as_deref(), config.bigfont_path.as_deref(), Some(path), Display::new(64, 32, 1), Some(quirks)).map_err(|e| {
+        eprintln!("{}", e);
+        e.exit_code()
+    })?;
+    apply_memory_config(&mut cpu, config.memory_init, config.memory_wrap);
+    cpu.load_segments(&config.segments).map_err(|e| {
+        eprintln!("{}", e);
+        e.exit_code()
+    })?;
+    print_messages(&mut cpu);
+    Ok(cpu)
+}
+
+// Prints what the core has to say: load warnings, trap reports, status changes and the like
+fn print_messages(cpu: &mut Chip8) {
+    for message in cpu.take_messages() {
+        println!("{}", message);
+    }
+}
+
+// The profiling reports asked for on the command line, and any last messages
+fn print_reports(cpu: &mut Chip8, speed: u32) {
+    let reports = [cpu.quirk_report(), cpu.block_report(), cpu.tickrate_report(speed), cpu.opcode_timing_report()];
+    for report in reports.into_iter().flatten() {
+        println!("{}", report);
+    }
+    print_messages(cpu);
+}
+
+// c8 run <rom> --cycles N [--print-state] [--print-screen [hash|braille]] [--dump out.sav] [--speed hz] [--profile name] [--start address] [--memory-init init] [--segment file@address]...
+// Runs a ROM for a fixed number of instructions without a window. --print-state dumps the
+// final registers, timers and framebuffer hash as JSON on stdout for shell pipelines, and
+// --dump writes a savestate to compare against a known good one in regression tests.
+fn run_once(args: &[String]) -> i32 {
+    const USAGE: &str = "usage: c8 run <rom> --cycles N [--print-state] [--print-screen [hash|braille]] [--dump out.sav] [--speed hz] [--profile name] [--start address] [--memory-init init] [--segment file@address]...";
+
+    let mut path: Option<&str> = None;
+    let mut cycles: Option<u64> = None;
+    let mut print_state = false;
+    let mut print_screen: Option<AsciiStyle> = None;
+    let mut dump: Option<&str> = None;
+    let mut start: Option<u16> = None;
+    let mut config = Config::load(CONFIG_PATH).unwrap_or_else(|e| {
+        eprintln!("ignoring invalid {}: {}", CONFIG_PATH, e);
+        Config::default()
+    });
+
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cycles" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => cycles = Some(n),
+                None => {
+                    eprintln!("--cycles expects a number of instructions, e.g. 100000");
+                    return 2;
+                }
+            },
+            "--start" => match args.next().and_then(|address| parse_address(address)) {
+                Some(address) => start = Some(address),
+                None => {
+                    eprintln!("{}", START_HELP);
+                    return 2;
+                }
+            },
+            "--memory-init" => match args.next().map(|init| MemoryInit::parse(init)) {
+                Some(Ok(init)) => config.memory_init = init,
+                Some(Err(e)) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+                None => {
+                    eprintln!("{}", MEMORY_INIT_HELP);
+                    return 2;
+                }
+            },
+            "--segment" => match args.next().map(|text| Segment::parse(text)) {
+                Some(Ok(segment)) => config.segments.push(segment),
+                Some(Err(e)) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+                None => {
+                    eprintln!("{}", SEGMENT_HELP);
+                    return 2;
+                }
+            },
+            "--print-state" => print_state = true,
+            "--print-screen" => print_screen = Some(print_screen_style(&mut args)),
+            "--dump" => match args.next() {
+                Some(out) => dump = Some(out),
+                None => {
+                    eprintln!("--dump expects an output file");
+                    return 2;
+                }
+            },
+            "--speed" => match args.next().and_then(|hz| hz.parse().ok()) {
+                Some(hz) if hz > 0 => config.speed = hz,
+                _ => {
+                    eprintln!("--speed expects instructions per second, e.g. 1000");
+                    return 2;
+                }
+            },
+            "--profile" => match quirk_preset(args.next().map_or("", String::as_str)) {
+                Ok(quirks) => config.quirks = quirks,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+            },
+            other if path.is_none() && !other.starts_with('-') => path = Some(other),
+            other => {
+                eprintln!("unexpected argument {}", other);
+                return 2;
+            }
+        }
+    }
+
+    let (Some(path), Some(cycles)) = (path, cycles) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    let mut cpu = match windowless_cpu(path, start, &mut config) {
+        Ok(cpu) => cpu,
+        Err(code) => return code,
+    };
+    let result = cpu.run_headless(&[HaltCondition::MaxCycles(cycles)], (config.speed as u64 / 60).max(1));
+    print_messages(&mut cpu);
+
+    if print_state {
+        println!("{}", cpu.state_json());
+    } else {
+        println!("stopped: {} after {} cycles at {:#06X}, display {:#018X}", result.reason, result.cycles, result.pc, result.display_hash);
+    }
+    if let Some(style) = print_screen {
+        println!("{}", cpu.display().to_ascii_art(style));
+    }
+    if let Some(out) = dump {
+        if let Err(e) = cpu.save_state(out) {
+            eprintln!("failed to write {}: {}", out, e);
+            return 1;
+        }
+    }
+
+    0
+}
+
+// c8 compat <directory> [--seconds s] [--timeout secs] [--jobs n] [--speed hz] [--profile name]
+// Runs every ROM in a directory headless for a while, one machine per ROM spread over all
+// cores, and reports which ones halted, hung or failed to load.
+fn run_compat(args: &[String]) -> i32 {
+    const USAGE: &str = "usage: c8 compat <directory> [--seconds s] [--timeout secs] [--jobs n] [--speed hz] [--profile name]";
+
+    let mut dir: Option<&str> = None;
+    let mut seconds: u64 = 60;
+    let mut timeout = Duration::from_secs(30);
+    let mut jobs: usize = 0; // rayon picks one per core
+    let mut config = Config::load(CONFIG_PATH).unwrap_or_else(|e| {
+        eprintln!("ignoring invalid {}: {}", CONFIG_PATH, e);
+        Config::default()
+    });
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seconds" => match args.next().and_then(|seconds| seconds.parse().ok()) {
+                Some(value) if value > 0 => seconds = value,
+                _ => {
+                    eprintln!("--seconds expects how many seconds of emulated time to run each ROM for");
+                    return 2;
+                }
+            },
+            "--timeout" => match args.next().and_then(|secs| secs.parse::<f64>().ok()) {
+                Some(secs) if secs > 0.0 => timeout = Duration::from_secs_f64(secs),
+                _ => {
+                    eprintln!("--timeout expects the host seconds to allow each ROM, e.g. 30");
+                    return 2;
+                }
+            },
+            "--jobs" => match args.next().and_then(|jobs| jobs.parse().ok()) {
+                Some(value) if value > 0 => jobs = value,
+                _ => {
+                    eprintln!("--jobs expects how many ROMs to run at once");
+                    return 2;
+                }
+            },
+            "--speed" => match args.next().and_then(|hz| hz.parse().ok()) {
+                Some(hz) if hz > 0 => config.speed = hz,
+                _ => {
+                    eprintln!("--speed expects instructions per second, e.g. 1000");
+                    return 2;
+                }
+            },
+            "--profile" => match quirk_preset(args.next().map_or("", String::as_str)) {
+                Ok(quirks) => config.quirks = quirks,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+            },
+            other if dir.is_none() && !other.starts_with('-') => dir = Some(other),
+            other => {
+                eprintln!("unexpected argument {}", other);
+                return 2;
+            }
+        }
+    }
+
+    let Some(dir) = dir else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let roms = match RomMenu::scan(dir) {
+        Ok(menu) => menu.roms,
+        Err(e) => {
+            eprintln!("failed to read {}: {}", dir, e);
+            return 1;
+        }
+    };
+
+    let pool = match rayon::ThreadPoolBuilder::new().num_threads(jobs).build() {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("failed to start worker threads: {}", e);
+            return 1;
+        }
+    };
+
+    let cycles_per_frame = (config.speed as u64 / 60).max(1);
+    let limits = CompatLimits { cycles: seconds * 60 * cycles_per_frame, cycles_per_frame, timeout };
+    let done = AtomicUsize::new(0);
+    let started = Instant::now();
+    let progress = |done: usize| {
+        const BAR: usize = 40;
+        let filled = done * BAR / roms.len().max(1);
+        eprint!("\r[{}{}] {}/{}", "#".repeat(filled), ".".repeat(BAR - filled), done, roms.len());
+        _ = std::io::stderr().flush();
+    };
+
+    progress(0);
+    let results: Vec<_> = pool.install(|| {
+        roms.par_iter()
+            .map(|path| {
+                let result = compat::run_rom(path, config.quirks.clone(), limits);
+                progress(done.fetch_add(1, Ordering::Relaxed) + 1);
+                result
+            })
+            .collect()
+    });
+    eprintln!(" in {:.1}s", started.elapsed().as_secs_f64());
+
+    print!("{}", compat::report(&results));
+    if results.iter().any(|result| result.outcome.is_problem()) {
+        1
+    } else {
+        0
+    }
+}
+
+const USAGE: &str = "usage: c8 [rom | directory] [--speed hz] [--scale n] [--profile chip8|chip48|schip1.1|xochip|megachip|eti660] [--patch file] [--import dump] [--watch expr] \
+[--watch-write addr[:end]] [--annotations file.toml] [--rotate degrees] [--run-ahead frames] \
+[--livesplit [address]] [--broadcast [address]] [--shm file] [--block-stats] [--latency] [--suggest-speed] [--opcode-timing] [--rewind] [--audio-scope] [--key-stats [file.json]] [--no-database] \
+[--start address] [--memory-init zero|open-bus|random[:seed]|byte] [--segment file@address]
+       c8 disasm | rom fix | rom octocart | debug | headless | run | compat | spectate ...";
+
+// Memory --rewind keeps frames in, a few minutes of a typical ROM
+const REWIND_BUDGET: usize = 16 << 20;
+
+// The emulator command's options, all parsed up front so a typo is an error before the
+// window opens. Speed and scale override config.toml when given.
+#[derive(Default)]
+struct CliOptions {
+    rom: Option<String>,
+    speed: Option<u32>, // instructions per second
+    scale: Option<u16>,
+    quirks: Option<Quirks>, // from --profile, over both config.toml and a bundle's platform
+    start: Option<u16>,     // --start, over the profile's start address
+    memory_init: Option<MemoryInit>,
+    patch: Option<String>,
+    import: Option<String>,
+    watches: Vec<String>,
+    watch_writes: Vec<(u16, u16)>, // start and exclusive end
+    annotations: Option<String>,
+    rotation: Option<Rotation>,
+    run_ahead: u8, // frames, 0 for off
+    livesplit: Option<String>, // address to serve on
+    broadcast: Option<String>, // address to serve on
+    shm: Option<String>,
+    segments: Vec<Segment>,
+    key_stats: Option<Option<String>>, // with the JSON file to write, if any
+    block_stats: bool,
+    suggest_speed: bool,
+    opcode_timing: bool,
+    rewind: bool,
+    audio_scope: bool,
+    latency: bool,
+    no_database: bool,
+}
+
+const START_HELP: &str = "--start expects the address programs load and start at in hex, e.g. 0x600";
+
+const SEGMENT_HELP: &str = "--segment expects file@address, e.g. overlay.bin@0x800";
+
+const MEMORY_INIT_HELP: &str = "--memory-init expects zero, open-bus, random, random:SEED or a byte, e.g. 0xFF";
+
+// Fills memory the ROM didn't load into, printing a random seed so the run can be repeated
+// with random:SEED, and sets what accesses past the end do
+fn apply_memory_config(cpu: &mut Chip8, init: MemoryInit, wrap: MemoryWrap) {
+    if let MemoryInit::Random(seed) = init {
+        eprintln!("unloaded memory filled from random:{}", seed);
+    }
+    cpu.set_memory_init(init);
+    cpu.set_memory_wrap(wrap);
+}
+
+fn quirk_preset(name: &str) -> Result<Quirks, String> {
+    Quirks::preset(name).ok_or_else(|| format!("unknown profile {}, expected one of {}", name, QUIRK_PRESETS.join(", ")))
+}
+
+fn parse_cli(args: &[String]) -> Result<CliOptions, String> {
+    let mut options = CliOptions::default();
+
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "--speed" => match args.next().and_then(|hz| hz.parse().ok()) {
+                Some(hz) if hz > 0 => options.speed = Some(hz),
+                _ => return Err("--speed expects instructions per second, e.g. 1000".to_string()),
+            },
+            "--scale" => match args.next().and_then(|scale| scale.parse().ok()) {
+                Some(scale) if scale > 0 => options.scale = Some(scale),
+                _ => return Err("--scale expects a whole number of window pixels per CHIP-8 pixel".to_string()),
+            },
+            "--profile" => match args.next() {
+                Some(name) => options.quirks = Some(quirk_preset(name)?),
+                None => return Err(format!("--profile expects one of {}", QUIRK_PRESETS.join(", "))),
+            },
+            "--start" => match args.next().and_then(|address| parse_address(address)) {
+                Some(address) => options.start = Some(address),
+                None => return Err(START_HELP.to_string()),
+            },
+            "--memory-init" => match args.next() {
+                Some(init) => options.memory_init = Some(MemoryInit::parse(init)?),
+                None => return Err(MEMORY_INIT_HELP.to_string()),
+            },
+            "--patch" => options.patch = Some(args.next().ok_or("--patch expects an IPS or BPS file")?.clone()),
+            "--import" => options.import = Some(args.next().ok_or("--import expects a savestate or memory dump")?.clone()),
+            "--watch" => options.watches.push(args.next().ok_or("--watch expects an expression")?.clone()),
+            "--watch-write" => {
+                let range = args.next().and_then(|text| match text.split_once(':') {
+                    Some((start, end)) => Some((parse_address(start)?, parse_address(end)?)),
+                    None => parse_address(text).map(|address| (address, address + 1)),
+                });
+                options.watch_writes.push(range.ok_or("--watch-write expects an address or start:end in hex, e.g. 0x3F0:0x3F8")?);
+            }
+            "--annotations" => options.annotations = Some(args.next().ok_or("--annotations expects a TOML file")?.clone()),
+            "--rotate" => match args.next().and_then(|degrees| degrees.parse().ok()).and_then(Rotation::from_degrees) {
+                Some(rotation) => options.rotation = Some(rotation),
+                None => return Err("--rotate expects 0, 90, 180 or 270".to_string()),
+            },
+            "--run-ahead" => match args.next().and_then(|frames| frames.parse().ok()) {
+                Some(frames @ 1..=2) => options.run_ahead = frames,
+                _ => return Err("--run-ahead expects 1 or 2 frames".to_string()),
+            },
+            "--shm" => options.shm = Some(args.next().ok_or("--shm expects a file to map, e.g. /dev/shm/c8")?.clone()),
+            "--segment" => match args.next().map(|text| Segment::parse(text)) {
+                Some(segment) => options.segments.push(segment?),
+                None => return Err(SEGMENT_HELP.to_string()),
+            },
+            // These take an optional value, so a following flag isn't it
+            "--livesplit" => options.livesplit = Some(args.next_if(|value| !value.starts_with("--")).map_or("127.0.0.1:16834", String::as_str).to_string()),
+            "--broadcast" => options.broadcast = Some(args.next_if(|value| !value.starts_with("--")).map_or("0.0.0.0:7008", String::as_str).to_string()),
+            "--key-stats" => options.key_stats = Some(args.next_if(|value| !value.starts_with("--")).cloned()),
+            "--block-stats" => options.block_stats = true,
+            "--suggest-speed" => options.suggest_speed = true,
+            "--opcode-timing" => options.opcode_timing = true,
+            "--rewind" => options.rewind = true,
+            "--audio-scope" => options.audio_scope = true,
+            "--latency" => options.latency = true,
+            "--no-database" => options.no_database = true,
+            flag if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+            rom if options.rom.is_none() => options.rom = Some(rom.to_string()),
+            extra => return Err(format!("unexpected argument {}", extra)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match (args.get(1).map(String::as_str), args.get(2).map(String::as_str)) {
+        (Some("disasm"), _) => std::process::exit(run_disasm(&args[2..])),
+        (Some("rom"), Some("fix")) => std::process::exit(run_rom_fix(&args[3..])),
+        (Some("rom"), Some("octocart")) => std::process::exit(run_rom_octocart(&args[3..])),
+        (Some("spectate"), _) => std::process::exit(run_spectate(&args[2..])),
+        (Some("debug"), _) => std::process::exit(run_debug(&args[2..])),
+        (Some("headless"), _) => std::process::exit(run_headless(&args[2..])),
+        (Some("run"), _) => std::process::exit(run_once(&args[2..])),
+        (Some("compat"), _) => std::process::exit(run_compat(&args[2..])),
+        // c8 <rom> --cycles N [--dump out.sav] is the batch form of c8 run
+        _ if args.iter().any(|arg| arg == "--cycles") => std::process::exit(run_once(&args[1..])),
+        _ => {}
+    }
+
+    let mut options = match parse_cli(&args[1..]) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let mut config = Config::load(CONFIG_PATH).unwrap_or_else(|e| {
+        eprintln!("ignoring invalid {}: {}", CONFIG_PATH, e);
+        Config::default()
+    });
+    config.speed = options.speed.unwrap_or(config.speed);
+    config.scale = options.scale.unwrap_or(config.scale);
+
+    let mut display: Display = Display::new(64, 32, config.scale);
+    if let Some(palette) = config.palette.clone() {
+        display.add_palette(palette);
+    }
+    for &key in &config.sticky_keys {
+        display.keypad.sticky[key as usize] = true;
+    }
+    let mut frontend = SdlFrontend::new(&display).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(e.exit_code());
+    });
+    for (chip8_key, host_key) in &config.keys {
+        match Key::from_name(host_key) {
+            Some(key) => frontend.bind(*chip8_key as usize, key),
+            None => eprintln!("ignoring unknown key {} in {}", host_key, CONFIG_PATH),
+        }
+    }
+
+    // c8 <directory>: pick ROMs from a menu on the emulated screen instead of starting one
+    let mut menu: Option<RomMenu> = None;
+    if let Some(dir) = options.rom.take_if(|path| std::path::Path::new(path).is_dir()) {
+        match RomMenu::scan(&dir) {
+            Ok(roms) => menu = Some(roms),
+            Err(e) => {
+                eprintln!("can't list {}: {}", dir, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let program_path = options.rom.as_deref().unwrap_or("");
+    let mut load_error: Option<String> = None;
+
+    // .c8b bundles carry the ROM plus title, platform and keymap metadata, and Octocarts the
+    // ROM plus Octo's quirks, speed and colors
+    let mut quirks: Option<Quirks> = None;
+    let mut loaded_rom: Option<Vec<u8>> = None; // when it isn't the file as is
+    let mut cart_palette: Option<Palette> = None;
+    let mut about: Vec<String> = vec![format!("File: {}", program_path)];
+    if let Ok(data) = std::fs::read(program_path) {
+        if octo::is_octocart(&data) {
+            match octo::parse_cart(&data) {
+                Ok(cart) => {
+                    about.push("Octocart".to_string());
+                    let mut cart_quirks = Quirks::preset("xochip").unwrap();
+                    cart.options.apply_quirks(&mut cart_quirks);
+                    quirks = Some(cart_quirks);
+                    if let Some(speed) = cart.options.speed().filter(|_| options.speed.is_none()) {
+                        config.speed = speed;
+                    }
+                    cart_palette = cart.options.palette();
+                    loaded_rom = Some(cart.rom);
+                }
+                Err(e) => {
+                    eprintln!("failed to read Octocart {}: {}", program_path, e);
+                    load_error = Some(e.to_string());
+                }
+            }
+        } else if bundle::is_bundle(&data) {
+            match bundle::parse_bundle(&data) {
+                Ok(bundle) => {
+                    let title = match (&bundle.title, &bundle.author) {
+                        (Some(title), Some(author)) => format!("{} by {} - Rust Chip-8", title, author),
+                        (Some(title), None) => format!("{} - Rust Chip-8", title),
+                        _ => "Rust Chip-8".to_string(),
+                    };
+                    display.set_title(&title);
+
+                    if let Some(ref title) = bundle.title {
+                        about.push(format!("Title: {}", title));
+                    }
+                    if let Some(ref author) = bundle.author {
+                        about.push(format!("Author: {}", author));
+                    }
+                    about.push(format!("Platform: {:?}", bundle.platform));
+
+                    for (chip8_key, host_key) in &bundle.keymap {
+                        if let Some(key) = Key::from_name(host_key) {
+                            frontend.bind(*chip8_key as usize, key);
+                        }
+                    }
+
+                    quirks = Some(bundle.platform.quirks());
+                    loaded_rom = Some(bundle.rom);
+                }
+                Err(e) => {
+                    eprintln!("failed to read bundle {}: {}", program_path, e);
+                    load_error = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    // c8 --patch <file.ips|file.bps> patches the ROM before the database looks it up or it's
+    // loaded. Only plain ROM files take a patch; a bundle or Octocart would need rebuilding.
+    if let Some(ref patch_path) = options.patch {
+        if loaded_rom.is_some() || load_error.is_some() {
+            eprintln!("--patch only applies to plain ROM files, not bundles or Octocarts");
+            std::process::exit(2);
+        }
+        match patch::apply_patch_file(program_path, patch_path) {
+            Ok(rom) => loaded_rom = Some(rom),
+            Err(e) => {
+                eprintln!("failed to apply patch {}: {}", patch_path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // A ROM the program database knows starts with its platform, speed and colors, over
+    // config.toml but under a bundle's platform, --profile and --speed. --no-database skips it.
+    let mut known: Option<DatabaseEntry> = None;
+    if let Some(ref path) = config.database_path.clone().filter(|_| !options.no_database) {
+        match Database::load(path) {
+            Ok(database) => {
+                let rom = loaded_rom.clone().or_else(|| std::fs::read(program_path).ok()).unwrap_or_default();
+                known = database.lookup(&rom).cloned();
+            }
+            Err(e) => eprintln!("ignoring the program database {}: {}", path, e),
+        }
+    }
+    if let Some(ref entry) = known {
+        println!("{} is {} in the program database, running it as {}", program_path, entry.title, entry.platform);
+        about.push(format!("Database: {} ({})", entry.title, entry.platform));
+        quirks = quirks.or(Some(entry.quirks.clone()));
+        if let Some(speed) = entry.speed.filter(|_| options.speed.is_none()) {
+            config.speed = speed;
+        }
+    }
+
+    let mut quirks = options.quirks.take().or(quirks).unwrap_or_else(|| std::mem::take(&mut config.quirks));
+    quirks.start_address = options.start.unwrap_or(quirks.start_address);
+    let mut cpu: Chip8 = Chip8::new(config.font_path.as_deref(), config.bigfont_path.as_deref(), None, display, Some(quirks)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(e.exit_code());
+    });
+
+    // Without a ROM to run the window stays up with a splash (or the reason loading failed)
+    // until one is dropped on it
+    match (&loaded_rom, load_error) {
+        _ if options.rom.is_none() => match menu {
+            Some(ref menu) => cpu.show_message(&menu.lines()),
+            None => cpu.show_message(&splash::splash_lines()),
+        },
+        (Some(rom), _) => cpu.load_rom(rom, cpu.start_address()),
+        (None, Some(reason)) => cpu.show_message(&splash::error_lines(program_path, &reason)),
+        (None, None) => {
+            if let Err(e) = cpu.read_to_memory(program_path, cpu.start_address()) {
+                eprintln!("{}", e);
+                cpu.show_message(&splash::error_lines(program_path, &e.to_string()));
+            }
+        }
+    }
+
+    apply_memory_config(&mut cpu, options.memory_init.unwrap_or(config.memory_init), config.memory_wrap);
+    if let Some(colors) = known.and_then(|entry| entry.colors) {
+        cpu.display_mut().add_palette(Palette::new("database", colors));
+    }
+    if let Some(palette) = cart_palette {
+        cpu.display_mut().add_palette(palette);
+    }
+
+    if let Some(rom) = loaded_rom.or_else(|| std::fs::read(program_path).ok()) {
+        about.push(format!("Size: {} bytes", rom.len()));
+        about.push(format!("CRC32: {:08X}", patch::crc32(&rom)));
+    }
+    about.push(String::new());
+    about.push("F1: close, F6: controls".to_string());
+
+    // c8 --segment <file@address> (repeatable), after the segments in config.toml, e.g.
+    // --segment overlay.bin@0x800
+    let mut segments = config.segments.clone();
+    segments.append(&mut options.segments);
+    if let Err(e) = cpu.load_segments(&segments) {
+        eprintln!("failed to load segment: {}", e);
+        std::process::exit(1);
+    }
+
+    // c8 --import <dump>: continue a session from another emulator's savestate or memory dump
+    if let Some(ref import_path) = options.import {
+        match std::fs::read(import_path).and_then(|data| savestate::import(&data)) {
+            Ok((format, state)) => {
+                cpu.import_state(&state);
+                println!("imported {} from {}", format, import_path);
+            }
+            Err(e) => {
+                eprintln!("failed to import {}: {}", import_path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // c8 --watch <expr> (repeatable), e.g. --watch V3 --watch "mem[0x3F0]"
+    for source in &options.watches {
+        if let Err(e) = cpu.watches.add(source) {
+            eprintln!("invalid watch expression: {}", e);
+            std::process::exit(2);
+        }
+    }
+
+    // c8 --annotations <file.toml>: label addresses in trap reports
+    if let Some(ref path) = options.annotations {
+        match Annotations::load(path) {
+            Ok(symbols) => cpu.symbols = Some(symbols),
+            Err(e) => eprintln!("ignoring annotations: {}", e),
+        }
+    }
+
+    // c8 --watch-write <addr[:end]> (repeatable): report stores into the range and who made them,
+    // highlighted in the F5 memory viewer. The end is exclusive.
+    for &(start, end) in &options.watch_writes {
+        cpu.watch_writes(start as u32..end as u32);
+    }
+
+    if options.rom.is_some() {
+        match RomConfig::load(program_path) {
+            Ok(config) => cpu.set_rom_config(config),
+            Err(e) => eprintln!("ignoring invalid {}: {}", RomConfig::path_for(program_path), e),
+        }
+        if let Some(profile) = cpu.input_profile() {
+            frontend.apply_profile(&profile.bindings);
+        }
+    }
+
+    // c8 --block-stats: count basic block executions and print the hottest blocks on exit
+    if options.block_stats {
+        cpu.enable_block_stats();
+    }
+
+    // c8 --suggest-speed: measure how much of each frame the ROM spends working before it
+    // waits on the delay timer, and suggest a speed on exit
+    if options.suggest_speed {
+        cpu.enable_tickrate_probe();
+    }
+
+    // c8 --opcode-timing: time each instruction class on the host and print where the time
+    // went on exit
+    if options.opcode_timing {
+        cpu.enable_opcode_timing();
+    }
+
+    // c8 --rewind: keep the last minutes of frames so F12 can go back a second at a time
+    if options.rewind {
+        cpu.enable_rewind(REWIND_BUDGET);
+    }
+
+    // c8 --audio-scope: start with the sound timer and XO-CHIP pattern panel up, as End does
+    if options.audio_scope {
+        cpu.toggle_audio_scope();
+    }
+
+    // global_hotkeys = true in config.toml: media keys pause and mute even while unfocused
+    if config.global_hotkeys {
+        match GlobalKeys::listen() {
+            Ok(global_keys) => frontend.global_keys = Some(global_keys),
+            Err(e) => eprintln!("ignoring global_hotkeys: {}", e),
+        }
+    }
+
+    // c8 --latency: flash the screen on every key press and report event-to-present latency
+    if options.latency {
+        frontend.latency = Some(LatencyProbe::default());
+    }
+
+    // c8 --rotate <0|90|180|270>: turn the picture clockwise, mouse editing follows
+    if let Some(rotation) = options.rotation {
+        cpu.display_mut().rotation = rotation;
+    }
+
+    // c8 --livesplit [address]: serve speedrun splits to LiveSplit One, default localhost:16834
+    let mut livesplit: Option<LiveSplitServer> = None;
+    if let Some(ref address) = options.livesplit {
+        match LiveSplitServer::bind(address) {
+            Ok(server) => {
+                println!("livesplit: listening on ws://{}", address);
+                livesplit = Some(server);
+            }
+            Err(e) => eprintln!("livesplit: can't listen on {}: {}", address, e),
+        }
+    }
+
+    // c8 --key-stats [file.json]: print a heatmap of the keys pressed on exit, and write the
+    // counts as JSON when given a file
+    let key_stats = options.key_stats.take();
+
+    // c8 --broadcast [address]: stream frames to read-only `c8 spectate` clients
+    let mut broadcaster: Option<Broadcaster> = None;
+    if let Some(ref address) = options.broadcast {
+        match Broadcaster::bind(address) {
+            Ok(server) => {
+                println!("broadcasting on {}", address);
+                broadcaster = Some(server);
+            }
+            Err(e) => eprintln!("can't broadcast on {}: {}", address, e),
+        }
+    }
+
+    // c8 --shm <file>: publish the framebuffer and registers every frame through a shared
+    // memory file, e.g. /dev/shm/c8, for external visualizers
+    if let Some(ref path) = options.shm {
+        #[cfg(all(feature = "shm", unix))]
+        match chip8_core::shm::SharedState::create(path) {
+            Ok(shared) => cpu.shared_state = Some(shared),
+            Err(e) => eprintln!("can't share state through {}: {}", path, e),
+        }
+        #[cfg(not(all(feature = "shm", unix)))]
+        eprintln!("ignoring --shm {}: c8 was built without the shm feature (unix only)", path);
+    }
+
+    // c8 --run-ahead <1|2>: show frames emulated ahead of time to hide input latency
+    let run_ahead = options.run_ahead;
+
+    // A ROM that failed to load leaves the CPU halted on the error splash
+    let mut roms = Roms {
+        current: options.rom.clone().filter(|_| !cpu.halted),
+        about,
+        in_menu: menu.is_some(),
+        menu,
+        recent: RecentRoms::load(RECENT_PATH).unwrap_or_else(|e| {
+            eprintln!("ignoring {}: {}", RECENT_PATH, e);
+            RecentRoms::default()
+        }),
+    };
+
+    // Without a ROM or directory on the command line, offer a file picker; the splash stays up
+    // if it's cancelled
+    if options.rom.is_none() && !roms.in_menu {
+        if let Some(path) = file_dialog::pick_rom() {
+            if load_rom_file(&mut cpu, &mut frontend, &path) {
+                roms.current = Some(path);
+            }
+        }
+    }
+    if let Some(path) = roms.current.clone() {
+        roms.remember(&path);
+    }
+
+    cpu.set_autosave(config.autosave, &autosave_path(&config.savestate_path));
+    cpu.display_mut().toast("F6: controls");
+    run(&mut cpu, &mut frontend, livesplit, broadcaster, run_ahead, &config, roms);
+    if let Some(json_path) = key_stats {
+        print_key_stats(&cpu.display().keypad, json_path.as_deref());
+    }
+}
+
+const HOTKEYS: [&str; 10] = [
+    "F1 ABOUT    F2 DRAW MODE   F3 SPRITE DUMP",
+    "F4 PALETTE  F5 MEMORY      F6 CONTROLS",
+    "F7 INPUT PROFILE           F8 RAM SEARCH",
+    "F9 RELOAD ROM              F10 RECENT ROMS",
+    "PAUSE PAUSE                F11 STEP WHILE PAUSED",
+    "KEYPAD . SAVE STATE        KEYPAD ENTER LOAD STATE",
+    "KEYPAD 0-9 STATE SLOT",
+    "END AUDIO PANEL            PAGE UP QUIRKS",
+    "",
+    "F6: close",
+];
+
+const RAM_SEARCH_HELP: [&str; 4] = [
+    "KP+ MORE  KP- LESS  KP* CHANGED  KP/ SAME",
+    "KP0-9 THEN KPENTER: EQUAL TO",
+    "INS PIN+FREEZE FIRST  DEL UNPIN LAST",
+    "HOME RESTART  F8 CLOSE",
+];
+
+const RECENT_HELP: [&str; 1] = ["KP1-9 OPEN  F10 CLOSE"];
+
+const QUIRKS_HELP: [&str; 1] = ["UP/DOWN PICK  ENTER FLIP  PAGE UP CLOSE"];
+
+// Handles a key meant for the open quirks page, moving the cursor or flipping the quirk under
+// it; returns false if it wasn't one
+fn quirks_key(hotkeys: &mut Hotkeys, cpu: &mut Chip8, cursor: &mut usize) -> bool {
+    if hotkeys.take(Hotkey::Up) {
+        *cursor = (*cursor + RUNTIME_QUIRKS.len() - 1) % RUNTIME_QUIRKS.len();
+    } else if hotkeys.take(Hotkey::Down) {
+        *cursor = (*cursor + 1) % RUNTIME_QUIRKS.len();
+    } else if hotkeys.take(Hotkey::Select) {
+        let name = RUNTIME_QUIRKS[*cursor];
+        if let Some(setting) = cpu.toggle_quirk(name) {
+            cpu.display_mut().toast(&format!("{} {}", name, setting));
+        }
+    } else {
+        return false;
+    }
+    true
+}
+
+// The quirks page: every quirk that can be flipped while running, the picked one marked
+fn quirks_overlay(cpu: &Chip8, cursor: usize) -> Overlay {
+    let mut lines: Vec<String> = RUNTIME_QUIRKS
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| format!("{} {:<21}{}", if idx == cursor { '>' } else { ' ' }, name, cpu.quirks().setting(name).unwrap_or("?")))
+        .collect();
+    lines.push(String::new());
+    lines.extend(QUIRKS_HELP.iter().map(|line| line.to_string()));
+    Overlay::new("Quirks", lines)
+}
+
+// Handles a key meant for the open RAM search panel; returns false if it wasn't one, so the
+// usual hotkeys get it
+fn ram_search_key(hotkeys: &mut Hotkeys, cpu: &mut Chip8, search: &mut RamSearch, typed: &mut String) -> bool {
+    let comparison = if hotkeys.take(Hotkey::More) {
+        Some(Comparison::Increased)
+    } else if hotkeys.take(Hotkey::Less) {
+        Some(Comparison::Decreased)
+    } else if hotkeys.take(Hotkey::Changed) {
+        Some(Comparison::Changed)
+    } else if hotkeys.take(Hotkey::Same) {
+        Some(Comparison::Unchanged)
+    } else if hotkeys.take(Hotkey::LoadState) {
+        match std::mem::take(typed).parse() {
+            Ok(value) => Some(Comparison::EqualTo(value)),
+            Err(_) => {
+                cpu.display_mut().toast("Type 0-255 first");
+                return true;
+            }
+        }
+    } else {
+        None
+    };
+    if let Some(comparison) = comparison {
+        search.filter(cpu.memory_bus(), comparison);
+        return true;
+    }
+
+    if let Some(digit) = hotkeys.take_digit() {
+        if typed.len() < 3 {
+            typed.push(char::from(b'0' + digit));
+        }
+    } else if hotkeys.take(Hotkey::Erase) {
+        typed.pop();
+    } else if hotkeys.take(Hotkey::Pin) {
+        if let Some(&address) = search.candidates.first() {
+            search.pin(address);
+            cpu.freeze(address, cpu.memory(address));
+        }
+    } else if hotkeys.take(Hotkey::Unpin) {
+        if let Some(&address) = search.pins.last() {
+            search.unpin(address);
+            cpu.unfreeze(address);
+        }
+    } else if hotkeys.take(Hotkey::Restart) {
+        *search = RamSearch::new(cpu.memory_bus());
+        typed.clear();
+    } else {
+        return false;
+    }
+    true
+}
+
+// The key press heatmap --key-stats prints on exit, and the counts as JSON if asked to
+fn print_key_stats(keypad: &Keypad, json_path: Option<&str>) {
+    println!("key presses:");
+    for line in keypad.heatmap_lines() {
+        println!("  {}", line);
+    }
+    if let Some(path) = json_path {
+        match std::fs::write(path, keypad.presses_json()) {
+            Ok(()) => println!("key presses written to {}", path),
+            Err(e) => eprintln!("failed to write {}: {}", path, e),
+        }
+    }
+}
+
+// The F6 page: the keypad with its host keys, then the hotkeys
+fn controls_overlay(cpu: &Chip8, frontend: &SdlFrontend) -> Overlay {
+    let keypad = &cpu.display().keypad;
+    let mut lines = frontend.keymap_lines(keypad);
+    if keypad.sticky.contains(&true) {
+        lines.push("(KEY) TOGGLES, # WHILE ON".to_string());
+    }
+    lines.push(String::new());
+    lines.extend(HOTKEYS.iter().map(|line| line.to_string()));
+
+    let title = match cpu.input_profile() {
+        Some(profile) => format!("Controls ({})", profile.name),
+        None => "Controls".to_string(),
+    };
+    Overlay::new(&title, lines)
+}
+
+// Opens a page, or closes it if it's the one already showing
+fn quit_overlay(cpu: &Chip8) -> Overlay {
+    let minutes = cpu.unsaved_frames() / (60 * 60);
+    let progress = if minutes > 0 { format!("{} min played since the last save", minutes) } else { "High scores or saves written".to_string() };
+    let lines = vec![progress, String::new(), "Enter  save state and quit".to_string(), "Esc    keep playing".to_string(), "Close  quit anyway".to_string()];
+    Overlay::new("Quit without saving?", lines)
+}
+
+fn toggle_overlay(display: &mut Display, overlay: Overlay) {
+    display.overlay = match display.overlay {
+        Some(ref open) if open.title == overlay.title => None,
+        _ => Some(overlay),
+    };
+}
+
+// The ROM running now, for F1 and for F9 to reload, and the ones opened before it for F10.
+// Started on a directory, the frontend switches between its menu and the ROM picked from it.
+struct Roms {
+    current: Option<String>,
+    about: Vec<String>, // what F1 shows about the ROM started from the command line
+    recent: RecentRoms,
+    menu: Option<RomMenu>,
+    in_menu: bool, // the menu is on screen rather than a ROM
+}
+
+impl Roms {
+    // Makes a ROM that just loaded the current one, at the top of the saved list
+    fn remember(&mut self, path: &str) {
+        self.current = Some(path.to_string());
+        self.recent.add(path);
+        if let Err(e) = self.recent.save(RECENT_PATH) {
+            eprintln!("failed to save {}: {}", RECENT_PATH, e);
+        }
+    }
+
+    fn overlay(&self) -> Overlay {
+        let mut lines = self.recent.lines();
+        lines.push(String::new());
+        lines.extend(RECENT_HELP.iter().map(|line| line.to_string()));
+        Overlay::new("Recent ROMs", lines)
+    }
+}
+
+// Starts a ROM picked, dragged onto the window or reloaded after startup, with its per-ROM
+// config, or shows why it can't run. Returns whether it's running.
+fn load_rom_file(cpu: &mut Chip8, frontend: &mut SdlFrontend, path: &str) -> bool {
+    let name = std::path::Path::new(path).file_name().map_or(path.to_string(), |name| name.to_string_lossy().into_owned());
+    let rom = match std::fs::read(path) {
+        Ok(data) if octo::is_octocart(&data) => octo::parse_cart(&data).map(|cart| (cart.rom, Some(cart.options))).map_err(|e| e.to_string()),
+        Ok(data) if bundle::is_bundle(&data) => bundle::parse_bundle(&data).map(|bundle| (bundle.rom, None)).map_err(|e| e.to_string()),
+        Ok(data) => Ok((data, None)),
+        Err(e) => Err(e.to_string()),
+    };
+
+    match rom {
+        Ok((rom, cart_options)) => {
+            cpu.load_program(&rom);
+            if let Some(options) = cart_options {
+                cpu.apply_octo_options(&options); // all but the speed, which is set for the session
+            }
+            match RomConfig::load(path) {
+                Ok(config) => cpu.set_rom_config(config),
+                Err(e) => eprintln!("ignoring invalid {}: {}", RomConfig::path_for(path), e),
+            }
+            let bindings = cpu.input_profile().map_or(Vec::new(), |profile| profile.bindings.clone());
+            frontend.apply_profile(&bindings);
+            cpu.display_mut().set_title(&format!("{} - Rust Chip-8", name));
+            println!("loaded {}", path);
+            true
+        }
+        Err(reason) => {
+            eprintln!("can't load {}: {}", path, reason);
+            cpu.show_message(&splash::error_lines(&name, &reason));
+            false
+        }
+    }
+}
+
+// Runs the Chip-8 emulator until the window is closed
+fn run(cpu: &mut Chip8, frontend: &mut SdlFrontend, mut livesplit: Option<LiveSplitServer>, mut broadcaster: Option<Broadcaster>, run_ahead: u8, config: &Config, mut roms: Roms) {
+    let cpu_target = Duration::from_nanos(1_000_000_000 / config.speed as u64);
+    let mut last_timer_tick = Instant::now();
+    let timer_target = Duration::from_millis(16); // 60 Hz
+    let cycles_per_frame = (timer_target.as_nanos() / cpu_target.as_nanos()) as u64;
+    let mut hexview: Option<HexView> = None;
+    let mut ram_search: Option<RamSearch> = None;
+    let mut typed = String::new(); // value being typed into the RAM search panel
+    let mut quirks_cursor: Option<usize> = None; // the quirks page, while open
+    let mut user_paused = false; // paused with the Pause key, which regaining focus leaves alone
+    let (mut user_muted, mut focus_muted) = (false, false); // by the mute media key, and by focus loss
+    let mut confirming_quit = false; // asking whether to save before quitting
+    let mut slot: u8 = 0; // savestate slot, picked with the keypad digits
+
+    loop {
+        // Poll input before each CPU batch so instructions see the freshest key state
+        let mut hotkeys = Hotkeys::default();
+        let mut open: Option<String> = None; // a ROM dragged onto the window
+        let mut focus_changed: Option<bool> = None;
+        let mut quit = false;
+        for event in frontend.poll_events() {
+            event::apply(cpu.display_mut(), &event);
+            match event {
+                EmuEvent::Quit => quit = true,
+                EmuEvent::Hotkey(hotkey) => hotkeys.push(hotkey),
+                EmuEvent::DropFile(path) => open = Some(path),
+                EmuEvent::FocusChanged { focused, .. } => focus_changed = Some(focused),
+                _ => {}
+            }
+        }
+        // Closing with unsaved progress asks first: Enter saves and quits, Escape goes back to
+        // the game and closing again quits without saving
+        if quit && !confirming_quit && cpu.unsaved_progress() {
+            quit = false;
+            confirming_quit = true;
+            cpu.set_paused(true);
+            cpu.display_mut().overlay = Some(quit_overlay(cpu));
+        } else if confirming_quit && hotkeys.take(Hotkey::Select) {
+            cpu.quick_save(&slot_path(&config.savestate_path, roms.current.as_deref(), slot));
+            quit = true;
+        } else if confirming_quit && hotkeys.take(Hotkey::Back) {
+            confirming_quit = false;
+            cpu.set_paused(user_paused);
+            cpu.display_mut().overlay = None;
+        }
+        if quit {
+            print_reports(cpu, config.speed);
+            if let Some(ref probe) = frontend.latency {
+                println!("{}", probe.report());
+            }
+            break;
+        }
+
+        // F9 re-reads the current ROM from disk, e.g. after rebuilding it
+        if open.is_none() && hotkeys.take(Hotkey::Reload) {
+            match roms.current {
+                Some(ref path) => open = Some(path.clone()),
+                None => cpu.display_mut().toast("No ROM to reload"),
+            }
+        }
+        let recent_open = cpu.display().overlay.as_ref().is_some_and(|overlay| overlay.title == "Recent ROMs");
+        if open.is_none() && recent_open {
+            if let Some(digit) = hotkeys.take_digit().filter(|&digit| digit > 0) {
+                open = roms.recent.paths.get(digit as usize - 1).cloned();
+            }
+        }
+        if let Some(path) = open {
+            let reload = roms.current.as_deref() == Some(path.as_str());
+            if load_rom_file(cpu, frontend, &path) {
+                roms.remember(&path);
+                roms.in_menu = false;
+                if reload {
+                    cpu.display_mut().toast("Reloaded");
+                }
+            }
+        }
+
+        // Up/Down and Enter pick from the directory menu; Escape, or the ROM exiting with
+        // 00FD, goes back to it
+        if let Some(ref mut menu) = roms.menu {
+            if !roms.in_menu && (cpu.exited || hotkeys.take(Hotkey::Back)) {
+                roms.in_menu = true;
+                cpu.show_message(&menu.lines());
+            } else if roms.in_menu {
+                if hotkeys.take(Hotkey::Up) {
+                    menu.up();
+                    cpu.show_message(&menu.lines());
+                } else if hotkeys.take(Hotkey::Down) {
+                    menu.down();
+                    cpu.show_message(&menu.lines());
+                } else if hotkeys.take(Hotkey::Select) {
+                    if let Some(path) = menu.selected_path().map(str::to_string) {
+                        // A ROM that can't load shows why, Escape comes back from that too
+                        roms.in_menu = false;
+                        if load_rom_file(cpu, frontend, &path) {
+                            roms.remember(&path);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(focused) = focus_changed {
+            if config.pause_on_focus_loss && cpu.paused() == focused && !user_paused {
+                cpu.set_paused(!focused);
+                cpu.display_mut().toast(if focused { "Resumed" } else { "Paused" });
+            }
+            focus_muted = config.mute_on_focus_loss && !focused;
+            frontend.set_muted(user_muted || focus_muted);
+        }
+
+        let now = Instant::now();
+        cpu.run_until(now, cpu_target);
+
+        let panel_key = match (&mut ram_search, &mut quirks_cursor) {
+            (Some(ref mut search), _) => ram_search_key(&mut hotkeys, cpu, search, &mut typed),
+            (None, Some(ref mut cursor)) => quirks_key(&mut hotkeys, cpu, cursor),
+            (None, None) => false,
+        };
+
+        if panel_key {
+            // handled by the panel
+        } else if let Some(digit) = hotkeys.take_digit() {
+            slot = digit;
+            cpu.display_mut().toast(&format!("State slot {}", slot));
+        } else if hotkeys.take(Hotkey::SaveState) {
+            cpu.quick_save(&slot_path(&config.savestate_path, roms.current.as_deref(), slot));
+            cpu.display_mut().toast(&format!("Saved slot {}", slot));
+        } else if hotkeys.take(Hotkey::LoadState) {
+            let path = slot_path(&config.savestate_path, roms.current.as_deref(), slot);
+            match cpu.load_state(&path) {
+                Ok(()) => {
+                    println!("read savestate!");
+                    cpu.display_mut().toast(&format!("Loaded slot {}", slot));
+                }
+                Err(e) => {
+                    println!("couldn't load {}: {}", path, e);
+                    cpu.display_mut().toast(&format!("Slot {} not loaded", slot));
+                }
+            }
+        } else if hotkeys.take(Hotkey::Pause) {
+            user_paused = !cpu.paused();
+            cpu.set_paused(user_paused);
+        } else if hotkeys.take(Hotkey::Mute) {
+            user_muted = !user_muted;
+            frontend.set_muted(user_muted || focus_muted);
+            cpu.display_mut().toast(if user_muted { "Muted" } else { "Unmuted" });
+        } else if hotkeys.take(Hotkey::Rewind) {
+            let rewound = cpu.rewind(60);
+            cpu.display_mut().toast(if rewound { "Rewound 1s" } else { "Nothing to rewind" });
+        } else if hotkeys.take(Hotkey::AudioScope) {
+            cpu.toggle_audio_scope();
+        } else if hotkeys.take(Hotkey::Step) {
+            if cpu.paused() {
+                cpu.step_paused();
+                let pc = cpu.pc();
+                cpu.display_mut().toast(&format!("PC {:#06X}", pc));
+            }
+        } else if hotkeys.take(Hotkey::DrawMode) {
+            cpu.toggle_draw_mode();
+        } else if hotkeys.take(Hotkey::SpriteDump) {
+            println!("{}", cpu.display().sprite_dump());
+        } else if hotkeys.take(Hotkey::Palette) {
+            cpu.cycle_palette();
+        } else if hotkeys.take(Hotkey::About) {
+            hexview = None;
+            ram_search = None;
+            quirks_cursor = None;
+            toggle_overlay(cpu.display_mut(), Overlay::new("About this ROM", roms.about.clone()));
+        } else if hotkeys.take(Hotkey::Controls) {
+            hexview = None;
+            ram_search = None;
+            quirks_cursor = None;
+            let controls = controls_overlay(cpu, frontend);
+            toggle_overlay(cpu.display_mut(), controls);
+        } else if hotkeys.take(Hotkey::InputProfile) {
+            let bindings = cpu.cycle_input_profile().map_or(Vec::new(), |profile| profile.bindings.clone());
+            frontend.apply_profile(&bindings);
+        } else if hotkeys.take(Hotkey::Memory) {
+            hexview = match hexview {
+                Some(_) => {
+                    cpu.display_mut().overlay = None;
+                    None
+                }
+                None => Some(HexView::new(cpu.index())),
+            };
+            ram_search = None;
+            quirks_cursor = None;
+        } else if hotkeys.take(Hotkey::Recent) {
+            hexview = None;
+            ram_search = None;
+            quirks_cursor = None;
+            toggle_overlay(cpu.display_mut(), roms.overlay());
+        } else if hotkeys.take(Hotkey::RamSearch) {
+            ram_search = match ram_search {
+                Some(_) => {
+                    cpu.display_mut().overlay = None;
+                    None
+                }
+                None => Some(RamSearch::new(cpu.memory_bus())),
+            };
+            hexview = None;
+            quirks_cursor = None;
+            typed.clear();
+        } else if hotkeys.take(Hotkey::Quirks) {
+            quirks_cursor = match quirks_cursor {
+                Some(_) => {
+                    cpu.display_mut().overlay = None;
+                    None
+                }
+                None => Some(0),
+            };
+            hexview = None;
+            ram_search = None;
+        }
+
+        let next_timer_tick = last_timer_tick + timer_target;
+        if now >= next_timer_tick {
+            last_timer_tick = next_timer_tick; // Update to the next target time
+            cpu.tick_frame();
+            print_messages(cpu);
+            frontend.update_audio(cpu.display());
+
+            if let Some(ref mut server) = livesplit {
+                server.poll();
+            }
+            if let Some(ref mut speedrun) = cpu.speedrun {
+                for event in speedrun.take_events() {
+                    match event {
+                        SplitEvent::Start => println!("speedrun: started"),
+                        SplitEvent::Split(idx, frames) => println!("speedrun: {} {}", speedrun.splits[idx].name, format_time(frames)),
+                        SplitEvent::Finish(frames) => println!("speedrun: finished in {}", format_time(frames)),
+                        SplitEvent::Reset => println!("speedrun: reset"),
+                    }
+                    if let Some(ref mut server) = livesplit {
+                        server.send(event);
+                    }
+                }
+            }
+
+            for hit in cpu.take_write_hits() {
+                println!("watchpoint: {:#06X} = {:#04X} written by {:#06X}", hit.address, hit.value, hit.pc);
+                if let Some(ref mut hexview) = hexview {
+                    hexview.record(&[hit]);
+                }
+            }
+            if let Some(ref mut hexview) = hexview {
+                hexview.tick_frame();
+                cpu.display_mut().overlay = Some(Overlay::new("Memory (F5: close)", hexview.lines(cpu.memory_bus())));
+            }
+            // Keep the Controls page live so latched toggle keys show as they change
+            if cpu.display().overlay.as_ref().is_some_and(|overlay| overlay.title.starts_with("Controls")) {
+                let controls = controls_overlay(cpu, frontend);
+                cpu.display_mut().overlay = Some(controls);
+            }
+            if let Some(ref search) = ram_search {
+                let mut lines = search.lines(cpu.memory_bus(), &cpu.frozen);
+                lines.push(String::new());
+                if !typed.is_empty() {
+                    lines.push(format!("EQUAL TO: {}", typed));
+                }
+                lines.extend(RAM_SEARCH_HELP.iter().map(|line| line.to_string()));
+                cpu.display_mut().overlay = Some(Overlay::new("RAM search", lines));
+            }
+            if let Some(cursor) = quirks_cursor {
+                let page = quirks_overlay(cpu, cursor);
+                cpu.display_mut().overlay = Some(page);
+            }
+
+            // Redraw once per frame, unless nothing on screen changed
+            if run_ahead > 0 {
+                cpu.run_ahead(run_ahead, cycles_per_frame, |display| frontend.draw(display));
+            } else if frontend.needs_redraw(cpu.display_mut()) {
+                frontend.draw(cpu.display());
+            }
+
+            if let Some(ref mut broadcaster) = broadcaster {
+                for address in broadcaster.broadcast(cpu.display_mut()) {
+                    println!("spectator {} joined", address);
+                }
+            }
+        }
+
+        // Avoid busy-waiting
+        std::thread::yield_now();
+    }
+}
@@ -0,0 +1,472 @@
+use chip8_core::audio::Audio;
+use chip8_core::display::Display;
+use chip8_core::error::EmulatorError;
+use chip8_core::event::{EmuEvent, Hotkey};
+use chip8_core::overlay::{self, AudioScope, Overlay};
+use chip8_core::keypad::{Keypad, KEYPAD_LAYOUT};
+use crate::global_keys::GlobalKeys;
+use crate::latency::LatencyProbe;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::render::BlendMode;
+use sdl2::surface::Surface;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::{EventPump, TimerSubsystem};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::{Duration, Instant};
+
+pub type Key = Keycode;
+
+// Host keys for the emulator's hotkeys; the Controls page describes them in HOTKEYS
+const HOTKEY_BINDINGS: [(Keycode, Hotkey); 39] = [
+    (Keycode::F1, Hotkey::About),
+    (Keycode::F2, Hotkey::DrawMode),
+    (Keycode::F3, Hotkey::SpriteDump),
+    (Keycode::F4, Hotkey::Palette),
+    (Keycode::F5, Hotkey::Memory),
+    (Keycode::F6, Hotkey::Controls),
+    (Keycode::F7, Hotkey::InputProfile),
+    (Keycode::F8, Hotkey::RamSearch),
+    (Keycode::F9, Hotkey::Reload),
+    (Keycode::F10, Hotkey::Recent),
+    (Keycode::F11, Hotkey::Step),
+    (Keycode::F12, Hotkey::Rewind),
+    (Keycode::Pause, Hotkey::Pause),
+    (Keycode::KpPeriod, Hotkey::SaveState),
+    (Keycode::KpEnter, Hotkey::LoadState),
+    (Keycode::Up, Hotkey::Up),
+    (Keycode::Down, Hotkey::Down),
+    (Keycode::Return, Hotkey::Select),
+    (Keycode::Escape, Hotkey::Back),
+    (Keycode::Kp0, Hotkey::Digit(0)),
+    (Keycode::Kp1, Hotkey::Digit(1)),
+    (Keycode::Kp2, Hotkey::Digit(2)),
+    (Keycode::Kp3, Hotkey::Digit(3)),
+    (Keycode::Kp4, Hotkey::Digit(4)),
+    (Keycode::Kp5, Hotkey::Digit(5)),
+    (Keycode::Kp6, Hotkey::Digit(6)),
+    (Keycode::Kp7, Hotkey::Digit(7)),
+    (Keycode::Kp8, Hotkey::Digit(8)),
+    (Keycode::Kp9, Hotkey::Digit(9)),
+    (Keycode::KpPlus, Hotkey::More),
+    (Keycode::KpMinus, Hotkey::Less),
+    (Keycode::KpMultiply, Hotkey::Changed),
+    (Keycode::KpDivide, Hotkey::Same),
+    (Keycode::Backspace, Hotkey::Erase),
+    (Keycode::Insert, Hotkey::Pin),
+    (Keycode::Delete, Hotkey::Unpin),
+    (Keycode::Home, Hotkey::Restart),
+    (Keycode::End, Hotkey::AudioScope),
+    (Keycode::PageUp, Hotkey::Quirks),
+];
+
+// Default QWERTY layout of the COSMAC VIP hex keypad, indexed by CHIP-8 key
+pub const DEFAULT_KEYMAP: [Keycode; 16] = [
+    Keycode::X, Keycode::Num1, Keycode::Num2, Keycode::Num3,
+    Keycode::Q, Keycode::W, Keycode::E, Keycode::A,
+    Keycode::S, Keycode::D, Keycode::Z, Keycode::C,
+    Keycode::Num4, Keycode::R, Keycode::F, Keycode::V,
+];
+
+// Square wave, or an XO-CHIP pattern when a ROM has loaded one, whose amplitude ramps toward
+// the target instead of jumping, so even 1-tick beeps start and stop without a click
+struct SquareWave {
+    phase_inc: f32,
+    phase: f32,
+    audio: Audio,
+    freq: f32, // output samples per second
+    position: f32, // pattern bits played, wrapping at the end of the pattern
+    volume: f32,
+    amplitude: f32,
+    target: f32,
+    ramp_step: f32, // amplitude change per sample
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [Self::Channel]) {
+        for x in out.iter_mut() {
+            self.amplitude += (self.target - self.amplitude).clamp(-self.ramp_step, self.ramp_step);
+            let high = if self.audio.pattern.is_some() {
+                self.position = (self.position + self.audio.rate() / self.freq) % Audio::BITS as f32;
+                self.audio.bit(self.position as usize)
+            } else {
+                self.phase = (self.phase + self.phase_inc) % 1.0;
+                self.phase < 0.5
+            };
+            *x = if high { self.amplitude } else { -self.amplitude };
+        }
+    }
+}
+
+pub struct SdlFrontend {
+    canvas: Canvas<Window>,
+    event_pump: EventPump,
+    audio_device: AudioDevice<SquareWave>,
+    timer: TimerSubsystem,
+    pub keymap: [Keycode; 16],
+    base_keymap: [Keycode; 16], // the keymap before any input profile
+    beep: bool,
+    audio: Audio,
+    title: String,
+    size: (u32, u32),
+    icon_set: bool,
+    drawn: Option<u64>, // what the last frame drawn showed, see needs_redraw
+    pub latency: Option<LatencyProbe>,
+    pub global_keys: Option<GlobalKeys>,
+    muted: bool,
+}
+
+impl SdlFrontend {
+    pub fn new(display: &Display) -> Result<SdlFrontend, EmulatorError> {
+        let failed = |what: &str, e: String| EmulatorError::Renderer(format!("{} failed: {}", what, e));
+
+        let sdl_context = sdl2::init().map_err(|e| failed("SDL init", e))?;
+        let video_subsystem = sdl_context.video().map_err(|e| failed("video init", e))?;
+        let audio_subsystem = sdl_context.audio().map_err(|e| failed("audio init", e))?;
+
+        let size = display.window_size();
+        let window = video_subsystem
+            .window(&display.title, size.0, size.1)
+            .position_centered()
+            .resizable()
+            .build()
+            .map_err(|e| failed("creating the window", e.to_string()))?;
+
+        let canvas = window.into_canvas().build().map_err(|e| failed("creating the renderer", e.to_string()))?;
+
+        let spec = AudioSpecDesired {
+            freq: Some(44100),
+            channels: Some(1),
+            samples: None,
+        };
+
+        let audio_device = audio_subsystem.open_playback(None, &spec, |spec| {
+            SquareWave {
+                phase_inc: 440.0 / spec.freq as f32,
+                phase: 0.0,
+                audio: Audio::default(),
+                freq: spec.freq as f32,
+                position: 0.0,
+                volume: 0.05,
+                amplitude: 0.0,
+                target: 0.0,
+                ramp_step: 0.05 / (spec.freq as f32 * 0.002), // 2ms ramp
+            }
+        }).map_err(|e| failed("opening the audio device", e))?;
+        audio_device.resume();
+
+        Ok(SdlFrontend {
+            canvas,
+            event_pump: sdl_context.event_pump().map_err(|e| failed("event pump init", e))?,
+            audio_device,
+            timer: sdl_context.timer().map_err(|e| failed("timer init", e))?,
+            keymap: DEFAULT_KEYMAP,
+            base_keymap: DEFAULT_KEYMAP,
+            beep: false,
+            audio: Audio::default(),
+            title: display.title.clone(),
+            size,
+            icon_set: false,
+            drawn: None,
+            latency: None,
+            global_keys: None,
+            muted: false,
+        })
+    }
+
+    // Returns the CHIP-8 key bound to a host key
+    pub fn map_key(&self, key: Keycode) -> Option<u8> {
+        self.keymap.iter().position(|&mapped| mapped == key).map(|idx| idx as u8)
+    }
+
+    // Binds a CHIP-8 key (0x0-0xF) to a host key
+    pub fn bind(&mut self, chip8_key: usize, key: Keycode) {
+        if chip8_key < self.keymap.len() {
+            self.keymap[chip8_key] = key;
+            self.base_keymap[chip8_key] = key;
+        }
+    }
+
+    // Lays an input profile's bindings over the usual keymap, or restores it with no bindings.
+    // A host key taken from another CHIP-8 key is swapped so no key ends up bound twice.
+    pub fn apply_profile(&mut self, bindings: &[(u8, String)]) {
+        self.keymap = self.base_keymap;
+        for (chip8_key, name) in bindings {
+            let Some(key) = Keycode::from_name(name) else {
+                println!("input profile: unknown key {}", name);
+                continue;
+            };
+            let chip8_key = *chip8_key as usize;
+            if let Some(other) = self.keymap.iter().position(|&mapped| mapped == key) {
+                self.keymap[other] = self.keymap[chip8_key];
+            }
+            self.keymap[chip8_key] = key;
+        }
+    }
+
+    // Cheat sheet of the current keymap laid out like the COSMAC VIP keypad, each CHIP-8 key
+    // followed by the host key bound to it: [key] when held, (key) when it toggles and # after
+    // a toggle key that's latched down
+    pub fn keymap_lines(&self, keypad: &Keypad) -> Vec<String> {
+        let cells: Vec<Vec<String>> = KEYPAD_LAYOUT
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&key| match (keypad.sticky[key], keypad.keypad[key]) {
+                        (false, _) => format!("{:X}=[{}]", key, self.keymap[key].name()),
+                        (true, false) => format!("{:X}=({})", key, self.keymap[key].name()),
+                        (true, true) => format!("{:X}=({})#", key, self.keymap[key].name()),
+                    })
+                    .collect()
+            })
+            .collect();
+        let width = cells.iter().flatten().map(|cell| cell.chars().count()).max().unwrap_or(0);
+
+        cells
+            .iter()
+            .map(|row| row.iter().map(|cell| format!("{:<width$}", cell, width = width)).collect::<Vec<String>>().join(" ").trim_end().to_string())
+            .collect()
+    }
+
+    // Turns pending SDL events into emulator events: CHIP-8 keys through the keymap, hotkeys
+    // through HOTKEY_BINDINGS (a key can be both), and window events. Global media keys
+    // follow, when they're listened for.
+    pub fn poll_events(&mut self) -> Vec<EmuEvent> {
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+
+        // SDL stamps events in milliseconds since init, map them back onto Instants
+        let poll_instant = Instant::now();
+        let poll_ticks = self.timer.ticks();
+        let event_instant = |timestamp: u32| poll_instant - Duration::from_millis(poll_ticks.saturating_sub(timestamp) as u64);
+
+        let mut emu_events = Vec::new();
+        for event in events {
+            match event {
+                Event::Quit { .. } => emu_events.push(EmuEvent::Quit),
+                Event::KeyDown { keycode: Some(key), timestamp, repeat, .. } => {
+                    if let Some(ref mut probe) = self.latency {
+                        if !repeat {
+                            probe.key_pressed(event_instant(timestamp));
+                        }
+                    }
+                    if let Some(chip8_key) = self.map_key(key) {
+                        emu_events.push(EmuEvent::KeyChip8 { key: chip8_key, down: true, repeat, time: event_instant(timestamp) });
+                    }
+                    if let Some(&(_, hotkey)) = HOTKEY_BINDINGS.iter().find(|&&(bound, _)| bound == key) {
+                        emu_events.push(EmuEvent::Hotkey(hotkey));
+                    }
+                }
+
+                Event::KeyUp { keycode: Some(key), timestamp, .. } => {
+                    if let Some(chip8_key) = self.map_key(key) {
+                        emu_events.push(EmuEvent::KeyChip8 { key: chip8_key, down: false, repeat: false, time: event_instant(timestamp) });
+                    }
+                }
+
+                Event::DropFile { filename, .. } => emu_events.push(EmuEvent::DropFile(filename)),
+
+                Event::Window { win_event: WindowEvent::FocusGained, timestamp, .. } => {
+                    emu_events.push(EmuEvent::FocusChanged { focused: true, time: event_instant(timestamp) });
+                }
+                Event::Window { win_event: WindowEvent::FocusLost, timestamp, .. } => {
+                    emu_events.push(EmuEvent::FocusChanged { focused: false, time: event_instant(timestamp) });
+                }
+                Event::Window { win_event: WindowEvent::SizeChanged(width, height), .. } => {
+                    emu_events.push(EmuEvent::WindowResized(width as u32, height as u32));
+                }
+                Event::Window { win_event: WindowEvent::Exposed, .. } => self.drawn = None,
+
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => emu_events.push(EmuEvent::Paint { x, y, start: true }),
+                Event::MouseMotion { mousestate, x, y, .. } if mousestate.left() => emu_events.push(EmuEvent::Paint { x, y, start: false }),
+
+                _ => {}
+            }
+        }
+
+        if let Some(ref global_keys) = self.global_keys {
+            emu_events.extend(global_keys.pressed().into_iter().map(EmuEvent::Hotkey));
+        }
+        emu_events
+    }
+
+    pub fn update_audio(&mut self, display: &Display) {
+        if self.beep == display.beep() && self.audio == display.audio {
+            return;
+        }
+
+        self.beep = display.beep();
+        self.audio = display.audio;
+        let mut wave = self.audio_device.lock();
+        if wave.audio.pattern != self.audio.pattern {
+            wave.position = 0.0;
+        }
+        wave.audio = self.audio;
+        wave.target = if self.beep && !self.muted { wave.volume } else { 0.0 };
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        let mut wave = self.audio_device.lock();
+        wave.target = if self.beep && !self.muted { wave.volume } else { 0.0 };
+    }
+
+    // Whether draw() would show anything different from the last frame. Text on screen and
+    // flash reduction's fades always redraw; otherwise only a changed framebuffer, palette or
+    // window does.
+    pub fn needs_redraw(&mut self, display: &mut Display) -> bool {
+        let mut hasher = DefaultHasher::new();
+        display.fingerprint().hash(&mut hasher);
+        display.palette().colors.hash(&mut hasher);
+        (display.brightness.to_bits(), display.gamma.to_bits()).hash(&mut hasher);
+        (display.window, display.window_size(), &display.title).hash(&mut hasher);
+        let drawn = hasher.finish();
+
+        let busy = display.overlay.is_some() || !display.hud.is_empty() || display.badge.is_some() || !display.toasts.is_empty() || display.scope.is_some();
+        let redraw = busy || display.flash_reduction || self.latency.is_some() || self.drawn != Some(drawn);
+        self.drawn = Some(drawn);
+        redraw
+    }
+
+    pub fn draw(&mut self, display: &Display) {
+        if self.title != display.title {
+            self.title = display.title.clone();
+            _ = self.canvas.window_mut().set_title(&self.title);
+        }
+
+        let size = display.window_size();
+        if self.size != size {
+            self.size = size;
+            self.canvas.window_mut().set_size(size.0, size.1).unwrap();
+        }
+
+        if self.latency.as_ref().is_some_and(LatencyProbe::flash_due) {
+            self.canvas.set_draw_color(Color::RGB(255, 255, 255));
+            self.canvas.clear();
+            self.canvas.present();
+            self.latency.as_mut().unwrap().presented(Instant::now());
+            return;
+        }
+
+        let (r, g, b) = display.background();
+        self.canvas.set_draw_color(Color::RGB(r, g, b));
+        self.canvas.clear();
+
+        // One fill per run of same-colored pixels, switching draw color only when it changes
+        let mut prev_color: Option<(u8, u8, u8)> = None;
+        for run in display.runs() {
+            if prev_color != Some(run.color) {
+                self.canvas.set_draw_color(Color::RGB(run.color.0, run.color.1, run.color.2));
+                prev_color = Some(run.color);
+            }
+
+            let (x, y, width, height) = display.run_rect(&run);
+            self.canvas.fill_rect(sdl2::rect::Rect::new(x, y, width, height)).unwrap();
+        }
+
+        if !display.hud.is_empty() {
+            self.draw_hud(&display.hud, (display.scale / 4).max(1) as i32);
+        }
+
+        if let Some(ref badge) = display.badge {
+            let pixel = (display.scale / 4).max(1) as i32;
+            self.draw_text_box(std::slice::from_ref(badge), pixel * 2, pixel * 2, pixel, Color::RGB(255, 96, 96));
+        }
+
+        if !display.toasts.is_empty() {
+            let lines: Vec<String> = display.toasts.iter().map(|(message, _)| message.clone()).collect();
+            self.draw_toasts(&lines, (display.scale / 4).max(1) as i32);
+        }
+
+        if let Some(ref scope) = display.scope {
+            self.draw_scope(scope, (display.scale / 4).max(1) as i32);
+        }
+
+        if let Some(ref overlay) = display.overlay {
+            self.draw_overlay(overlay, (display.scale / 4).max(1) as i32);
+        }
+
+        if !self.icon_set && display.is_interesting() {
+            self.set_icon(display);
+        }
+
+        self.canvas.present();
+    }
+
+    // Draws a text page on a translucent box in the top left corner
+    fn draw_overlay(&mut self, overlay: &Overlay, pixel: i32) {
+        let (width, height) = overlay.size();
+        let margin = pixel * 4;
+
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+        let backdrop = sdl2::rect::Rect::new(margin / 2, margin / 2, (width as i32 * pixel + margin) as u32, (height as i32 * pixel + margin) as u32);
+        self.canvas.fill_rect(backdrop).unwrap();
+        self.canvas.set_blend_mode(BlendMode::None);
+
+        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
+        let rects: Vec<sdl2::rect::Rect> = overlay
+            .pixels()
+            .iter()
+            .map(|&(x, y)| sdl2::rect::Rect::new(margin + x as i32 * pixel, margin + y as i32 * pixel, pixel as u32, pixel as u32))
+            .collect();
+        self.canvas.fill_rects(&rects).unwrap();
+    }
+
+    // Draws small status text in the top right corner
+    fn draw_hud(&mut self, lines: &[String], pixel: i32) {
+        let (width, _) = overlay::text_size(lines);
+        let left = self.canvas.output_size().unwrap().0 as i32 - (width as i32 + 2) * pixel;
+        self.draw_text_box(lines, left, pixel * 2, pixel, Color::RGB(255, 255, 255));
+    }
+
+    // Draws short-lived messages in the bottom left corner
+    fn draw_toasts(&mut self, lines: &[String], pixel: i32) {
+        let (_, height) = overlay::text_size(lines);
+        let top = self.canvas.output_size().unwrap().1 as i32 - (height as i32 + 2) * pixel;
+        self.draw_text_box(lines, pixel * 2, top, pixel, Color::RGB(255, 220, 64));
+    }
+
+    // Draws the audio panel in the bottom right corner
+    fn draw_scope(&mut self, scope: &AudioScope, pixel: i32) {
+        let (width, height) = scope.size();
+        let (window_width, window_height) = self.canvas.output_size().unwrap();
+        let left = window_width as i32 - (width as i32 + 2) * pixel;
+        let top = window_height as i32 - (height as i32 + 2) * pixel;
+        self.draw_pixel_box(&scope.pixels(), (width, height), left, top, pixel, Color::RGB(96, 255, 160));
+    }
+
+    fn draw_text_box(&mut self, lines: &[String], left: i32, top: i32, pixel: i32, color: Color) {
+        self.draw_pixel_box(&overlay::text_pixels(lines), overlay::text_size(lines), left, top, pixel, color);
+    }
+
+    fn draw_pixel_box(&mut self, pixels: &[(u16, u16)], (width, height): (u16, u16), left: i32, top: i32, pixel: i32, color: Color) {
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 180));
+        let backdrop = sdl2::rect::Rect::new(left - pixel, top - pixel, ((width as i32 + 2) * pixel) as u32, ((height as i32 + 1) * pixel) as u32);
+        self.canvas.fill_rect(backdrop).unwrap();
+        self.canvas.set_blend_mode(BlendMode::None);
+
+        self.canvas.set_draw_color(color);
+        let rects: Vec<sdl2::rect::Rect> = pixels
+            .iter()
+            .map(|&(x, y)| sdl2::rect::Rect::new(left + x as i32 * pixel, top + y as i32 * pixel, pixel as u32, pixel as u32))
+            .collect();
+        self.canvas.fill_rects(&rects).unwrap();
+    }
+
+    // Uses the current frame as the window icon
+    fn set_icon(&mut self, display: &Display) {
+        const ICON_SIZE: u16 = 64;
+        let mut rgba = display.icon(ICON_SIZE);
+        if let Ok(surface) = Surface::from_data(&mut rgba, ICON_SIZE as u32, ICON_SIZE as u32, ICON_SIZE as u32 * 4, PixelFormatEnum::RGBA32) {
+            self.canvas.window_mut().set_icon(surface);
+        }
+        self.icon_set = true;
+    }
+}